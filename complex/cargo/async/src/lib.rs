@@ -0,0 +1,118 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! `async` versions of `ListManager`'s methods, for services running on Tokio.
+//!
+//! Each method runs the matching `ListManager` method with `spawn_blocking`, so
+//! SQLite work happens on Tokio's blocking thread pool instead of holding up the
+//! runtime's worker threads. The methods must be awaited within a Tokio runtime.
+
+use std::panic;
+
+use list::items::Item;
+use list::labels::Label;
+use list::ListManager;
+use tokio::task;
+
+/// Wraps a `ListManager`, whose clones share its storage, so the async and
+/// synchronous APIs can be used side by side.
+#[derive(Debug, Clone)]
+pub struct AsyncListManager {
+    manager: ListManager,
+}
+
+impl From<ListManager> for AsyncListManager {
+    fn from(manager: ListManager) -> Self {
+        AsyncListManager::new(manager)
+    }
+}
+
+impl AsyncListManager {
+    pub fn new(manager: ListManager) -> Self {
+        AsyncListManager {
+            manager: manager,
+        }
+    }
+
+    pub fn manager(&self) -> &ListManager {
+        &self.manager
+    }
+
+    /// Call `f` with a clone of the manager on the blocking pool.
+    async fn run<T, F>(&self, f: F) -> T
+    where F: FnOnce(&mut ListManager) -> T + Send + 'static,
+          T: Send + 'static {
+        let mut manager = self.manager.clone();
+        match task::spawn_blocking(move || f(&mut manager)).await {
+            Ok(value) => value,
+            // A panic carries on in the caller, as if `f` had been called directly.
+            Err(e) => match e.try_into_panic() {
+                Ok(payload) => panic::resume_unwind(payload),
+                Err(e) => panic!("blocking task failed: {}", e),
+            },
+        }
+    }
+
+    pub async fn create_label(&self, name: String, color: String) -> Option<Label> {
+        self.run(move |manager| manager.create_label(name, color)).await
+    }
+
+    pub async fn fetch_label(&self, name: String) -> Option<Label> {
+        self.run(move |manager| manager.fetch_label(&name)).await
+    }
+
+    pub async fn fetch_or_create_label(&self, name: String, color: String) -> Option<Label> {
+        self.run(move |manager| manager.fetch_or_create_label(&name, &color)).await
+    }
+
+    pub async fn fetch_labels(&self) -> Vec<Label> {
+        self.run(|manager| manager.fetch_labels()).await
+    }
+
+    pub async fn fetch_labels_for_item(&self, item_uuid: String) -> Vec<Label> {
+        self.run(move |manager| manager.fetch_labels_for_item(&item_uuid)).await
+    }
+
+    pub async fn fetch_items(&self) -> Vec<Item> {
+        self.run(|manager| manager.fetch_items()).await
+    }
+
+    /// Up to `limit` of the items `fetch_items` returns, skipping the first `offset`.
+    pub async fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> {
+        self.run(move |manager| manager.fetch_items_page(offset, limit)).await
+    }
+
+    pub async fn count_items(&self) -> Result<usize, String> {
+        self.run(|manager| manager.count_items()).await
+    }
+
+    pub async fn fetch_items_with_label(&self, label: Label) -> Vec<Item> {
+        self.run(move |manager| manager.fetch_items_with_label(&label)).await
+    }
+
+    pub async fn fetch_item(&self, uuid: String) -> Option<Item> {
+        self.run(move |manager| manager.fetch_item(&uuid)).await
+    }
+
+    /// Save a copy of `item` under a new uuid, which is returned.
+    pub async fn create_item(&self, item: Item) -> Result<String, String> {
+        self.run(move |manager| manager.create_item(&item)).await
+    }
+
+    pub async fn update_item(&self, item: Item, existing_labels: Vec<Label>) -> Result<(), String> {
+        self.run(move |manager| manager.update_item(&item, existing_labels)).await
+    }
+
+    /// Delete the item with `uuid` along with its labels. Returns false if there is no
+    /// such item.
+    pub async fn delete_item(&self, uuid: String) -> Result<bool, String> {
+        self.run(move |manager| manager.delete_item(&uuid)).await
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Drives `AsyncListManager` from many tasks at once on a multi-threaded runtime.
+
+use list::dates::Date;
+use list::items::Item;
+use list::storage::MemoryStorage;
+use list::ListManager;
+use toodle_async::AsyncListManager;
+
+fn manager() -> AsyncListManager {
+    AsyncListManager::new(ListManager::new(None))
+}
+
+fn item(name: &str) -> Item {
+    Item::new("".to_string(), name.to_string(), None, None, vec![])
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_create_fetch_update_delete() {
+    let manager = manager();
+    let home = manager.create_label("home".to_string(), "#ff0000".to_string()).await.expect("expected a label");
+    assert_eq!(manager.create_label("home".to_string(), "#00ff00".to_string()).await, None);
+    assert_eq!(manager.fetch_label("home".to_string()).await, Some(home.clone()));
+    let work = manager.fetch_or_create_label("work".to_string(), "#0000ff".to_string()).await.expect("expected a label");
+    assert_eq!(manager.fetch_labels().await, vec![home.clone(), work.clone()]);
+
+    let mut item = Item::new("".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![home.clone()]);
+    item.uuid = manager.create_item(item.clone()).await.unwrap();
+    assert_eq!(manager.fetch_item(item.uuid.clone()).await, Some(item.clone()));
+
+    let existing_labels = manager.fetch_labels_for_item(item.uuid.clone()).await;
+    item.name = "buy oat milk".to_string();
+    item.labels = vec![work.clone()];
+    manager.update_item(item.clone(), existing_labels).await.unwrap();
+    assert_eq!(manager.fetch_items_with_label(work).await, vec![item.clone()]);
+    assert_eq!(manager.fetch_items_with_label(home).await, vec![]);
+
+    assert!(manager.delete_item(item.uuid.clone()).await.unwrap());
+    assert!(!manager.delete_item(item.uuid.clone()).await.unwrap());
+    assert_eq!(manager.fetch_items().await, vec![]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_tasks() {
+    let manager = manager();
+    let tasks: Vec<_> = (0..50).map(|index| {
+        let manager = manager.clone();
+        tokio::spawn(async move { manager.create_item(item(&format!("item {}", index))).await })
+    }).collect();
+    let mut uuids = Vec::new();
+    for task in tasks {
+        uuids.push(task.await.unwrap().unwrap());
+    }
+    uuids.sort();
+    uuids.dedup();
+    assert_eq!(uuids.len(), 50);
+
+    assert_eq!(manager.count_items().await, Ok(50));
+    let mut paged = Vec::new();
+    loop {
+        let page = manager.fetch_items_page(paged.len(), 15).await.unwrap();
+        if page.is_empty() {
+            break;
+        }
+        paged.extend(page);
+    }
+    assert_eq!(paged, manager.fetch_items().await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_shares_storage_with_sync_manager() {
+    let mut sync_manager = ListManager::with_storage(MemoryStorage::new());
+    let manager = AsyncListManager::from(sync_manager.clone());
+    let uuid = manager.create_item(item("call mum")).await.unwrap();
+    assert_eq!(sync_manager.fetch_item(&uuid).map(|item| item.name.clone()), Some("call mum".to_string()));
+    assert_eq!(manager.manager().count_items(), Ok(1));
+}
@@ -0,0 +1,125 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Generates the C headers from the `extern "C"` functions in the Rust sources into
+//! `OUT_DIR`. The headers used by the iOS project are checked in, and the `c_abi`
+//! test fails if they differ from the generated ones; build with
+//! `TOODLE_UPDATE_HEADERS=1` to overwrite them. Don't edit them by hand.
+
+extern crate cbindgen;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use cbindgen::{
+    Builder,
+    Config,
+    ItemType,
+    Language,
+};
+
+struct Header {
+    /// Where the header is checked in, relative to the workspace root. It is generated
+    /// at the same path under `OUT_DIR`.
+    path: &'static str,
+    /// The sources whose exported functions and types the header declares.
+    sources: &'static [&'static str],
+    /// Headers this one depends on for the types it uses.
+    includes: &'static [&'static str],
+    /// Declarations of types defined in sources that aren't part of this header.
+    forward_declarations: &'static str,
+    /// Types to declare even though no declared function uses them, such as enums
+    /// whose values cross the FFI as plain numbers.
+    types: &'static [&'static str],
+}
+
+const HEADERS: &'static [Header] = &[
+    Header {
+        path: "store/store.h",
+        sources: &["store/src/lib.rs"],
+        includes: &[],
+        forward_declarations: "",
+        types: &[],
+    },
+    Header {
+        path: "list/labels.h",
+        sources: &["list/src/labels.rs"],
+        includes: &[],
+        forward_declarations: "typedef uint64_t ListManagerHandle;",
+        types: &[],
+    },
+    Header {
+        path: "list/items.h",
+        sources: &["list/src/items.rs", "list/src/managers.rs", "list/src/date_phrases.rs", "list/src/cursor.rs", "list/src/attachments.rs", "list/src/csv.rs", "list/src/markdown.rs", "list/src/buffers.rs", "list/src/quick_add.rs", "ffi-utils/src/lib.rs"],
+        includes: &["labels.h"],
+        forward_declarations: "",
+        types: &[],
+    },
+    Header {
+        path: "src/toodle.h",
+        sources: &["src/lib.rs", "ffi-utils/src/lib.rs"],
+        includes: &["items.h", "labels.h"],
+        forward_declarations: "",
+        types: &["LogLevel"],
+    },
+];
+
+fn main() {
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let root = Path::new(&root);
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    let update = env::var_os("TOODLE_UPDATE_HEADERS").is_some_and(|value| !value.is_empty() && value != "0");
+    for header in HEADERS.iter() {
+        let mut config = Config::default();
+        config.language = Language::C;
+        config.usize_is_size_t = true;
+        // Rust constants such as default colors and formats aren't part of the ABI.
+        config.export.item_types = vec![ItemType::Functions, ItemType::OpaqueItems, ItemType::Structs, ItemType::Enums, ItemType::Typedefs];
+        config.export.include = header.types.iter().map(|name| name.to_string()).collect();
+        // C enumerators share one namespace, so `LogLevel::Error` becomes `LogLevel_Error`.
+        config.enumeration.prefix_with_name = true;
+        config.no_includes = true;
+        config.sys_includes = vec!["stdbool.h".to_string(), "stddef.h".to_string(), "stdint.h".to_string()];
+        config.includes = header.includes.iter().map(|include| include.to_string()).collect();
+        config.include_guard = Some(guard_name(header.path));
+        config.autogen_warning = Some("/* Generated by build.rs from the Rust sources. Do not edit by hand. */".to_string());
+        if !header.forward_declarations.is_empty() {
+            config.after_includes = Some(format!("\n{}", header.forward_declarations));
+        }
+
+        let mut builder = Builder::new().with_config(config);
+        for source in header.sources.iter() {
+            println!("cargo:rerun-if-changed={}", source);
+            builder = builder.with_src(root.join(source));
+        }
+        let bindings = builder.generate().expect(&format!("failed to generate {}", header.path));
+        let generated = out_dir.join(header.path);
+        fs::create_dir_all(generated.parent().unwrap()).unwrap();
+        bindings.write_to_file(&generated);
+        if update {
+            bindings.write_to_file(root.join(header.path));
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=TOODLE_UPDATE_HEADERS");
+}
+
+/// `src/toodle.h` is guarded by `TOODLE_H` and `list/items.h` by `TOODLE_ITEMS_H`.
+fn guard_name(path: &str) -> String {
+    let file_name = Path::new(path).file_name().unwrap().to_str().unwrap();
+    let stem = file_name.replace('.', "_").to_uppercase();
+    if stem.starts_with("TOODLE") {
+        stem
+    } else {
+        format!("TOODLE_{}", stem)
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Command-line parsing. Options may come before or after the subcommand, and take
+//! their value either as the next argument or after an `=`.
+
+use chrono::NaiveDateTime;
+
+use list::dates::{
+    parse_zone,
+    Date,
+};
+
+pub const USAGE: &'static str = "\
+usage: toodle [--db PATH] [--json] COMMAND [ARGS]
+
+The database is PATH, or $TOODLE_DB if --db isn't given. --json prints items and
+labels as JSON rather than as a table.
+
+commands:
+    add NAME [--due DATE] [--tz ZONE] [--label LABEL]...
+    list [--all | --done] [--label LABEL]
+    complete ITEM [--undo]
+    edit ITEM [--name NAME] [--due DATE | --no-due] [--tz ZONE]
+              [--add-label LABEL]... [--remove-label LABEL]...
+    label [list]
+    label create NAME [--color COLOR]
+    search TEXT [--all | --done]
+    export (ics | csv | todotxt | markdown) [--label LABEL]
+    help
+
+ITEM is a uuid or any unique prefix of one. DATE is a day (2017-11-05) or a time
+(2017-11-05 14:30), which is read in ZONE if --tz is given and in UTC otherwise.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// Which items `list` and `search` show.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Open,
+    Done,
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportFormat {
+    Ics,
+    Csv,
+    TodoTxt,
+    Markdown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Add { name: String, due: Option<Date>, labels: Vec<String> },
+    List { filter: Filter, label: Option<String> },
+    Complete { item: String, undo: bool },
+    Edit {
+        item: String,
+        name: Option<String>,
+        /// `Some(None)` clears the due date.
+        due: Option<Option<Date>>,
+        add_labels: Vec<String>,
+        remove_labels: Vec<String>,
+    },
+    Labels,
+    CreateLabel { name: String, color: Option<String> },
+    Search { text: String, filter: Filter },
+    Export { format: ExportFormat, label: Option<String> },
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub db: Option<String>,
+    pub format: Format,
+    pub command: Command,
+}
+
+/// Options that are always accepted, and whether they take a value.
+const GLOBAL_OPTIONS: &'static [(&'static str, bool)] = &[("db", true), ("json", false), ("help", false)];
+
+/// Each command and the options it accepts.
+const COMMANDS: &'static [(&'static str, &'static [(&'static str, bool)])] = &[
+    ("add", &[("due", true), ("tz", true), ("label", true)]),
+    ("list", &[("all", false), ("done", false), ("label", true)]),
+    ("complete", &[("undo", false)]),
+    ("edit", &[("name", true), ("due", true), ("no-due", false), ("tz", true), ("add-label", true), ("remove-label", true)]),
+    ("label", &[("color", true)]),
+    ("search", &[("all", false), ("done", false)]),
+    ("export", &[("label", true)]),
+];
+
+/// The arguments left once the options have been taken out.
+struct Parsed {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Parsed {
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    /// The last value given for `name`.
+    fn value(&self, name: &str) -> Option<String> {
+        self.values(name).pop()
+    }
+
+    fn values(&self, name: &str) -> Vec<String> {
+        self.options.iter()
+            .filter(|(option, _)| option == name)
+            .filter_map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    /// The due date given with `--due` and `--tz`, if any.
+    fn due(&self) -> Result<Option<Date>, String> {
+        let zone = self.value("tz");
+        match self.value("due") {
+            Some(due) => Ok(Some(parse_date(&due, zone.as_deref())?)),
+            None if zone.is_some() => Err("--tz needs --due".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    fn filter(&self) -> Result<Filter, String> {
+        match (self.flag("all"), self.flag("done")) {
+            (true, true) => Err("--all and --done can't be used together".to_string()),
+            (true, false) => Ok(Filter::All),
+            (false, true) => Ok(Filter::Done),
+            (false, false) => Ok(Filter::Open),
+        }
+    }
+}
+
+/// Parse the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Invocation, String> {
+    // Find the command with every option allowed, which is enough to tell options'
+    // values from positional arguments, then check its options.
+    let every_option: Vec<(&str, bool)> = COMMANDS.iter().flat_map(|&(_, options)| options.iter().cloned()).collect();
+    let command = split(args, &every_option)?.positional.into_iter().next();
+    let options = COMMANDS.iter()
+        .find(|&&(name, _)| command.as_deref() == Some(name))
+        .map_or(&[][..], |&(_, options)| options);
+    let parsed = split(args, options)?;
+    let invocation = |command| Invocation {
+        db: parsed.value("db"),
+        format: if parsed.flag("json") { Format::Json } else { Format::Table },
+        command: command,
+    };
+    if parsed.flag("help") {
+        return Ok(invocation(Command::Help));
+    }
+
+    let words: Vec<&str> = parsed.positional.iter().map(|word| word.as_str()).collect();
+    let command = match words.as_slice() {
+        [] | ["help"] => Command::Help,
+        ["add", name] => Command::Add { name: name.to_string(), due: parsed.due()?, labels: parsed.values("label") },
+        ["list"] => Command::List { filter: parsed.filter()?, label: parsed.value("label") },
+        ["complete", item] => Command::Complete { item: item.to_string(), undo: parsed.flag("undo") },
+        ["edit", item] => {
+            let due = match (parsed.due()?, parsed.flag("no-due")) {
+                (Some(_), true) => return Err("--due and --no-due can't be used together".to_string()),
+                (Some(due), false) => Some(Some(due)),
+                (None, true) => Some(None),
+                (None, false) => None,
+            };
+            Command::Edit {
+                item: item.to_string(),
+                name: parsed.value("name"),
+                due: due,
+                add_labels: parsed.values("add-label"),
+                remove_labels: parsed.values("remove-label"),
+            }
+        },
+        ["label"] | ["label", "list"] if !parsed.flag("color") => Command::Labels,
+        ["label", "create", name] => Command::CreateLabel { name: name.to_string(), color: parsed.value("color") },
+        ["search", text] => Command::Search { text: text.to_string(), filter: parsed.filter()? },
+        ["export", format] => {
+            let format = match *format {
+                "ics" => ExportFormat::Ics,
+                "csv" => ExportFormat::Csv,
+                "todotxt" => ExportFormat::TodoTxt,
+                "markdown" => ExportFormat::Markdown,
+                _ => return Err(format!("unknown export format {:?}", format)),
+            };
+            let label = parsed.value("label");
+            if label.is_some() && format != ExportFormat::Markdown {
+                return Err("--label can only be used with markdown exports".to_string());
+            }
+            Command::Export { format: format, label: label }
+        },
+        [command, ..] if COMMANDS.iter().any(|&(name, _)| name == *command) => {
+            return Err(format!("wrong arguments for {}", command));
+        },
+        [command, ..] => return Err(format!("unknown command {:?}", command)),
+    };
+    Ok(invocation(command))
+}
+
+/// Separate `args` into positional arguments and the global options and `options`.
+fn split(args: &[String], options: &[(&str, bool)]) -> Result<Parsed, String> {
+    let mut parsed = Parsed { positional: vec![], options: vec![] };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.positional.extend(args.cloned());
+            break;
+        }
+        if !arg.starts_with("--") {
+            parsed.positional.push(arg.clone());
+            continue;
+        }
+        let (name, inline) = match arg[2..].find('=') {
+            Some(index) => (&arg[2..2 + index], Some(arg[3 + index..].to_string())),
+            None => (&arg[2..], None),
+        };
+        let takes_value = match GLOBAL_OPTIONS.iter().chain(options.iter()).find(|&&(option, _)| option == name) {
+            Some(&(_, takes_value)) => takes_value,
+            None => return Err(format!("unknown option --{}", name)),
+        };
+        let value = match (takes_value, inline) {
+            (true, Some(value)) => Some(value),
+            (true, None) => Some(args.next().ok_or_else(|| format!("--{} needs a value", name))?.clone()),
+            (false, Some(_)) => return Err(format!("--{} doesn't take a value", name)),
+            (false, None) => None,
+        };
+        parsed.options.push((name.to_string(), value));
+    }
+    Ok(parsed)
+}
+
+/// Read a day as an all-day date, or a time of day as a timestamp in `zone`, or in
+/// UTC if there is no zone.
+pub fn parse_date(text: &str, zone: Option<&str>) -> Result<Date, String> {
+    let zone = match zone {
+        Some(zone) => Some(parse_zone(zone)?),
+        None => None,
+    };
+    if let Ok(day) = text.parse::<Date>() {
+        if day.is_all_day() {
+            return match zone {
+                Some(_) => Err("--tz can't be used with a day".to_string()),
+                None => Ok(day),
+            };
+        }
+    }
+    let local = ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"].iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .next()
+        .ok_or_else(|| format!("can't read {:?} as a date", text))?;
+    Ok(match zone {
+        Some(zone) => Date::from_local(local, zone),
+        None => Date::from_utc(local),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    fn command(line: &str) -> Command {
+        parse(&args(line)).unwrap().command
+    }
+
+    #[test]
+    fn test_global_options() {
+        let invocation = parse(&args("list --db=todo.db --json")).unwrap();
+        assert_eq!(invocation.db, Some("todo.db".to_string()));
+        assert_eq!(invocation.format, Format::Json);
+        let invocation = parse(&args("--db todo.db list")).unwrap();
+        assert_eq!(invocation.db, Some("todo.db".to_string()));
+        assert_eq!(invocation.format, Format::Table);
+        assert_eq!(command(""), Command::Help);
+        assert_eq!(command("add milk --help"), Command::Help);
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(command("add milk --label home --label errands --due 2017-11-05"), Command::Add {
+            name: "milk".to_string(),
+            due: Date::day(2017, 11, 5),
+            labels: vec!["home".to_string(), "errands".to_string()],
+        });
+        assert_eq!(command("list --done"), Command::List { filter: Filter::Done, label: None });
+        assert_eq!(command("complete 1a2b --undo"), Command::Complete { item: "1a2b".to_string(), undo: true });
+        assert_eq!(command("edit 1a2b --no-due --remove-label home"), Command::Edit {
+            item: "1a2b".to_string(),
+            name: None,
+            due: Some(None),
+            add_labels: vec![],
+            remove_labels: vec!["home".to_string()],
+        });
+        assert_eq!(command("label"), Command::Labels);
+        assert_eq!(command("label create home --color #ff0000"), Command::CreateLabel {
+            name: "home".to_string(),
+            color: Some("#ff0000".to_string()),
+        });
+        assert_eq!(command("export markdown --label home"), Command::Export { format: ExportFormat::Markdown, label: Some("home".to_string()) });
+        assert_eq!(command("search -- --milk"), Command::Search { text: "--milk".to_string(), filter: Filter::Open });
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(&args("frobnicate")), Err("unknown command \"frobnicate\"".to_string()));
+        assert_eq!(parse(&args("add")), Err("wrong arguments for add".to_string()));
+        assert_eq!(parse(&args("list --color red")), Err("unknown option --color".to_string()));
+        assert_eq!(parse(&args("add milk --due")), Err("--due needs a value".to_string()));
+        assert_eq!(parse(&args("list --all=yes")), Err("--all doesn't take a value".to_string()));
+        assert_eq!(parse(&args("list --all --done")), Err("--all and --done can't be used together".to_string()));
+        assert_eq!(parse(&args("export csv --label home")), Err("--label can only be used with markdown exports".to_string()));
+        assert_eq!(parse(&args("add milk --due 2017-11-05 --tz Mars/Olympus")), Err("unknown time zone \"Mars/Olympus\"".to_string()));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2017-11-05", None), Ok(Date::day(2017, 11, 5).unwrap()));
+        assert_eq!(parse_date("2017-11-05 12:00", None), Ok(Date::from_millis(1509883200000).unwrap()));
+        assert_eq!(parse_date("2017-11-05T12:00:01", None), Ok(Date::from_millis(1509883201000).unwrap()));
+        assert_eq!(parse_date("2017-11-05 07:00", Some("America/New_York")), Date::from_millis_in(1509883200000, "America/New_York").map_err(String::from));
+        assert_eq!(parse_date("2017-11-05", Some("UTC")), Err("--tz can't be used with a day".to_string()));
+        assert_eq!(parse_date("next week", None), Err("can't read \"next week\" as a date".to_string()));
+    }
+}
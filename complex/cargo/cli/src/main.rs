@@ -0,0 +1,208 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! `toodle`, for reading and editing a to-do list database from the command line.
+//! Everything goes through `list::ListManager`, so the tool reads and writes
+//! databases exactly as the apps do.
+
+extern crate chrono;
+extern crate list;
+
+mod args;
+mod output;
+
+use std::env;
+use std::process;
+
+use list::csv::CsvExportOptions;
+use list::dates::Date;
+use list::items::Item;
+use list::json;
+use list::labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+use list::ListManager;
+
+use args::{
+    Command,
+    ExportFormat,
+    Filter,
+    Format,
+    Invocation,
+    USAGE,
+};
+
+fn main() {
+    let args: Result<Vec<String>, String> = env::args_os().skip(1)
+        .map(|arg| arg.into_string().map_err(|arg| format!("argument {:?} isn't valid UTF-8", arg)))
+        .collect();
+    let invocation = match args.and_then(|args| args::parse(&args)) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("toodle: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        },
+    };
+    match run(invocation) {
+        Ok(out) => print!("{}", out),
+        Err(e) => {
+            eprintln!("toodle: {}", e);
+            process::exit(1);
+        },
+    }
+}
+
+/// Carry out the command, returning what it prints.
+fn run(invocation: Invocation) -> Result<String, String> {
+    if invocation.command == Command::Help {
+        return Ok(format!("{}\n", USAGE));
+    }
+    let mut manager = open(invocation.db)?;
+    let format = invocation.format;
+
+    match invocation.command {
+        Command::Add { name, due, labels } => {
+            let labels = labels.iter().map(|name| label_for_item(&manager, name)).collect::<Result<Vec<Label>, String>>()?;
+            let uuid = manager.create_item(&Item::new("".to_string(), name, due, None, labels))?;
+            Ok(show_item(&fetch(&mut manager, &uuid)?, &format))
+        },
+        Command::List { filter, label } => {
+            let items = match label {
+                Some(name) => manager.fetch_items_with_label(&existing_label(&manager, &name)?),
+                None => manager.fetch_items(),
+            };
+            Ok(show_items(&filtered(items, &filter), &format))
+        },
+        Command::Complete { item, undo } => {
+            let mut item = find_item(&mut manager, &item)?;
+            item.completion_date = if undo { None } else { Some(Date::now()) };
+            let labels = item.labels.clone();
+            manager.update_item(&item, labels)?;
+            Ok(show_item(&fetch(&mut manager, &item.uuid)?, &format))
+        },
+        Command::Edit { item, name, due, add_labels, remove_labels } => {
+            let mut item = find_item(&mut manager, &item)?;
+            let existing_labels = item.labels.clone();
+            if let Some(name) = name {
+                item.name = name;
+            }
+            if let Some(due) = due {
+                item.due_date = due;
+            }
+            for name in add_labels.iter() {
+                if !item.labels.iter().any(|label| &label.name == name) {
+                    item.labels.push(label_for_item(&manager, name)?);
+                }
+            }
+            for name in remove_labels.iter() {
+                if !item.labels.iter().any(|label| &label.name == name) {
+                    return Err(format!("item doesn't have label {:?}", name));
+                }
+                item.labels.retain(|label| &label.name != name);
+            }
+            manager.update_item(&item, existing_labels)?;
+            Ok(show_item(&fetch(&mut manager, &item.uuid)?, &format))
+        },
+        Command::Labels => {
+            let labels = manager.fetch_labels();
+            Ok(match format {
+                Format::Table => output::labels_table(&labels),
+                Format::Json => format!("{}\n", json::json_array(&labels, json::label_json)),
+            })
+        },
+        Command::CreateLabel { name, color } => {
+            let color = color.unwrap_or_else(|| DEFAULT_COLOR.to_string());
+            let label = manager.create_label(name.clone(), color).ok_or_else(|| format!("label {:?} already exists", name))?;
+            Ok(match format {
+                Format::Table => output::labels_table(&[label]),
+                Format::Json => format!("{}\n", json::label_json(&label)),
+            })
+        },
+        Command::Search { text, filter } => {
+            let text = text.to_lowercase();
+            let items = manager.fetch_items().into_iter().filter(|item| item.name.to_lowercase().contains(&text)).collect();
+            Ok(show_items(&filtered(items, &filter), &format))
+        },
+        Command::Export { format, label } => Ok(match format {
+            ExportFormat::Ics => manager.export_ics(),
+            ExportFormat::Csv => manager.export_csv(&CsvExportOptions::default()).map_err(|e| e.to_string())?,
+            ExportFormat::TodoTxt => {
+                let export = manager.export_todo_txt();
+                for dropped in export.dropped.iter() {
+                    eprintln!("toodle: line {}: {}", dropped.line, dropped.field);
+                }
+                export.text
+            },
+            ExportFormat::Markdown => match label {
+                Some(name) => manager.export_markdown(Some(&existing_label(&manager, &name)?)),
+                None => manager.export_markdown(None),
+            },
+        }),
+        Command::Help => unreachable!(),
+    }
+}
+
+/// Open the database at `db`, or at `$TOODLE_DB`, creating it if it doesn't exist.
+fn open(db: Option<String>) -> Result<ListManager, String> {
+    let db = match db.or_else(|| env::var("TOODLE_DB").ok()) {
+        Some(db) => db,
+        None => return Err("no database given; use --db PATH or set TOODLE_DB".to_string()),
+    };
+    ListManager::open(&db)
+}
+
+/// The item whose uuid is, or starts with, `prefix`.
+fn find_item(manager: &mut ListManager, prefix: &str) -> Result<Item, String> {
+    if prefix.is_empty() {
+        return Err("no item given".to_string());
+    }
+    let mut matches: Vec<Item> = manager.fetch_items().into_iter().filter(|item| item.uuid.starts_with(prefix)).collect();
+    match matches.len() {
+        0 => Err(format!("no item matches {:?}", prefix)),
+        1 => Ok(matches.remove(0)),
+        count => Err(format!("{:?} matches {} items", prefix, count)),
+    }
+}
+
+fn fetch(manager: &mut ListManager, uuid: &String) -> Result<Item, String> {
+    manager.fetch_item(uuid).ok_or_else(|| format!("item {} wasn't saved", uuid))
+}
+
+fn existing_label(manager: &ListManager, name: &String) -> Result<Label, String> {
+    manager.fetch_label(name).ok_or_else(|| format!("no label called {:?}", name))
+}
+
+/// The label called `name`, created with the default color if it doesn't exist.
+fn label_for_item(manager: &ListManager, name: &String) -> Result<Label, String> {
+    manager.fetch_or_create_label(name, &DEFAULT_COLOR.to_string()).ok_or_else(|| format!("can't create label {:?}", name))
+}
+
+fn filtered(items: Vec<Item>, filter: &Filter) -> Vec<Item> {
+    items.into_iter().filter(|item| match *filter {
+        Filter::Open => item.completion_date.is_none(),
+        Filter::Done => item.completion_date.is_some(),
+        Filter::All => true,
+    }).collect()
+}
+
+fn show_item(item: &Item, format: &Format) -> String {
+    match *format {
+        Format::Table => output::items_table(::std::slice::from_ref(item)),
+        Format::Json => format!("{}\n", json::item_json(item)),
+    }
+}
+
+fn show_items(items: &[Item], format: &Format) -> String {
+    match *format {
+        Format::Table => output::items_table(items),
+        Format::Json => format!("{}\n", json::json_array(items, json::item_json)),
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Items and labels as aligned tables for people. Scripts get JSON from
+//! `list::json` instead.
+
+use list::dates::Date;
+use list::items::Item;
+use list::labels::Label;
+
+/// Characters of the uuid shown in tables, which is enough to pick out an item.
+const SHORT_UUID: usize = 8;
+
+pub fn items_table(items: &[Item]) -> String {
+    let rows = items.iter().map(|item| vec![
+        item.uuid.chars().take(SHORT_UUID).collect(),
+        if item.completion_date.is_some() { "x".to_string() } else { "".to_string() },
+        item.priority.map_or("".to_string(), |priority| priority.name().to_string()),
+        item.due_date.as_ref().map_or("".to_string(), format_date),
+        cell(&item.name),
+        cell(&item.labels.iter().map(|label| label.name.as_str()).collect::<Vec<&str>>().join(", ")),
+    ]).collect();
+    table(&["ID", "DONE", "PRIORITY", "DUE", "NAME", "LABELS"], rows)
+}
+
+pub fn labels_table(labels: &[Label]) -> String {
+    let rows = labels.iter().map(|label| vec![cell(&label.name), cell(&label.color)]).collect();
+    table(&["NAME", "COLOR"], rows)
+}
+
+/// A date the way the person who set it would read it: a day, or a time in its
+/// time zone.
+pub fn format_date(date: &Date) -> String {
+    match date.zone() {
+        _ if date.is_all_day() => date.calendar_day().format("%Y-%m-%d").to_string(),
+        Some(zone) => format!("{} {}", date.local().format("%Y-%m-%d %H:%M"), zone.name()),
+        None => format!("{} UTC", date.local().format("%Y-%m-%d %H:%M")),
+    }
+}
+
+/// `text` with control characters, which would break up the table, as spaces.
+fn cell(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+/// Left-aligned columns separated by two spaces, with trailing space trimmed.
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    for row in Some(headers).into_iter().chain(rows) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths.iter()) {
+            line.push_str(cell);
+            line.extend(::std::iter::repeat_n(' ', width - cell.chars().count() + 2));
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use list::items::Priority;
+
+    fn item() -> Item {
+        let labels = vec![
+            Label { name: "home".to_string(), color: "#ff0000".to_string() },
+            Label { name: "errands".to_string(), color: "#00ff00".to_string() },
+        ];
+        let mut item = Item::new("0123456789abcdef".to_string(), "buy \"milk\"\n".to_string(), Date::from_millis_in(1509883200345, "America/New_York").ok(), Date::day(2017, 11, 6), labels);
+        item.priority = Some(Priority::High);
+        item
+    }
+
+    #[test]
+    fn test_items_table() {
+        let plain = Item::new("fedcba98".to_string(), "call mum".to_string(), Date::from_millis(1509883200000).ok(), None, vec![]);
+        assert_eq!(items_table(&[item(), plain]),
+                   "ID        DONE  PRIORITY  DUE                                NAME         LABELS\n\
+                    01234567  x     high      2017-11-05 07:00 America/New_York  buy \"milk\"   home, errands\n\
+                    fedcba98                  2017-11-05 12:00 UTC               call mum\n");
+    }
+
+    #[test]
+    fn test_labels_table() {
+        assert_eq!(labels_table(&item().labels), "NAME     COLOR\nhome     #ff0000\nerrands  #00ff00\n");
+        assert_eq!(labels_table(&[]), "NAME  COLOR\n");
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Runs the `toodle` binary against a database in the temporary directory.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+struct Database {
+    path: PathBuf,
+}
+
+impl Database {
+    fn new(name: &str) -> Database {
+        let path = env::temp_dir().join(format!("toodle-cli-{}-{}.db", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        Database { path: path }
+    }
+
+    /// Run `toodle` with `args`, returning its exit code, stdout and stderr.
+    fn run(&self, args: &[&str]) -> (i32, String, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_toodle"))
+            .arg("--db")
+            .arg(&self.path)
+            .args(args)
+            .env_remove("TOODLE_DB")
+            .output()
+            .expect("expected toodle to run");
+        (output.status.code().unwrap_or(-1),
+         String::from_utf8(output.stdout).unwrap(),
+         String::from_utf8(output.stderr).unwrap())
+    }
+
+    /// Run `toodle` with `args`, expecting it to succeed, and return its stdout.
+    fn ok(&self, args: &[&str]) -> String {
+        let (code, stdout, stderr) = self.run(args);
+        assert_eq!(code, 0, "toodle {:?} failed: {}", args, stderr);
+        stdout
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The value of the first `"key":"value"` string field in `json`.
+fn json_field<'a>(json: &'a str, key: &str) -> &'a str {
+    let start = json.find(&format!("\"{}\":\"", key)).expect("expected the field") + key.len() + 4;
+    &json[start..start + json[start..].find('"').unwrap()]
+}
+
+#[test]
+fn test_add_complete_and_list() {
+    let db = Database::new("list");
+    let milk = db.ok(&["--json", "add", "buy milk", "--due", "2017-11-05", "--label", "errands"]);
+    assert!(milk.contains("\"due_date\":{\"millis\":1509840000000,\"all_day\":true,\"zone\":null}"), "{}", milk);
+    assert!(milk.contains("\"labels\":[{\"name\":\"errands\",\"color\":\"#000000\"}]"), "{}", milk);
+    let uuid = json_field(&milk, "uuid").to_string();
+    db.ok(&["add", "call mum", "--due", "2017-11-05 07:00", "--tz", "America/New_York"]);
+
+    let table = db.ok(&["list"]);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", table);
+    assert_eq!(lines[0], "ID        DONE  PRIORITY  DUE                                NAME      LABELS");
+    assert!(lines[1].ends_with("2017-11-05                         buy milk  errands"), "{}", table);
+    assert!(lines[2].ends_with("2017-11-05 07:00 America/New_York  call mum"), "{}", table);
+
+    let completed = db.ok(&["--json", "complete", &uuid[..6]]);
+    assert!(!completed.contains("\"completion_date\":null"), "{}", completed);
+    assert_eq!(db.ok(&["list"]).lines().count(), 2);
+    assert_eq!(db.ok(&["list", "--done"]).lines().count(), 2);
+    assert_eq!(db.ok(&["list", "--all", "--label", "errands"]).lines().count(), 2);
+
+    db.ok(&["complete", &uuid, "--undo"]);
+    assert_eq!(db.ok(&["list", "--done", "--json"]), "[]\n");
+}
+
+#[test]
+fn test_edit_and_search() {
+    let db = Database::new("edit");
+    let uuid = json_field(&db.ok(&["--json", "add", "buy milk", "--label", "errands"]), "uuid").to_string();
+    db.ok(&["add", "call mum"]);
+
+    let edited = db.ok(&["edit", &uuid, "--json", "--name", "buy oat milk", "--due", "2017-11-05T12:00", "--add-label", "home", "--remove-label", "errands"]);
+    assert!(edited.contains("\"name\":\"buy oat milk\""), "{}", edited);
+    assert!(edited.contains("\"due_date\":{\"millis\":1509883200000,\"all_day\":false,\"zone\":null}"), "{}", edited);
+    assert!(edited.contains("\"labels\":[{\"name\":\"home\",\"color\":\"#000000\"}]"), "{}", edited);
+    let cleared = db.ok(&["edit", &uuid, "--json", "--no-due"]);
+    assert!(cleared.contains("\"due_date\":null"), "{}", cleared);
+
+    let found = db.ok(&["search", "OAT", "--json"]);
+    assert_eq!(json_field(&found, "uuid"), uuid);
+    assert_eq!(db.ok(&["search", "bread", "--json"]), "[]\n");
+
+    let (code, _, stderr) = db.run(&["edit", &uuid, "--remove-label", "errands"]);
+    assert_eq!((code, stderr.as_str()), (1, "toodle: item doesn't have label \"errands\"\n"));
+    let (code, _, stderr) = db.run(&["complete", "zzz"]);
+    assert_eq!((code, stderr.as_str()), (1, "toodle: no item matches \"zzz\"\n"));
+}
+
+#[test]
+fn test_labels() {
+    let db = Database::new("labels");
+    db.ok(&["label", "create", "home", "--color", "#ff0000"]);
+    db.ok(&["label", "create", "work"]);
+    assert_eq!(db.ok(&["label"]), "NAME  COLOR\nhome  #ff0000\nwork  #000000\n");
+    assert_eq!(db.ok(&["label", "list", "--json"]), "[{\"name\":\"home\",\"color\":\"#ff0000\"},{\"name\":\"work\",\"color\":\"#000000\"}]\n");
+
+    let (code, _, stderr) = db.run(&["label", "create", "home"]);
+    assert_eq!((code, stderr.as_str()), (1, "toodle: label \"home\" already exists\n"));
+    let (code, _, stderr) = db.run(&["list", "--label", "play"]);
+    assert_eq!((code, stderr.as_str()), (1, "toodle: no label called \"play\"\n"));
+}
+
+#[test]
+fn test_export() {
+    let db = Database::new("export");
+    db.ok(&["add", "buy milk", "--label", "errands"]);
+    db.ok(&["add", "call mum", "--label", "family"]);
+    assert_eq!(db.ok(&["export", "markdown", "--label", "family"]), "# family\n\n- [ ] call mum #family\n");
+    assert_eq!(db.ok(&["export", "todotxt"]), "buy milk +errands\ncall mum +family\n");
+    assert!(db.ok(&["export", "ics"]).contains("SUMMARY:buy milk"));
+    assert!(db.ok(&["export", "csv"]).lines().count() == 3);
+
+    db.ok(&["add", "x marks the spot"]);
+    let (code, stdout, stderr) = db.run(&["export", "todotxt"]);
+    assert_eq!(code, 0);
+    assert!(stdout.ends_with("x marks the spot\n"), "{}", stdout);
+    assert_eq!(stderr, "toodle: line 3: name \"x marks the spot\" won't read back the same\n");
+}
+
+#[test]
+fn test_usage_errors() {
+    let db = Database::new("usage");
+    let (code, _, stderr) = db.run(&["frobnicate"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("toodle: unknown command \"frobnicate\"\n\nusage: toodle"), "{}", stderr);
+    assert!(db.ok(&["help"]).starts_with("usage: toodle"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_toodle")).arg("list").env_remove("TOODLE_DB").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "toodle: no database given; use --db PATH or set TOODLE_DB\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_toodle")).args(["--db", "/nonexistent/dir/todo.db", "list"]).output().unwrap();
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "toodle: /nonexistent/dir: no such directory\n");
+}
@@ -8,23 +8,1007 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+extern crate log;
+
+/// Conversions between Rust strings and the C strings and byte ranges passed across
+/// the FFI.
+///
+/// Strict conversions fail on NULL, on bytes that aren't UTF-8 and, for C strings
+/// going out, on interior NUL bytes that would truncate the string. The lossy variants
+/// only fail on NULL, replacing invalid UTF-8 with U+FFFD and dropping NUL bytes.
+/// Hosts that can pass a length, such as Swift and JNI, can use the length-delimited
+/// conversions, which don't need a terminating NUL and can carry NUL bytes.
 pub mod strings {
+    use std::fmt;
     use std::os::raw::c_char;
     use std::ffi::{
         CString,
         CStr
     };
+    use std::slice;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum StringError {
+        /// The pointer is NULL.
+        Null,
+        /// The bytes aren't UTF-8. The ones before this offset are.
+        InvalidUtf8(usize),
+        /// The string has a NUL byte at this offset, so it can't be a C string.
+        InteriorNul(usize),
+    }
+
+    impl fmt::Display for StringError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                StringError::Null => write!(f, "string is NULL"),
+                StringError::InvalidUtf8(offset) => write!(f, "string isn't valid UTF-8 at byte {}", offset),
+                StringError::InteriorNul(offset) => write!(f, "string has a NUL byte at byte {}", offset),
+            }
+        }
+    }
+
+    impl From<StringError> for String {
+        fn from(error: StringError) -> String {
+            error.to_string()
+        }
+    }
+
+    /// Copy a NUL-terminated C string, failing if it is NULL or isn't UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// A non-NULL `cchar` must point to a NUL-terminated string that stays valid for
+    /// the call. The caller keeps ownership of it.
+    pub unsafe fn c_char_to_string(cchar: *const c_char) -> Result<String, StringError> {
+        if cchar.is_null() {
+            return Err(StringError::Null);
+        }
+        bytes_to_utf8(CStr::from_ptr(cchar).to_bytes())
+    }
+
+    /// Copy a NUL-terminated C string, replacing anything that isn't UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// Same as `c_char_to_string`.
+    pub unsafe fn c_char_to_string_lossy(cchar: *const c_char) -> Result<String, StringError> {
+        if cchar.is_null() {
+            return Err(StringError::Null);
+        }
+        Ok(CStr::from_ptr(cchar).to_string_lossy().into_owned())
+    }
+
+    /// Copy `len` bytes of UTF-8 starting at `data`, which may be NULL if `len` is 0.
+    ///
+    /// # Safety
+    ///
+    /// A non-NULL `data` must point to at least `len` readable bytes that stay valid
+    /// for the call. The caller keeps ownership of them.
+    pub unsafe fn bytes_to_string(data: *const u8, len: usize) -> Result<String, StringError> {
+        bytes_to_utf8(byte_slice(data, len)?)
+    }
+
+    /// Copy `len` bytes starting at `data`, replacing anything that isn't UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// Same as `bytes_to_string`.
+    pub unsafe fn bytes_to_string_lossy(data: *const u8, len: usize) -> Result<String, StringError> {
+        Ok(String::from_utf8_lossy(byte_slice(data, len)?).into_owned())
+    }
+
+    /// Make a C string, failing if `r_string` has a NUL byte in it. Free the result with
+    /// `free_c_char`.
+    pub fn string_to_c_char(r_string: String) -> Result<*mut c_char, StringError> {
+        string_to_c_string(r_string).map(CString::into_raw)
+    }
+
+    /// Make a C string, dropping any NUL bytes in `r_string`.
+    pub fn string_to_c_char_lossy(r_string: String) -> *mut c_char {
+        let bytes: Vec<u8> = r_string.into_bytes().into_iter().filter(|&b| b != 0).collect();
+        CString::new(bytes).unwrap().into_raw()
+    }
+
+    /// Like `string_to_c_char`, but leaves the C string owned by Rust.
+    pub fn string_to_c_string(r_string: String) -> Result<CString, StringError> {
+        CString::new(r_string).map_err(|e| StringError::InteriorNul(e.nul_position()))
+    }
+
+    /// Free a string made by `string_to_c_char`. NULL is ignored.
+    ///
+    /// # Safety
+    ///
+    /// A non-NULL `cchar` must have come from `string_to_c_char` or
+    /// `string_to_c_char_lossy` and not have been freed already. It can't be used
+    /// afterwards.
+    pub unsafe fn free_c_char(cchar: *const c_char) {
+        if !cchar.is_null() {
+            let _ = CString::from_raw(cchar as *mut c_char);
+        }
+    }
+
+    fn bytes_to_utf8(bytes: &[u8]) -> Result<String, StringError> {
+        match ::std::str::from_utf8(bytes) {
+            Ok(string) => Ok(string.to_string()),
+            Err(e) => Err(StringError::InvalidUtf8(e.valid_up_to())),
+        }
+    }
+
+    unsafe fn byte_slice<'a>(data: *const u8, len: usize) -> Result<&'a [u8], StringError> {
+        if len == 0 {
+            Ok(&[])
+        } else if data.is_null() {
+            Err(StringError::Null)
+        } else {
+            Ok(slice::from_raw_parts(data, len))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use std::ptr;
+
+        /// A xorshift generator, so that failures can be reproduced from the seed.
+        struct Random(u64);
+
+        impl Random {
+            fn next(&mut self) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0
+            }
+
+            /// Random bytes, biased towards the ones that matter for UTF-8 and C strings.
+            fn bytes(&mut self) -> Vec<u8> {
+                let len = (self.next() % 24) as usize;
+                (0..len).map(|_| {
+                    match self.next() % 4 {
+                        0 => b"\0a\xc3\xa9\xe2\x98\x95\xf0\x9f\x8d\x95\xff"[(self.next() % 12) as usize],
+                        1 => (self.next() % 0x80) as u8,
+                        _ => self.next() as u8,
+                    }
+                }).collect()
+            }
+        }
+
+        #[test]
+        fn test_strict_conversions() {
+            unsafe {
+                let c_string = CString::new("café ☕").unwrap();
+                assert_eq!(c_char_to_string(c_string.as_ptr()), Ok("café ☕".to_string()));
+                let invalid = CString::new(vec![b'a', 0xc3, b'b']).unwrap();
+                assert_eq!(c_char_to_string(invalid.as_ptr()), Err(StringError::InvalidUtf8(1)));
+                assert_eq!(c_char_to_string(ptr::null()), Err(StringError::Null));
+
+                assert_eq!(string_to_c_char("a\0b".to_string()), Err(StringError::InteriorNul(1)));
+                let round_trip = string_to_c_char("café".to_string()).unwrap();
+                assert_eq!(c_char_to_string(round_trip), Ok("café".to_string()));
+                free_c_char(round_trip);
+                free_c_char(ptr::null());
+            }
+        }
+
+        #[test]
+        fn test_lossy_conversions() {
+            unsafe {
+                let invalid = CString::new(vec![b'a', 0xc3, b'b']).unwrap();
+                assert_eq!(c_char_to_string_lossy(invalid.as_ptr()), Ok("a\u{fffd}b".to_string()));
+                assert_eq!(c_char_to_string_lossy(ptr::null()), Err(StringError::Null));
+
+                let stripped = string_to_c_char_lossy("a\0b\0".to_string());
+                assert_eq!(c_char_to_string(stripped), Ok("ab".to_string()));
+                free_c_char(stripped);
+            }
+        }
+
+        #[test]
+        fn test_length_delimited_conversions() {
+            unsafe {
+                let bytes = b"a\0b";
+                assert_eq!(bytes_to_string(bytes.as_ptr(), 3), Ok("a\0b".to_string()));
+                assert_eq!(bytes_to_string(bytes.as_ptr(), 1), Ok("a".to_string()));
+                assert_eq!(bytes_to_string(ptr::null(), 0), Ok("".to_string()));
+                assert_eq!(bytes_to_string(ptr::null(), 1), Err(StringError::Null));
+                assert_eq!(bytes_to_string(b"\xe2\x98".as_ptr(), 2), Err(StringError::InvalidUtf8(0)));
+                assert_eq!(bytes_to_string_lossy(b"\xe2\x98!".as_ptr(), 3), Ok("\u{fffd}!".to_string()));
+            }
+        }
+
+        #[test]
+        fn test_random_bytes() {
+            let mut random = Random(0x2545f4914f6cdd1d);
+            for _ in 0..10000 {
+                let bytes = random.bytes();
+                let expected = String::from_utf8(bytes.clone());
+                unsafe {
+                    let strict = bytes_to_string(bytes.as_ptr(), bytes.len());
+                    match expected {
+                        Ok(ref string) => assert_eq!(strict.as_ref(), Ok(string), "{:?}", bytes),
+                        Err(ref e) => assert_eq!(strict, Err(StringError::InvalidUtf8(e.utf8_error().valid_up_to())), "{:?}", bytes),
+                    }
+                    let lossy = bytes_to_string_lossy(bytes.as_ptr(), bytes.len()).unwrap();
+                    assert_eq!(lossy, String::from_utf8_lossy(&bytes), "{:?}", bytes);
+
+                    // Going out as a C string only fails on NUL bytes, and what comes back
+                    // in is what went out.
+                    let nul = lossy.bytes().position(|b| b == 0);
+                    match string_to_c_char(lossy.clone()) {
+                        Ok(c_string) => {
+                            assert_eq!(nul, None, "{:?}", bytes);
+                            assert_eq!(c_char_to_string(c_string), Ok(lossy.clone()));
+                            free_c_char(c_string);
+                        },
+                        Err(e) => assert_eq!(Some(e), nul.map(StringError::InteriorNul), "{:?}", bytes),
+                    }
+                    let c_string = string_to_c_char_lossy(lossy.clone());
+                    assert_eq!(c_char_to_string(c_string), Ok(lossy.replace('\0', "")));
+                    free_c_char(c_string);
+
+                    // Reading the C string view of the bytes agrees with reading up to the
+                    // first NUL.
+                    let terminated: Vec<u8> = bytes.iter().cloned().take_while(|&b| b != 0).collect();
+                    let c_bytes = CString::new(terminated.clone()).unwrap();
+                    assert_eq!(c_char_to_string_lossy(c_bytes.as_ptr()).unwrap(), String::from_utf8_lossy(&terminated));
+                }
+            }
+        }
+    }
+}
+
+/// Bytes handed across the FFI.
+pub mod buffers {
+    use std::ptr;
+
+    /// Bytes handed across the FFI, freed with `toodle_buffer_free`. `data` is NULL if
+    /// the call that returned the buffer failed; an empty buffer is a non-NULL `data`
+    /// with a `len` of 0.
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct ByteBuffer {
+        pub len: usize,
+        pub data: *mut u8,
+    }
+
+    impl ByteBuffer {
+        pub fn from_vec(bytes: Vec<u8>) -> ByteBuffer {
+            let bytes = bytes.into_boxed_slice();
+            ByteBuffer {
+                len: bytes.len(),
+                data: Box::into_raw(bytes) as *mut u8,
+            }
+        }
+
+        /// A string's UTF-8 bytes, without a terminating NUL.
+        pub fn from_string(string: String) -> ByteBuffer {
+            ByteBuffer::from_vec(string.into_bytes())
+        }
+
+        pub fn null() -> ByteBuffer {
+            ByteBuffer {
+                len: 0,
+                data: ptr::null_mut(),
+            }
+        }
+
+        /// Take back the bytes of a buffer returned by `from_vec`.
+        ///
+        /// # Safety
+        ///
+        /// The buffer must be NULL or have come unchanged from `from_vec` or
+        /// `from_string`, and this takes ownership of it: no copy of the buffer can be
+        /// freed or read afterwards.
+        pub unsafe fn into_vec(self) -> Option<Vec<u8>> {
+            if self.data.is_null() {
+                None
+            } else {
+                Some(Box::from_raw(ptr::slice_from_raw_parts_mut(self.data, self.len)).into_vec())
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_byte_buffer() {
+            unsafe {
+                let buffer = ByteBuffer::from_vec(vec![1, 2, 3]);
+                assert_eq!(buffer.len, 3);
+                assert_eq!(buffer.into_vec(), Some(vec![1, 2, 3]));
+
+                let empty = ByteBuffer::from_string("".to_string());
+                assert!(!empty.data.is_null());
+                assert_eq!(empty.into_vec(), Some(vec![]));
+                assert_eq!(ByteBuffer::null().into_vec(), None);
+            }
+        }
+    }
+}
+
+/// Opaque 64-bit handles for objects owned by the library.
+///
+/// Objects handed to C live in a `HandleRegistry` and are referred to by handle
+/// rather than by pointer. A handle packs the slot the object lives in together with
+/// the slot's generation, which changes whenever the slot is freed, and the id of the
+/// registry that issued it. Using a handle after its object has been destroyed, or
+/// one from a different registry, is reported as an error rather than touching freed
+/// or unrelated memory. 0 is never a valid handle and plays the part of NULL.
+pub mod handles {
+    use std::fmt;
+    use std::sync::Mutex;
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    pub type Handle = u64;
+
+    const INDEX_BITS: u32 = 32;
+    const GENERATION_BITS: u32 = 16;
+
+    static NEXT_REGISTRY_ID: AtomicUsize = AtomicUsize::new(1);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum HandleError {
+        /// The handle is 0.
+        Null,
+        /// The object the handle referred to has been destroyed.
+        Stale,
+        /// The handle wasn't issued by this registry.
+        Foreign,
+    }
+
+    struct Slot<T> {
+        generation: u16,
+        value: Option<T>,
+    }
+
+    /// A registry of objects of one type, indexed by handle.
+    pub struct HandleMap<T> {
+        id: u16,
+        slots: Vec<Slot<T>>,
+        free: Vec<u32>,
+    }
+
+    impl<T> HandleMap<T> {
+        /// Create a map that only accepts handles it issued itself, as identified by `id`.
+        pub const fn new(id: u16) -> Self {
+            HandleMap {
+                id,
+                slots: Vec::new(),
+                free: Vec::new(),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.slots.len() - self.free.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn insert(&mut self, value: T) -> Handle {
+            let index = match self.free.pop() {
+                Some(index) => index,
+                None => {
+                    self.slots.push(Slot { generation: 1, value: None });
+                    (self.slots.len() - 1) as u32
+                },
+            };
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            (self.id as u64) << (INDEX_BITS + GENERATION_BITS) | (slot.generation as u64) << INDEX_BITS | index as u64
+        }
+
+        pub fn get(&self, handle: Handle) -> Result<&T, HandleError> {
+            let index = self.check(handle)?;
+            Ok(self.slots[index].value.as_ref().unwrap())
+        }
+
+        pub fn get_mut(&mut self, handle: Handle) -> Result<&mut T, HandleError> {
+            let index = self.check(handle)?;
+            Ok(self.slots[index].value.as_mut().unwrap())
+        }
+
+        pub fn remove(&mut self, handle: Handle) -> Result<T, HandleError> {
+            let index = self.check(handle)?;
+            let slot = &mut self.slots[index];
+            // Skip 0 when wrapping so that no handle is ever 0.
+            slot.generation = slot.generation.checked_add(1).unwrap_or(1);
+            self.free.push(index as u32);
+            Ok(slot.value.take().unwrap())
+        }
+
+        /// The index of the occupied slot `handle` refers to.
+        fn check(&self, handle: Handle) -> Result<usize, HandleError> {
+            if handle == 0 {
+                return Err(HandleError::Null);
+            }
+            let id = (handle >> (INDEX_BITS + GENERATION_BITS)) as u16;
+            let generation = (handle >> INDEX_BITS) as u16;
+            let index = (handle as u32) as usize;
+            if id != self.id || index >= self.slots.len() {
+                return Err(HandleError::Foreign);
+            }
+            let slot = &self.slots[index];
+            if slot.generation != generation || slot.value.is_none() {
+                return Err(HandleError::Stale);
+            }
+            Ok(index)
+        }
+    }
+
+    /// A `HandleMap` that can live in a `static` and be shared between threads, whose
+    /// errors name the kind of object it holds.
+    pub struct HandleRegistry<T> {
+        kind: &'static str,
+        map: Mutex<Option<HandleMap<T>>>,
+    }
+
+    impl<T> HandleRegistry<T> {
+        /// Create a registry of objects described as `kind` in error messages.
+        pub const fn new(kind: &'static str) -> Self {
+            HandleRegistry {
+                kind,
+                map: Mutex::new(None),
+            }
+        }
+
+        /// Add `value` to the registry, returning its handle.
+        pub fn insert(&self, value: T) -> Handle {
+            self.with_map(|map| map.insert(value))
+        }
+
+        /// Call `f` with the object `handle` refers to.
+        pub fn get<R, F>(&self, handle: Handle, f: F) -> Result<R, String>
+        where F: FnOnce(&T) -> R {
+            self.with_map(|map| map.get(handle).map(f)).map_err(|e| self.describe(e, handle))
+        }
+
+        /// Call `f` with the object `handle` refers to, mutably.
+        pub fn get_mut<R, F>(&self, handle: Handle, f: F) -> Result<R, String>
+        where F: FnOnce(&mut T) -> R {
+            self.with_map(|map| map.get_mut(handle).map(f)).map_err(|e| self.describe(e, handle))
+        }
+
+        /// Take the object `handle` refers to out of the registry, invalidating `handle`.
+        pub fn remove(&self, handle: Handle) -> Result<T, String> {
+            self.with_map(|map| map.remove(handle)).map_err(|e| self.describe(e, handle))
+        }
+
+        /// Like `remove`, but a handle of 0 is allowed and does nothing, as with freeing NULL.
+        pub fn destroy(&self, handle: Handle) -> Result<(), String> {
+            if handle == 0 {
+                return Ok(());
+            }
+            // Drop the object after releasing the lock, in case it owns handles itself.
+            let value = self.remove(handle)?;
+            drop(value);
+            Ok(())
+        }
+
+        pub fn len(&self) -> usize {
+            self.with_map(|map| map.len())
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        fn with_map<R, F>(&self, f: F) -> R
+        where F: FnOnce(&mut HandleMap<T>) -> R {
+            // A panic while the lock was held can't have left the map half-updated, as
+            // callers' code only runs between its updates, so carry on regardless.
+            let mut map = self.map.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let map = map.get_or_insert_with(|| {
+                HandleMap::new(NEXT_REGISTRY_ID.fetch_add(1, Ordering::SeqCst) as u16)
+            });
+            f(map)
+        }
+
+        fn describe(&self, error: HandleError, handle: Handle) -> String {
+            match error {
+                HandleError::Null => format!("{} handle is 0", self.kind),
+                HandleError::Stale => format!("{} handle {:#x} has been destroyed", self.kind, handle),
+                HandleError::Foreign => format!("{:#x} is not a {} handle", handle, self.kind),
+            }
+        }
+    }
+
+    impl fmt::Display for HandleError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                HandleError::Null => write!(f, "handle is 0"),
+                HandleError::Stale => write!(f, "handle has been destroyed"),
+                HandleError::Foreign => write!(f, "handle was issued by another registry"),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_insert_get_remove() {
+            let mut map = HandleMap::new(1);
+            assert!(map.is_empty());
+            let first = map.insert("first");
+            let second = map.insert("second");
+            assert!(first != 0 && first != second);
+            assert_eq!(map.get(first), Ok(&"first"));
+            *map.get_mut(second).unwrap() = "changed";
+            assert_eq!(map.get(second), Ok(&"changed"));
+            assert_eq!(map.len(), 2);
+
+            assert_eq!(map.remove(first), Ok("first"));
+            assert_eq!(map.len(), 1);
+            assert_eq!(map.get(first), Err(HandleError::Stale));
+            assert_eq!(map.remove(first), Err(HandleError::Stale));
+            map.remove(second).unwrap();
+            assert!(map.is_empty());
+        }
+
+        #[test]
+        fn test_reused_slot_rejects_old_handle() {
+            let mut map = HandleMap::new(1);
+            let old = map.insert(1);
+            map.remove(old).unwrap();
+            let new = map.insert(2);
+            assert_eq!(old as u32, new as u32);
+            assert_eq!(map.get(old), Err(HandleError::Stale));
+            assert_eq!(map.get(new), Ok(&2));
+        }
+
+        #[test]
+        fn test_foreign_and_null_handles() {
+            let mut map = HandleMap::new(1);
+            let mut other = HandleMap::new(2);
+            let handle = other.insert(1);
+            map.insert(1);
+            assert_eq!(map.get(handle), Err(HandleError::Foreign));
+            assert_eq!(map.get(0), Err(HandleError::Null));
+            assert_eq!(map.get(handle + 5), Err(HandleError::Foreign));
+        }
+
+        #[test]
+        fn test_generation_never_makes_zero_handle() {
+            let mut map = HandleMap::new(0);
+            for _ in 0..70000 {
+                let handle = map.insert(());
+                assert!(handle != 0);
+                map.remove(handle).unwrap();
+            }
+        }
+
+        #[test]
+        fn test_registry_messages() {
+            static REGISTRY: HandleRegistry<u8> = HandleRegistry::new("thing");
+            assert!(REGISTRY.is_empty());
+            let handle = REGISTRY.insert(1);
+            assert!(!REGISTRY.is_empty());
+            assert_eq!(REGISTRY.get(handle, |value| *value), Ok(1));
+            REGISTRY.get_mut(handle, |value| *value = 2).unwrap();
+            assert_eq!(REGISTRY.destroy(handle), Ok(()));
+            assert_eq!(REGISTRY.destroy(0), Ok(()));
+            assert!(REGISTRY.is_empty());
+            assert_eq!(REGISTRY.get(handle, |value| *value), Err(format!("thing handle {:#x} has been destroyed", handle)));
+            assert_eq!(REGISTRY.get(0, |value| *value), Err("thing handle is 0".to_string()));
+            assert_eq!(REGISTRY.remove(1), Err("0x1 is not a thing handle".to_string()));
+        }
+    }
+}
+
+/// Keeps failures inside exported functions from crossing the C boundary.
+///
+/// Every exported function runs its body through `guard`, which turns both returned
+/// errors and panics into a message that C callers can read with
+/// `toodle_last_error()` and a sentinel return value such as NULL or 0.
+pub mod error {
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::os::raw::c_char;
+    use std::panic::{
+        self,
+        AssertUnwindSafe,
+    };
+
+    use strings::{
+        bytes_to_string,
+        c_char_to_string,
+        StringError,
+    };
+
+    thread_local! {
+        static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+    }
+
+    /// The error from the most recent call into the library on this thread, or
+    /// `None` if it succeeded.
+    pub fn last_error() -> Option<String> {
+        LAST_ERROR.with(|error| error.borrow().clone())
+    }
+
+    pub fn set_last_error(message: String) {
+        LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+    }
+
+    pub fn clear_last_error() {
+        LAST_ERROR.with(|error| *error.borrow_mut() = None);
+    }
+
+    /// Run `body`, returning its value. If it fails or panics, the failure is recorded
+    /// as the last error and `default` is returned instead.
+    pub fn guard<T, F>(default: T, body: F) -> T
+    where F: FnOnce() -> Result<T, String> {
+        clear_last_error();
+        match panic::catch_unwind(AssertUnwindSafe(body)) {
+            Ok(Ok(value)) => value,
+            Ok(Err(message)) => {
+                set_last_error(message);
+                default
+            },
+            Err(payload) => {
+                set_last_error(format!("panic: {}", panic_message(&payload)));
+                default
+            },
+        }
+    }
+
+    fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        }
+    }
+
+    /// Borrow the value behind the argument `name`, failing if it is NULL.
+    ///
+    /// # Safety
+    ///
+    /// A non-NULL `ptr` must point to a valid `T` that nothing mutates for `'a`.
+    pub unsafe fn check_ref<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, String> {
+        if ptr.is_null() {
+            Err(format!("{} is NULL", name))
+        } else {
+            Ok(&*ptr)
+        }
+    }
+
+    /// Mutably borrow the value behind the argument `name`, failing if it is NULL.
+    ///
+    /// # Safety
+    ///
+    /// A non-NULL `ptr` must point to a valid `T` that nothing else reads or writes
+    /// for `'a`.
+    pub unsafe fn check_mut<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T, String> {
+        if ptr.is_null() {
+            Err(format!("{} is NULL", name))
+        } else {
+            Ok(&mut *ptr)
+        }
+    }
+
+    /// Copy the string argument `name`, failing if it is NULL or isn't UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// Same as `c_char_to_string`.
+    pub unsafe fn check_str(ptr: *const c_char, name: &str) -> Result<String, String> {
+        c_char_to_string(ptr).map_err(|e| describe_string_error(e, name))
+    }
+
+    /// Copy the string argument `name` unless it is NULL, failing if it isn't UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// Same as `c_char_to_string`.
+    pub unsafe fn check_optional_str(ptr: *const c_char, name: &str) -> Result<Option<String>, String> {
+        if ptr.is_null() {
+            Ok(None)
+        } else {
+            check_str(ptr, name).map(Some)
+        }
+    }
+
+    /// Copy the length-delimited string argument `name`, failing if it isn't UTF-8 or
+    /// is NULL with a non-zero length.
+    ///
+    /// # Safety
+    ///
+    /// Same as `bytes_to_string`.
+    pub unsafe fn check_bytes_str(data: *const u8, len: usize, name: &str) -> Result<String, String> {
+        bytes_to_string(data, len).map_err(|e| describe_string_error(e, name))
+    }
+
+    fn describe_string_error(error: StringError, name: &str) -> String {
+        match error {
+            StringError::Null => format!("{} is NULL", name),
+            StringError::InvalidUtf8(offset) => format!("{} isn't valid UTF-8 at byte {}", name, offset),
+            StringError::InteriorNul(offset) => format!("{} has a NUL byte at byte {}", name, offset),
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use std::ptr;
+
+        #[test]
+        fn test_guard_records_errors() {
+            assert_eq!(guard(0, || Ok(1)), 1);
+            assert_eq!(last_error(), None);
+
+            assert_eq!(guard(0, || Err("failed".to_string())), 0);
+            assert_eq!(last_error(), Some("failed".to_string()));
+
+            assert_eq!(guard(2, || Ok(3)), 3);
+            assert_eq!(last_error(), None);
+        }
+
+        #[test]
+        fn test_guard_catches_panics() {
+            assert_eq!(guard(0, || -> Result<u8, String> { panic!("no value") }), 0);
+            assert_eq!(last_error(), Some("panic: no value".to_string()));
+
+            let index = 3;
+            assert_eq!(guard(0, || Ok(vec![1, 2][index])), 0);
+            assert!(last_error().unwrap().starts_with("panic: index out of bounds"));
+        }
+
+        #[test]
+        fn test_null_arguments() {
+            unsafe {
+                assert_eq!(check_ref(ptr::null::<u8>(), "item"), Err("item is NULL".to_string()));
+                assert_eq!(check_mut(ptr::null_mut::<u8>(), "item"), Err("item is NULL".to_string()));
+                assert_eq!(check_str(ptr::null(), "name"), Err("name is NULL".to_string()));
+                assert_eq!(check_optional_str(ptr::null(), "name"), Ok(None));
+                assert_eq!(check_bytes_str(ptr::null(), 2, "name"), Err("name is NULL".to_string()));
+                let invalid = b"ab\xff\0";
+                assert_eq!(check_str(invalid.as_ptr() as *const c_char, "name"), Err("name isn't valid UTF-8 at byte 2".to_string()));
+                assert_eq!(check_optional_str(invalid.as_ptr() as *const c_char, "name"), Err("name isn't valid UTF-8 at byte 2".to_string()));
+                assert_eq!(check_bytes_str(invalid.as_ptr(), 4, "name"), Err("name isn't valid UTF-8 at byte 2".to_string()));
+                assert_eq!(check_bytes_str(invalid.as_ptr(), 2, "name"), Ok("ab".to_string()));
+                let value = 1;
+                assert_eq!(check_ref(&value, "value"), Ok(&1));
+            }
+        }
+    }
+}
+
+/// Forwards the library's log messages to a callback registered by the host.
+///
+/// The crates log through the `log` facade, attaching structured fields such as an
+/// item's uuid as key-values. Nothing is logged until the host registers a callback
+/// with `toodle_set_logger`, which lets iOS forward messages to os_log and Android to
+/// logcat.
+pub mod logging {
+    use std::ffi::CString;
+    use std::fmt::Write;
+    use std::os::raw::{
+        c_char,
+        c_void,
+    };
+    use std::sync::RwLock;
+
+    use log::{
+        self,
+        kv,
+        Level,
+        LevelFilter,
+        Log,
+        Metadata,
+        Record,
+    };
+
+    /// How severe a log message is, from most to least. Levels cross the FFI as their
+    /// numbers, since C can pass any value where it expects an enum.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum LogLevel {
+        Error = 1,
+        Warn = 2,
+        Info = 3,
+        Debug = 4,
+        Trace = 5,
+    }
 
-    pub fn c_char_to_string(cchar: *const c_char) -> String {
-        let c_str = unsafe { CStr::from_ptr(cchar) };
-        let r_str = match c_str.to_str() {
-            Err(_) => "",
-            Ok(string) => string,
-        };
-        r_str.to_string()
+    impl LogLevel {
+        pub fn from_number(number: u32) -> Result<LogLevel, String> {
+            match number {
+                1 => Ok(LogLevel::Error),
+                2 => Ok(LogLevel::Warn),
+                3 => Ok(LogLevel::Info),
+                4 => Ok(LogLevel::Debug),
+                5 => Ok(LogLevel::Trace),
+                _ => Err(format!("unknown log level {}", number)),
+            }
+        }
     }
 
-    pub fn string_to_c_char(r_string: String) -> *mut c_char {
-        CString::new(r_string).unwrap().into_raw()
+    impl From<Level> for LogLevel {
+        fn from(level: Level) -> LogLevel {
+            match level {
+                Level::Error => LogLevel::Error,
+                Level::Warn => LogLevel::Warn,
+                Level::Info => LogLevel::Info,
+                Level::Debug => LogLevel::Debug,
+                Level::Trace => LogLevel::Trace,
+            }
+        }
+    }
+
+    impl From<LogLevel> for LevelFilter {
+        fn from(level: LogLevel) -> LevelFilter {
+            match level {
+                LogLevel::Error => LevelFilter::Error,
+                LogLevel::Warn => LevelFilter::Warn,
+                LogLevel::Info => LevelFilter::Info,
+                LogLevel::Debug => LevelFilter::Debug,
+                LogLevel::Trace => LevelFilter::Trace,
+            }
+        }
+    }
+
+    /// Receives a log message, or is NULL to turn logging off. `target` names the
+    /// module that logged the message, such as `list::items`, and `fields` holds its
+    /// structured fields as space-separated `key=value` pairs, or is empty. `level`
+    /// is a `LogLevel`. The strings are only valid for the duration of the call, which
+    /// may come from any thread.
+    pub type LogCallback = Option<extern "C" fn(context: *mut c_void, level: u32, target: *const c_char, message: *const c_char, fields: *const c_char)>;
+
+    #[derive(Clone, Copy)]
+    struct Sink {
+        callback: extern "C" fn(*mut c_void, u32, *const c_char, *const c_char, *const c_char),
+        context: *mut c_void,
+    }
+
+    // The host is responsible for the context being usable from any thread.
+    unsafe impl Send for Sink {}
+    unsafe impl Sync for Sink {}
+
+    struct HostLogger {
+        sink: RwLock<Option<Sink>>,
+    }
+
+    static LOGGER: HostLogger = HostLogger { sink: RwLock::new(None) };
+
+    impl Log for HostLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &Record) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            // Let go of the lock before calling the host, whose callback may replace
+            // itself with `set_logger`.
+            let sink = match *self.sink.read().unwrap_or_else(|e| e.into_inner()) {
+                Some(sink) => sink,
+                None => return,
+            };
+            let target = lossy_c_string(record.target().to_string());
+            let message = lossy_c_string(record.args().to_string());
+            let fields = lossy_c_string(format_fields(record.key_values()));
+            (sink.callback)(sink.context, LogLevel::from(record.level()) as u32, target.as_ptr(), message.as_ptr(), fields.as_ptr());
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Send messages at `level` or more severe to `callback`, replacing any callback
+    /// registered before, or stop logging if `callback` is `None`. A message already
+    /// being logged on another thread may still reach the old callback. Fails if
+    /// something other than the library has installed a logger.
+    pub fn set_logger(callback: LogCallback, context: *mut c_void, level: LogLevel) -> Result<(), String> {
+        let mut sink = LOGGER.sink.write().unwrap_or_else(|e| e.into_inner());
+        if !is_installed() {
+            log::set_logger(&LOGGER).map_err(|_| "another logger is already installed".to_string())?;
+        }
+        *sink = callback.map(|callback| Sink { callback, context });
+        log::set_max_level(if sink.is_some() { level.into() } else { LevelFilter::Off });
+        Ok(())
+    }
+
+    fn is_installed() -> bool {
+        log::logger() as *const dyn Log as *const () == &LOGGER as *const HostLogger as *const ()
+    }
+
+    fn format_fields(source: &dyn kv::Source) -> String {
+        struct Fields(String);
+
+        impl<'kvs> kv::VisitSource<'kvs> for Fields {
+            fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+                if !self.0.is_empty() {
+                    self.0.push(' ');
+                }
+                write!(self.0, "{}={}", key, value)?;
+                Ok(())
+            }
+        }
+
+        let mut fields = Fields(String::new());
+        // Formatting into a string can't fail.
+        let _ = source.visit(&mut fields);
+        fields.0
+    }
+
+    fn lossy_c_string(string: String) -> CString {
+        let bytes: Vec<u8> = string.into_bytes().into_iter().filter(|&b| b != 0).collect();
+        CString::new(bytes).unwrap()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        use std::ffi::CStr;
+        use std::ptr;
+        use std::sync::Mutex;
+
+        static MESSAGES: Mutex<Vec<(LogLevel, String, String, String)>> = Mutex::new(Vec::new());
+
+        extern "C" fn record(_context: *mut c_void, level: u32, target: *const c_char, message: *const c_char, fields: *const c_char) {
+            let string = |s: *const c_char| unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() };
+            MESSAGES.lock().unwrap().push((LogLevel::from_number(level).unwrap(), string(target), string(message), string(fields)));
+        }
+
+        /// Hands logging over to `record` from inside the callback.
+        extern "C" fn replace_self(context: *mut c_void, level: u32, target: *const c_char, message: *const c_char, fields: *const c_char) {
+            set_logger(Some(record), ptr::null_mut(), LogLevel::Trace).unwrap();
+            record(context, level, target, message, fields);
+        }
+
+        fn take_messages() -> Vec<(LogLevel, String, String, String)> {
+            MESSAGES.lock().unwrap().drain(..).collect()
+        }
+
+        #[test]
+        fn test_forwards_messages_to_callback() {
+            log::info!(target: "test", "before any callback");
+            assert_eq!(log::max_level(), LevelFilter::Off);
+
+            set_logger(Some(record), ptr::null_mut(), LogLevel::Info).unwrap();
+            log::info!(target: "test", uuid = "abc", count = 2; "created {}", "item");
+            log::warn!(target: "test", "no\0nul");
+            log::debug!(target: "test", "too verbose");
+            assert_eq!(take_messages(), vec![
+                (LogLevel::Info, "test".to_string(), "created item".to_string(), "uuid=abc count=2".to_string()),
+                (LogLevel::Warn, "test".to_string(), "nonul".to_string(), "".to_string()),
+            ]);
+
+            set_logger(Some(record), ptr::null_mut(), LogLevel::Trace).unwrap();
+            log::trace!(target: "test", "verbose");
+            assert_eq!(take_messages().len(), 1);
+
+            set_logger(Some(replace_self), ptr::null_mut(), LogLevel::Info).unwrap();
+            log::info!(target: "test", "replacing");
+            log::trace!(target: "test", "replaced");
+            assert_eq!(take_messages().len(), 2);
+
+            set_logger(None, ptr::null_mut(), LogLevel::Trace).unwrap();
+            log::error!(target: "test", "after the callback is removed");
+            assert_eq!(take_messages(), vec![]);
+            assert_eq!(log::max_level(), LevelFilter::Off);
+        }
+
+        #[test]
+        fn test_level_numbers() {
+            for number in 1..6 {
+                assert_eq!(LogLevel::from_number(number).map(|level| level as u32), Ok(number));
+            }
+            assert_eq!(LogLevel::from_number(0), Err("unknown log level 0".to_string()));
+            assert_eq!(LogLevel::from_number(6), Err("unknown log level 6".to_string()));
+        }
     }
 }
@@ -0,0 +1,144 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * A copy of an item, the Java counterpart of an `ItemHandle`. Setters only change
+ * the copy; save it with {@link ListManager#updateItem}. Dates are milliseconds
+ * since 1970-01-01 UTC, or null for none.
+ */
+public class Item implements AutoCloseable {
+    static {
+        System.loadLibrary("toodle");
+    }
+
+    long handle;
+
+    /**
+     * An unsaved item with no name.
+     */
+    public Item() {
+        this(nativeNew());
+    }
+
+    Item(long handle) {
+        this.handle = handle;
+    }
+
+    public String getUuid() {
+        return nativeGetUuid(handle);
+    }
+
+    public String getName() {
+        return nativeGetName(handle);
+    }
+
+    public void setName(String name) {
+        nativeSetName(handle, name);
+    }
+
+    public Long getDueDate() {
+        return nativeGetDueDate(handle);
+    }
+
+    public void setDueDate(Long dueDate) {
+        nativeSetDueDate(handle, dueDate != null, dueDate != null ? dueDate : 0);
+    }
+
+    public boolean isDueDateAllDay() {
+        return nativeDueDateIsAllDay(handle);
+    }
+
+    /**
+     * The IANA time zone the due date was set in, or null if it is all-day or UTC.
+     */
+    public String getDueDateZone() {
+        return nativeGetDueDateZone(handle);
+    }
+
+    public void setDueDateInZone(long millis, String zone) {
+        nativeSetDueDateInZone(handle, millis, zone);
+    }
+
+    /**
+     * Make the item due on the whole UTC day that {@code millis} falls on.
+     */
+    public void setAllDayDueDate(long millis) {
+        nativeSetAllDayDueDate(handle, millis);
+    }
+
+    public Long getCompletionDate() {
+        return nativeGetCompletionDate(handle);
+    }
+
+    public void setCompletionDate(Long completionDate) {
+        nativeSetCompletionDate(handle, completionDate != null, completionDate != null ? completionDate : 0);
+    }
+
+    public boolean isCompletionDateAllDay() {
+        return nativeCompletionDateIsAllDay(handle);
+    }
+
+    public String getCompletionDateZone() {
+        return nativeGetCompletionDateZone(handle);
+    }
+
+    public void setCompletionDateInZone(long millis, String zone) {
+        nativeSetCompletionDateInZone(handle, millis, zone);
+    }
+
+    public void setAllDayCompletionDate(long millis) {
+        nativeSetAllDayCompletionDate(handle, millis);
+    }
+
+    /**
+     * The item's priority, or null if it has none.
+     */
+    public Priority getPriority() {
+        return Priority.fromValue(nativeGetPriority(handle));
+    }
+
+    public void setPriority(Priority priority) {
+        nativeSetPriority(handle, priority != null ? priority.value : 0);
+    }
+
+    public List<Label> getLabels() {
+        List<Label> labels = new ArrayList<>();
+        for (long label : nativeGetLabels(handle)) {
+            labels.add(new Label(label));
+        }
+        return labels;
+    }
+
+    @Override
+    public void close() {
+        nativeDestroy(handle);
+        handle = 0;
+    }
+
+    private static native long nativeNew();
+    private static native void nativeDestroy(long item);
+    private static native String nativeGetUuid(long item);
+    private static native String nativeGetName(long item);
+    private static native void nativeSetName(long item, String name);
+    private static native Long nativeGetDueDate(long item);
+    private static native void nativeSetDueDate(long item, boolean hasDueDate, long dueDate);
+    private static native boolean nativeDueDateIsAllDay(long item);
+    private static native String nativeGetDueDateZone(long item);
+    private static native void nativeSetDueDateInZone(long item, long millis, String zone);
+    private static native void nativeSetAllDayDueDate(long item, long millis);
+    private static native Long nativeGetCompletionDate(long item);
+    private static native void nativeSetCompletionDate(long item, boolean hasCompletionDate, long completionDate);
+    private static native boolean nativeCompletionDateIsAllDay(long item);
+    private static native String nativeGetCompletionDateZone(long item);
+    private static native void nativeSetCompletionDateInZone(long item, long millis, String zone);
+    private static native void nativeSetAllDayCompletionDate(long item, long millis);
+    private static native int nativeGetPriority(long item);
+    private static native void nativeSetPriority(long item, int priority);
+    private static native long[] nativeGetLabels(long item);
+}
@@ -0,0 +1,44 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * A copy of a label, the Java counterpart of a `LabelHandle`. Changes only affect
+ * the copy.
+ */
+public class Label implements AutoCloseable {
+    static {
+        System.loadLibrary("toodle");
+    }
+
+    long handle;
+
+    Label(long handle) {
+        this.handle = handle;
+    }
+
+    public String getName() {
+        return nativeGetName(handle);
+    }
+
+    public String getColor() {
+        return nativeGetColor(handle);
+    }
+
+    public void setColor(String color) {
+        nativeSetColor(handle, color);
+    }
+
+    @Override
+    public void close() {
+        nativeDestroy(handle);
+        handle = 0;
+    }
+
+    private static native void nativeDestroy(long label);
+    private static native String nativeGetName(long label);
+    private static native String nativeGetColor(long label);
+    private static native void nativeSetColor(long label, String color);
+}
@@ -0,0 +1,90 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * Creates, updates and fetches items and labels, the Java counterpart of a
+ * `ListManagerHandle`. Dates are milliseconds since 1970-01-01 UTC, or null for
+ * none.
+ */
+public class ListManager implements AutoCloseable {
+    static {
+        System.loadLibrary("toodle");
+    }
+
+    private long handle;
+
+    ListManager(long handle) {
+        this.handle = handle;
+    }
+
+    public List<Label> getAllLabels() {
+        List<Label> labels = new ArrayList<>();
+        for (long label : nativeGetAllLabels(handle)) {
+            labels.add(new Label(label));
+        }
+        return labels;
+    }
+
+    public Label createLabel(String name, String color) {
+        return new Label(nativeCreateLabel(handle, name, color));
+    }
+
+    public List<Item> getAllItems() {
+        List<Item> items = new ArrayList<>();
+        for (long item : nativeGetAllItems(handle)) {
+            items.add(new Item(item));
+        }
+        return items;
+    }
+
+    public Item createItem(String name, Long dueDate, Long completionDate, List<Label> labels) {
+        return new Item(nativeCreateItem(handle, name,
+                                         dueDate != null, dueDate != null ? dueDate : 0,
+                                         completionDate != null, completionDate != null ? completionDate : 0,
+                                         handles(labels)));
+    }
+
+    /**
+     * Save {@code item} with the given fields. A null {@code name} keeps the item's
+     * name, while null dates clear them.
+     */
+    public void updateItem(Item item, String name, Long dueDate, Long completionDate, List<Label> labels) {
+        nativeUpdateItem(handle, item.handle, name,
+                         dueDate != null, dueDate != null ? dueDate : 0,
+                         completionDate != null, completionDate != null ? completionDate : 0,
+                         handles(labels));
+    }
+
+    @Override
+    public void close() {
+        nativeDestroy(handle);
+        handle = 0;
+    }
+
+    private static long[] handles(List<Label> labels) {
+        long[] handles = new long[labels.size()];
+        for (int index = 0; index < handles.length; index++) {
+            handles[index] = labels.get(index).handle;
+        }
+        return handles;
+    }
+
+    private static native void nativeDestroy(long manager);
+    private static native long[] nativeGetAllLabels(long manager);
+    private static native long nativeCreateLabel(long manager, String name, String color);
+    private static native long[] nativeGetAllItems(long manager);
+    private static native long nativeCreateItem(long manager, String name,
+                                                boolean hasDueDate, long dueDate,
+                                                boolean hasCompletionDate, long completionDate,
+                                                long[] labels);
+    private static native void nativeUpdateItem(long manager, long item, String name,
+                                                boolean hasDueDate, long dueDate,
+                                                boolean hasCompletionDate, long completionDate,
+                                                long[] labels);
+}
@@ -0,0 +1,32 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * How severe a log message is, from most to least. The values match the library's
+ * `LogLevel`.
+ */
+public enum LogLevel {
+    ERROR(1),
+    WARN(2),
+    INFO(3),
+    DEBUG(4),
+    TRACE(5);
+
+    final int value;
+
+    LogLevel(int value) {
+        this.value = value;
+    }
+
+    static LogLevel fromValue(int value) {
+        for (LogLevel level : values()) {
+            if (level.value == value) {
+                return level;
+            }
+        }
+        return TRACE;
+    }
+}
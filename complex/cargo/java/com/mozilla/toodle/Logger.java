@@ -0,0 +1,18 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * Receives the library's log messages, for example to pass them on to logcat. It may
+ * be called from any thread.
+ */
+public interface Logger {
+    /**
+     * @param target the module that logged the message, such as {@code list::items}
+     * @param fields the message's structured fields as space-separated
+     *               {@code key=value} pairs, or an empty string
+     */
+    void log(LogLevel level, String target, String message, String fields);
+}
@@ -0,0 +1,33 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * How important an item is. The values match the library's `Priority`, with 0
+ * for none.
+ */
+public enum Priority {
+    LOW(1),
+    MEDIUM(2),
+    HIGH(3);
+
+    final int value;
+
+    Priority(int value) {
+        this.value = value;
+    }
+
+    /**
+     * The priority numbered {@code value}, or null for 0.
+     */
+    static Priority fromValue(int value) {
+        for (Priority priority : values()) {
+            if (priority.value == value) {
+                return priority;
+            }
+        }
+        return null;
+    }
+}
@@ -0,0 +1,46 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * A to-do store, the Java counterpart of a `ToodleHandle`.
+ */
+public class Toodle implements AutoCloseable {
+    static {
+        System.loadLibrary("toodle");
+    }
+
+    private long handle;
+
+    /**
+     * Open the store at {@code uri}, or an in-memory store if it is {@code ":memory:"}.
+     */
+    public Toodle(String uri) {
+        this.handle = nativeNew(uri);
+    }
+
+    public ListManager getList() {
+        return new ListManager(nativeList(handle));
+    }
+
+    /**
+     * Send the library's log messages at {@code level} or more severe to
+     * {@code logger}, or turn logging off if it is null.
+     */
+    public static void setLogger(Logger logger, LogLevel level) {
+        nativeSetLogger(logger, level.value);
+    }
+
+    @Override
+    public void close() {
+        nativeDestroy(handle);
+        handle = 0;
+    }
+
+    private static native long nativeNew(String uri);
+    private static native void nativeDestroy(long toodle);
+    private static native long nativeList(long toodle);
+    private static native void nativeSetLogger(Logger logger, int level);
+}
@@ -0,0 +1,15 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle;
+
+/**
+ * Thrown when a call into the library fails, with the message `toodle_last_error`
+ * would have returned to a C caller.
+ */
+public class ToodleException extends RuntimeException {
+    public ToodleException(String message) {
+        super(message);
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Times `ListManager` against generated lists of thousands of labelled items,
+//! with SQLite and with in-memory storage.
+//!
+//! Run with `cargo bench -p list`, or `cargo bench -p list -- fetch_items` to run
+//! only the benchmarks whose names contain `fetch_items`. Fetching should take
+//! about the same time per item at every size; time per item that grows with the
+//! list means queries are being run per item again.
+
+extern crate list;
+
+use std::env;
+use std::hint::black_box;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use list::dates::Date;
+use list::items::Item;
+use list::labels::Label;
+use list::storage::MemoryStorage;
+use list::ListManager;
+
+const ITEM_COUNTS: [usize; 2] = [500, 5000];
+const LABEL_COUNT: usize = 50;
+/// How long to keep repeating a benchmark for, after its first run.
+const BENCH_TIME: Duration = Duration::from_millis(500);
+
+struct Dataset {
+    manager: ListManager,
+    uuids: Vec<String>,
+    labels: Vec<Label>,
+}
+
+/// Fill `manager` with `count` items, each with up to three of `LABEL_COUNT`
+/// labels, and some with due and completion dates.
+fn generate(mut manager: ListManager, count: usize) -> Dataset {
+    let labels: Vec<Label> = (0..LABEL_COUNT).map(|i| {
+        manager.create_label(format!("label {}", i), format!("#{:06x}", i * 0x051f)).expect("expected a label")
+    }).collect();
+    let uuids = (0..count).map(|i| {
+        let item_labels = (0..i % 4).map(|k| labels[(i + k * 7) % LABEL_COUNT].clone()).collect();
+        let due_date = if i % 2 == 0 { Date::from_millis(1509883200000 + i as i64 * 60000).ok() } else { None };
+        let completion_date = if i % 5 == 0 { Date::day(2017, 11, 5) } else { None };
+        manager.create_item(&Item::new("".to_string(), format!("item {}", i), due_date, completion_date, item_labels)).unwrap()
+    }).collect();
+    Dataset { manager: manager, uuids: uuids, labels: labels }
+}
+
+/// Run `f` until `BENCH_TIME` has passed, and print the mean time per run.
+fn bench<F: FnMut()>(name: &str, filter: &Option<String>, mut f: F) {
+    if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+        return;
+    }
+    f();
+    let start = Instant::now();
+    let mut runs: u32 = 0;
+    while runs < 3 || start.elapsed() < BENCH_TIME {
+        f();
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    println!("{:<48} {:>12.3} ms/run  ({} runs)", name, mean.as_secs_f64() * 1000.0, runs);
+}
+
+fn bench_storage(storage: &str, new_manager: &dyn Fn() -> ListManager, filter: &Option<String>) {
+    for &count in ITEM_COUNTS.iter() {
+        bench(&format!("{}/create_{}_items", storage, count), filter, || {
+            black_box(generate(new_manager(), count));
+        });
+
+        let mut data = generate(new_manager(), count);
+        bench(&format!("{}/fetch_items/{}", storage, count), filter, || {
+            black_box(data.manager.fetch_items());
+        });
+        let label = data.labels[0].clone();
+        bench(&format!("{}/fetch_items_with_label/{}", storage, count), filter, || {
+            black_box(data.manager.fetch_items_with_label(&label));
+        });
+        let sample: Vec<String> = data.uuids.iter().step_by(count / 100).cloned().collect();
+        bench(&format!("{}/fetch_item_x100/{}", storage, count), filter, || {
+            for uuid in sample.iter() {
+                black_box(data.manager.fetch_item(uuid));
+            }
+        });
+        bench(&format!("{}/fetch_labels_for_item_x100/{}", storage, count), filter, || {
+            for uuid in sample.iter() {
+                black_box(data.manager.fetch_labels_for_item(uuid));
+            }
+        });
+        let items: Vec<Item> = sample.iter().filter_map(|uuid| data.manager.fetch_item(uuid)).collect();
+        bench(&format!("{}/update_item_x100/{}", storage, count), filter, || {
+            for item in items.iter() {
+                let mut updated = item.clone();
+                updated.name.push('!');
+                updated.labels = vec![label.clone()];
+                data.manager.update_item(&updated, item.labels.clone()).unwrap();
+                data.manager.update_item(item, updated.labels.clone()).unwrap();
+            }
+        });
+    }
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; anything else names the benchmarks to run.
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    bench_storage("sqlite", &|| ListManager::new(None), &filter);
+    bench_storage("memory", &|| ListManager::with_storage(MemoryStorage::new()), &filter);
+}
@@ -1,32 +1,887 @@
-#ifndef items_h
-#define items_h
+#ifndef TOODLE_ITEMS_H
+#define TOODLE_ITEMS_H
 
-struct list_manager;
-struct label;
-struct item;
+/* Generated by build.rs from the Rust sources. Do not edit by hand. */
 
-const struct item* _Nonnull list_manager_create_item(const struct list_manager* _Nonnull manager, const char* _Nonnull name, const int64_t* _Nullable due_date, const int64_t* _Nullable completion_date, struct label*_Nonnull* _Nonnull list);
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include "labels.h"
 
-const void list_manager_update_item(const struct list_manager* _Nonnull manager, const struct item* _Nonnull item, const char* _Nullable name, const int64_t* _Nullable due_date, const int64_t* _Nullable completion_date, struct label*_Nonnull* _Nullable list);
+/**
+ * An item handed across the FFI, freed with `item_destroy`.
+ */
+typedef uint64_t ItemHandle;
 
-const struct item*_Nonnull*_Nonnull list_manager_get_all_items(const struct list_manager* _Nonnull manager);
-const size_t item_list_count(const struct item*_Nonnull* _Nonnull item);
-const void item_list_destroy(const struct item*_Nonnull* _Nonnull item);
-const struct item* _Nullable item_list_entry_at(const struct item*_Nonnull* _Nonnull item, size_t index);
+/**
+ * Bytes handed across the FFI, freed with `toodle_buffer_free`. `data` is NULL if
+ * the call that returned the buffer failed; an empty buffer is a non-NULL `data`
+ * with a `len` of 0.
+ */
+typedef struct ByteBuffer {
+  size_t len;
+  uint8_t *data;
+} ByteBuffer;
 
-const void item_destroy(const struct item* _Nonnull item);
+/**
+ * A copy of an item's fields that C can read directly. The strings and labels
+ * belong to the snapshot and stay valid until it is passed to `item_snapshot_free`.
+ * Dates are milliseconds since the Unix epoch and are only meaningful when the
+ * matching `has_` flag is set; their zones are NULL when they have none.
+ */
+typedef struct ItemC {
+  const char *uuid;
+  const char *name;
+  bool has_due_date;
+  int64_t due_date;
+  bool due_date_all_day;
+  const char *due_date_zone;
+  bool has_completion_date;
+  int64_t completion_date;
+  bool completion_date_all_day;
+  const char *completion_date_zone;
+  const LabelC *labels;
+  size_t labels_len;
+  /**
+   * 0 for no priority, otherwise 1 for low to 3 for high.
+   */
+  uint8_t priority;
+} ItemC;
 
-const char* _Nonnull item_get_uuid(const struct item* _Nonnull item);
-const char* _Nonnull item_get_name(const struct item* _Nonnull item);
-const void item_set_name(struct item* _Nonnull item, const char* _Nonnull description);
-int64_t* _Nullable item_get_due_date(const struct item* _Nonnull item);
-const void item_set_due_date(struct item* _Nonnull item, const int64_t* _Nullable due_date);
-int64_t* _Nullable item_get_completion_date(const struct item* _Nonnull item);
-const void item_set_completion_date(struct item* _Nonnull item, const int64_t* _Nullable completion_date);
+/**
+ * A list of items handed across the FFI, read with `item_list_count` and
+ * `item_list_entry_at` and freed with `item_list_destroy`.
+ */
+typedef uint64_t ItemListHandle;
 
+/**
+ * A date parsed from a phrase. `millis` is since the Unix epoch; for an all-day
+ * date it's the midnight UTC that starts the day, and otherwise the date is shown
+ * in the zone the phrase was parsed in.
+ */
+typedef struct DatePhraseC {
+  int64_t millis;
+  bool all_day;
+} DatePhraseC;
 
-const struct label*_Nonnull*_Nonnull item_get_labels(const struct list_manager* _Nonnull manager);
-const size_t item_labels_count(const struct label*_Nonnull* _Nonnull label);
-const struct label* _Nullable item_label_at(const struct label*_Nonnull* _Nonnull label, size_t index);
+/**
+ * A cursor handed across the FFI, freed with `item_cursor_destroy`.
+ */
+typedef uint64_t ItemCursorHandle;
 
-#endif /* items_h */
+/**
+ * Receives one item from `list_manager_for_each_item`, and returns false to stop
+ * before the next one. The snapshot is only valid for the duration of the call and
+ * is freed by the library afterwards.
+ */
+typedef bool (*ItemCallback)(void *context, const struct ItemC *item);
+
+/**
+ * A new attachment being written, finished with `attachment_writer_finish` or
+ * discarded with `attachment_writer_destroy`.
+ */
+typedef uint64_t AttachmentWriterHandle;
+
+/**
+ * An attachment handed across the FFI, freed with `attachment_destroy`.
+ */
+typedef uint64_t AttachmentHandle;
+
+/**
+ * A list of attachments handed across the FFI, read with `attachment_list_count`
+ * and `attachment_list_entry_at` and freed with `attachment_list_destroy`.
+ */
+typedef uint64_t AttachmentListHandle;
+
+/**
+ * An attachment's contents being read, freed with `attachment_reader_destroy`.
+ */
+typedef uint64_t AttachmentReaderHandle;
+
+/**
+ * The result of a CSV import handed across the FFI, freed with `csv_import_destroy`.
+ */
+typedef uint64_t CsvImportHandle;
+
+/**
+ * The result of a Markdown import handed across the FFI, freed with
+ * `markdown_import_destroy`.
+ */
+typedef uint64_t MarkdownImportHandle;
+
+/**
+ * The result of `list_manager_quick_add`, freed with `quick_add_destroy`.
+ */
+typedef uint64_t QuickAddHandle;
+
+ItemHandle item_new(void);
+
+void item_destroy(ItemHandle item);
+
+char *item_get_uuid(ItemHandle item);
+
+char *item_get_name(ItemHandle item);
+
+void item_set_name(ItemHandle item, const char *name);
+
+/**
+ * The item's name as UTF-8 without a terminating NUL, for hosts that can take a
+ * length and so can read names with NUL bytes in them. Free with `toodle_buffer_free`.
+ *
+ * # Safety
+ *
+ * `item` is a handle, so any value is safe; an invalid one returns a NULL buffer
+ * and sets the last error. The returned buffer belongs to the caller, who must free
+ * it once with `toodle_buffer_free`.
+ */
+struct ByteBuffer item_get_name_utf8(ItemHandle item);
+
+/**
+ * Set the item's name from `len` bytes of UTF-8 at `name`, which needn't end in NUL.
+ *
+ * # Safety
+ *
+ * A non-NULL `name` must point to at least `len` readable bytes that stay valid for
+ * the call. The caller keeps ownership of them.
+ */
+void item_set_name_utf8(ItemHandle item, const uint8_t *name, size_t len);
+
+/**
+ * The item's due date in milliseconds since the Unix epoch, or NULL if it has none.
+ * Free with `item_date_destroy`.
+ */
+int64_t *item_get_due_date(ItemHandle item);
+
+/**
+ * Free a date returned by `item_get_due_date` or `item_get_completion_date`.
+ *
+ * # Safety
+ *
+ * `date` must be NULL or a date returned by `item_get_due_date` or
+ * `item_get_completion_date` that hasn't been freed already. It can't be read
+ * afterwards.
+ */
+void item_date_destroy(int64_t *date);
+
+/**
+ * Whether the item's due date is a whole day, given as the midnight UTC that starts it.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+bool item_due_date_is_all_day(ItemHandle item);
+
+/**
+ * The IANA time zone of the item's due date, or NULL if it has none. Free with
+ * `toodle_string_free`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`. The returned string belongs to the caller, who must
+ * free it once with `toodle_string_free`.
+ */
+char *item_get_due_date_zone(ItemHandle item);
+
+/**
+ * Set the item's due date to a time in milliseconds since the Unix epoch, with no
+ * time zone, or clear it if `due_date` is NULL.
+ */
+void item_set_due_date(ItemHandle item, const int64_t *due_date);
+
+/**
+ * Set the item's due date to `millis` since the Unix epoch, shown in the IANA time
+ * zone `zone`, e.g. "America/New_York".
+ *
+ * # Safety
+ *
+ * `zone` must point to a NUL-terminated string that stays valid for the call; NULL
+ * is rejected with an error. The caller keeps ownership of it.
+ */
+void item_set_due_date_in_zone(ItemHandle item, int64_t millis, const char *zone);
+
+/**
+ * Make the item due on the whole UTC day that `millis` falls on.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+void item_set_all_day_due_date(ItemHandle item, int64_t millis);
+
+/**
+ * The item's priority: 0 if it has none, otherwise 1 for low to 3 for high.
+ *
+ * # Safety
+ *
+ * `item` is a handle looked up in a registry rather than a pointer, so any value is
+ * safe: 0, or a handle that has been destroyed, fails and sets the last error.
+ */
+uint8_t item_get_priority(ItemHandle item);
+
+/**
+ * Set the item's priority to 1 for low to 3 for high, or clear it with 0.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+void item_set_priority(ItemHandle item, uint8_t priority);
+
+/**
+ * The item's completion date in milliseconds since the Unix epoch, or NULL if it has
+ * none. Free with `item_date_destroy`.
+ */
+int64_t *item_get_completion_date(ItemHandle item);
+
+/**
+ * Whether the item's completion date is a whole day, given as the midnight UTC that
+ * starts it.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+bool item_completion_date_is_all_day(ItemHandle item);
+
+/**
+ * The IANA time zone of the item's completion date, or NULL if it has none. Free
+ * with `toodle_string_free`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *item_get_completion_date_zone(ItemHandle item);
+
+/**
+ * Set the item's completion date to a time in milliseconds since the Unix epoch,
+ * with no time zone, or clear it if `completion_date` is NULL.
+ */
+void item_set_completion_date(ItemHandle item, const int64_t *completion_date);
+
+/**
+ * Set the item's completion date to `millis` since the Unix epoch, shown in the IANA
+ * time zone `zone`.
+ *
+ * # Safety
+ *
+ * Same as `item_set_due_date_in_zone`.
+ */
+void item_set_completion_date_in_zone(ItemHandle item, int64_t millis, const char *zone);
+
+/**
+ * Mark the item as completed on the whole UTC day that `millis` falls on.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+void item_set_all_day_completion_date(ItemHandle item, int64_t millis);
+
+LabelListHandle item_get_labels(ItemHandle item);
+
+size_t item_labels_count(ItemHandle item);
+
+LabelHandle item_label_at(ItemHandle item, size_t index);
+
+/**
+ * Read all of the item's fields, including its labels, in one call. The snapshot
+ * must be freed with `item_snapshot_free`; its pointers are NULL if `item` isn't a
+ * valid handle.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`. The snapshot owns its strings and labels, and must
+ * be passed to `item_snapshot_free` exactly once.
+ */
+struct ItemC item_snapshot(ItemHandle item);
+
+/**
+ * Free a snapshot returned by `item_snapshot`. Each snapshot must be freed once.
+ *
+ * # Safety
+ *
+ * `snapshot` must have come unchanged from `item_snapshot` and not have been freed
+ * already, as this frees its strings and labels. No copy of it can be read
+ * afterwards.
+ */
+void item_snapshot_free(struct ItemC snapshot);
+
+/**
+ * Create an item with the `label_count` labels in `labels`, which may be NULL when
+ * there are none. Dates are milliseconds since the Unix epoch, with no time zone, and
+ * NULL dates leave the item without that date. Returns 0 if the item couldn't be
+ * created, otherwise an item that must be freed with `item_destroy`.
+ */
+ItemHandle list_manager_create_item(ListManagerHandle manager,
+                                    const char *name,
+                                    const int64_t *due_date,
+                                    const int64_t *completion_date,
+                                    const LabelHandle *labels,
+                                    size_t label_count);
+
+/**
+ * Save `item` with the given values, which are then also applied to `item` itself;
+ * if saving fails, `item` is left as it was. A NULL `name` keeps the current name;
+ * NULL dates and labels are cleared. A date equal to the one the item already has
+ * keeps its time zone or stays all-day.
+ */
+void list_manager_update_item(ListManagerHandle manager,
+                              ItemHandle item,
+                              const char *name,
+                              const int64_t *due_date,
+                              const int64_t *completion_date,
+                              const LabelHandle *labels,
+                              size_t label_count);
+
+ItemListHandle list_manager_get_all_items(ListManagerHandle manager);
+
+/**
+ * # Safety
+ *
+ * `item_list` is a handle, so any value is safe. Once destroyed it is invalid, and
+ * destroying it again, or destroying 0, only fails and sets the last error. Items
+ * copied out of the list are separate handles and stay valid.
+ */
+void item_list_destroy(ItemListHandle item_list);
+
+size_t item_list_count(ItemListHandle item_list);
+
+/**
+ * Copy the item at `index`. The returned item must be freed with `item_destroy`.
+ */
+ItemHandle item_list_entry_at(ItemListHandle item_list, size_t index);
+
+/**
+ * # Safety
+ *
+ * `manager` is a handle, so any value is safe. Once destroyed it is invalid, and
+ * destroying it again, or destroying 0, only fails and sets the last error. Other
+ * handles on the same list are unaffected.
+ */
+void list_manager_destroy(ListManagerHandle manager);
+
+/**
+ * Parse `phrase`, such as "tomorrow 5pm" or "next friday", as if it were `now`
+ * milliseconds since the Unix epoch in the IANA time zone `zone`. Returns false if
+ * the phrase isn't a date, leaving `date` untouched.
+ *
+ * # Safety
+ *
+ * `phrase` and `zone` must point to NUL-terminated strings that stay valid for the
+ * call; NULL is rejected with an error. The caller keeps ownership of them. A
+ * non-NULL `date` must point to a `DatePhraseC` that nothing else reads or writes
+ * during the call; it holds no pointers, so there is nothing to free.
+ */
+bool toodle_parse_date_phrase(const char *phrase,
+                              int64_t now,
+                              const char *zone,
+                              struct DatePhraseC *date);
+
+/**
+ * Set the item's due date from `phrase`, parsed as by `toodle_parse_date_phrase`.
+ * Returns false if the phrase isn't a date, leaving the item untouched.
+ *
+ * # Safety
+ *
+ * Same as `toodle_parse_date_phrase` for `phrase` and `zone`, and as
+ * `item_get_priority` for `item`.
+ */
+bool item_set_due_date_phrase(ItemHandle item, const char *phrase, int64_t now, const char *zone);
+
+/**
+ * Make a cursor over every item, which fetches `page_size` items at a time. Returns
+ * 0 if `page_size` is 0, otherwise a cursor that must be freed with
+ * `item_cursor_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemCursorHandle list_manager_item_cursor(ListManagerHandle manager, size_t page_size);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void item_cursor_destroy(ItemCursorHandle cursor);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t item_cursor_page_size(ItemCursorHandle cursor);
+
+/**
+ * How many items there were when the cursor was made.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t item_cursor_total_count(ItemCursorHandle cursor);
+
+/**
+ * Take the next item, fetching the next page first if the current one is used up.
+ * Returns 0 with no error once every item has been returned, 0 with an error if
+ * the page failed to load, otherwise an item that must be freed with `item_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemHandle item_cursor_next(ItemCursorHandle cursor);
+
+/**
+ * Take the items left in the current page, or the next page if they've all been
+ * returned. The list is empty once every item has been returned, and must be freed
+ * with `item_list_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemListHandle item_cursor_next_page(ItemCursorHandle cursor);
+
+/**
+ * Pass a snapshot of every item to `callback`, along with `context`, fetching
+ * `page_size` items at a time, until there are no more or `callback` returns false.
+ * Returns how many items were passed to `callback`, or 0 if iterating failed,
+ * including if a page failed to load part way through.
+ *
+ * # Safety
+ *
+ * `callback` is called on this thread before this returns. The snapshot it is
+ * passed belongs to this function and is freed once `callback` returns, so it must
+ * not be kept or passed to `item_snapshot_free`. `context` is only passed on, and
+ * may be NULL.
+ */
+size_t list_manager_for_each_item(ListManagerHandle manager,
+                                  size_t page_size,
+                                  ItemCallback callback,
+                                  void *context);
+
+/**
+ * Keep the contents of attachments added from now on as files in `directory`,
+ * which is created if it doesn't exist, instead of in the database. The setting is
+ * saved in the database, so it applies to every handle on the same list, including
+ * those opened later. Contents already stored stay where they are.
+ *
+ * # Safety
+ *
+ * `directory` must point to a NUL-terminated string that stays valid for the call;
+ * NULL is rejected with an error. The caller keeps ownership of it.
+ */
+void list_manager_set_attachment_directory(ListManagerHandle manager, const char *directory);
+
+/**
+ * Start writing a new attachment on `item`, which must have been saved. A NULL
+ * `mime_type` is `application/octet-stream`. Returns 0 if the attachment can't be
+ * written, otherwise a writer to pass the contents to with `attachment_writer_write`.
+ *
+ * # Safety
+ *
+ * `name` must point to a NUL-terminated string, and `mime_type` must be NULL or
+ * point to one; each must stay valid for the call. The caller keeps ownership of
+ * them. The writer must be passed once to either `attachment_writer_finish` or
+ * `attachment_writer_destroy`.
+ */
+AttachmentWriterHandle list_manager_attachment_writer(ListManagerHandle manager,
+                                                      ItemHandle item,
+                                                      const char *name,
+                                                      const char *mime_type);
+
+/**
+ * Append the `len` bytes at `data` to the attachment. Returns false if they
+ * couldn't be written.
+ *
+ * # Safety
+ *
+ * A non-NULL `data` must point to at least `len` readable bytes that stay valid for
+ * the call. The caller keeps ownership of them.
+ */
+bool attachment_writer_write(AttachmentWriterHandle writer, const uint8_t *data, size_t len);
+
+/**
+ * Save the attachment written so far. The writer is freed whether or not this
+ * succeeds. Returns 0 if the attachment couldn't be saved, otherwise an attachment
+ * that must be freed with `attachment_destroy`.
+ *
+ * # Safety
+ *
+ * `writer` is a handle, so any value is safe. It is invalid once this returns, so
+ * writing to, finishing or destroying it afterwards only fails and sets the last
+ * error.
+ */
+AttachmentHandle attachment_writer_finish(AttachmentWriterHandle writer);
+
+/**
+ * Discard an attachment that hasn't been finished.
+ *
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void attachment_writer_destroy(AttachmentWriterHandle writer);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`. The list must be freed once with
+ * `attachment_list_destroy`.
+ */
+AttachmentListHandle list_manager_get_item_attachments(ListManagerHandle manager, ItemHandle item);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void attachment_list_destroy(AttachmentListHandle attachment_list);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t attachment_list_count(AttachmentListHandle attachment_list);
+
+/**
+ * Copy the attachment at `index`. The returned attachment must be freed with
+ * `attachment_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+AttachmentHandle attachment_list_entry_at(AttachmentListHandle attachment_list, size_t index);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void attachment_destroy(AttachmentHandle attachment);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *attachment_get_id(AttachmentHandle attachment);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *attachment_get_name(AttachmentHandle attachment);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *attachment_get_mime_type(AttachmentHandle attachment);
+
+/**
+ * The SHA-256 hash of the contents, in lowercase hex.
+ *
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *attachment_get_hash(AttachmentHandle attachment);
+
+/**
+ * The size of the contents in bytes.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+uint64_t attachment_get_size(AttachmentHandle attachment);
+
+/**
+ * Start reading the contents of `attachment` with `attachment_reader_read`. Returns
+ * 0 if they can't be read.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`. The reader must be freed once with
+ * `attachment_reader_destroy`.
+ */
+AttachmentReaderHandle list_manager_attachment_reader(ListManagerHandle manager,
+                                                      AttachmentHandle attachment);
+
+/**
+ * Copy up to `capacity` bytes of the contents into `buffer`, continuing from where
+ * the last read stopped. Returns how many bytes were copied, which is 0 with no
+ * error once all of them have been read.
+ *
+ * # Safety
+ *
+ * A non-NULL `buffer` must point to at least `capacity` writable bytes that nothing
+ * else reads or writes during the call. The caller keeps ownership of it.
+ */
+size_t attachment_reader_read(AttachmentReaderHandle reader, uint8_t *buffer, size_t capacity);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void attachment_reader_destroy(AttachmentReaderHandle reader);
+
+/**
+ * Delete `attachment`, and its contents unless another attachment has the same
+ * ones. Returns false if it had already been deleted.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+bool list_manager_delete_attachment(ListManagerHandle manager, AttachmentHandle attachment);
+
+/**
+ * Delete attachments whose items no longer exist, and stored contents that no
+ * attachment uses. Returns how many attachments and contents were deleted in all.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t list_manager_purge_attachments(ListManagerHandle manager);
+
+/**
+ * Export every item as CSV. `columns` is an optional comma separated list of column
+ * names, `date_format` and `day_format` optional `strftime` formats for timestamps
+ * and all-day dates, and `label_separator` an optional single character to put
+ * between labels; NULL selects the default. Returns NULL if `columns` names an
+ * unknown column, either format is invalid or `label_separator` isn't one character.
+ *
+ * # Safety
+ *
+ * Each of `columns`, `date_format`, `day_format` and `label_separator` must be NULL
+ * or point to a NUL-terminated string that stays valid for the call. The caller
+ * keeps ownership of them. The returned string belongs to the caller, who must free
+ * it once with `toodle_string_free`.
+ */
+char *list_manager_export_csv(ListManagerHandle manager,
+                              const char *columns,
+                              const char *date_format,
+                              const char *day_format,
+                              const char *label_separator);
+
+/**
+ * Import items from CSV using a mapping description such as `"Task=name,Due=due_date"`,
+ * or the default mapping if `mapping` is NULL. Rows that can't be imported are
+ * reported in the result; returns 0 if the mapping is invalid or the file can't be
+ * read at all, such as one without a header row.
+ *
+ * # Safety
+ *
+ * `csv` must point to a NUL-terminated string, and `mapping` and `date_format` must
+ * be NULL or point to one; each must stay valid for the call. The caller keeps
+ * ownership of them. The import must be freed once with `csv_import_destroy`.
+ */
+CsvImportHandle list_manager_import_csv(ListManagerHandle manager,
+                                        const char *csv,
+                                        const char *mapping,
+                                        const char *date_format);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void csv_import_destroy(CsvImportHandle import);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t csv_import_item_count(CsvImportHandle import);
+
+/**
+ * Copy the imported item at `index`. The returned item must be freed with `item_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemHandle csv_import_item_at(CsvImportHandle import, size_t index);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t csv_import_error_count(CsvImportHandle import);
+
+/**
+ * The 1-based row of the error at `index`, counting the header as row 1.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t csv_import_error_row(CsvImportHandle import, size_t index);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *csv_import_error_message(CsvImportHandle import, size_t index);
+
+/**
+ * Export the items with `label`, or every item if `label` is 0, as a Markdown
+ * checklist.
+ *
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *list_manager_export_markdown(ListManagerHandle manager, LabelHandle label);
+
+/**
+ * Create an item for every checklist entry in `markdown`. The result holds the
+ * created items and the nested entries that were flattened into top-level items.
+ *
+ * # Safety
+ *
+ * `markdown` must point to a NUL-terminated string that stays valid for the call;
+ * NULL is rejected with an error. The caller keeps ownership of it. The import must
+ * be freed once with `markdown_import_destroy`.
+ */
+MarkdownImportHandle list_manager_import_markdown(ListManagerHandle manager, const char *markdown);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void markdown_import_destroy(MarkdownImportHandle import);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t markdown_import_item_count(MarkdownImportHandle import);
+
+/**
+ * Copy the imported item at `index`. The returned item must be freed with `item_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemHandle markdown_import_item_at(MarkdownImportHandle import, size_t index);
+
+/**
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t markdown_import_flattened_count(MarkdownImportHandle import);
+
+/**
+ * The 1-based line of the flattened entry at `index`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+size_t markdown_import_flattened_line(MarkdownImportHandle import, size_t index);
+
+/**
+ * The name of the entry the flattened entry at `index` was nested under.
+ *
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *markdown_import_flattened_parent(MarkdownImportHandle import, size_t index);
+
+/**
+ * Every item as an `ItemList` message. The buffer must be freed with
+ * `toodle_buffer_free`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_name_utf8`.
+ */
+struct ByteBuffer list_manager_get_all_items_buffer(ListManagerHandle manager);
+
+/**
+ * Every label as a `LabelList` message. The buffer must be freed with
+ * `toodle_buffer_free`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_name_utf8`.
+ */
+struct ByteBuffer list_manager_get_all_labels_buffer(ListManagerHandle manager);
+
+/**
+ * Free a buffer returned by any of the library's functions.
+ *
+ * # Safety
+ *
+ * `buffer` must have come unchanged from one of the library's functions and not
+ * have been freed already; a NULL buffer is ignored. No copy of it can be read or
+ * freed afterwards.
+ */
+void toodle_buffer_free(struct ByteBuffer buffer);
+
+/**
+ * Create an item from quick-add `text`, such as "Buy milk #groceries !high due
+ * fri", creating any labels that don't exist yet. Due dates are read as if it were
+ * `now` milliseconds since the Unix epoch in the IANA time zone `zone`. Returns 0
+ * if no item was created, otherwise a result to read the item and a description of
+ * what was recognised from.
+ *
+ * # Safety
+ *
+ * `text` and `zone` must point to NUL-terminated strings that stay valid for the
+ * call; NULL is rejected with an error. The caller keeps ownership of them. The
+ * result must be freed once with `quick_add_destroy`.
+ */
+QuickAddHandle list_manager_quick_add(ListManagerHandle manager,
+                                      const char *text,
+                                      int64_t now,
+                                      const char *zone);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void quick_add_destroy(QuickAddHandle quick_add);
+
+/**
+ * Copy the created item. The returned item must be freed with `item_destroy`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`.
+ */
+ItemHandle quick_add_get_item(QuickAddHandle quick_add);
+
+/**
+ * What was recognised besides the name, such as `label "groceries", priority
+ * high`, or an empty string if nothing was. Free with `toodle_string_free`.
+ *
+ * # Safety
+ *
+ * Same as `item_get_due_date_zone`.
+ */
+char *quick_add_get_description(QuickAddHandle quick_add);
+
+#endif /* TOODLE_ITEMS_H */
@@ -1,22 +1,82 @@
-#ifndef categories_h
-#define categories_h
+#ifndef TOODLE_LABELS_H
+#define TOODLE_LABELS_H
 
-#import "items.h"
+/* Generated by build.rs from the Rust sources. Do not edit by hand. */
 
-struct list_manager;
-struct label;
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
 
-struct label* _Nonnull list_manager_create_label(const struct list_manager* _Nonnull manager, const char* _Nonnull name, const char* _Nonnull color);
-const struct label* _Nonnull* _Nonnull list_manager_get_all_labels(const struct list_manager* _Nonnull manager);
-const size_t label_list_count(const struct label* _Nonnull* _Nonnull list);
-const void label_list_destroy(const struct label* _Nonnull* _Nonnull list);
-const struct label* _Nonnull label_list_entry_at(const struct label* _Nonnull* _Nonnull list, size_t index);
-const void add_label(const struct label* _Nonnull* _Nonnull list, const struct label* _Nonnull label);
+typedef uint64_t ListManagerHandle;
 
-const void label_destroy(const struct label* _Nonnull label);
-const char* _Nonnull label_get_name(const struct label* _Nonnull label);
-const char* _Nonnull label_get_color(const struct label* _Nonnull label);
-const void label_set_color(struct label* _Nonnull label, const char* _Nonnull color);
+/**
+ * A label handed across the FFI, freed with `label_destroy`.
+ */
+typedef uint64_t LabelHandle;
 
+/**
+ * A list of labels handed across the FFI, read with `label_list_count` and
+ * `label_list_entry_at` and freed with `label_list_destroy`.
+ */
+typedef uint64_t LabelListHandle;
 
-#endif /* categories_h */
+/**
+ * A copy of a label's fields that C can read directly. The strings belong to the
+ * snapshot and stay valid until it is passed to `label_snapshot_free`.
+ */
+typedef struct LabelC {
+  const char *name;
+  const char *color;
+} LabelC;
+
+void label_destroy(LabelHandle label);
+
+char *label_get_name(LabelHandle label);
+
+char *label_get_color(LabelHandle label);
+
+void label_set_color(LabelHandle label, const char *color);
+
+/**
+ * Create a label. The returned label must be freed with `label_destroy`.
+ */
+LabelHandle list_manager_create_label(ListManagerHandle manager,
+                                      const char *name,
+                                      const char *color);
+
+LabelListHandle list_manager_get_all_labels(ListManagerHandle manager);
+
+/**
+ * # Safety
+ *
+ * Same as `item_list_destroy`.
+ */
+void label_list_destroy(LabelListHandle label_list);
+
+size_t label_list_count(LabelListHandle label_list);
+
+/**
+ * Copy the label at `index`. The returned label must be freed with `label_destroy`.
+ */
+LabelHandle label_list_entry_at(LabelListHandle label_list, size_t index);
+
+/**
+ * Read all of the label's fields in one call. The snapshot must be freed with
+ * `label_snapshot_free`; its strings are NULL if `label` isn't a valid handle.
+ *
+ * # Safety
+ *
+ * Same as `item_get_priority`. The snapshot owns its strings, and must be passed to
+ * `label_snapshot_free` exactly once.
+ */
+struct LabelC label_snapshot(LabelHandle label);
+
+/**
+ * # Safety
+ *
+ * `snapshot` must have come unchanged from `label_snapshot` and not have been freed
+ * already, as this frees its strings. No copy of it can be read afterwards.
+ */
+void label_snapshot_free(struct LabelC snapshot);
+
+#endif /* TOODLE_LABELS_H */
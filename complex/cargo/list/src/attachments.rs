@@ -0,0 +1,1151 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Files attached to items, such as photos.
+//!
+//! Each attachment's name, MIME type, size and SHA-256 hash are kept in the
+//! `attachments` table, and its contents in a blob named by the hash, so attaching
+//! the same file twice stores it once. Blobs live either in the `attachment_blobs`
+//! table or as files in a directory, usually next to the database. Which one new
+//! blobs go to is saved in the database, and each attachment records where its blob
+//! went. A blob is deleted along with the last attachment that uses it, but files
+//! are only touched once the database changes have been committed.
+//!
+//! Contents are written and read in pieces, through an `AttachmentWriter` and an
+//! `AttachmentReader`, so a large file is never held in memory all at once.
+
+use std::env;
+use std::fs::{
+    self,
+    File,
+};
+use std::io::{
+    self,
+    BufWriter,
+    Read,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::os::raw::c_char;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::ptr;
+use std::slice;
+
+use ffi_utils::error::{
+    check_optional_str,
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::strings::string_to_c_char;
+use rusqlite::{
+    self,
+    Connection,
+    DatabaseName,
+    Row,
+};
+use rusqlite::blob::ZeroBlob;
+use sha2::{
+    Digest,
+    Sha256,
+};
+use store::Store;
+use uuid::Uuid;
+
+use items::{
+    ItemHandle,
+    ITEMS,
+};
+use {
+    manager_from_handle,
+    ListManagerHandle,
+};
+
+const SCHEMA: [(&'static str, &'static str); 5] = [
+    ("attachments", r#"CREATE TABLE IF NOT EXISTS attachments (
+                id TEXT NOT NULL PRIMARY KEY,
+                item_uuid TEXT NOT NULL,
+                name TEXT NOT NULL,
+                mime_type TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                directory TEXT
+            )"#),
+    ("attachments_item_uuid", r#"CREATE INDEX IF NOT EXISTS attachments_item_uuid ON attachments (item_uuid)"#),
+    ("attachments_hash", r#"CREATE INDEX IF NOT EXISTS attachments_hash ON attachments (hash)"#),
+    ("attachment_blobs", r#"CREATE TABLE IF NOT EXISTS attachment_blobs (
+                hash TEXT NOT NULL PRIMARY KEY,
+                data BLOB NOT NULL
+            )"#),
+    ("attachment_settings", r#"CREATE TABLE IF NOT EXISTS attachment_settings (
+                name TEXT NOT NULL PRIMARY KEY,
+                value TEXT NOT NULL
+            )"#),
+];
+
+const ATTACHMENT_COLUMNS: &'static str = "id, item_uuid, name, mime_type, size, hash, directory";
+
+/// The `attachment_settings` row naming the directory for new blobs, which is absent
+/// while they go in the database.
+const BLOB_DIRECTORY_SETTING: &'static str = "blob_directory";
+
+/// The MIME type of attachments created without one.
+pub const DEFAULT_MIME_TYPE: &'static str = "application/octet-stream";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub id: String,
+    pub item_uuid: String,
+    pub name: String,
+    pub mime_type: String,
+    /// The size of the contents in bytes.
+    pub size: u64,
+    /// The SHA-256 hash of the contents, in lowercase hex.
+    pub hash: String,
+    /// Where the contents are, which is wherever new blobs went when the attachment
+    /// was added.
+    pub location: BlobLocation,
+}
+
+/// Where the contents of attachments are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlobLocation {
+    /// In the `attachment_blobs` table, which limits them to 2 GiB each.
+    Database,
+    /// As files in the directory, named by their hashes.
+    Directory(PathBuf),
+}
+
+impl BlobLocation {
+    /// The value of the `directory` column for blobs here.
+    fn directory_column(&self) -> Option<String> {
+        match *self {
+            BlobLocation::Database => None,
+            // Directories are checked to be UTF-8 before anything is stored in them.
+            BlobLocation::Directory(ref directory) => Some(directory.to_string_lossy().into_owned()),
+        }
+    }
+
+    fn from_directory_column(directory: Option<String>) -> BlobLocation {
+        match directory {
+            Some(directory) => BlobLocation::Directory(PathBuf::from(directory)),
+            None => BlobLocation::Database,
+        }
+    }
+}
+
+/// The attachments in a store. Where new blobs go is a setting in the database, so
+/// every `Attachments` on the same database agrees on it.
+#[derive(Debug, Clone)]
+pub struct Attachments {
+    store: Store,
+}
+
+fn attachment_from_row(row: &Row) -> rusqlite::Result<Attachment> {
+    let size: i64 = row.get_checked(4)?;
+    Ok(Attachment {
+        id: row.get_checked(0)?,
+        item_uuid: row.get_checked(1)?,
+        name: row.get_checked(2)?,
+        mime_type: row.get_checked(3)?,
+        size: size as u64,
+        hash: row.get_checked(5)?,
+        location: BlobLocation::from_directory_column(row.get_checked(6)?),
+    })
+}
+
+fn query_attachments(conn: &Connection, sql: &str, uuid: &str) -> Result<Vec<Attachment>, String> {
+    let mut stmt = conn.prepare_cached(sql).map_err(|e| e.to_string())?;
+    let attachments: rusqlite::Result<Vec<Attachment>> = stmt.query_and_then(&[&uuid], attachment_from_row).map_err(|e| e.to_string())?.collect();
+    attachments.map_err(|e| e.to_string())
+}
+
+fn blob_rowid(conn: &Connection, hash: &str) -> Result<Option<i64>, String> {
+    let mut stmt = conn.prepare_cached(r#"SELECT rowid FROM attachment_blobs WHERE hash=?"#).map_err(|e| e.to_string())?;
+    let mut rows = stmt.query_and_then(&[&hash], |row| row.get_checked(0)).map_err(|e| e.to_string())?;
+    rows.next().map_or(Ok(None), |rowid| rowid.map(Some).map_err(|e| e.to_string()))
+}
+
+fn stored_location(conn: &Connection) -> Result<BlobLocation, String> {
+    let mut stmt = conn.prepare_cached(r#"SELECT value FROM attachment_settings WHERE name=?"#).map_err(|e| e.to_string())?;
+    let mut rows = stmt.query_and_then(&[&BLOB_DIRECTORY_SETTING], |row| row.get_checked(0)).map_err(|e| e.to_string())?;
+    let directory = rows.next().map_or(Ok(None), |directory| directory.map(Some).map_err(|e| e.to_string()))?;
+    Ok(BlobLocation::from_directory_column(directory))
+}
+
+/// The file holding the blob for `hash` in `directory`.
+fn blob_path(directory: &Path, hash: &str) -> PathBuf {
+    // Spread the blobs over subdirectories, as some file systems slow down with
+    // thousands of files in one directory.
+    directory.join(&hash[..2]).join(&hash[2..])
+}
+
+/// Remove blob files whose rows are gone. They are only removed once that has been
+/// committed, so a failure leaves a stray file for `purge` rather than an attachment
+/// without contents.
+fn remove_blob_files(paths: &[PathBuf]) {
+    for path in paths.iter() {
+        trace!(path:? = path; "deleting unused blob");
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!(path:? = path, error:% = e; "failed to remove unused blob");
+            }
+        }
+    }
+}
+
+impl Attachments {
+    /// The attachments in `store`, creating their tables if they don't exist yet.
+    pub fn new(store: Store) -> Self {
+        {
+            let conn = store.conn.lock().unwrap();
+            for &(name, sql) in SCHEMA.iter() {
+                if let Err(e) = conn.execute(sql, &[]) {
+                    error!(name = name, error:% = e; "failed to create table or index");
+                }
+            }
+        }
+        Attachments {
+            store: store,
+        }
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Where the contents of new attachments go, which is the database unless
+    /// `set_location` has said otherwise.
+    pub fn location(&self) -> Result<BlobLocation, String> {
+        let conn = self.store.conn.lock().unwrap();
+        stored_location(&conn)
+    }
+
+    /// Keep the contents of attachments added from now on in `location`, creating the
+    /// directory if there is one. Contents already stored stay where they are and
+    /// can still be read.
+    pub fn set_location(&self, location: &BlobLocation) -> Result<(), String> {
+        let conn = self.store.conn.lock().unwrap();
+        match *location {
+            BlobLocation::Database => {
+                conn.execute(r#"DELETE FROM attachment_settings WHERE name=?"#, &[&BLOB_DIRECTORY_SETTING])
+                    .map_err(|e| e.to_string())?;
+            },
+            BlobLocation::Directory(ref directory) => {
+                let path = directory.to_str().ok_or_else(|| format!("attachment directory {:?} isn't valid UTF-8", directory))?;
+                fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+                conn.execute(r#"INSERT OR REPLACE INTO attachment_settings (name, value) VALUES (?, ?)"#, &[&BLOB_DIRECTORY_SETTING, &path])
+                    .map_err(|e| e.to_string())?;
+            },
+        }
+        debug!(location:? = location; "set attachment location");
+        Ok(())
+    }
+
+    /// Start writing a new attachment on the item with `item_uuid`, which is saved
+    /// by `AttachmentWriter::finish`.
+    pub fn writer(&self, item_uuid: &str, name: &str, mime_type: &str) -> Result<AttachmentWriter, String> {
+        let location = self.location()?;
+        let directory = match location {
+            BlobLocation::Database => env::temp_dir(),
+            BlobLocation::Directory(ref directory) => directory.join("tmp"),
+        };
+        fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+        let path = directory.join(format!("toodle-attachment-{}", Uuid::new_v4().simple()));
+        let file = File::create(&path).map_err(|e| e.to_string())?;
+        Ok(AttachmentWriter {
+            attachments: self.clone(),
+            item_uuid: item_uuid.to_string(),
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            location: location,
+            path: path,
+            file: BufWriter::new(file),
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    /// Attach everything `contents` reads to the item with `item_uuid`.
+    pub fn add<R: Read>(&self, item_uuid: &str, name: &str, mime_type: &str, contents: &mut R) -> Result<Attachment, String> {
+        let mut writer = self.writer(item_uuid, name, mime_type)?;
+        io::copy(contents, &mut writer).map_err(|e| e.to_string())?;
+        writer.finish()
+    }
+
+    pub fn fetch(&self, id: &str) -> Result<Option<Attachment>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM attachments WHERE id=?", ATTACHMENT_COLUMNS);
+        Ok(query_attachments(&conn, &sql, id)?.pop())
+    }
+
+    /// The item's attachments, in the order they were added.
+    pub fn fetch_for_item(&self, item_uuid: &str) -> Result<Vec<Attachment>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM attachments WHERE item_uuid=? ORDER BY rowid", ATTACHMENT_COLUMNS);
+        query_attachments(&conn, &sql, item_uuid)
+    }
+
+    /// Start reading the contents of `attachment` from the beginning.
+    pub fn reader(&self, attachment: &Attachment) -> Result<AttachmentReader, String> {
+        let source = match attachment.location {
+            BlobLocation::Database => {
+                let conn = self.store.conn.lock().unwrap();
+                let rowid = blob_rowid(&conn, &attachment.hash)?.ok_or_else(|| format!("no contents for attachment {}", attachment.id))?;
+                BlobSource::Database(rowid)
+            },
+            BlobLocation::Directory(ref directory) => {
+                let file = File::open(blob_path(directory, &attachment.hash)).map_err(|e| e.to_string())?;
+                BlobSource::File(file)
+            },
+        };
+        Ok(AttachmentReader {
+            store: self.store.clone(),
+            source: source,
+            offset: 0,
+        })
+    }
+
+    /// Delete the attachment with `id`. Returns false if there is none.
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        Ok(!self.delete_where("id", id)?.is_empty())
+    }
+
+    /// Delete every attachment on the item with `item_uuid`, returning how many
+    /// there were.
+    pub fn delete_for_item(&self, item_uuid: &str) -> Result<usize, String> {
+        let count = self.delete_where("item_uuid", item_uuid)?.len();
+        debug!(uuid = item_uuid, count = count; "deleted attachments");
+        Ok(count)
+    }
+
+    /// Delete attachments whose items no longer exist, and blobs that no attachment
+    /// uses, such as those left by a crash. Returns how many of each were deleted.
+    pub fn purge(&self) -> Result<(usize, usize), String> {
+        let mut files = vec![];
+        let (attachments, blobs) = {
+            let mut conn = self.store.conn.lock().unwrap();
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            // Look for stray files in every directory blobs have gone to, including
+            // those only the deleted attachments used.
+            let mut directories = vec![];
+            if let BlobLocation::Directory(directory) = stored_location(&tx)? {
+                directories.push(directory);
+            }
+            {
+                let mut stmt = tx.prepare(r#"SELECT DISTINCT directory FROM attachments WHERE directory IS NOT NULL"#)
+                                 .map_err(|e| e.to_string())?;
+                let rows = stmt.query_and_then(&[], |row| row.get_checked::<_, String>(0)).map_err(|e| e.to_string())?;
+                for directory in rows {
+                    let directory = PathBuf::from(directory.map_err(|e| e.to_string())?);
+                    if !directories.contains(&directory) {
+                        directories.push(directory);
+                    }
+                }
+            }
+            // The items are in the same database, in `SqliteStorage`'s table.
+            let attachments = tx.execute(r#"DELETE FROM attachments WHERE item_uuid NOT IN (SELECT uuid FROM items)"#, &[])
+                                .map_err(|e| e.to_string())? as usize;
+            let mut blobs = tx.execute(r#"DELETE FROM attachment_blobs WHERE hash NOT IN (SELECT hash FROM attachments WHERE directory IS NULL)"#, &[])
+                              .map_err(|e| e.to_string())? as usize;
+            for directory in directories.into_iter() {
+                let hashes = blob_files(&directory)?;
+                let location = BlobLocation::Directory(directory);
+                for hash in hashes.into_iter() {
+                    if delete_unused_blob(&tx, &hash, &location, &mut files)? {
+                        blobs += 1;
+                    }
+                }
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+            (attachments, blobs)
+        };
+        remove_blob_files(&files);
+        debug!(attachments = attachments, blobs = blobs; "purged attachments");
+        Ok((attachments, blobs))
+    }
+
+    /// Delete the attachments whose `column` is `value`, along with any blobs no
+    /// other attachment uses, returning the deleted attachments.
+    fn delete_where(&self, column: &str, value: &str) -> Result<Vec<Attachment>, String> {
+        let mut files = vec![];
+        let attachments = {
+            let mut conn = self.store.conn.lock().unwrap();
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            let sql = format!("SELECT {} FROM attachments WHERE {}=?", ATTACHMENT_COLUMNS, column);
+            let attachments = query_attachments(&tx, &sql, value)?;
+            tx.execute(&format!("DELETE FROM attachments WHERE {}=?", column), &[&value]).map_err(|e| e.to_string())?;
+            for attachment in attachments.iter() {
+                delete_unused_blob(&tx, &attachment.hash, &attachment.location, &mut files)?;
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+            attachments
+        };
+        remove_blob_files(&files);
+        Ok(attachments)
+    }
+}
+
+/// Delete the blob for `hash` in `location` if no attachment uses it, returning
+/// whether there was one. Blobs in the database go with the transaction `conn` is
+/// in, but files are only added to `files`, to be removed once it has committed.
+fn delete_unused_blob(conn: &Connection, hash: &str, location: &BlobLocation, files: &mut Vec<PathBuf>) -> Result<bool, String> {
+    let users: i64 = conn.query_row(r#"SELECT count(*) FROM attachments WHERE hash=? AND directory IS ?"#, &[&hash, &location.directory_column()], |row| row.get(0))
+                         .map_err(|e| e.to_string())?;
+    if users > 0 {
+        return Ok(false);
+    }
+    match *location {
+        BlobLocation::Database => {
+            let count = conn.execute(r#"DELETE FROM attachment_blobs WHERE hash=?"#, &[&hash]).map_err(|e| e.to_string())?;
+            Ok(count > 0)
+        },
+        BlobLocation::Directory(ref directory) => {
+            let path = blob_path(directory, hash);
+            if !path.exists() {
+                return Ok(false);
+            }
+            files.push(path);
+            Ok(true)
+        },
+    }
+}
+
+/// The hashes of the blobs in `directory`, leaving out anything that isn't one.
+fn blob_files(directory: &Path) -> Result<Vec<String>, String> {
+    let mut hashes = vec![];
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(hashes),
+        Err(e) => return Err(e.to_string()),
+    };
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let prefix = entry.file_name().to_string_lossy().into_owned();
+        if prefix.len() != 2 || !entry.path().is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(entry.path()).map_err(|e| e.to_string())? {
+            let rest = file.map_err(|e| e.to_string())?.file_name().to_string_lossy().into_owned();
+            let hash = format!("{}{}", prefix, rest);
+            if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                hashes.push(hash);
+            }
+        }
+    }
+    Ok(hashes)
+}
+
+/// Writes a new attachment's contents to a temporary file while hashing them. The
+/// attachment is only saved by `finish`; dropping the writer discards it.
+#[derive(Debug)]
+pub struct AttachmentWriter {
+    attachments: Attachments,
+    item_uuid: String,
+    name: String,
+    mime_type: String,
+    /// Where the contents go, fixed when the writer starts.
+    location: BlobLocation,
+    path: PathBuf,
+    file: BufWriter<File>,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl Write for AttachmentWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl AttachmentWriter {
+    /// Save the attachment, reusing the stored contents of any identical file.
+    pub fn finish(mut self) -> Result<Attachment, String> {
+        self.file.flush().map_err(|e| e.to_string())?;
+        let hash: String = self.hasher.clone().finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let attachment = Attachment {
+            id: Uuid::new_v4().simple().to_string(),
+            item_uuid: self.item_uuid.clone(),
+            name: self.name.clone(),
+            mime_type: self.mime_type.clone(),
+            size: self.size,
+            hash: hash,
+            location: self.location.clone(),
+        };
+        // Hold the connection until the file is in place, so nothing on this store
+        // can delete the blob in between.
+        let mut conn = self.attachments.store.conn.lock().unwrap();
+        {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            if attachment.location == BlobLocation::Database {
+                save_database_blob(&tx, &attachment.hash, &self.path, attachment.size)?;
+            }
+            let sql = format!("INSERT INTO attachments ({}) VALUES (?, ?, ?, ?, ?, ?, ?)", ATTACHMENT_COLUMNS);
+            tx.execute(&sql, &[&attachment.id, &attachment.item_uuid, &attachment.name, &attachment.mime_type, &(attachment.size as i64), &attachment.hash,
+                               &attachment.location.directory_column()])
+              .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+        if let BlobLocation::Directory(ref directory) = attachment.location {
+            if let Err(e) = save_blob_file(directory, &attachment.hash, &self.path) {
+                // Take the attachment out again rather than leave it without contents.
+                if let Err(e) = conn.execute(r#"DELETE FROM attachments WHERE id=?"#, &[&attachment.id]) {
+                    warn!(id = attachment.id.as_str(), error:% = e; "failed to remove attachment without contents");
+                }
+                return Err(e);
+            }
+        }
+        debug!(id = attachment.id.as_str(), uuid = attachment.item_uuid.as_str(), size = attachment.size; "added attachment");
+        Ok(attachment)
+    }
+}
+
+/// Store the file at `path` as the blob for `hash` in `attachment_blobs` unless
+/// there already is one.
+fn save_database_blob(conn: &Connection, hash: &str, path: &Path, size: u64) -> Result<(), String> {
+    if blob_rowid(conn, hash)?.is_some() {
+        return Ok(());
+    }
+    if size > i32::max_value() as u64 {
+        return Err(format!("{} bytes is too large to store in the database", size));
+    }
+    conn.execute(r#"INSERT INTO attachment_blobs (hash, data) VALUES (?, ?)"#, &[&hash, &ZeroBlob(size as i32)])
+        .map_err(|e| e.to_string())?;
+    let mut blob = conn.blob_open(DatabaseName::Main, "attachment_blobs", "data", conn.last_insert_rowid(), false)
+                       .map_err(|e| e.to_string())?;
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    io::copy(&mut file, &mut blob).map_err(|e| e.to_string())?;
+    blob.close().map_err(|e| e.to_string())
+}
+
+/// Move the file at `path` into `directory` as the blob for `hash` unless there
+/// already is one.
+fn save_blob_file(directory: &Path, hash: &str, path: &Path) -> Result<(), String> {
+    let blob_path = blob_path(directory, hash);
+    if blob_path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(blob_path.parent().unwrap()).map_err(|e| e.to_string())?;
+    fs::rename(path, &blob_path).map_err(|e| e.to_string())
+}
+
+impl Drop for AttachmentWriter {
+    fn drop(&mut self) {
+        // Gone already if it was moved into the blob directory.
+        if let Err(e) = fs::remove_file(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!(path:? = self.path, error:% = e; "failed to remove temporary attachment file");
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum BlobSource {
+    /// The rowid of the blob in `attachment_blobs`.
+    Database(i64),
+    File(File),
+}
+
+/// Reads an attachment's contents a piece at a time.
+#[derive(Debug)]
+pub struct AttachmentReader {
+    store: Store,
+    source: BlobSource,
+    offset: u64,
+}
+
+impl Read for AttachmentReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match self.source {
+            BlobSource::Database(rowid) => {
+                // Only hold the blob open for each read, so the connection is free
+                // between them.
+                let conn = self.store.conn.lock().unwrap();
+                let mut blob = conn.blob_open(DatabaseName::Main, "attachment_blobs", "data", rowid, true)
+                                   .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                blob.seek(SeekFrom::Start(self.offset))?;
+                blob.read(buf)?
+            },
+            BlobSource::File(ref mut file) => file.read(buf)?,
+        };
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+/// An attachment handed across the FFI, freed with `attachment_destroy`.
+pub type AttachmentHandle = u64;
+
+/// A list of attachments handed across the FFI, read with `attachment_list_count`
+/// and `attachment_list_entry_at` and freed with `attachment_list_destroy`.
+pub type AttachmentListHandle = u64;
+
+/// A new attachment being written, finished with `attachment_writer_finish` or
+/// discarded with `attachment_writer_destroy`.
+pub type AttachmentWriterHandle = u64;
+
+/// An attachment's contents being read, freed with `attachment_reader_destroy`.
+pub type AttachmentReaderHandle = u64;
+
+pub static ATTACHMENTS: HandleRegistry<Attachment> = HandleRegistry::new("attachment");
+pub static ATTACHMENT_LISTS: HandleRegistry<Vec<Attachment>> = HandleRegistry::new("attachment list");
+pub static ATTACHMENT_WRITERS: HandleRegistry<AttachmentWriter> = HandleRegistry::new("attachment writer");
+pub static ATTACHMENT_READERS: HandleRegistry<AttachmentReader> = HandleRegistry::new("attachment reader");
+
+/// Keep the contents of attachments added from now on as files in `directory`,
+/// which is created if it doesn't exist, instead of in the database. The setting is
+/// saved in the database, so it applies to every handle on the same list, including
+/// those opened later. Contents already stored stay where they are.
+///
+/// # Safety
+///
+/// `directory` must point to a NUL-terminated string that stays valid for the call;
+/// NULL is rejected with an error. The caller keeps ownership of it.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_set_attachment_directory(manager: ListManagerHandle, directory: *const c_char) {
+    guard((), || {
+        let directory = PathBuf::from(check_str(directory, "directory")?);
+        manager_from_handle(manager)?.set_attachment_directory(directory)
+    })
+}
+
+/// Start writing a new attachment on `item`, which must have been saved. A NULL
+/// `mime_type` is `application/octet-stream`. Returns 0 if the attachment can't be
+/// written, otherwise a writer to pass the contents to with `attachment_writer_write`.
+///
+/// # Safety
+///
+/// `name` must point to a NUL-terminated string, and `mime_type` must be NULL or
+/// point to one; each must stay valid for the call. The caller keeps ownership of
+/// them. The writer must be passed once to either `attachment_writer_finish` or
+/// `attachment_writer_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_attachment_writer(manager: ListManagerHandle, item: ItemHandle, name: *const c_char, mime_type: *const c_char) -> AttachmentWriterHandle {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        let item_uuid = ITEMS.get(item, |item| item.uuid.clone())?;
+        let name = check_str(name, "name")?;
+        let mime_type = check_optional_str(mime_type, "mime_type")?.unwrap_or(DEFAULT_MIME_TYPE.to_string());
+        let writer = manager.attachment_writer(&item_uuid, &name, &mime_type)?;
+        Ok(ATTACHMENT_WRITERS.insert(writer))
+    })
+}
+
+/// Append the `len` bytes at `data` to the attachment. Returns false if they
+/// couldn't be written.
+///
+/// # Safety
+///
+/// A non-NULL `data` must point to at least `len` readable bytes that stay valid for
+/// the call. The caller keeps ownership of them.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_writer_write(writer: AttachmentWriterHandle, data: *const u8, len: usize) -> bool {
+    guard(false, || {
+        if data.is_null() && len > 0 {
+            return Err("data is NULL".to_string());
+        }
+        let data = if len == 0 { &[][..] } else { slice::from_raw_parts(data, len) };
+        ATTACHMENT_WRITERS.get_mut(writer, |writer| writer.write_all(data).map_err(|e| e.to_string()))??;
+        Ok(true)
+    })
+}
+
+/// Save the attachment written so far. The writer is freed whether or not this
+/// succeeds. Returns 0 if the attachment couldn't be saved, otherwise an attachment
+/// that must be freed with `attachment_destroy`.
+///
+/// # Safety
+///
+/// `writer` is a handle, so any value is safe. It is invalid once this returns, so
+/// writing to, finishing or destroying it afterwards only fails and sets the last
+/// error.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_writer_finish(writer: AttachmentWriterHandle) -> AttachmentHandle {
+    guard(0, || {
+        let attachment = ATTACHMENT_WRITERS.remove(writer)?.finish()?;
+        Ok(ATTACHMENTS.insert(attachment))
+    })
+}
+
+/// Discard an attachment that hasn't been finished.
+///
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_writer_destroy(writer: AttachmentWriterHandle) {
+    guard((), || ATTACHMENT_WRITERS.destroy(writer))
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`. The list must be freed once with
+/// `attachment_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_item_attachments(manager: ListManagerHandle, item: ItemHandle) -> AttachmentListHandle {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        let item_uuid = ITEMS.get(item, |item| item.uuid.clone())?;
+        let attachments = manager.attachments()?.fetch_for_item(&item_uuid)?;
+        Ok(ATTACHMENT_LISTS.insert(attachments))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_list_destroy(attachment_list: AttachmentListHandle) {
+    guard((), || ATTACHMENT_LISTS.destroy(attachment_list))
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_list_count(attachment_list: AttachmentListHandle) -> usize {
+    guard(0, || ATTACHMENT_LISTS.get(attachment_list, |attachments| attachments.len()))
+}
+
+/// Copy the attachment at `index`. The returned attachment must be freed with
+/// `attachment_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_list_entry_at(attachment_list: AttachmentListHandle, index: usize) -> AttachmentHandle {
+    guard(0, || {
+        let attachment = ATTACHMENT_LISTS.get(attachment_list, |attachments| attachments.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(ATTACHMENTS.insert(attachment))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_destroy(attachment: AttachmentHandle) {
+    guard((), || ATTACHMENTS.destroy(attachment))
+}
+
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_get_id(attachment: AttachmentHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let id = ATTACHMENTS.get(attachment, |attachment| attachment.id.clone())?;
+        Ok(string_to_c_char(id)?)
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_get_name(attachment: AttachmentHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let name = ATTACHMENTS.get(attachment, |attachment| attachment.name.clone())?;
+        Ok(string_to_c_char(name)?)
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_get_mime_type(attachment: AttachmentHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let mime_type = ATTACHMENTS.get(attachment, |attachment| attachment.mime_type.clone())?;
+        Ok(string_to_c_char(mime_type)?)
+    })
+}
+
+/// The SHA-256 hash of the contents, in lowercase hex.
+///
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_get_hash(attachment: AttachmentHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let hash = ATTACHMENTS.get(attachment, |attachment| attachment.hash.clone())?;
+        Ok(string_to_c_char(hash)?)
+    })
+}
+
+/// The size of the contents in bytes.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_get_size(attachment: AttachmentHandle) -> u64 {
+    guard(0, || ATTACHMENTS.get(attachment, |attachment| attachment.size))
+}
+
+/// Start reading the contents of `attachment` with `attachment_reader_read`. Returns
+/// 0 if they can't be read.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`. The reader must be freed once with
+/// `attachment_reader_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_attachment_reader(manager: ListManagerHandle, attachment: AttachmentHandle) -> AttachmentReaderHandle {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        let attachment = ATTACHMENTS.get(attachment, |attachment| attachment.clone())?;
+        let reader = manager.attachments()?.reader(&attachment)?;
+        Ok(ATTACHMENT_READERS.insert(reader))
+    })
+}
+
+/// Copy up to `capacity` bytes of the contents into `buffer`, continuing from where
+/// the last read stopped. Returns how many bytes were copied, which is 0 with no
+/// error once all of them have been read.
+///
+/// # Safety
+///
+/// A non-NULL `buffer` must point to at least `capacity` writable bytes that nothing
+/// else reads or writes during the call. The caller keeps ownership of it.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_reader_read(reader: AttachmentReaderHandle, buffer: *mut u8, capacity: usize) -> usize {
+    guard(0, || {
+        if buffer.is_null() && capacity > 0 {
+            return Err("buffer is NULL".to_string());
+        }
+        let buffer = if capacity == 0 { &mut [][..] } else { slice::from_raw_parts_mut(buffer, capacity) };
+        ATTACHMENT_READERS.get_mut(reader, |reader| reader.read(buffer).map_err(|e| e.to_string()))?
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn attachment_reader_destroy(reader: AttachmentReaderHandle) {
+    guard((), || ATTACHMENT_READERS.destroy(reader))
+}
+
+/// Delete `attachment`, and its contents unless another attachment has the same
+/// ones. Returns false if it had already been deleted.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_delete_attachment(manager: ListManagerHandle, attachment: AttachmentHandle) -> bool {
+    guard(false, || {
+        let manager = manager_from_handle(manager)?;
+        let id = ATTACHMENTS.get(attachment, |attachment| attachment.id.clone())?;
+        manager.attachments()?.delete(&id)
+    })
+}
+
+/// Delete attachments whose items no longer exist, and stored contents that no
+/// attachment uses. Returns how many attachments and contents were deleted in all.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_purge_attachments(manager: ListManagerHandle) -> usize {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        let (attachments, blobs) = manager.attachments()?.purge()?;
+        Ok(attachments + blobs)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ffi::CString;
+
+    use items::Item;
+    use labels::Label;
+    use storage::{
+        ListStorage,
+        SqliteStorage,
+    };
+    use {
+        ListManager,
+        MANAGERS,
+    };
+
+    /// Storage in SQLite that fails to delete any item.
+    #[derive(Debug)]
+    struct UndeletableItems(SqliteStorage);
+
+    impl ListStorage for UndeletableItems {
+        fn create_label(&self, label: &Label) -> Result<(), String> { self.0.create_label(label) }
+        fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> { self.0.fetch_label(name) }
+        fn fetch_labels(&self) -> Result<Vec<Label>, String> { self.0.fetch_labels() }
+        fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> { self.0.fetch_labels_for_item(uuid) }
+        fn create_item(&self, item: &Item) -> Result<(), String> { self.0.create_item(item) }
+        fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> { self.0.fetch_item(uuid) }
+        fn fetch_items(&self) -> Result<Vec<Item>, String> { self.0.fetch_items() }
+        fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> { self.0.fetch_items_page(offset, limit) }
+        fn count_items(&self) -> Result<usize, String> { self.0.count_items() }
+        fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> { self.0.fetch_items_with_label(name) }
+        fn update_item(&self, item: &Item, existing_labels: &[Label]) -> Result<(), String> { self.0.update_item(item, existing_labels) }
+        fn delete_item(&self, _: &str) -> Result<bool, String> { Err("database is locked".to_string()) }
+    }
+
+    /// A directory under the system's temporary directory, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            TempDir(env::temp_dir().join(format!("toodle-test-{}", Uuid::new_v4().simple())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Run `check` with blobs in the database and in a directory.
+    fn for_each_location<F: Fn(ListManager)>(check: F) {
+        check(ListManager::new(None));
+        let directory = TempDir::new();
+        let manager = ListManager::new(None);
+        manager.set_attachment_directory(directory.0.clone()).unwrap();
+        check(manager);
+    }
+
+    fn create_item(manager: &mut ListManager, name: &str) -> String {
+        manager.create_item(&Item::new("".to_string(), name.to_string(), None, None, vec![])).unwrap()
+    }
+
+    fn read_all(attachments: &Attachments, attachment: &Attachment) -> Vec<u8> {
+        let mut contents = vec![];
+        attachments.reader(attachment).unwrap().read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    fn blob_count(attachments: &Attachments) -> usize {
+        match attachments.location().unwrap() {
+            BlobLocation::Database => {
+                let conn = attachments.store().conn.lock().unwrap();
+                let count: i64 = conn.query_row("SELECT count(*) FROM attachment_blobs", &[], |row| row.get(0)).unwrap();
+                count as usize
+            },
+            BlobLocation::Directory(directory) => blob_files(&directory).unwrap().len(),
+        }
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        for_each_location(|mut manager| {
+            let uuid = create_item(&mut manager, "holiday");
+            // Big enough to take several reads and writes.
+            let contents: Vec<u8> = (0..100000u32).map(|i| (i % 251) as u8).collect();
+            let mut writer = manager.attachment_writer(&uuid, "photo.jpg", "image/jpeg").unwrap();
+            for chunk in contents.chunks(4096) {
+                writer.write_all(chunk).unwrap();
+            }
+            let attachment = writer.finish().unwrap();
+            assert_eq!(attachment.size, 100000);
+            assert_eq!(attachment.hash.len(), 64);
+            assert_eq!(attachment.mime_type, "image/jpeg");
+
+            let attachments = manager.attachments().unwrap();
+            assert_eq!(attachments.fetch(&attachment.id), Ok(Some(attachment.clone())));
+            assert_eq!(attachments.fetch_for_item(&uuid), Ok(vec![attachment.clone()]));
+            assert_eq!(read_all(attachments, &attachment), contents);
+
+            let mut reader = attachments.reader(&attachment).unwrap();
+            let mut buffer = [0; 10];
+            assert_eq!(reader.read(&mut buffer).unwrap(), 10);
+            assert_eq!(&buffer[..], &contents[..10]);
+
+            let empty = attachments.add(&uuid, "empty", DEFAULT_MIME_TYPE, &mut io::empty()).unwrap();
+            assert_eq!(empty.hash, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+            assert_eq!(read_all(attachments, &empty), Vec::<u8>::new());
+        });
+    }
+
+    #[test]
+    fn test_identical_contents_are_stored_once() {
+        for_each_location(|mut manager| {
+            let first = create_item(&mut manager, "first");
+            let second = create_item(&mut manager, "second");
+            let attachments = manager.attachments().unwrap().clone();
+            let a = attachments.add(&first, "a.txt", "text/plain", &mut &b"same"[..]).unwrap();
+            let b = attachments.add(&second, "b.txt", "text/plain", &mut &b"same"[..]).unwrap();
+            attachments.add(&second, "c.txt", "text/plain", &mut &b"different"[..]).unwrap();
+            assert_eq!(a.hash, b.hash);
+            assert!(a.id != b.id);
+            assert_eq!(blob_count(&attachments), 2);
+
+            // The contents stay until the last attachment using them is deleted.
+            assert_eq!(attachments.delete(&a.id), Ok(true));
+            assert_eq!(attachments.delete(&a.id), Ok(false));
+            assert_eq!(read_all(&attachments, &b), b"same".to_vec());
+            assert_eq!(blob_count(&attachments), 2);
+            assert_eq!(attachments.delete(&b.id), Ok(true));
+            assert_eq!(blob_count(&attachments), 1);
+            assert!(attachments.reader(&b).is_err());
+        });
+    }
+
+    #[test]
+    fn test_discarded_writer_saves_nothing() {
+        for_each_location(|mut manager| {
+            let uuid = create_item(&mut manager, "item");
+            let mut writer = manager.attachment_writer(&uuid, "draft", "text/plain").unwrap();
+            writer.write_all(b"unfinished").unwrap();
+            let path = writer.path.clone();
+            drop(writer);
+            assert!(!path.exists());
+            assert_eq!(manager.attachments().unwrap().fetch_for_item(&uuid), Ok(vec![]));
+            assert_eq!(blob_count(manager.attachments().unwrap()), 0);
+        });
+    }
+
+    #[test]
+    fn test_deleting_item_deletes_attachments() {
+        for_each_location(|mut manager| {
+            let kept = create_item(&mut manager, "kept");
+            let deleted = create_item(&mut manager, "deleted");
+            let attachments = manager.attachments().unwrap().clone();
+            let attachment = attachments.add(&kept, "a.txt", "text/plain", &mut &b"kept"[..]).unwrap();
+            attachments.add(&deleted, "b.txt", "text/plain", &mut &b"deleted"[..]).unwrap();
+            attachments.add(&deleted, "c.txt", "text/plain", &mut &b"kept"[..]).unwrap();
+
+            assert!(manager.delete_item(&deleted).unwrap());
+            assert_eq!(attachments.fetch_for_item(&deleted), Ok(vec![]));
+            assert_eq!(attachments.fetch_for_item(&kept), Ok(vec![attachment]));
+            assert_eq!(blob_count(&attachments), 1);
+        });
+    }
+
+    #[test]
+    fn test_failed_item_delete_keeps_attachments() {
+        for_each_location(|mut manager| {
+            let uuid = create_item(&mut manager, "item");
+            let attachments = manager.attachments().unwrap().clone();
+            let attachment = attachments.add(&uuid, "a.txt", "text/plain", &mut &b"kept"[..]).unwrap();
+
+            let storage = UndeletableItems(SqliteStorage::new(attachments.store().clone()));
+            let mut failing = ListManager::with_storage(storage).with_attachments(attachments.clone());
+            assert_eq!(failing.delete_item(&uuid), Err("database is locked".to_string()));
+            assert!(manager.fetch_item(&uuid).is_some());
+            assert_eq!(attachments.fetch_for_item(&uuid), Ok(vec![attachment.clone()]));
+            assert_eq!(read_all(&attachments, &attachment), b"kept".to_vec());
+        });
+    }
+
+    #[test]
+    fn test_purge() {
+        for_each_location(|mut manager| {
+            let uuid = create_item(&mut manager, "item");
+            let attachments = manager.attachments().unwrap().clone();
+            attachments.add(&uuid, "kept.txt", "text/plain", &mut &b"kept"[..]).unwrap();
+            attachments.add("deleted elsewhere", "orphan.txt", "text/plain", &mut &b"orphan"[..]).unwrap();
+            assert_eq!(attachments.purge(), Ok((1, 1)));
+            assert_eq!(attachments.purge(), Ok((0, 0)));
+            assert_eq!(attachments.fetch_for_item(&uuid).unwrap().len(), 1);
+            assert_eq!(blob_count(&attachments), 1);
+        });
+    }
+
+    #[test]
+    fn test_location_is_shared_by_managers_on_the_same_database() {
+        let directory = TempDir::new();
+        let blobs = directory.0.join("blobs");
+        fs::create_dir_all(&directory.0).unwrap();
+        let uri = directory.0.join("list.db").to_str().unwrap().to_string();
+        let mut first = ListManager::new(uri.clone());
+        let uuid = create_item(&mut first, "item");
+        let in_database = first.attachments().unwrap().add(&uuid, "a.txt", "text/plain", &mut &b"in the database"[..]).unwrap();
+        first.set_attachment_directory(blobs.clone()).unwrap();
+        let in_directory = first.attachments().unwrap().add(&uuid, "b.txt", "text/plain", &mut &b"in a file"[..]).unwrap();
+        assert_eq!(in_database.location, BlobLocation::Database);
+        assert_eq!(in_directory.location, BlobLocation::Directory(blobs.clone()));
+
+        // A manager opened afterwards uses the same location, and can read and
+        // delete contents stored in either place.
+        let mut second = ListManager::new(uri);
+        let attachments = second.attachments().unwrap().clone();
+        assert_eq!(attachments.location(), Ok(BlobLocation::Directory(blobs.clone())));
+        assert_eq!(attachments.fetch_for_item(&uuid), Ok(vec![in_database.clone(), in_directory.clone()]));
+        assert_eq!(read_all(&attachments, &in_database), b"in the database".to_vec());
+        assert_eq!(read_all(&attachments, &in_directory), b"in a file".to_vec());
+        assert!(second.delete_item(&uuid).unwrap());
+        assert_eq!(blob_files(&blobs).unwrap().len(), 0);
+        assert_eq!(attachments.purge(), Ok((0, 0)));
+
+        attachments.set_location(&BlobLocation::Database).unwrap();
+        assert_eq!(first.attachments().unwrap().location(), Ok(BlobLocation::Database));
+    }
+
+    #[test]
+    fn test_writer_needs_an_item() {
+        let manager = ListManager::new(None);
+        assert_eq!(manager.attachment_writer("missing", "a.txt", "text/plain").unwrap_err(), "no item with uuid missing");
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let directory = TempDir::new();
+        let mut manager = ListManager::new(None);
+        let uuid = create_item(&mut manager, "item");
+        let item = ITEMS.insert(manager.fetch_item(&uuid).unwrap());
+        let manager = MANAGERS.insert(manager);
+        unsafe {
+            let path = CString::new(directory.0.to_str().unwrap()).unwrap();
+            list_manager_set_attachment_directory(manager, path.as_ptr());
+            let name = CString::new("notes.txt").unwrap();
+            let writer = list_manager_attachment_writer(manager, item, name.as_ptr(), ptr::null());
+            assert!(attachment_writer_write(writer, b"hello ".as_ptr(), 6));
+            assert!(attachment_writer_write(writer, b"world".as_ptr(), 5));
+            let attachment = attachment_writer_finish(writer);
+            assert_eq!(attachment_get_size(attachment), 11);
+            assert_eq!(attachment_writer_finish(writer), 0);
+            assert_eq!(blob_files(&directory.0).unwrap().len(), 1);
+
+            let reader = list_manager_attachment_reader(manager, attachment);
+            let mut buffer = [0u8; 8];
+            assert_eq!(attachment_reader_read(reader, buffer.as_mut_ptr(), buffer.len()), 8);
+            assert_eq!(&buffer, b"hello wo");
+            assert_eq!(attachment_reader_read(reader, buffer.as_mut_ptr(), buffer.len()), 3);
+            assert_eq!(attachment_reader_read(reader, buffer.as_mut_ptr(), buffer.len()), 0);
+            attachment_reader_destroy(reader);
+
+            let list = list_manager_get_item_attachments(manager, item);
+            assert_eq!(attachment_list_count(list), 1);
+            attachment_list_destroy(list);
+            assert!(list_manager_delete_attachment(manager, attachment));
+            assert_eq!(blob_files(&directory.0).unwrap().len(), 0);
+            attachment_destroy(attachment);
+        }
+        ITEMS.destroy(item).unwrap();
+        MANAGERS.destroy(manager).unwrap();
+    }
+}
@@ -0,0 +1,499 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Whole lists of items and labels as a single byte buffer, so that a host can fetch
+//! everything it needs to render a list in one call instead of one per field.
+//!
+//! Buffers hold Protocol Buffers messages described by `list/toodle.proto`; hosts
+//! decode them with code generated from that schema by `protoc`. Dates are `Date`
+//! messages holding milliseconds since the Unix epoch, and fields that an item
+//! doesn't have are left out.
+
+use std::fmt;
+
+use dates::Date;
+pub use ffi_utils::buffers::ByteBuffer;
+use ffi_utils::error::guard;
+use items::{
+    Item,
+    Priority,
+};
+use labels::Label;
+use manager_from_handle;
+use ListManagerHandle;
+
+// Field numbers from `list/toodle.proto`.
+const LABEL_NAME: u32 = 1;
+const LABEL_COLOR: u32 = 2;
+
+const DATE_MILLIS: u32 = 1;
+const DATE_ALL_DAY: u32 = 2;
+const DATE_ZONE: u32 = 3;
+
+const ITEM_UUID: u32 = 1;
+const ITEM_NAME: u32 = 2;
+const ITEM_LABELS: u32 = 5;
+const ITEM_DUE_DATE: u32 = 6;
+const ITEM_COMPLETION_DATE: u32 = 7;
+const ITEM_PRIORITY: u32 = 8;
+
+const ITEM_LIST_ITEMS: u32 = 1;
+const LABEL_LIST_LABELS: u32 = 1;
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LENGTH_DELIMITED: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The buffer ends in the middle of a field.
+    Truncated,
+    /// A varint longer than 64 bits.
+    Overlong,
+    /// A field with a wire type that isn't valid for it, or that doesn't exist.
+    WireType(u32, u8),
+    /// A string field that isn't UTF-8.
+    InvalidString(u32),
+    /// A date field whose milliseconds or time zone can't be read.
+    InvalidDate(u32),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Truncated => write!(f, "buffer ends in the middle of a field"),
+            DecodeError::Overlong => write!(f, "varint is longer than 64 bits"),
+            DecodeError::WireType(field, wire_type) => write!(f, "field {} has unexpected wire type {}", field, wire_type),
+            DecodeError::InvalidString(field) => write!(f, "field {} isn't valid UTF-8", field),
+            DecodeError::InvalidDate(field) => write!(f, "field {} isn't a valid date", field),
+        }
+    }
+}
+
+/// Encode `items` as an `ItemList` message.
+pub fn encode_items(items: &[Item]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for item in items.iter() {
+        write_bytes(&mut out, ITEM_LIST_ITEMS, &encode_item(item));
+    }
+    out
+}
+
+/// Encode `labels` as a `LabelList` message.
+pub fn encode_labels(labels: &[Label]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for label in labels.iter() {
+        write_bytes(&mut out, LABEL_LIST_LABELS, &encode_label(label));
+    }
+    out
+}
+
+pub fn encode_item(item: &Item) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_string(&mut out, ITEM_UUID, &item.uuid);
+    write_string(&mut out, ITEM_NAME, &item.name);
+    for label in item.labels.iter() {
+        write_bytes(&mut out, ITEM_LABELS, &encode_label(label));
+    }
+    if let Some(ref date) = item.due_date {
+        write_bytes(&mut out, ITEM_DUE_DATE, &encode_date(date));
+    }
+    if let Some(ref date) = item.completion_date {
+        write_bytes(&mut out, ITEM_COMPLETION_DATE, &encode_date(date));
+    }
+    if let Some(priority) = item.priority {
+        write_int64(&mut out, ITEM_PRIORITY, priority.number() as i64);
+    }
+    out
+}
+
+pub fn encode_date(date: &Date) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_int64(&mut out, DATE_MILLIS, date.millis());
+    if date.is_all_day() {
+        write_int64(&mut out, DATE_ALL_DAY, 1);
+    }
+    if let Some(zone) = date.zone() {
+        write_string(&mut out, DATE_ZONE, zone.name());
+    }
+    out
+}
+
+pub fn encode_label(label: &Label) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_string(&mut out, LABEL_NAME, &label.name);
+    write_string(&mut out, LABEL_COLOR, &label.color);
+    out
+}
+
+/// Decode an `ItemList` message. Unknown fields are skipped, so buffers written by a
+/// newer schema can still be read.
+pub fn decode_items(buffer: &[u8]) -> Result<Vec<Item>, DecodeError> {
+    let mut items = Vec::new();
+    let mut reader = Reader::new(buffer);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (ITEM_LIST_ITEMS, Value::Bytes(bytes)) => items.push(decode_item(bytes)?),
+            (ITEM_LIST_ITEMS, value) => return Err(DecodeError::WireType(field, value.wire_type())),
+            _ => {},
+        }
+    }
+    Ok(items)
+}
+
+/// Decode a `LabelList` message.
+pub fn decode_labels(buffer: &[u8]) -> Result<Vec<Label>, DecodeError> {
+    let mut labels = Vec::new();
+    let mut reader = Reader::new(buffer);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (LABEL_LIST_LABELS, Value::Bytes(bytes)) => labels.push(decode_label(bytes)?),
+            (LABEL_LIST_LABELS, value) => return Err(DecodeError::WireType(field, value.wire_type())),
+            _ => {},
+        }
+    }
+    Ok(labels)
+}
+
+pub fn decode_item(buffer: &[u8]) -> Result<Item, DecodeError> {
+    let mut item = Item::new("".to_string(), "".to_string(), None, None, vec![]);
+    let mut reader = Reader::new(buffer);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (ITEM_UUID, value) => item.uuid = value.string(field)?,
+            (ITEM_NAME, value) => item.name = value.string(field)?,
+            (ITEM_DUE_DATE, Value::Bytes(bytes)) => item.due_date = Some(decode_date(field, bytes)?),
+            (ITEM_COMPLETION_DATE, Value::Bytes(bytes)) => item.completion_date = Some(decode_date(field, bytes)?),
+            (ITEM_LABELS, Value::Bytes(bytes)) => item.labels.push(decode_label(bytes)?),
+            // Priorities from a newer schema are left out, like unknown fields.
+            (ITEM_PRIORITY, Value::Varint(value)) => item.priority = Priority::from_number(value as i64).unwrap_or(None),
+            (ITEM_DUE_DATE, value) | (ITEM_COMPLETION_DATE, value) | (ITEM_LABELS, value) | (ITEM_PRIORITY, value) => {
+                return Err(DecodeError::WireType(field, value.wire_type()));
+            },
+            _ => {},
+        }
+    }
+    Ok(item)
+}
+
+/// Decode a `Date` message read from the item field `item_field`.
+pub fn decode_date(item_field: u32, buffer: &[u8]) -> Result<Date, DecodeError> {
+    let mut millis = 0;
+    let mut all_day = false;
+    let mut zone = String::new();
+    let mut reader = Reader::new(buffer);
+    while let Some((field, value)) = reader.next_field()? {
+        match (field, value) {
+            (DATE_MILLIS, Value::Varint(value)) => millis = value as i64,
+            (DATE_ALL_DAY, Value::Varint(value)) => all_day = value != 0,
+            (DATE_ZONE, value) => zone = value.string(field)?,
+            (DATE_MILLIS, value) | (DATE_ALL_DAY, value) => return Err(DecodeError::WireType(field, value.wire_type())),
+            _ => {},
+        }
+    }
+    let date = if all_day {
+        Date::day_from_millis(millis)
+    } else if zone.is_empty() {
+        Date::from_millis(millis)
+    } else {
+        Date::from_millis_in(millis, &zone)
+    };
+    date.map_err(|_| DecodeError::InvalidDate(item_field))
+}
+
+pub fn decode_label(buffer: &[u8]) -> Result<Label, DecodeError> {
+    let mut label = Label {
+        name: "".to_string(),
+        color: "".to_string(),
+    };
+    let mut reader = Reader::new(buffer);
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            LABEL_NAME => label.name = value.string(field)?,
+            LABEL_COLOR => label.color = value.string(field)?,
+            _ => {},
+        }
+    }
+    Ok(label)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_key(out: &mut Vec<u8>, field: u32, wire_type: u8) {
+    write_varint(out, (field as u64) << 3 | wire_type as u64);
+}
+
+fn write_int64(out: &mut Vec<u8>, field: u32, value: i64) {
+    write_key(out, field, WIRE_VARINT);
+    write_varint(out, value as u64);
+}
+
+fn write_bytes(out: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(out, field, WIRE_LENGTH_DELIMITED);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn write_string(out: &mut Vec<u8>, field: u32, value: &str) {
+    write_bytes(out, field, value.as_bytes());
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// A fixed-width value, which no field in the schema uses.
+    Fixed(u8),
+}
+
+impl<'a> Value<'a> {
+    fn wire_type(&self) -> u8 {
+        match *self {
+            Value::Varint(_) => WIRE_VARINT,
+            Value::Bytes(_) => WIRE_LENGTH_DELIMITED,
+            Value::Fixed(wire_type) => wire_type,
+        }
+    }
+
+    fn string(self, field: u32) -> Result<String, DecodeError> {
+        match self {
+            Value::Bytes(bytes) => String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidString(field)),
+            value => Err(DecodeError::WireType(field, value.wire_type())),
+        }
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Reader<'a> {
+        Reader { buffer: buffer }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.buffer.len() {
+            return Err(DecodeError::Truncated);
+        }
+        let (taken, rest) = self.buffer.split_at(len);
+        self.buffer = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in 0..10 {
+            let byte = self.take(1)?[0];
+            if shift == 9 && byte > 1 {
+                return Err(DecodeError::Overlong);
+            }
+            value |= ((byte & 0x7f) as u64) << (7 * shift);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Overlong)
+    }
+
+    /// The next field number and its value, or `None` at the end of the buffer.
+    fn next_field(&mut self) -> Result<Option<(u32, Value<'a>)>, DecodeError> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = (key >> 3) as u32;
+        let wire_type = (key & 0x7) as u8;
+        let value = match wire_type {
+            WIRE_VARINT => Value::Varint(self.varint()?),
+            WIRE_FIXED64 => {
+                self.take(8)?;
+                Value::Fixed(wire_type)
+            },
+            WIRE_LENGTH_DELIMITED => {
+                let len = self.varint()?;
+                if len > self.buffer.len() as u64 {
+                    return Err(DecodeError::Truncated);
+                }
+                Value::Bytes(self.take(len as usize)?)
+            },
+            WIRE_FIXED32 => {
+                self.take(4)?;
+                Value::Fixed(wire_type)
+            },
+            _ => return Err(DecodeError::WireType(field, wire_type)),
+        };
+        Ok(Some((field, value)))
+    }
+}
+
+/// Every item as an `ItemList` message. The buffer must be freed with
+/// `toodle_buffer_free`.
+///
+/// # Safety
+///
+/// Same as `item_get_name_utf8`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_all_items_buffer(manager: ListManagerHandle) -> ByteBuffer {
+    guard(ByteBuffer::null(), || {
+        let mut manager = manager_from_handle(manager)?;
+        Ok(ByteBuffer::from_vec(encode_items(&manager.fetch_items())))
+    })
+}
+
+/// Every label as a `LabelList` message. The buffer must be freed with
+/// `toodle_buffer_free`.
+///
+/// # Safety
+///
+/// Same as `item_get_name_utf8`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_all_labels_buffer(manager: ListManagerHandle) -> ByteBuffer {
+    guard(ByteBuffer::null(), || {
+        let manager = manager_from_handle(manager)?;
+        Ok(ByteBuffer::from_vec(encode_labels(&manager.fetch_labels())))
+    })
+}
+
+/// Free a buffer returned by any of the library's functions.
+///
+/// # Safety
+///
+/// `buffer` must have come unchanged from one of the library's functions and not
+/// have been freed already; a NULL buffer is ignored. No copy of it can be read or
+/// freed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_buffer_free(buffer: ByteBuffer) {
+    guard((), || {
+        let _ = buffer.into_vec();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use labels::DEFAULT_COLOR;
+    #[cfg(feature = "sqlite")]
+    use ListManager;
+    #[cfg(feature = "sqlite")]
+    use MANAGERS;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            color: DEFAULT_COLOR.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut urgent = Item::new("c3".to_string(), "file taxes".to_string(), None, None, vec![]);
+        urgent.priority = Some(Priority::High);
+        let items = vec![
+            urgent,
+            Item::new("a1".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![label("home"), label("work")]),
+            Item::new("b2".to_string(), "café ☕".to_string(), Date::from_millis_in(1509883200345, "Asia/Tokyo").ok(), Date::from_millis(-86400000).ok(), vec![]),
+            Item::new("".to_string(), "".to_string(), None, None, vec![]),
+        ];
+        assert_eq!(decode_items(&encode_items(&items)), Ok(items));
+        assert_eq!(decode_items(&[]), Ok(vec![]));
+
+        let labels = vec![label("home"), label("")];
+        assert_eq!(decode_labels(&encode_labels(&labels)), Ok(labels));
+    }
+
+    #[test]
+    fn test_encoding_matches_schema() {
+        // Each field is a key of `field << 3 | wire type` followed by a varint or a
+        // length and bytes. Negative dates take the full ten bytes of a varint.
+        assert_eq!(encode_label(&Label { name: "a".to_string(), color: "#fff".to_string() }),
+                   b"\x0a\x01a\x12\x04#fff".to_vec());
+        assert_eq!(encode_date(&Date::day(1970, 1, 2).unwrap()), b"\x08\x80\xb8\x99\x29\x10\x01".to_vec());
+        assert_eq!(encode_date(&Date::from_millis_in(0, "UTC").unwrap()), b"\x08\x00\x1a\x03UTC".to_vec());
+
+        let item = Item::new("u".to_string(), "n".to_string(), Date::from_millis(300).ok(), Date::from_millis(-1).ok(), vec![]);
+        let mut expected = b"\x0a\x01u\x12\x01n\x32\x03\x08\xac\x02\x3a\x0b\x08".to_vec();
+        expected.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(encode_item(&item), expected);
+
+        let mut item = Item::new("u".to_string(), "n".to_string(), None, None, vec![]);
+        item.priority = Some(Priority::Medium);
+        assert_eq!(encode_item(&item), b"\x0a\x01u\x12\x01n\x40\x02".to_vec());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_skipped() {
+        let mut buffer = encode_label(&label("home"));
+        write_int64(&mut buffer, 9, 42);
+        write_string(&mut buffer, 10, "later");
+        buffer.extend_from_slice(&[0x5d, 1, 2, 3, 4]);
+        buffer.extend_from_slice(&[0x61, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(decode_label(&buffer), Ok(label("home")));
+
+        // Fields 3 and 4 held dates in seconds before `Date` messages replaced them.
+        let item = Item::new("a1".to_string(), "buy milk".to_string(), None, None, vec![]);
+        let mut buffer = encode_item(&item);
+        write_int64(&mut buffer, 3, 1509883200);
+        write_int64(&mut buffer, 4, 1509883200);
+        // As are priorities this version doesn't know.
+        write_int64(&mut buffer, 8, 4);
+        assert_eq!(decode_item(&buffer), Ok(item));
+    }
+
+    #[test]
+    fn test_malformed_buffers() {
+        let buffer = encode_items(&[Item::new("a1".to_string(), "buy milk".to_string(), None, None, vec![label("home")])]);
+        for len in 1..buffer.len() {
+            assert_eq!(decode_items(&buffer[..len]), Err(DecodeError::Truncated), "length {}", len);
+        }
+        assert_eq!(decode_items(&[0x08, 0x01]), Err(DecodeError::WireType(1, WIRE_VARINT)));
+        assert_eq!(decode_label(&[0x0a, 0x01, 0xff]), Err(DecodeError::InvalidString(1)));
+        assert_eq!(decode_label(&[0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]), Err(DecodeError::Overlong));
+        assert_eq!(decode_label(&[0x0b]), Err(DecodeError::WireType(1, 3)));
+
+        // Dates out of chrono's range and unknown time zones.
+        let mut date = Vec::new();
+        write_int64(&mut date, DATE_MILLIS, i64::max_value());
+        let mut item = Vec::new();
+        write_bytes(&mut item, ITEM_DUE_DATE, &date);
+        assert_eq!(decode_item(&item), Err(DecodeError::InvalidDate(ITEM_DUE_DATE)));
+        let mut date = Vec::new();
+        write_string(&mut date, DATE_ZONE, "Nowhere/Special");
+        let mut item = Vec::new();
+        write_bytes(&mut item, ITEM_COMPLETION_DATE, &date);
+        assert_eq!(decode_item(&item), Err(DecodeError::InvalidDate(ITEM_COMPLETION_DATE)));
+        assert_eq!(decode_item(&[0x30, 0x01]), Err(DecodeError::WireType(ITEM_DUE_DATE, WIRE_VARINT)));
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_list_manager_buffers() {
+        let mut manager = ListManager::new(None);
+        let home = manager.create_label("home".to_string(), DEFAULT_COLOR.to_string()).unwrap();
+        let uuid = manager.create_item(&Item::new("".to_string(), "buy milk".to_string(), Date::from_millis(1509883200000).ok(), None, vec![home.clone()])).unwrap();
+        let handle = MANAGERS.insert(manager.clone());
+        unsafe {
+            let items = list_manager_get_all_items_buffer(handle).into_vec().unwrap();
+            assert_eq!(decode_items(&items), Ok(manager.fetch_items()));
+            assert_eq!(decode_items(&items).unwrap()[0].uuid, uuid);
+            let labels = list_manager_get_all_labels_buffer(handle).into_vec().unwrap();
+            assert_eq!(decode_labels(&labels), Ok(vec![home]));
+        }
+        MANAGERS.destroy(handle).unwrap();
+    }
+}
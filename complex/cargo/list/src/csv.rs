@@ -0,0 +1,654 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! CSV (RFC 4180) export and import of items.
+//!
+//! Exports write a header row naming each column, followed by one row per item.
+//! Imports are driven by a `CsvColumnMapping` that says which header in the file
+//! feeds which item field; rows that can't be turned into an item are reported
+//! individually rather than failing the whole import.
+
+use std::fmt;
+use std::fmt::Write;
+use std::os::raw::c_char;
+use std::ptr;
+
+use chrono::{
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    TimeZone,
+    Utc,
+};
+
+use dates::Date;
+use ffi_utils::error::{
+    check_optional_str,
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::strings::string_to_c_char;
+use items::{
+    Item,
+    ItemHandle,
+    Priority,
+    ITEMS,
+};
+use labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+use manager_from_handle;
+use ListManager;
+use ListManagerHandle;
+
+pub const DEFAULT_DATE_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.fZ";
+pub const DEFAULT_DAY_FORMAT: &'static str = "%Y-%m-%d";
+pub const DEFAULT_LABEL_SEPARATOR: char = ';';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvColumn {
+    Uuid,
+    Name,
+    DueDate,
+    CompletionDate,
+    Labels,
+    /// `low`, `medium`, `high`, or empty for none.
+    Priority,
+}
+
+impl CsvColumn {
+    pub fn all() -> Vec<CsvColumn> {
+        vec![CsvColumn::Uuid, CsvColumn::Name, CsvColumn::DueDate, CsvColumn::CompletionDate, CsvColumn::Labels, CsvColumn::Priority]
+    }
+
+    pub fn from_name(name: &str) -> Option<CsvColumn> {
+        match name.trim() {
+            "uuid" => Some(CsvColumn::Uuid),
+            "name" => Some(CsvColumn::Name),
+            "due_date" => Some(CsvColumn::DueDate),
+            "completion_date" => Some(CsvColumn::CompletionDate),
+            "labels" => Some(CsvColumn::Labels),
+            "priority" => Some(CsvColumn::Priority),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CsvColumn::Uuid => "uuid",
+            CsvColumn::Name => "name",
+            CsvColumn::DueDate => "due_date",
+            CsvColumn::CompletionDate => "completion_date",
+            CsvColumn::Labels => "labels",
+            CsvColumn::Priority => "priority",
+        }
+    }
+
+    /// Parse a comma separated list of column names, e.g. `"name,due_date"`.
+    pub fn parse_list(description: &str) -> Result<Vec<CsvColumn>, CsvError> {
+        description.split(',')
+                   .map(|name| CsvColumn::from_name(name).ok_or(CsvError::UnknownColumn(name.trim().to_string())))
+                   .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvError {
+    /// A column name in an export list or mapping that isn't an item field.
+    UnknownColumn(String),
+    /// A mapping entry that isn't of the form `header=column`.
+    MalformedMapping(String),
+    /// The mapping doesn't say which header holds the item name.
+    NoNameColumn,
+    /// A header named in the mapping that isn't present in the file.
+    MissingHeader(String),
+    /// A quoted field that runs to the end of the input.
+    UnterminatedQuote(usize),
+    /// The input has no header row.
+    Empty,
+    /// An export date format that can't be written, such as one with an unknown
+    /// specifier or a day format that asks for the time.
+    InvalidDateFormat(String),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CsvError::UnknownColumn(ref name) => write!(f, "unknown column {:?}", name),
+            CsvError::MalformedMapping(ref entry) => write!(f, "malformed mapping {:?}, expected header=column", entry),
+            CsvError::NoNameColumn => write!(f, "mapping has no name column"),
+            CsvError::MissingHeader(ref header) => write!(f, "header {:?} not found", header),
+            CsvError::UnterminatedQuote(row) => write!(f, "row {}: unterminated quoted field", row),
+            CsvError::Empty => write!(f, "no header row"),
+            CsvError::InvalidDateFormat(ref format) => write!(f, "invalid date format {:?}", format),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvExportOptions {
+    pub columns: Vec<CsvColumn>,
+    /// `strftime` format used for timestamps, which are always written in UTC.
+    pub date_format: String,
+    /// `strftime` format used for all-day dates.
+    pub day_format: String,
+    pub label_separator: char,
+}
+
+impl CsvExportOptions {
+    /// Fails if either date format can't be written. Timestamps are formatted in
+    /// UTC, so `date_format` may ask for the offset with `%z`; all-day dates have
+    /// neither a time nor an offset.
+    pub fn validate(&self) -> Result<(), CsvError> {
+        let day = NaiveDate::from_ymd_opt(2017, 11, 5).expect("expected a valid day");
+        let mut out = String::new();
+        if write!(out, "{}", Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN)).format(&self.date_format)).is_err() {
+            return Err(CsvError::InvalidDateFormat(self.date_format.clone()));
+        }
+        if write!(out, "{}", day.format(&self.day_format)).is_err() {
+            return Err(CsvError::InvalidDateFormat(self.day_format.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CsvExportOptions {
+    fn default() -> Self {
+        CsvExportOptions {
+            columns: CsvColumn::all(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            day_format: DEFAULT_DAY_FORMAT.to_string(),
+            label_separator: DEFAULT_LABEL_SEPARATOR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvColumnMapping {
+    /// Pairs of header in the file and the item field it populates.
+    pub columns: Vec<(String, CsvColumn)>,
+    /// `strftime` format used to parse dates. Dates with a time are read as UTC, and
+    /// dates without one are all-day.
+    pub date_format: String,
+    /// `strftime` format for all-day dates that don't match `date_format`.
+    pub day_format: String,
+    pub label_separator: char,
+}
+
+impl Default for CsvColumnMapping {
+    /// The mapping that reads back a file written with the default export options.
+    fn default() -> Self {
+        CsvColumnMapping {
+            columns: CsvColumn::all().into_iter().map(|column| (column.name().to_string(), column)).collect(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            day_format: DEFAULT_DAY_FORMAT.to_string(),
+            label_separator: DEFAULT_LABEL_SEPARATOR,
+        }
+    }
+}
+
+impl CsvColumnMapping {
+    /// Parse a mapping description such as `"Task=name,Due=due_date,Tags=labels"`.
+    /// Headers may not contain `,` or `=`.
+    pub fn parse(description: &str) -> Result<CsvColumnMapping, CsvError> {
+        let mut columns = Vec::new();
+        for entry in description.split(',') {
+            let mut parts = entry.splitn(2, '=');
+            let header = parts.next().unwrap_or("").trim();
+            let column = parts.next().ok_or(CsvError::MalformedMapping(entry.to_string()))?;
+            if header.is_empty() {
+                return Err(CsvError::MalformedMapping(entry.to_string()));
+            }
+            let column = CsvColumn::from_name(column).ok_or(CsvError::UnknownColumn(column.trim().to_string()))?;
+            columns.push((header.to_string(), column));
+        }
+        if !columns.iter().any(|&(_, column)| column == CsvColumn::Name) {
+            return Err(CsvError::NoNameColumn);
+        }
+        Ok(CsvColumnMapping {
+            columns: columns,
+            ..CsvColumnMapping::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvRowError {
+    /// 1-based row number in the file, counting the header as row 1.
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvImport {
+    pub items: Vec<Item>,
+    pub errors: Vec<CsvRowError>,
+}
+
+/// The result of a CSV import handed across the FFI, freed with `csv_import_destroy`.
+pub type CsvImportHandle = u64;
+
+pub static CSV_IMPORTS: HandleRegistry<CsvImport> = HandleRegistry::new("CSV import");
+
+/// Write `items` as CSV, failing if the options' date formats are invalid.
+pub fn export_csv(items: &[Item], options: &CsvExportOptions) -> Result<String, CsvError> {
+    options.validate()?;
+    let mut out = String::new();
+    let header: Vec<&str> = options.columns.iter().map(|column| column.name()).collect();
+    write_row(&mut out, &header);
+    for item in items.iter() {
+        let fields: Vec<String> = options.columns.iter().map(|column| {
+            match *column {
+                CsvColumn::Uuid => item.uuid.clone(),
+                CsvColumn::Name => item.name.clone(),
+                CsvColumn::DueDate => format_date(&item.due_date, options),
+                CsvColumn::CompletionDate => format_date(&item.completion_date, options),
+                CsvColumn::Labels => {
+                    let names: Vec<&str> = item.labels.iter().map(|label| label.name.as_str()).collect();
+                    names.join(&options.label_separator.to_string())
+                },
+                CsvColumn::Priority => item.priority.map_or("", Priority::name).to_string(),
+            }
+        }).collect();
+        let fields: Vec<&str> = fields.iter().map(|field| field.as_str()).collect();
+        write_row(&mut out, &fields);
+    }
+    Ok(out)
+}
+
+/// Parse `csv` into items according to `mapping`. Labels are created with the
+/// default color; it is up to the caller to reconcile them with existing labels.
+pub fn parse_csv(csv: &str, mapping: &CsvColumnMapping) -> Result<CsvImport, CsvError> {
+    let mut rows = read_rows(csv)?.into_iter();
+    let (_, header) = rows.next().ok_or(CsvError::Empty)?;
+
+    let mut indices = Vec::with_capacity(mapping.columns.len());
+    for &(ref name, column) in mapping.columns.iter() {
+        let index = header.iter()
+                          .position(|h| h.trim() == name.as_str())
+                          .ok_or(CsvError::MissingHeader(name.clone()))?;
+        indices.push((index, column));
+    }
+
+    let mut import = CsvImport { items: vec![], errors: vec![] };
+    for (row, fields) in rows {
+        if fields.len() == 1 && fields[0].is_empty() {
+            continue;
+        }
+        match parse_row(&fields, &indices, mapping) {
+            Ok(item) => import.items.push(item),
+            Err(message) => import.errors.push(CsvRowError { row: row, message: message }),
+        }
+    }
+    Ok(import)
+}
+
+fn parse_row(fields: &[String], indices: &[(usize, CsvColumn)], mapping: &CsvColumnMapping) -> Result<Item, String> {
+    let mut item = Item::new("".to_string(), "".to_string(), None, None, vec![]);
+    for &(index, column) in indices.iter() {
+        let field = fields.get(index).ok_or(format!("expected at least {} fields, found {}", index + 1, fields.len()))?;
+        match column {
+            CsvColumn::Uuid => item.uuid = field.trim().to_string(),
+            CsvColumn::Name => item.name = field.trim().to_string(),
+            CsvColumn::DueDate => item.due_date = parse_date(field, mapping)?,
+            CsvColumn::CompletionDate => item.completion_date = parse_date(field, mapping)?,
+            CsvColumn::Labels => {
+                for name in field.split(mapping.label_separator).map(|name| name.trim()).filter(|name| !name.is_empty()) {
+                    if !item.labels.iter().any(|label| label.name == name) {
+                        item.labels.push(Label { name: name.to_string(), color: DEFAULT_COLOR.to_string() });
+                    }
+                }
+            },
+            CsvColumn::Priority => {
+                item.priority = match field.trim() {
+                    "" => None,
+                    name => Some(name.parse()?),
+                };
+            },
+        }
+    }
+    if item.name.is_empty() {
+        return Err("item has no name".to_string());
+    }
+    Ok(item)
+}
+
+/// `date` in the format for its kind. The formats must have been validated.
+fn format_date(date: &Option<Date>, options: &CsvExportOptions) -> String {
+    match *date {
+        Some(Date::AllDay(day)) => day.format(&options.day_format).to_string(),
+        Some(ref date) => Utc.from_utc_datetime(&date.utc()).format(&options.date_format).to_string(),
+        None => String::new(),
+    }
+}
+
+fn parse_date(field: &str, mapping: &CsvColumnMapping) -> Result<Option<Date>, String> {
+    let field = field.trim();
+    if field.is_empty() {
+        return Ok(None);
+    }
+    match NaiveDateTime::parse_from_str(field, &mapping.date_format) {
+        Ok(utc) => Ok(Some(Date::from_utc(utc))),
+        Err(e) => NaiveDate::parse_from_str(field, &mapping.date_format)
+            .or_else(|_| NaiveDate::parse_from_str(field, &mapping.day_format))
+            .map(|day| Some(Date::AllDay(day)))
+            .map_err(|_| format!("invalid date {:?}: {}", field, e)),
+    }
+}
+
+fn write_row<T: AsRef<str>>(out: &mut String, fields: &[T]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        let field = field.as_ref();
+        let needs_quotes = field.contains(|c| c == ',' || c == '"' || c == '\r' || c == '\n')
+                           || field.starts_with(' ') || field.ends_with(' ');
+        if needs_quotes {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Split `csv` into records, each paired with the 1-based line it starts on.
+fn read_rows(csv: &str) -> Result<Vec<(usize, Vec<String>)>, CsvError> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut row_start = 1;
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => in_quotes = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                },
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => fields.push(::std::mem::replace(&mut field, String::new())),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' => {
+                fields.push(::std::mem::replace(&mut field, String::new()));
+                rows.push((row_start, ::std::mem::replace(&mut fields, Vec::new())));
+                line += 1;
+                row_start = line;
+            },
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(CsvError::UnterminatedQuote(row_start));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        rows.push((row_start, fields));
+    }
+    Ok(rows)
+}
+
+/// Export every item as CSV. `columns` is an optional comma separated list of column
+/// names, `date_format` and `day_format` optional `strftime` formats for timestamps
+/// and all-day dates, and `label_separator` an optional single character to put
+/// between labels; NULL selects the default. Returns NULL if `columns` names an
+/// unknown column, either format is invalid or `label_separator` isn't one character.
+///
+/// # Safety
+///
+/// Each of `columns`, `date_format`, `day_format` and `label_separator` must be NULL
+/// or point to a NUL-terminated string that stays valid for the call. The caller
+/// keeps ownership of them. The returned string belongs to the caller, who must free
+/// it once with `toodle_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_export_csv(manager: ListManagerHandle, columns: *const c_char, date_format: *const c_char, day_format: *const c_char, label_separator: *const c_char) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let mut manager = manager_from_handle(manager)?;
+        let mut options = CsvExportOptions::default();
+        if let Some(columns) = check_optional_str(columns, "columns")? {
+            options.columns = CsvColumn::parse_list(&columns).map_err(|e| e.to_string())?;
+        }
+        if let Some(date_format) = check_optional_str(date_format, "date_format")? {
+            options.date_format = date_format;
+        }
+        if let Some(day_format) = check_optional_str(day_format, "day_format")? {
+            options.day_format = day_format;
+        }
+        if let Some(separator) = check_optional_str(label_separator, "label_separator")? {
+            let mut chars = separator.chars();
+            options.label_separator = match (chars.next(), chars.next()) {
+                (Some(separator), None) => separator,
+                _ => return Err(format!("label_separator {:?} isn't a single character", separator)),
+            };
+        }
+        let csv = manager.export_csv(&options).map_err(|e| e.to_string())?;
+        Ok(string_to_c_char(csv)?)
+    })
+}
+
+/// Import items from CSV using a mapping description such as `"Task=name,Due=due_date"`,
+/// or the default mapping if `mapping` is NULL. Rows that can't be imported are
+/// reported in the result; returns 0 if the mapping is invalid or the file can't be
+/// read at all, such as one without a header row.
+///
+/// # Safety
+///
+/// `csv` must point to a NUL-terminated string, and `mapping` and `date_format` must
+/// be NULL or point to one; each must stay valid for the call. The caller keeps
+/// ownership of them. The import must be freed once with `csv_import_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_import_csv(manager: ListManagerHandle, csv: *const c_char, mapping: *const c_char, date_format: *const c_char) -> CsvImportHandle {
+    guard(0, || {
+        let mut manager = manager_from_handle(manager)?;
+        let csv = check_str(csv, "csv")?;
+        let mapping = check_optional_str(mapping, "mapping")?;
+        let date_format = check_optional_str(date_format, "date_format")?;
+        let import = import_csv_with_description(&mut manager, &csv, mapping, date_format).map_err(|e| e.to_string())?;
+        Ok(CSV_IMPORTS.insert(import))
+    })
+}
+
+/// Import `csv` as `list_manager_import_csv` does, with an optional mapping
+/// description and date format.
+pub fn import_csv_with_description(manager: &mut ListManager, csv: &str, mapping: Option<String>, date_format: Option<String>) -> Result<CsvImport, CsvError> {
+    let mut mapping = match mapping {
+        Some(mapping) => CsvColumnMapping::parse(&mapping)?,
+        None => CsvColumnMapping::default(),
+    };
+    if let Some(date_format) = date_format {
+        mapping.date_format = date_format;
+    }
+    manager.import_csv(csv, &mapping)
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_destroy(import: CsvImportHandle) {
+    guard((), || CSV_IMPORTS.destroy(import))
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_item_count(import: CsvImportHandle) -> usize {
+    guard(0, || CSV_IMPORTS.get(import, |import| import.items.len()))
+}
+
+/// Copy the imported item at `index`. The returned item must be freed with `item_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_item_at(import: CsvImportHandle, index: usize) -> ItemHandle {
+    guard(0, || {
+        let item = CSV_IMPORTS.get(import, |import| import.items.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(ITEMS.insert(item))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_error_count(import: CsvImportHandle) -> usize {
+    guard(0, || CSV_IMPORTS.get(import, |import| import.errors.len()))
+}
+
+/// The 1-based row of the error at `index`, counting the header as row 1.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_error_row(import: CsvImportHandle, index: usize) -> usize {
+    guard(0, || {
+        CSV_IMPORTS.get(import, |import| import.errors.get(index).map(|error| error.row).ok_or_else(|| format!("index {} out of range", index)))?
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn csv_import_error_message(import: CsvImportHandle, index: usize) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let message = CSV_IMPORTS.get(import, |import| import.errors.get(index).map(|error| error.message.clone()).ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(string_to_c_char(message)?)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use items::Item;
+    use labels::Label;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            color: DEFAULT_COLOR.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut items = vec![
+            Item::new("uuid1".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![label("groceries")]),
+            Item::new("uuid2".to_string(), "say \"hi\", then leave\nquietly".to_string(), Date::from_millis(1509883200345).ok(), Date::from_millis(1509800000000).ok(), vec![]),
+            Item::new("uuid3".to_string(), " padded ".to_string(), None, None, vec![label("home"), label("weekend")]),
+        ];
+        items[0].priority = Some(Priority::High);
+        let csv = export_csv(&items, &CsvExportOptions::default()).unwrap();
+        assert!(csv.starts_with("uuid,name,due_date,completion_date,labels,priority\r\n"));
+        assert!(csv.contains(",2017-11-05,,groceries,high\r\n"));
+        assert!(csv.contains(",2017-11-05T12:00:00.345Z,2017-11-04T12:53:20Z,"));
+        let import = parse_csv(&csv, &CsvColumnMapping::default()).expect("expected an import");
+        assert_eq!(import.errors, vec![]);
+        assert_eq!(import.items[0], items[0]);
+        assert_eq!(import.items[1], items[1]);
+        // Names are trimmed on the way in.
+        assert_eq!(import.items[2].name, "padded");
+        assert_eq!(import.items[2].labels, items[2].labels);
+    }
+
+    #[test]
+    fn test_export_columns_and_date_format() {
+        let items = vec![
+            Item::new("uuid1".to_string(), "buy milk".to_string(), Date::from_millis_in(1509922800000, "America/New_York").ok(), None, vec![label("a"), label("b")]),
+            Item::new("uuid2".to_string(), "pay rent".to_string(), Date::day(2017, 12, 1), None, vec![]),
+        ];
+        let mut options = CsvExportOptions {
+            columns: CsvColumn::parse_list("name, labels,due_date").unwrap(),
+            date_format: "%d/%m/%Y %H:%M".to_string(),
+            day_format: "%d/%m/%Y".to_string(),
+            label_separator: '|',
+        };
+        // Timestamps are written in UTC even if they have a time zone.
+        assert_eq!(export_csv(&items, &options), Ok("name,labels,due_date\r\nbuy milk,a|b,05/11/2017 23:00\r\npay rent,,01/12/2017\r\n".to_string()));
+
+        // Bad formats are rejected even when no date would use them.
+        options.date_format = "%Q".to_string();
+        assert_eq!(export_csv(&[], &options), Err(CsvError::InvalidDateFormat("%Q".to_string())));
+        options.date_format = "%d/%m/%Y %H:%M".to_string();
+        options.day_format = "%d/%m/%Y %H:%M".to_string();
+        assert_eq!(export_csv(&items, &options), Err(CsvError::InvalidDateFormat("%d/%m/%Y %H:%M".to_string())));
+        assert_eq!(CsvColumn::parse_list("name,notes"), Err(CsvError::UnknownColumn("notes".to_string())));
+
+        // Timestamps can be written with their offset, which is always UTC's.
+        options.date_format = "%Y-%m-%dT%H:%M:%S%:z".to_string();
+        options.day_format = "%Y-%m-%d".to_string();
+        assert!(export_csv(&items, &options).unwrap().contains(",2017-11-05T23:00:00+00:00\r\n"));
+        options.date_format = "%Y%m%dT%H%M%S%z".to_string();
+        assert!(export_csv(&items, &options).unwrap().contains(",20171105T230000+0000\r\n"));
+    }
+
+    #[test]
+    fn test_import_with_mapping() {
+        let csv = "Task,Notes,Due,Tags,Importance\n\
+                   Buy milk,ignored,05/11/2017,groceries;errands,High\n\
+                   ,no name,,,\n\
+                   Call mum,,tomorrow,,\n\
+                   \n\
+                   Short row\n\
+                   Pay rent,,01/12/2017,home,\n\
+                   Walk dog,,,,urgent\n";
+        let mut mapping = CsvColumnMapping::parse("Task=name, Due=due_date ,Tags=labels,Importance=priority").expect("expected a mapping");
+        mapping.date_format = "%d/%m/%Y".to_string();
+        let import = parse_csv(csv, &mapping).expect("expected an import");
+
+        assert_eq!(import.items.len(), 2);
+        assert_eq!(import.items[0].name, "Buy milk");
+        assert_eq!(import.items[0].due_date, Date::day(2017, 11, 5));
+        assert_eq!(import.items[0].labels, vec![label("groceries"), label("errands")]);
+        assert_eq!(import.items[0].priority, Some(Priority::High));
+        assert_eq!(import.items[1].name, "Pay rent");
+        assert_eq!(import.items[1].priority, None);
+
+        let rows: Vec<usize> = import.errors.iter().map(|e| e.row).collect();
+        assert_eq!(rows, vec![3, 4, 6, 8]);
+        assert_eq!(import.errors[3].message, "unknown priority \"urgent\"");
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert_eq!(CsvColumnMapping::parse("Task=due_date"), Err(CsvError::NoNameColumn));
+        assert_eq!(CsvColumnMapping::parse("Task"), Err(CsvError::MalformedMapping("Task".to_string())));
+        assert_eq!(CsvColumnMapping::parse("Task=title"), Err(CsvError::UnknownColumn("title".to_string())));
+
+        let mapping = CsvColumnMapping::parse("Task=name").unwrap();
+        assert_eq!(parse_csv("", &mapping), Err(CsvError::Empty));
+        assert_eq!(parse_csv("Title\nx\n", &mapping), Err(CsvError::MissingHeader("Task".to_string())));
+        assert_eq!(parse_csv("Task\n\"unterminated\n", &mapping), Err(CsvError::UnterminatedQuote(2)));
+    }
+}
@@ -0,0 +1,406 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Reading a list a page at a time, so that only one page of items is in memory
+//! however long the list is.
+//!
+//! Pages are fetched by their position in the list, so items created or deleted
+//! while a cursor is part way through may be skipped or returned twice.
+
+use std::collections::VecDeque;
+use std::os::raw::c_void;
+
+use ffi_utils::error::guard;
+use ffi_utils::handles::HandleRegistry;
+use items::{
+    Item,
+    ItemC,
+    ItemHandle,
+    ItemListHandle,
+    ITEMS,
+    ITEM_LISTS,
+};
+use {
+    manager_from_handle,
+    ListManager,
+    ListManagerHandle,
+};
+
+/// Iterates over every item in a list, fetching `page_size` of them at a time.
+#[derive(Debug)]
+pub struct ItemCursor {
+    manager: ListManager,
+    page_size: usize,
+    total_count: usize,
+    /// How many items have been fetched so far.
+    offset: usize,
+    page: VecDeque<Item>,
+    /// Set once a page comes back short, after which there's nothing left to fetch.
+    done: bool,
+}
+
+impl ItemCursor {
+    pub fn new(manager: ListManager, page_size: usize) -> Result<ItemCursor, String> {
+        if page_size == 0 {
+            return Err("page size is 0".to_string());
+        }
+        let total_count = manager.count_items()?;
+        Ok(ItemCursor {
+            manager: manager,
+            page_size: page_size,
+            total_count: total_count,
+            offset: 0,
+            page: VecDeque::with_capacity(page_size),
+            done: false,
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// How many items there were when the cursor was made.
+    pub fn total_count(&self) -> usize {
+        self.total_count
+    }
+
+    /// The items left in the current page, or the next page if they've all been
+    /// returned. Empty once every item has been returned.
+    pub fn next_page(&mut self) -> Result<Vec<Item>, String> {
+        self.fill()?;
+        Ok(self.page.drain(..).collect())
+    }
+
+    /// Fetch the next page if the current one is used up. A page that fails to
+    /// load is fetched again next time.
+    fn fill(&mut self) -> Result<(), String> {
+        if !self.page.is_empty() || self.done {
+            return Ok(());
+        }
+        let page = self.manager.fetch_items_page(self.offset, self.page_size)?;
+        debug!(offset = self.offset, count = page.len(); "fetched page of items");
+        self.offset += page.len();
+        self.done = page.len() < self.page_size;
+        self.page.extend(page);
+        Ok(())
+    }
+}
+
+/// Yields an error in place of each page that fails to load.
+impl Iterator for ItemCursor {
+    type Item = Result<Item, String>;
+
+    fn next(&mut self) -> Option<Result<Item, String>> {
+        match self.fill() {
+            Ok(()) => self.page.pop_front().map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// A cursor handed across the FFI, freed with `item_cursor_destroy`.
+pub type ItemCursorHandle = u64;
+
+pub static ITEM_CURSORS: HandleRegistry<ItemCursor> = HandleRegistry::new("item cursor");
+
+/// Receives one item from `list_manager_for_each_item`, and returns false to stop
+/// before the next one. The snapshot is only valid for the duration of the call and
+/// is freed by the library afterwards.
+pub type ItemCallback = Option<extern "C" fn(context: *mut c_void, item: *const ItemC) -> bool>;
+
+/// Make a cursor over every item, which fetches `page_size` items at a time. Returns
+/// 0 if `page_size` is 0, otherwise a cursor that must be freed with
+/// `item_cursor_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_item_cursor(manager: ListManagerHandle, page_size: usize) -> ItemCursorHandle {
+    guard(0, || {
+        let cursor = ItemCursor::new(manager_from_handle(manager)?, page_size)?;
+        Ok(ITEM_CURSORS.insert(cursor))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn item_cursor_destroy(cursor: ItemCursorHandle) {
+    guard((), || ITEM_CURSORS.destroy(cursor))
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_cursor_page_size(cursor: ItemCursorHandle) -> usize {
+    guard(0, || ITEM_CURSORS.get(cursor, |cursor| cursor.page_size()))
+}
+
+/// How many items there were when the cursor was made.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_cursor_total_count(cursor: ItemCursorHandle) -> usize {
+    guard(0, || ITEM_CURSORS.get(cursor, |cursor| cursor.total_count()))
+}
+
+/// Take the next item, fetching the next page first if the current one is used up.
+/// Returns 0 with no error once every item has been returned, 0 with an error if
+/// the page failed to load, otherwise an item that must be freed with `item_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_cursor_next(cursor: ItemCursorHandle) -> ItemHandle {
+    guard(0, || {
+        let item = ITEM_CURSORS.get_mut(cursor, |cursor| cursor.next())?;
+        Ok(item.transpose()?.map_or(0, |item| ITEMS.insert(item)))
+    })
+}
+
+/// Take the items left in the current page, or the next page if they've all been
+/// returned. The list is empty once every item has been returned, and must be freed
+/// with `item_list_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_cursor_next_page(cursor: ItemCursorHandle) -> ItemListHandle {
+    guard(0, || {
+        let page = ITEM_CURSORS.get_mut(cursor, |cursor| cursor.next_page())??;
+        Ok(ITEM_LISTS.insert(page))
+    })
+}
+
+/// Pass a snapshot of every item to `callback`, along with `context`, fetching
+/// `page_size` items at a time, until there are no more or `callback` returns false.
+/// Returns how many items were passed to `callback`, or 0 if iterating failed,
+/// including if a page failed to load part way through.
+///
+/// # Safety
+///
+/// `callback` is called on this thread before this returns. The snapshot it is
+/// passed belongs to this function and is freed once `callback` returns, so it must
+/// not be kept or passed to `item_snapshot_free`. `context` is only passed on, and
+/// may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_for_each_item(manager: ListManagerHandle, page_size: usize, callback: ItemCallback, context: *mut c_void) -> usize {
+    guard(0, || {
+        let callback = callback.ok_or("callback is NULL")?;
+        let cursor = ItemCursor::new(manager_from_handle(manager)?, page_size)?;
+        let mut count = 0;
+        for item in cursor {
+            let snapshot = ItemC::new(&item?)?;
+            let more = callback(context, &snapshot);
+            snapshot.free();
+            count += 1;
+            if !more {
+                break;
+            }
+        }
+        Ok(count)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ffi::CStr;
+    use std::ptr;
+
+    use items::{
+        item_destroy,
+        item_get_name,
+        item_list_count,
+        item_list_destroy,
+    };
+    use labels::Label;
+    use ffi_utils::error::last_error;
+    use ffi_utils::strings::free_c_char;
+    use storage::{
+        ListStorage,
+        MemoryStorage,
+    };
+    use MANAGERS;
+
+    /// Storage in memory that fails to load any page after the first.
+    #[derive(Debug)]
+    struct BrokenPages(MemoryStorage);
+
+    impl ListStorage for BrokenPages {
+        fn create_label(&self, label: &Label) -> Result<(), String> { self.0.create_label(label) }
+        fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> { self.0.fetch_label(name) }
+        fn fetch_labels(&self) -> Result<Vec<Label>, String> { self.0.fetch_labels() }
+        fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> { self.0.fetch_labels_for_item(uuid) }
+        fn create_item(&self, item: &Item) -> Result<(), String> { self.0.create_item(item) }
+        fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> { self.0.fetch_item(uuid) }
+        fn fetch_items(&self) -> Result<Vec<Item>, String> { self.0.fetch_items() }
+        fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> {
+            if offset > 0 {
+                return Err("disk I/O error".to_string());
+            }
+            self.0.fetch_items_page(offset, limit)
+        }
+        fn count_items(&self) -> Result<usize, String> { self.0.count_items() }
+        fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> { self.0.fetch_items_with_label(name) }
+        fn update_item(&self, item: &Item, existing_labels: &[Label]) -> Result<(), String> { self.0.update_item(item, existing_labels) }
+        fn delete_item(&self, uuid: &str) -> Result<bool, String> { self.0.delete_item(uuid) }
+    }
+
+    fn manager_with_items(count: usize) -> ListManager {
+        let mut manager = ListManager::with_storage(MemoryStorage::new());
+        let home = manager.create_label("home".to_string(), "#ff0000".to_string()).unwrap();
+        for index in 0..count {
+            let labels = if index % 2 == 0 { vec![home.clone()] } else { vec![] };
+            manager.create_item(&Item::new("".to_string(), format!("item {}", index), None, None, labels)).unwrap();
+        }
+        manager
+    }
+
+    fn names(items: Vec<Item>) -> Vec<String> {
+        items.into_iter().map(|item| item.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_iterates_in_pages() {
+        let mut manager = manager_with_items(7);
+        let all = manager.fetch_items();
+        for &page_size in [1, 3, 7, 10].iter() {
+            let cursor = ItemCursor::new(manager.clone(), page_size).unwrap();
+            assert_eq!(cursor.total_count(), 7);
+            assert_eq!(cursor.collect::<Result<Vec<Item>, String>>(), Ok(all.clone()), "page size {}", page_size);
+        }
+        assert_eq!(ItemCursor::new(manager, 0).unwrap_err(), "page size is 0");
+    }
+
+    #[test]
+    fn test_next_page() {
+        let manager = manager_with_items(5);
+        let mut cursor = ItemCursor::new(manager, 2).unwrap();
+        assert_eq!(names(cursor.next_page().unwrap()), vec!["item 0", "item 1"]);
+        assert_eq!(cursor.next().map(|item| item.unwrap().name.clone()), Some("item 2".to_string()));
+        // The rest of a page that was started comes back before the next one.
+        assert_eq!(names(cursor.next_page().unwrap()), vec!["item 3"]);
+        assert_eq!(names(cursor.next_page().unwrap()), vec!["item 4"]);
+        assert_eq!(cursor.next_page(), Ok(vec![]));
+        assert!(cursor.next().is_none());
+    }
+
+    #[test]
+    fn test_items_have_labels() {
+        let manager = manager_with_items(4);
+        let labels: Vec<Vec<Label>> = ItemCursor::new(manager, 3).unwrap().map(|item| item.unwrap().labels.clone()).collect();
+        assert_eq!(labels.iter().map(|labels| labels.len()).collect::<Vec<_>>(), vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_cursor_handles() {
+        let manager = MANAGERS.insert(manager_with_items(3));
+        unsafe {
+            assert_eq!(list_manager_item_cursor(manager, 0), 0);
+            let cursor = list_manager_item_cursor(manager, 2);
+            assert_eq!(item_cursor_total_count(cursor), 3);
+            assert_eq!(item_cursor_page_size(cursor), 2);
+
+            let item = item_cursor_next(cursor);
+            let name = item_get_name(item);
+            assert_eq!(CStr::from_ptr(name).to_str(), Ok("item 0"));
+            free_c_char(name);
+            item_destroy(item);
+
+            let page = item_cursor_next_page(cursor);
+            assert_eq!(item_list_count(page), 1);
+            item_list_destroy(page);
+            let page = item_cursor_next_page(cursor);
+            assert_eq!(item_list_count(page), 1);
+            item_list_destroy(page);
+            assert_eq!(item_cursor_next(cursor), 0);
+            let page = item_cursor_next_page(cursor);
+            assert_eq!(item_list_count(page), 0);
+            item_list_destroy(page);
+            item_cursor_destroy(cursor);
+        }
+        MANAGERS.destroy(manager).unwrap();
+    }
+
+    #[test]
+    fn test_failed_pages() {
+        let mut manager = ListManager::with_storage(BrokenPages(MemoryStorage::new()));
+        for index in 0..3 {
+            manager.create_item(&Item::new("".to_string(), format!("item {}", index), None, None, vec![])).unwrap();
+        }
+        let mut cursor = ItemCursor::new(manager.clone(), 2).unwrap();
+        assert_eq!(names(cursor.next_page().unwrap()), vec!["item 0", "item 1"]);
+        assert_eq!(cursor.next_page(), Err("disk I/O error".to_string()));
+        assert_eq!(cursor.next(), Some(Err("disk I/O error".to_string())));
+
+        let manager = MANAGERS.insert(manager);
+        unsafe {
+            let cursor = list_manager_item_cursor(manager, 2);
+            for _ in 0..2 {
+                let item = item_cursor_next(cursor);
+                assert!(item != 0);
+                item_destroy(item);
+            }
+            assert_eq!(item_cursor_next(cursor), 0);
+            assert_eq!(last_error(), Some("disk I/O error".to_string()));
+            assert_eq!(item_cursor_next_page(cursor), 0);
+            assert_eq!(last_error(), Some("disk I/O error".to_string()));
+            item_cursor_destroy(cursor);
+
+            let mut visited = Visited { names: vec![], limit: 10 };
+            let context = &mut visited as *mut Visited as *mut c_void;
+            assert_eq!(list_manager_for_each_item(manager, 2, Some(visit), context), 0);
+            assert_eq!(last_error(), Some("disk I/O error".to_string()));
+            assert_eq!(visited.names, vec!["item 0", "item 1"]);
+        }
+        MANAGERS.destroy(manager).unwrap();
+    }
+
+    struct Visited {
+        names: Vec<String>,
+        limit: usize,
+    }
+
+    extern "C" fn visit(context: *mut c_void, item: *const ItemC) -> bool {
+        let visited = unsafe { &mut *(context as *mut Visited) };
+        let name = unsafe { CStr::from_ptr((*item).name) };
+        visited.names.push(name.to_string_lossy().into_owned());
+        visited.names.len() < visited.limit
+    }
+
+    #[test]
+    fn test_for_each_item() {
+        let manager = MANAGERS.insert(manager_with_items(5));
+        unsafe {
+            let mut visited = Visited { names: vec![], limit: 10 };
+            let context = &mut visited as *mut Visited as *mut c_void;
+            assert_eq!(list_manager_for_each_item(manager, 2, Some(visit), context), 5);
+            assert_eq!(visited.names, vec!["item 0", "item 1", "item 2", "item 3", "item 4"]);
+
+            // Returning false stops after that item.
+            let mut visited = Visited { names: vec![], limit: 3 };
+            let context = &mut visited as *mut Visited as *mut c_void;
+            assert_eq!(list_manager_for_each_item(manager, 2, Some(visit), context), 3);
+            assert_eq!(visited.names, vec!["item 0", "item 1", "item 2"]);
+
+            assert_eq!(list_manager_for_each_item(manager, 2, None, ptr::null_mut()), 0);
+        }
+        MANAGERS.destroy(manager).unwrap();
+    }
+}
@@ -0,0 +1,716 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Due dates typed as English phrases, such as "tomorrow 5pm", "next friday",
+//! "in 3 days" or "end of month".
+//!
+//! A phrase is read relative to the current time in the user's time zone, both of
+//! which are passed in so that results don't depend on the clock. Phrases with a
+//! time of day give timestamps in that zone, and those without give all-day dates.
+//! A phrase is made of at most one day and one time, in either order:
+//!
+//! * days: `today`, `tomorrow`, `yesterday`, `monday` (the next one after today),
+//!   `this monday` (today or the next one), `next monday` (in next week), `next
+//!   week`, `next month`, `next year`, `weekend`, `next weekend`, `end of week`,
+//!   `end of month`, `end of year`, `nov 5`, `5th of november`, `nov 5 2018`, `the
+//!   5th`, `2018-11-05` and `in 3 days`, `in a week`, `in two months`;
+//! * times: `5pm`, `5:30 pm`, `17:30`, `at 5`, `noon`, `midnight`, `morning`,
+//!   `afternoon` and `evening`.
+//!
+//! `tonight` and `this evening` are both. `now`, `in 2 hours` and `in 30 minutes`
+//! are an exact time and can't be combined with anything else. Weeks start on
+//! Monday. A time without a day is today if it's still to come, otherwise
+//! tomorrow, and a month and day without a year is the next time that date comes
+//! round, including today.
+
+use std::error::Error;
+use std::fmt;
+use std::os::raw::c_char;
+
+use chrono::{
+    Datelike,
+    Duration,
+    Months,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    TimeZone,
+    Weekday,
+};
+use chrono_tz::Tz;
+use ffi_utils::error::{
+    check_str,
+    guard,
+};
+
+use dates::{
+    parse_zone,
+    Date,
+};
+use items::{
+    ItemHandle,
+    ITEMS,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatePhraseError {
+    /// A phrase with no words in it.
+    Empty,
+    /// Words that aren't a day or time, or a second day or time.
+    Unrecognised(String),
+    /// A day that doesn't exist, such as "feb 30".
+    NoSuchDay(String),
+    /// A time that doesn't exist, such as "25:00" or "13pm".
+    InvalidTime(String),
+    /// A date too far away to represent.
+    OutOfRange(String),
+}
+
+impl fmt::Display for DatePhraseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DatePhraseError::Empty => write!(f, "no date given"),
+            DatePhraseError::Unrecognised(ref text) => write!(f, "unrecognised {:?} in date", text),
+            DatePhraseError::NoSuchDay(ref text) => write!(f, "no such day {:?}", text),
+            DatePhraseError::InvalidTime(ref text) => write!(f, "invalid time {:?}", text),
+            DatePhraseError::OutOfRange(ref text) => write!(f, "{:?} is out of range", text),
+        }
+    }
+}
+
+impl Error for DatePhraseError {}
+
+impl From<DatePhraseError> for String {
+    fn from(error: DatePhraseError) -> String {
+        error.to_string()
+    }
+}
+
+/// The date `phrase` describes, read as if it were `now` in `zone`.
+pub fn parse_date_phrase(phrase: &str, now: &Date, zone: Tz) -> Result<Date, DatePhraseError> {
+    let lowercase = phrase.to_lowercase().replace(',', " ");
+    let words: Vec<&str> = lowercase.split_whitespace().collect();
+    if words.is_empty() {
+        return Err(DatePhraseError::Empty);
+    }
+    let local_now = zone.from_utc_datetime(&now.utc()).naive_local();
+    let parser = Parser {
+        words: words,
+        now: local_now,
+        today: local_now.date(),
+    };
+
+    let mut day = None;
+    let mut time = None;
+    let mut later = None;
+    let mut at = 0;
+    while at < parser.words.len() {
+        let (part, used) = parser.part(at)?.ok_or_else(|| parser.unrecognised(at, parser.words.len()))?;
+        let free = later.is_none();
+        match part {
+            Part::Day(d) if free && day.is_none() => day = Some(d),
+            Part::Time(t) if free && time.is_none() => time = Some(t),
+            Part::DayAndTime(d, t) if free && day.is_none() && time.is_none() => {
+                day = Some(d);
+                time = Some(t);
+            },
+            Part::Later(duration) if free && day.is_none() && time.is_none() => later = Some(duration),
+            _ => return Err(parser.unrecognised(at, at + used)),
+        }
+        at += used;
+    }
+
+    match (later, day, time) {
+        (Some(later), _, _) => {
+            let out_of_range = || DatePhraseError::OutOfRange(phrase.trim().to_string());
+            let millis = now.millis().checked_add(later.num_milliseconds()).ok_or_else(out_of_range)?;
+            Date::from_millis(millis).map_err(|_| out_of_range())?;
+            Ok(Date::Timestamp { millis: millis, zone: Some(zone) })
+        },
+        (None, Some(day), Some(time)) => Ok(Date::from_local(day.and_time(time), zone)),
+        (None, Some(day), None) => Ok(Date::AllDay(day)),
+        (None, None, Some(time)) => {
+            let today = parser.today.and_time(time);
+            let day = if today > parser.now { parser.today } else { parser.today.succ_opt().unwrap_or(parser.today) };
+            Ok(Date::from_local(day.and_time(time), zone))
+        },
+        (None, None, None) => Err(DatePhraseError::Empty),
+    }
+}
+
+/// One piece of a phrase.
+#[derive(Debug)]
+enum Part {
+    Day(NaiveDate),
+    Time(NaiveTime),
+    DayAndTime(NaiveDate, NaiveTime),
+    /// The instant this long after now.
+    Later(Duration),
+}
+
+type Found = Result<Option<(Part, usize)>, DatePhraseError>;
+
+struct Parser<'a> {
+    words: Vec<&'a str>,
+    now: NaiveDateTime,
+    today: NaiveDate,
+}
+
+impl<'a> Parser<'a> {
+    /// The word at `at`, or "" past the end of the phrase.
+    fn word(&self, at: usize) -> &'a str {
+        self.words.get(at).cloned().unwrap_or("")
+    }
+
+    fn text(&self, from: usize, to: usize) -> String {
+        self.words[from..to.min(self.words.len())].join(" ")
+    }
+
+    fn unrecognised(&self, from: usize, to: usize) -> DatePhraseError {
+        DatePhraseError::Unrecognised(self.text(from, to))
+    }
+
+    /// The part starting at `at` and how many words it takes up.
+    fn part(&self, at: usize) -> Found {
+        match self.word(at) {
+            "on" | "by" => return after_word(self.day(at + 1)),
+            "at" => return after_word(self.time(at + 1, true)),
+            "now" => return Ok(Some((Part::Later(Duration::zero()), 1))),
+            "in" => return self.in_duration(at),
+            _ => {},
+        }
+        match self.day(at)? {
+            Some(found) => Ok(Some(found)),
+            None => self.time(at, false),
+        }
+    }
+
+    fn day(&self, at: usize) -> Found {
+        let today = self.today;
+        let word = self.word(at);
+        let next = self.word(at + 1);
+        let day = match word {
+            "today" => today,
+            "tomorrow" | "tmrw" | "tmr" => today + Duration::days(1),
+            "yesterday" => today - Duration::days(1),
+            "tonight" => return Ok(Some((Part::DayAndTime(today, hour(20)), 1))),
+            "weekend" => on_or_after(today, Weekday::Sat),
+            "eow" => self.end_of("week")?,
+            "eom" => self.end_of("month")?,
+            "eoy" => self.end_of("year")?,
+            "this" => {
+                if let Some(weekday) = weekday(next) {
+                    return Ok(Some((Part::Day(on_or_after(today, weekday)), 2)));
+                }
+                if let Some(time) = period(next) {
+                    return Ok(Some((Part::DayAndTime(today, time), 2)));
+                }
+                match next {
+                    "weekend" => return Ok(Some((Part::Day(on_or_after(today, Weekday::Sat)), 2))),
+                    _ => return Ok(None),
+                }
+            },
+            "next" => {
+                let next_monday = on_or_after(today + Duration::days(1), Weekday::Mon);
+                let day = match next {
+                    "week" => next_monday,
+                    "weekend" => next_monday + Duration::days(5),
+                    "month" => self.first_of_next_month()?,
+                    "year" => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1).ok_or_else(|| self.out_of_range(at, at + 2))?,
+                    _ => match weekday(next) {
+                        Some(weekday) => next_monday + Duration::days(weekday.num_days_from_monday() as i64),
+                        None => return Ok(None),
+                    },
+                };
+                return Ok(Some((Part::Day(day), 2)));
+            },
+            "end" if next == "of" => {
+                let skip = if self.word(at + 2) == "the" { 1 } else { 0 };
+                let day = self.end_of(self.word(at + 2 + skip))?;
+                return Ok(Some((Part::Day(day), 3 + skip)));
+            },
+            "the" => return after_word(self.day(at + 1)),
+            _ => {
+                if let Some(weekday) = weekday(word) {
+                    on_or_after(today + Duration::days(1), weekday)
+                } else if let Some(month) = month(word) {
+                    // "nov 5" or "nov 5 2018".
+                    return match ordinal(next) {
+                        Some(day) => self.month_day(at, month, day, 2),
+                        None => Ok(None),
+                    };
+                } else if let Some(day) = ordinal(word) {
+                    // "5 nov", "5th of november" or "5th nov 2018".
+                    let skip = if next == "of" { 1 } else { 0 };
+                    return match month(self.word(at + 1 + skip)) {
+                        Some(month) => self.month_day(at, month, day, 2 + skip),
+                        None => self.day_of_month(at),
+                    };
+                } else if let Ok(day) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                    day
+                } else {
+                    return Ok(None);
+                }
+            },
+        };
+        Ok(Some((Part::Day(day), 1)))
+    }
+
+    /// The last day of the current "week", "month" or "year".
+    fn end_of(&self, period: &str) -> Result<NaiveDate, DatePhraseError> {
+        let today = self.today;
+        match period {
+            "week" => Ok(on_or_after(today, Weekday::Sun)),
+            "month" => Ok(self.first_of_next_month()? - Duration::days(1)),
+            "year" => Ok(NaiveDate::from_ymd_opt(today.year(), 12, 31).expect("every year has a December 31st")),
+            _ => Err(DatePhraseError::Unrecognised(format!("end of {}", period).trim().to_string())),
+        }
+    }
+
+    fn first_of_next_month(&self) -> Result<NaiveDate, DatePhraseError> {
+        self.today.with_day(1)
+            .and_then(|first| first.checked_add_months(Months::new(1)))
+            .ok_or_else(|| DatePhraseError::OutOfRange("next month".to_string()))
+    }
+
+    /// `month` and `day` in the year after them, or the next time they come round
+    /// if there isn't one.
+    fn month_day(&self, at: usize, month: u32, day: u32, used: usize) -> Found {
+        if let Some(year) = year(self.word(at + used)) {
+            let date = NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| DatePhraseError::NoSuchDay(self.text(at, at + used + 1)))?;
+            return Ok(Some((Part::Day(date), used + 1)));
+        }
+        // February 29th can be up to eight years away.
+        let first_year = self.today.year();
+        for year in first_year..first_year + 9 {
+            match NaiveDate::from_ymd_opt(year, month, day) {
+                Some(date) if date >= self.today => return Ok(Some((Part::Day(date), used))),
+                _ => {},
+            }
+        }
+        Err(DatePhraseError::NoSuchDay(self.text(at, at + used)))
+    }
+
+    /// A day of the month such as "5th", which is the next day with that number.
+    fn day_of_month(&self, at: usize) -> Found {
+        let word = self.word(at);
+        let day = match ordinal(word) {
+            // A plain number could be a time.
+            Some(day) if word.ends_with(|c: char| c.is_ascii_alphabetic()) => day,
+            _ => return Ok(None),
+        };
+        let first = self.today.with_day(1).expect("every month has a 1st");
+        for months in 0..12 {
+            let date = first.checked_add_months(Months::new(months)).and_then(|month| month.with_day(day));
+            match date {
+                Some(date) if date >= self.today => return Ok(Some((Part::Day(date), 1))),
+                _ => {},
+            }
+        }
+        Err(DatePhraseError::NoSuchDay(word.to_string()))
+    }
+
+    /// "in 3 days", "in a week" or "in 2 hours".
+    fn in_duration(&self, at: usize) -> Found {
+        let (count, unit) = match (number(self.word(at + 1)), self.word(at + 2)) {
+            (Some(count), unit) => (count, unit.trim_end_matches('s')),
+            _ => return Ok(None),
+        };
+        let out_of_range = || self.out_of_range(at, at + 3);
+        let part = match unit {
+            "minute" | "min" => Part::Later(Duration::try_minutes(count as i64).ok_or_else(out_of_range)?),
+            "hour" | "hr" => Part::Later(Duration::try_hours(count as i64).ok_or_else(out_of_range)?),
+            "day" => Part::Day(self.today.checked_add_signed(Duration::days(count as i64)).ok_or_else(out_of_range)?),
+            "week" => Part::Day(self.today.checked_add_signed(Duration::weeks(count as i64)).ok_or_else(out_of_range)?),
+            "month" => Part::Day(self.today.checked_add_months(Months::new(count)).ok_or_else(out_of_range)?),
+            "year" => {
+                let months = count.checked_mul(12).ok_or_else(out_of_range)?;
+                Part::Day(self.today.checked_add_months(Months::new(months)).ok_or_else(out_of_range)?)
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some((part, 3)))
+    }
+
+    fn out_of_range(&self, from: usize, to: usize) -> DatePhraseError {
+        DatePhraseError::OutOfRange(self.text(from, to))
+    }
+
+    /// A time of day. Bare hours such as "5" are only times after "at".
+    fn time(&self, at: usize, bare: bool) -> Found {
+        let word = self.word(at);
+        if let Some(time) = period(word) {
+            return Ok(Some((Part::Time(time), 1)));
+        }
+        let time = match word {
+            "noon" | "midday" => hour(12),
+            "midnight" => hour(0),
+            _ => return self.clock(at, bare),
+        };
+        Ok(Some((Part::Time(time), 1)))
+    }
+
+    /// "5pm", "5 pm", "5:30pm", "17:30" or, if `bare`, "5".
+    fn clock(&self, at: usize, bare: bool) -> Found {
+        let word = self.word(at);
+        let (body, meridiem, used) = if word.ends_with("am") || word.ends_with("pm") {
+            (&word[..word.len() - 2], Some(&word[word.len() - 2..]), 1)
+        } else {
+            match self.word(at + 1) {
+                meridiem @ "am" | meridiem @ "pm" => (word, Some(meridiem), 2),
+                _ => (word, None, 1),
+            }
+        };
+        let mut pieces = body.splitn(2, ':');
+        let hours = pieces.next().unwrap_or("");
+        let minutes = pieces.next();
+        let digits = |text: &str, max_len: usize| !text.is_empty() && text.len() <= max_len && text.chars().all(|c| c.is_ascii_digit());
+        if !digits(hours, 2) || minutes.map_or(false, |minutes| minutes.len() != 2 || !digits(minutes, 2)) {
+            return Ok(None);
+        }
+        if meridiem.is_none() && minutes.is_none() && !bare {
+            return Ok(None);
+        }
+        let invalid = || DatePhraseError::InvalidTime(self.text(at, at + used));
+        let mut hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.map_or(Ok(0), |minutes| minutes.parse()).map_err(|_| invalid())?;
+        if let Some(meridiem) = meridiem {
+            if !(1..=12).contains(&hours) {
+                return Err(invalid());
+            }
+            hours = match meridiem {
+                "am" => hours % 12,
+                _ => hours % 12 + 12,
+            };
+        }
+        let time = NaiveTime::from_hms_opt(hours, minutes, 0).ok_or_else(invalid)?;
+        Ok(Some((Part::Time(time), used)))
+    }
+}
+
+/// Counts the word before the part `found` starts at, which must be there.
+fn after_word(found: Found) -> Found {
+    match found? {
+        Some((part, used)) => Ok(Some((part, used + 1))),
+        None => Ok(None),
+    }
+}
+
+fn hour(hour: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, 0, 0).expect("valid hour")
+}
+
+fn on_or_after(day: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (7 + weekday.num_days_from_monday() - day.weekday().num_days_from_monday()) % 7;
+    day + Duration::days(days as i64)
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    Some(match word {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "weds" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn month(word: &str) -> Option<u32> {
+    const MONTHS: [&'static str; 12] = ["january", "february", "march", "april", "may", "june", "july",
+                                        "august", "september", "october", "november", "december"];
+    let word = word.trim_end_matches('.');
+    if word.len() < 3 {
+        return None;
+    }
+    let word = if word == "sept" { "sep" } else { word };
+    MONTHS.iter().position(|month| month.starts_with(word)).map(|index| index as u32 + 1)
+}
+
+/// The times of day "morning", "afternoon" and "evening" stand for.
+fn period(word: &str) -> Option<NaiveTime> {
+    match word {
+        "morning" => Some(hour(9)),
+        "afternoon" => Some(hour(14)),
+        "evening" => Some(hour(18)),
+        _ => None,
+    }
+}
+
+/// A day of the month such as "5" or "5th".
+fn ordinal(word: &str) -> Option<u32> {
+    let digits = ["st", "nd", "rd", "th"].iter()
+                                         .filter_map(|suffix| word.strip_suffix(suffix))
+                                         .next()
+                                         .unwrap_or(word);
+    if digits.is_empty() || digits.len() > 2 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|day| *day >= 1)
+}
+
+fn year(word: &str) -> Option<i32> {
+    if word.len() == 4 && word.chars().all(|c| c.is_ascii_digit()) {
+        word.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn number(word: &str) -> Option<u32> {
+    const WORDS: [&'static str; 12] = ["one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+                                       "ten", "eleven", "twelve"];
+    match word {
+        "a" | "an" => Some(1),
+        _ if word.chars().all(|c| c.is_ascii_digit()) => word.parse().ok(),
+        _ => WORDS.iter().position(|number| *number == word).map(|index| index as u32 + 1),
+    }
+}
+
+/// A date parsed from a phrase. `millis` is since the Unix epoch; for an all-day
+/// date it's the midnight UTC that starts the day, and otherwise the date is shown
+/// in the zone the phrase was parsed in.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct DatePhraseC {
+    pub millis: i64,
+    pub all_day: bool,
+}
+
+/// Parse `phrase`, such as "tomorrow 5pm" or "next friday", as if it were `now`
+/// milliseconds since the Unix epoch in the IANA time zone `zone`. Returns false if
+/// the phrase isn't a date, leaving `date` untouched.
+///
+/// # Safety
+///
+/// `phrase` and `zone` must point to NUL-terminated strings that stay valid for the
+/// call; NULL is rejected with an error. The caller keeps ownership of them. A
+/// non-NULL `date` must point to a `DatePhraseC` that nothing else reads or writes
+/// during the call; it holds no pointers, so there is nothing to free.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_parse_date_phrase(phrase: *const c_char, now: i64, zone: *const c_char, date: *mut DatePhraseC) -> bool {
+    guard(false, || {
+        if date.is_null() {
+            return Err("date is NULL".to_string());
+        }
+        let parsed = parse_phrase_from_ptrs(phrase, now, zone)?;
+        *date = DatePhraseC {
+            millis: parsed.millis(),
+            all_day: parsed.is_all_day(),
+        };
+        Ok(true)
+    })
+}
+
+/// Set the item's due date from `phrase`, parsed as by `toodle_parse_date_phrase`.
+/// Returns false if the phrase isn't a date, leaving the item untouched.
+///
+/// # Safety
+///
+/// Same as `toodle_parse_date_phrase` for `phrase` and `zone`, and as
+/// `item_get_priority` for `item`.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_due_date_phrase(item: ItemHandle, phrase: *const c_char, now: i64, zone: *const c_char) -> bool {
+    guard(false, || {
+        let due_date = parse_phrase_from_ptrs(phrase, now, zone)?;
+        ITEMS.get_mut(item, |item| item.due_date = Some(due_date))?;
+        Ok(true)
+    })
+}
+
+unsafe fn parse_phrase_from_ptrs(phrase: *const c_char, now: i64, zone: *const c_char) -> Result<Date, String> {
+    let phrase = check_str(phrase, "phrase")?;
+    let zone = parse_zone(&check_str(zone, "zone")?)?;
+    let now = Date::from_millis(now)?;
+    Ok(parse_date_phrase(&phrase, &now, zone)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono_tz::America::New_York;
+    use chrono_tz::Europe::London;
+
+    // Wednesday 2017-11-01, 10:30 in New York.
+    const NOW: i64 = 1509546600000;
+
+    fn parse_at(phrase: &str, now: i64, zone: Tz) -> Result<String, DatePhraseError> {
+        let date = parse_date_phrase(phrase, &Date::from_millis(now).unwrap(), zone)?;
+        if date.is_all_day() {
+            return Ok(date.calendar_day().format("%Y-%m-%d").to_string());
+        }
+        assert_eq!(date.zone(), Some(zone));
+        Ok(date.local().format("%Y-%m-%d %H:%M").to_string())
+    }
+
+    fn parse(phrase: &str) -> Result<String, DatePhraseError> {
+        parse_at(phrase, NOW, New_York)
+    }
+
+    #[test]
+    fn test_phrases() {
+        let phrases = [
+            ("today", "2017-11-01"),
+            ("Today", "2017-11-01"),
+            ("tomorrow", "2017-11-02"),
+            ("tmrw", "2017-11-02"),
+            ("yesterday", "2017-10-31"),
+            ("on tomorrow", "2017-11-02"),
+
+            ("tomorrow 5pm", "2017-11-02 17:00"),
+            ("tomorrow at 5pm", "2017-11-02 17:00"),
+            ("5pm tomorrow", "2017-11-02 17:00"),
+            ("tomorrow, 5 pm", "2017-11-02 17:00"),
+            ("tomorrow at 17:30", "2017-11-02 17:30"),
+            ("tomorrow at 9", "2017-11-02 09:00"),
+            ("tomorrow 9:15am", "2017-11-02 09:15"),
+            ("tomorrow morning", "2017-11-02 09:00"),
+            ("tomorrow afternoon", "2017-11-02 14:00"),
+            ("tomorrow evening", "2017-11-02 18:00"),
+            ("12am tomorrow", "2017-11-02 00:00"),
+            ("tomorrow at noon", "2017-11-02 12:00"),
+
+            // Times without a day are the next time they come round.
+            ("5pm", "2017-11-01 17:00"),
+            ("5:45 PM", "2017-11-01 17:45"),
+            ("at 11", "2017-11-01 11:00"),
+            ("9am", "2017-11-02 09:00"),
+            ("10:30", "2017-11-02 10:30"),
+            ("12pm", "2017-11-01 12:00"),
+            ("noon", "2017-11-01 12:00"),
+            ("midday", "2017-11-01 12:00"),
+            ("midnight", "2017-11-02 00:00"),
+            ("evening", "2017-11-01 18:00"),
+            ("tonight", "2017-11-01 20:00"),
+            ("this evening", "2017-11-01 18:00"),
+            ("this morning", "2017-11-01 09:00"),
+
+            ("friday", "2017-11-03"),
+            ("fri", "2017-11-03"),
+            ("on friday", "2017-11-03"),
+            ("wednesday", "2017-11-08"),
+            ("monday", "2017-11-06"),
+            ("sunday", "2017-11-05"),
+            ("this wednesday", "2017-11-01"),
+            ("this friday", "2017-11-03"),
+            ("next friday", "2017-11-10"),
+            ("next monday", "2017-11-06"),
+            ("next wed", "2017-11-08"),
+            ("next sunday", "2017-11-12"),
+            ("friday 3pm", "2017-11-03 15:00"),
+            ("on friday at noon", "2017-11-03 12:00"),
+            ("next thurs at 8:00", "2017-11-09 08:00"),
+
+            ("next week", "2017-11-06"),
+            ("next month", "2017-12-01"),
+            ("next year", "2018-01-01"),
+            ("weekend", "2017-11-04"),
+            ("this weekend", "2017-11-04"),
+            ("next weekend", "2017-11-11"),
+            ("end of week", "2017-11-05"),
+            ("end of the week", "2017-11-05"),
+            ("eow", "2017-11-05"),
+            ("end of month", "2017-11-30"),
+            ("end of the month", "2017-11-30"),
+            ("eom", "2017-11-30"),
+            ("end of year", "2017-12-31"),
+            ("by end of month", "2017-11-30"),
+            ("end of month 5pm", "2017-11-30 17:00"),
+
+            ("in 3 days", "2017-11-04"),
+            ("in three days", "2017-11-04"),
+            ("in a day", "2017-11-02"),
+            ("in a week", "2017-11-08"),
+            ("in 2 weeks", "2017-11-15"),
+            ("in a month", "2017-12-01"),
+            ("in 1 year", "2018-11-01"),
+            ("in 3 days at 5pm", "2017-11-04 17:00"),
+            ("in 2 hours", "2017-11-01 12:30"),
+            ("in 30 minutes", "2017-11-01 11:00"),
+            ("in 45 mins", "2017-11-01 11:15"),
+            ("in an hour", "2017-11-01 11:30"),
+            ("now", "2017-11-01 10:30"),
+
+            ("nov 5", "2017-11-05"),
+            ("Nov. 5", "2017-11-05"),
+            ("5 nov", "2017-11-05"),
+            ("november 5th", "2017-11-05"),
+            ("5th of november", "2017-11-05"),
+            ("on the 5th of november", "2017-11-05"),
+            ("Nov 5, 2018", "2018-11-05"),
+            ("5 nov 2018", "2018-11-05"),
+            ("nov 1", "2017-11-01"),
+            ("oct 31", "2018-10-31"),
+            ("sept 3", "2018-09-03"),
+            ("dec 25 9am", "2017-12-25 09:00"),
+            ("feb 29", "2020-02-29"),
+            ("the 15th", "2017-11-15"),
+            ("15th", "2017-11-15"),
+            ("the 1st", "2017-11-01"),
+            ("the 31st", "2017-12-31"),
+            ("2018-03-14", "2018-03-14"),
+            ("2018-03-14 at 8pm", "2018-03-14 20:00"),
+        ];
+        for &(phrase, expected) in phrases.iter() {
+            assert_eq!(parse(phrase), Ok(expected.to_string()), "parsing {:?}", phrase);
+        }
+    }
+
+    #[test]
+    fn test_relative_to_zone() {
+        // 02:30 on Thursday in London is still Wednesday evening in New York.
+        let late = 1509589800000;
+        assert_eq!(parse_at("tomorrow", late, London), Ok("2017-11-03".to_string()));
+        assert_eq!(parse_at("tomorrow", late, New_York), Ok("2017-11-02".to_string()));
+        assert_eq!(parse_at("9am", late, London), Ok("2017-11-02 09:00".to_string()));
+    }
+
+    #[test]
+    fn test_clocks_going_back() {
+        // Saturday 2017-11-04 at noon in New York; the clocks go back overnight.
+        let saturday = 1509811200000;
+        assert_eq!(parse_at("in 1 day", saturday, New_York), Ok("2017-11-05".to_string()));
+        assert_eq!(parse_at("in 24 hours", saturday, New_York), Ok("2017-11-05 11:00".to_string()));
+        assert_eq!(parse_at("tomorrow at noon", saturday, New_York), Ok("2017-11-05 12:00".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let unrecognised = |text: &str| Err(DatePhraseError::Unrecognised(text.to_string()));
+        assert_eq!(parse(""), Err(DatePhraseError::Empty));
+        assert_eq!(parse("  , "), Err(DatePhraseError::Empty));
+        assert_eq!(parse("someday"), unrecognised("someday"));
+        assert_eq!(parse("tomorrow banana split"), unrecognised("banana split"));
+        assert_eq!(parse("5"), unrecognised("5"));
+        assert_eq!(parse("at"), unrecognised("at"));
+        assert_eq!(parse("on 5pm"), unrecognised("on 5pm"));
+        assert_eq!(parse("this week"), unrecognised("this week"));
+        assert_eq!(parse("in 3 fortnights"), unrecognised("in 3 fortnights"));
+        assert_eq!(parse("tomorrow friday"), unrecognised("friday"));
+        assert_eq!(parse("5pm noon"), unrecognised("noon"));
+        assert_eq!(parse("tonight at 9pm"), unrecognised("at 9pm"));
+        assert_eq!(parse("in 2 hours tomorrow"), unrecognised("tomorrow"));
+        assert_eq!(parse("end of time"), unrecognised("end of time"));
+
+        assert_eq!(parse("feb 30"), Err(DatePhraseError::NoSuchDay("feb 30".to_string())));
+        assert_eq!(parse("feb 29 2018"), Err(DatePhraseError::NoSuchDay("feb 29 2018".to_string())));
+        assert_eq!(parse("the 32nd"), Err(DatePhraseError::NoSuchDay("32nd".to_string())));
+        assert_eq!(parse("25:00"), Err(DatePhraseError::InvalidTime("25:00".to_string())));
+        assert_eq!(parse("13pm"), Err(DatePhraseError::InvalidTime("13pm".to_string())));
+        assert_eq!(parse("0 am"), Err(DatePhraseError::InvalidTime("0 am".to_string())));
+        assert_eq!(parse("in 4000000000 years"), Err(DatePhraseError::OutOfRange("in 4000000000 years".to_string())));
+
+        assert_eq!(String::from(DatePhraseError::NoSuchDay("feb 30".to_string())), "no such day \"feb 30\"");
+        assert_eq!(DatePhraseError::Unrecognised("x".to_string()).to_string(), "unrecognised \"x\" in date");
+    }
+}
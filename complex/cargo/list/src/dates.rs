@@ -0,0 +1,382 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The dates items are due and completed on.
+//!
+//! A date is either a whole calendar day or an instant. All-day dates, such as "the
+//! rent is due on the 1st", are the same day wherever they're read and have no time
+//! zone. Timestamps are milliseconds since the Unix epoch and may carry the IANA time
+//! zone they were set in, so that 9am in London is still shown as 9am in London once
+//! the clocks have changed.
+//!
+//! Across the FFI both are `i64` milliseconds since the epoch, and an all-day date is
+//! the midnight UTC that starts its day.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{
+    DateTime,
+    Duration,
+    Local,
+    LocalResult,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    Offset,
+    TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
+#[cfg(feature = "sqlite")]
+use rusqlite;
+#[cfg(feature = "sqlite")]
+use rusqlite::types::{
+    FromSql,
+    FromSqlError,
+    FromSqlResult,
+    ToSql,
+    ToSqlOutput,
+    ValueRef,
+};
+
+pub const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+const DAY_FORMAT: &'static str = "%Y-%m-%d";
+const TIMESTAMP_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S%.3f";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Date {
+    /// A calendar day with no time of day.
+    AllDay(NaiveDate),
+    /// An instant in milliseconds since the Unix epoch, and the time zone it's shown
+    /// in. Timestamps without a zone are shown in UTC.
+    Timestamp { millis: i64, zone: Option<Tz> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateError {
+    /// A time zone name that isn't in the IANA database.
+    UnknownZone(String),
+    /// Milliseconds too far from the epoch to be a calendar date.
+    OutOfRange(i64),
+    /// Stored text that isn't a date written by `Date`'s `Display`.
+    Malformed(String),
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DateError::UnknownZone(ref zone) => write!(f, "unknown time zone {:?}", zone),
+            DateError::OutOfRange(millis) => write!(f, "{} milliseconds is out of range", millis),
+            DateError::Malformed(ref text) => write!(f, "malformed date {:?}", text),
+        }
+    }
+}
+
+impl Error for DateError {}
+
+impl From<DateError> for String {
+    fn from(error: DateError) -> String {
+        error.to_string()
+    }
+}
+
+impl Date {
+    /// The all-day date `year`-`month`-`day`, or `None` if there's no such day.
+    pub fn day(year: i32, month: u32, day: u32) -> Option<Date> {
+        NaiveDate::from_ymd_opt(year, month, day).map(Date::AllDay)
+    }
+
+    /// The all-day date of the UTC day `millis` falls on. This is the inverse of
+    /// `millis` for all-day dates.
+    pub fn day_from_millis(millis: i64) -> Result<Date, DateError> {
+        Ok(Date::AllDay(utc(millis)?.date()))
+    }
+
+    /// A timestamp without a time zone.
+    pub fn from_millis(millis: i64) -> Result<Date, DateError> {
+        utc(millis)?;
+        Ok(Date::Timestamp { millis: millis, zone: None })
+    }
+
+    /// A timestamp shown in the IANA time zone `zone`, e.g. `"America/New_York"`.
+    pub fn from_millis_in(millis: i64, zone: &str) -> Result<Date, DateError> {
+        let zone = parse_zone(zone)?;
+        utc(millis)?;
+        Ok(Date::Timestamp { millis: millis, zone: Some(zone) })
+    }
+
+    /// The timestamp at `utc`, without a time zone.
+    pub fn from_utc(utc: NaiveDateTime) -> Date {
+        Date::Timestamp { millis: Utc.from_utc_datetime(&utc).timestamp_millis(), zone: None }
+    }
+
+    /// The timestamp at wall-clock time `local` in `zone`.
+    ///
+    /// As in RFC 5545, a time skipped when the clocks go forward is moved on by the
+    /// length of the gap, and a time that happens twice when they go back is the
+    /// first of the two.
+    pub fn from_local(local: NaiveDateTime, zone: Tz) -> Date {
+        let utc = match zone.from_local_datetime(&local) {
+            LocalResult::Single(date) | LocalResult::Ambiguous(date, _) => date.naive_utc(),
+            LocalResult::None => {
+                // Use the offset from before the gap, i.e. the one in force a day
+                // earlier; no zone changes its clocks twice in one day.
+                let before = zone.offset_from_utc_datetime(&(local - Duration::days(1))).fix();
+                local - Duration::seconds(before.local_minus_utc() as i64)
+            },
+        };
+        Date::Timestamp { millis: Utc.from_utc_datetime(&utc).timestamp_millis(), zone: Some(zone) }
+    }
+
+    /// The current time, without a time zone.
+    pub fn now() -> Date {
+        Date::Timestamp { millis: Utc::now().timestamp_millis(), zone: None }
+    }
+
+    /// Today in the local time zone, as an all-day date.
+    pub fn today() -> Date {
+        Date::AllDay(Local::now().date_naive())
+    }
+
+    pub fn is_all_day(&self) -> bool {
+        match *self {
+            Date::AllDay(_) => true,
+            Date::Timestamp { .. } => false,
+        }
+    }
+
+    pub fn zone(&self) -> Option<Tz> {
+        match *self {
+            Date::AllDay(_) => None,
+            Date::Timestamp { zone, .. } => zone,
+        }
+    }
+
+    /// Milliseconds since the Unix epoch. An all-day date is the midnight UTC that
+    /// starts its day.
+    pub fn millis(&self) -> i64 {
+        match *self {
+            Date::AllDay(_) => Utc.from_utc_datetime(&self.utc()).timestamp_millis(),
+            Date::Timestamp { millis, .. } => millis,
+        }
+    }
+
+    /// The date and time in UTC. An all-day date is midnight at the start of its day.
+    pub fn utc(&self) -> NaiveDateTime {
+        match *self {
+            Date::AllDay(day) => day.and_time(NaiveTime::MIN),
+            Date::Timestamp { millis, .. } => utc(millis).expect("timestamp out of range"),
+        }
+    }
+
+    /// The wall-clock date and time in the date's time zone, or in UTC if it has none.
+    pub fn local(&self) -> NaiveDateTime {
+        match self.zone() {
+            Some(zone) => zone.from_utc_datetime(&self.utc()).naive_local(),
+            None => self.utc(),
+        }
+    }
+
+    /// The calendar day the date falls on in its time zone.
+    pub fn calendar_day(&self) -> NaiveDate {
+        self.local().date()
+    }
+}
+
+/// The form dates are stored in: `2017-11-05` for all-day dates, and
+/// `2017-11-05 12:00:00.000` in UTC for timestamps, followed by a space and the time
+/// zone if there is one. Stored timestamps sort in time order.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Date::AllDay(day) => write!(f, "{}", day.format(DAY_FORMAT)),
+            Date::Timestamp { zone, .. } => {
+                write!(f, "{}", self.utc().format(TIMESTAMP_FORMAT))?;
+                match zone {
+                    Some(zone) => write!(f, " {}", zone.name()),
+                    None => Ok(()),
+                }
+            },
+        }
+    }
+}
+
+/// Reads the form written by `Display`, as well as the `2017-11-05 12:00:00` UTC
+/// timestamps stored before dates had milliseconds.
+impl FromStr for Date {
+    type Err = DateError;
+
+    fn from_str(text: &str) -> Result<Date, DateError> {
+        if let Ok(day) = NaiveDate::parse_from_str(text, DAY_FORMAT) {
+            return Ok(Date::AllDay(day));
+        }
+        let mut parts = text.splitn(3, ' ');
+        let date_time = match (parts.next(), parts.next()) {
+            (Some(day), Some(time)) => format!("{} {}", day, time),
+            _ => return Err(DateError::Malformed(text.to_string())),
+        };
+        let utc = NaiveDateTime::parse_from_str(&date_time, "%Y-%m-%d %H:%M:%S%.f")
+            .map_err(|_| DateError::Malformed(text.to_string()))?;
+        match parts.next() {
+            Some(zone) => Date::from_millis_in(Date::from_utc(utc).millis(), zone),
+            None => Ok(Date::from_utc(utc)),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ToSql for Date {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql for Date {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        value.as_str().and_then(|text| text.parse().map_err(|e: DateError| FromSqlError::Other(Box::new(e))))
+    }
+}
+
+pub fn parse_zone(zone: &str) -> Result<Tz, DateError> {
+    zone.parse().map_err(|_| DateError::UnknownZone(zone.to_string()))
+}
+
+fn utc(millis: i64) -> Result<NaiveDateTime, DateError> {
+    DateTime::<Utc>::from_timestamp_millis(millis)
+        .map(|date| date.naive_utc())
+        .ok_or(DateError::OutOfRange(millis))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono_tz::America::New_York;
+    use chrono_tz::Europe::London;
+
+    // 2017-11-05, the day clocks go back in New York.
+    const NOV_5: i64 = 1509840000000;
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn local(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_all_day() {
+        let day = Date::day(2017, 11, 5).unwrap();
+        assert!(day.is_all_day());
+        assert_eq!(day.zone(), None);
+        assert_eq!(day.millis(), NOV_5);
+        assert_eq!(Date::day_from_millis(NOV_5 + 23 * HOUR), Ok(day));
+        assert_eq!(Date::day_from_millis(NOV_5 - 1).unwrap(), Date::day(2017, 11, 4).unwrap());
+        assert_eq!(day.calendar_day(), NaiveDate::from_ymd_opt(2017, 11, 5).unwrap());
+        assert_eq!(Date::day(2017, 2, 29), None);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let date = Date::from_millis(NOV_5 + 12 * HOUR + 345).unwrap();
+        assert!(!date.is_all_day());
+        assert_eq!(date.millis(), 1509883200345);
+        assert_eq!(date.local(), local("2017-11-05 12:00") + Duration::milliseconds(345));
+
+        let zoned = Date::from_millis_in(NOV_5 + 12 * HOUR, "America/New_York").unwrap();
+        assert_eq!(zoned.zone(), Some(New_York));
+        assert_eq!(zoned.local(), local("2017-11-05 07:00"));
+
+        // Late evening in New York is already the next day in UTC.
+        let evening = Date::from_local(local("2017-11-05 22:00"), New_York);
+        assert_eq!(evening.utc().date(), NaiveDate::from_ymd_opt(2017, 11, 6).unwrap());
+        assert_eq!(evening.calendar_day(), NaiveDate::from_ymd_opt(2017, 11, 5).unwrap());
+    }
+
+    #[test]
+    fn test_clocks_going_forward() {
+        // New York skips from 02:00 to 03:00 EDT on 2017-03-12.
+        let before = Date::from_local(local("2017-03-12 01:59"), New_York);
+        assert_eq!(before.utc(), local("2017-03-12 06:59"));
+        let skipped = Date::from_local(local("2017-03-12 02:30"), New_York);
+        assert_eq!(skipped.utc(), local("2017-03-12 07:30"));
+        assert_eq!(skipped.local(), local("2017-03-12 03:30"));
+        let after = Date::from_local(local("2017-03-12 03:30"), New_York);
+        assert_eq!(after, skipped);
+
+        // A day that loses an hour is 23 hours long.
+        let start = Date::from_local(local("2017-03-12 00:00"), New_York);
+        let end = Date::from_local(local("2017-03-13 00:00"), New_York);
+        assert_eq!(end.millis() - start.millis(), 23 * HOUR);
+
+        // London goes forward at 01:00 UTC on 2017-03-26.
+        let skipped = Date::from_local(local("2017-03-26 01:15"), London);
+        assert_eq!(skipped.local(), local("2017-03-26 02:15"));
+        assert_eq!(skipped.utc(), local("2017-03-26 01:15"));
+    }
+
+    #[test]
+    fn test_clocks_going_back() {
+        // New York repeats 01:00 to 02:00 on 2017-11-05, first in EDT and then in EST.
+        let first = Date::from_local(local("2017-11-05 01:30"), New_York);
+        assert_eq!(first.utc(), local("2017-11-05 05:30"));
+        let second = Date::from_millis_in(first.millis() + HOUR, "America/New_York").unwrap();
+        assert_eq!(second.local(), local("2017-11-05 01:30"));
+        assert_eq!(Date::from_local(local("2017-11-05 02:00"), New_York).utc(), local("2017-11-05 07:00"));
+
+        // A day that gains an hour is 25 hours long, and a whole day is still one day.
+        let start = Date::from_local(local("2017-11-05 00:00"), New_York);
+        let end = Date::from_local(local("2017-11-06 00:00"), New_York);
+        assert_eq!(end.millis() - start.millis(), 25 * HOUR);
+        assert_eq!(Date::day(2017, 11, 6).unwrap().millis() - Date::day(2017, 11, 5).unwrap().millis(), MILLIS_PER_DAY);
+
+        // 09:00 in London is 09:00 UTC in winter and 08:00 UTC in summer.
+        let winter = Date::from_local(local("2017-10-30 09:00"), London);
+        let summer = Date::from_local(local("2017-10-28 09:00"), London);
+        assert_eq!(winter.utc(), local("2017-10-30 09:00"));
+        assert_eq!(summer.utc(), local("2017-10-28 08:00"));
+    }
+
+    #[test]
+    fn test_storage_format() {
+        let dates = vec![
+            Date::day(2017, 11, 5).unwrap(),
+            Date::from_millis(1509883200345).unwrap(),
+            Date::from_millis_in(1509883200000, "Europe/London").unwrap(),
+            Date::from_millis(-1).unwrap(),
+        ];
+        let text: Vec<String> = dates.iter().map(|date| date.to_string()).collect();
+        assert_eq!(text, vec![
+            "2017-11-05",
+            "2017-11-05 12:00:00.345",
+            "2017-11-05 12:00:00.000 Europe/London",
+            "1969-12-31 23:59:59.999",
+        ]);
+        for (date, text) in dates.iter().zip(text.iter()) {
+            assert_eq!(text.parse::<Date>().as_ref(), Ok(date));
+        }
+        assert_eq!("2017-11-05 12:00:00".parse::<Date>(), Date::from_millis(1509883200000));
+
+        assert_eq!("2017-11-05 12:00:00 Mars/Olympus_Mons".parse::<Date>(),
+                   Err(DateError::UnknownZone("Mars/Olympus_Mons".to_string())));
+        assert_eq!("tomorrow".parse::<Date>(), Err(DateError::Malformed("tomorrow".to_string())));
+        assert_eq!("2017-11-05 noon".parse::<Date>(), Err(DateError::Malformed("2017-11-05 noon".to_string())));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Date::from_millis_in(0, "Europe/Lodnon"), Err(DateError::UnknownZone("Europe/Lodnon".to_string())));
+        assert_eq!(Date::from_millis(i64::max_value()), Err(DateError::OutOfRange(i64::max_value())));
+        assert_eq!(Date::day_from_millis(i64::min_value()), Err(DateError::OutOfRange(i64::min_value())));
+        assert_eq!(String::from(DateError::UnknownZone("x".to_string())), "unknown time zone \"x\"");
+    }
+}
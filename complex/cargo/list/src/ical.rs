@@ -0,0 +1,526 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Conversion between `Item`s and iCalendar (RFC 5545) `VTODO` components.
+//!
+//! Only the properties that map onto an `Item` are read: `UID`, `SUMMARY`, `DUE`,
+//! `COMPLETED`, `PRIORITY` and `CATEGORIES`. Everything else, including other
+//! component types, is skipped.
+//!
+//! `PRIORITY` runs from 1 for the highest to 9 for the lowest, with 0 for none.
+//! High, medium and low priorities are written as 1, 5 and 9, and read back from
+//! 1 to 4, 5, and 6 to 9, the ranges RFC 5545 suggests for them.
+//!
+//! All-day due dates are written as `VALUE=DATE` and due dates with a time zone are
+//! written in local time with a `TZID` naming the IANA zone. Times are read in their
+//! `TZID` zone, or as UTC if they're floating or the zone isn't an IANA one.
+//! `COMPLETED` is always written in UTC, as RFC 5545 requires, and times are whole
+//! seconds.
+
+use std::fmt;
+
+use chrono::{
+    NaiveDate,
+    NaiveDateTime,
+};
+
+use dates::{
+    parse_zone,
+    Date,
+};
+use items::{
+    Item,
+    Priority,
+};
+use labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+
+const PRODID: &'static str = "-//Mozilla//Toodle//EN";
+const DATE_FORMAT: &'static str = "%Y%m%d";
+const LOCAL_DATE_TIME_FORMAT: &'static str = "%Y%m%dT%H%M%S";
+const DATE_TIME_FORMAT: &'static str = "%Y%m%dT%H%M%SZ";
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IcalError {
+    /// A content line without a `:` separating its name from its value.
+    MalformedLine(usize),
+    /// A `BEGIN` with no matching `END`, or an `END` with no matching `BEGIN`.
+    UnbalancedComponent(String),
+    /// A `VTODO` without a `SUMMARY`.
+    MissingSummary(usize),
+    /// A `DUE` or `COMPLETED` value that isn't a DATE or DATE-TIME.
+    InvalidDate(usize, String),
+    /// A `PRIORITY` value that isn't a number from 0 to 9.
+    InvalidPriority(usize, String),
+}
+
+impl fmt::Display for IcalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IcalError::MalformedLine(line) => write!(f, "line {}: malformed content line", line),
+            IcalError::UnbalancedComponent(ref name) => write!(f, "unbalanced component {}", name),
+            IcalError::MissingSummary(line) => write!(f, "line {}: VTODO has no SUMMARY", line),
+            IcalError::InvalidDate(line, ref value) => write!(f, "line {}: invalid date {:?}", line, value),
+            IcalError::InvalidPriority(line, ref value) => write!(f, "line {}: invalid priority {:?}", line, value),
+        }
+    }
+}
+
+/// Serialise a single item as a `VTODO` component.
+pub fn item_to_vtodo(item: &Item) -> String {
+    let mut out = String::new();
+    write_line(&mut out, "BEGIN:VTODO");
+    write_line(&mut out, &format!("UID:{}", escape_text(&item.uuid)));
+    write_line(&mut out, &format!("DTSTAMP:{}", format_date_time(&Date::now())));
+    write_line(&mut out, &format!("SUMMARY:{}", escape_text(&item.name)));
+    if let Some(ref due) = item.due_date {
+        write_line(&mut out, &format_date_property("DUE", due));
+    }
+    match item.completion_date {
+        Some(ref completed) => {
+            write_line(&mut out, "STATUS:COMPLETED");
+            write_line(&mut out, &format!("COMPLETED:{}", format_date_time(completed)));
+        },
+        None => write_line(&mut out, "STATUS:NEEDS-ACTION"),
+    }
+    if let Some(priority) = item.priority {
+        write_line(&mut out, &format!("PRIORITY:{}", format_priority(priority)));
+    }
+    if !item.labels.is_empty() {
+        let categories: Vec<String> = item.labels.iter().map(|label| escape_text(&label.name)).collect();
+        write_line(&mut out, &format!("CATEGORIES:{}", categories.join(",")));
+    }
+    write_line(&mut out, "END:VTODO");
+    out
+}
+
+/// Serialise `items` as a complete `VCALENDAR` object.
+pub fn items_to_ics(items: &[Item]) -> String {
+    let mut out = String::new();
+    write_line(&mut out, "BEGIN:VCALENDAR");
+    write_line(&mut out, "VERSION:2.0");
+    write_line(&mut out, &format!("PRODID:{}", PRODID));
+    for item in items.iter() {
+        out.push_str(&item_to_vtodo(item));
+    }
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Parse every `VTODO` in `ics` into an `Item`.
+///
+/// Labels are created from `CATEGORIES` with the default color; it is up to the
+/// caller to reconcile them with labels that already exist.
+pub fn parse_ics(ics: &str) -> Result<Vec<Item>, IcalError> {
+    let mut items = Vec::new();
+    let mut components: Vec<String> = Vec::new();
+    let mut current: Option<(usize, Item)> = None;
+    let mut has_summary = false;
+
+    for (line_number, line) in unfold(ics) {
+        if line.is_empty() {
+            continue;
+        }
+        let property = parse_content_line(&line).ok_or(IcalError::MalformedLine(line_number))?;
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.to_uppercase();
+                if component == "VTODO" && components.last().map(|c| c.as_str()) == Some("VCALENDAR") {
+                    current = Some((line_number, Item::new("".to_string(), "".to_string(), None, None, vec![])));
+                    has_summary = false;
+                }
+                components.push(component);
+                continue;
+            },
+            "END" => {
+                let component = property.value.to_uppercase();
+                if components.pop().as_ref() != Some(&component) {
+                    return Err(IcalError::UnbalancedComponent(component));
+                }
+                if component == "VTODO" {
+                    if let Some((begin, item)) = current.take() {
+                        if !has_summary {
+                            return Err(IcalError::MissingSummary(begin));
+                        }
+                        items.push(item);
+                    }
+                }
+                continue;
+            },
+            _ => {},
+        }
+
+        // Properties of nested components such as VALARM don't describe the item.
+        if components.last().map(|c| c.as_str()) != Some("VTODO") {
+            continue;
+        }
+        let item = match current {
+            Some((_, ref mut item)) => item,
+            None => continue,
+        };
+        match property.name.as_str() {
+            "UID" => item.uuid = unescape_text(&property.value),
+            "SUMMARY" => {
+                item.name = unescape_text(&property.value);
+                has_summary = true;
+            },
+            "DUE" => item.due_date = Some(parse_date(&property).ok_or(IcalError::InvalidDate(line_number, property.value.clone()))?),
+            "COMPLETED" => item.completion_date = Some(parse_date(&property).ok_or(IcalError::InvalidDate(line_number, property.value.clone()))?),
+            "PRIORITY" => item.priority = parse_priority(&property.value).ok_or(IcalError::InvalidPriority(line_number, property.value.clone()))?,
+            "CATEGORIES" => {
+                for name in split_list(&property.value) {
+                    if !name.is_empty() && !item.labels.iter().any(|label| label.name == name) {
+                        item.labels.push(Label { name: name, color: DEFAULT_COLOR.to_string() });
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    if let Some(component) = components.pop() {
+        return Err(IcalError::UnbalancedComponent(component));
+    }
+    Ok(items)
+}
+
+struct ContentLine {
+    name: String,
+    /// Parameter names, upper-cased, and their values without quotes.
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|&&(ref param, _)| param == name).map(|&(_, ref value)| value.as_str())
+    }
+}
+
+fn parse_content_line(line: &str) -> Option<ContentLine> {
+    // The name ends at the first `;` or `:`, but parameter values may themselves be
+    // quoted and contain either, so the value starts at the first unquoted `:`.
+    let mut in_quotes = false;
+    let mut name_end = None;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if name_end.is_none() => name_end = Some(index),
+            ':' if !in_quotes => {
+                let name = &line[..name_end.unwrap_or(index)];
+                if name.is_empty() {
+                    return None;
+                }
+                let params = match name_end {
+                    Some(end) => parse_params(&line[end + 1..index]),
+                    None => vec![],
+                };
+                return Some(ContentLine {
+                    name: name.to_uppercase(),
+                    params: params,
+                    value: line[index + 1..].to_string(),
+                });
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Parse `;` separated `NAME=value` parameters.
+fn parse_params(text: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in text.chars().chain(Some(';')) {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                if let Some(equals) = current.find('=') {
+                    params.push((current[..equals].to_uppercase(), current[equals + 1..].to_string()));
+                }
+                current.clear();
+            },
+            _ => current.push(c),
+        }
+    }
+    params
+}
+
+/// Undo RFC 5545 line folding, yielding each logical line with the number of the
+/// physical line it started on.
+fn unfold(ics: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in ics.split('\n').enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(&mut (_, ref mut last)) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push((index + 1, line.to_string()));
+    }
+    lines
+}
+
+/// Append `line` to `out`, folding it so that no physical line exceeds 75 octets.
+fn write_line(out: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split a comma separated list of TEXT values, honouring escaped commas.
+fn split_list(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            },
+            ',' => {
+                values.push(unescape_text(&current));
+                current.clear();
+            },
+            _ => current.push(c),
+        }
+    }
+    values.push(unescape_text(&current));
+    values
+}
+
+fn format_priority(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+/// The priority `value` stands for, which may be none, or `None` if it isn't a
+/// priority at all.
+fn parse_priority(value: &str) -> Option<Option<Priority>> {
+    match value.trim().parse::<u8>().ok()? {
+        0 => Some(None),
+        1..=4 => Some(Some(Priority::High)),
+        5 => Some(Some(Priority::Medium)),
+        6..=9 => Some(Some(Priority::Low)),
+        _ => None,
+    }
+}
+
+fn format_date_time(date: &Date) -> String {
+    date.utc().format(DATE_TIME_FORMAT).to_string()
+}
+
+/// A property holding `date` as a DATE, a DATE-TIME in its time zone, or a UTC DATE-TIME.
+fn format_date_property(name: &str, date: &Date) -> String {
+    match *date {
+        Date::AllDay(day) => format!("{};VALUE=DATE:{}", name, day.format(DATE_FORMAT)),
+        Date::Timestamp { zone: Some(zone), .. } => {
+            format!("{};TZID={}:{}", name, zone.name(), date.local().format(LOCAL_DATE_TIME_FORMAT))
+        },
+        Date::Timestamp { zone: None, .. } => format!("{}:{}", name, format_date_time(date)),
+    }
+}
+
+fn parse_date(property: &ContentLine) -> Option<Date> {
+    let value = property.value.as_str();
+    if !value.chars().all(|c| c.is_ascii_digit() || c == 'T' || c == 'Z') {
+        return None;
+    }
+    match value.len() {
+        8 => NaiveDate::parse_from_str(value, DATE_FORMAT).ok().map(Date::AllDay),
+        15 => {
+            let local = NaiveDateTime::parse_from_str(value, LOCAL_DATE_TIME_FORMAT).ok()?;
+            match property.param("TZID").and_then(|zone| parse_zone(zone).ok()) {
+                Some(zone) => Some(Date::from_local(local, zone)),
+                None => Some(Date::from_utc(local)),
+            }
+        },
+        16 if value.ends_with('Z') => NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).ok().map(Date::from_utc),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono_tz::Europe::London;
+
+    use items::Item;
+    use labels::Label;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            color: DEFAULT_COLOR.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut items = vec![
+            Item::new("uuid1".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![label("groceries")]),
+            Item::new("uuid2".to_string(), "call mum; then dad, maybe\\later".to_string(), Date::from_millis(1509883200000).ok(), Date::from_millis(1509800000000).ok(), vec![]),
+            Item::new("uuid4".to_string(), "call the bank".to_string(), Date::from_millis_in(1509883200000, "America/New_York").ok(), None, vec![]),
+            Item::new("uuid3".to_string(), "labels, with commas".to_string(), None, None, vec![label("home, garden"), label("weekend")]),
+        ];
+        items[0].priority = Some(Priority::High);
+        items[1].priority = Some(Priority::Medium);
+        items[2].priority = Some(Priority::Low);
+        let parsed = parse_ics(&items_to_ics(&items)).expect("expected items");
+        assert_eq!(parsed, items);
+    }
+
+    #[test]
+    fn test_long_lines_are_folded() {
+        let name = "a very long item name that goes on and on past the seventy five octet limit ✓✓✓";
+        let item = Item::new("uuid".to_string(), name.to_string(), None, None, vec![]);
+        let ics = items_to_ics(&[item.clone()]);
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS);
+        }
+        let parsed = parse_ics(&ics).expect("expected items");
+        assert_eq!(parsed, vec![item]);
+    }
+
+    #[test]
+    fn test_parse_foreign_calendar() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   VERSION:2.0\r\n\
+                   PRODID:-//Example Corp//Calendar//EN\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:event@example.com\r\n\
+                   SUMMARY:Not a todo\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VTODO\r\n\
+                   UID:todo@example.com\r\n\
+                   summary;LANGUAGE=en:Submit\r\n  \"quarterly\" report\r\n\
+                   DUE;VALUE=DATE:20171105\r\n\
+                   COMPLETED;TZID=\"Europe/London\":20171104T093000\r\n\
+                   CATEGORIES:work,finance\r\n\
+                   CATEGORIES:work\r\n\
+                   BEGIN:VALARM\r\n\
+                   ACTION:DISPLAY\r\n\
+                   SUMMARY:reminder\r\n\
+                   END:VALARM\r\n\
+                   END:VTODO\r\n\
+                   END:VCALENDAR\r\n";
+        let items = parse_ics(ics).expect("expected items");
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.uuid, "todo@example.com");
+        assert_eq!(item.name, "Submit \"quarterly\" report");
+        assert_eq!(item.due_date, Date::day(2017, 11, 5));
+        assert_eq!(item.completion_date, Date::from_millis_in(1509787800000, "Europe/London").ok());
+        assert_eq!(item.labels, vec![label("work"), label("finance")]);
+    }
+
+    #[test]
+    fn test_dates() {
+        let item = Item::new("uuid".to_string(), "x".to_string(), Date::from_millis_in(1509883200000, "America/New_York").ok(), None, vec![]);
+        assert!(item_to_vtodo(&item).contains("\r\nDUE;TZID=America/New_York:20171105T070000\r\n"));
+        let item = Item::new("uuid".to_string(), "x".to_string(), Date::day(2017, 11, 5), Date::day(2017, 11, 4), vec![]);
+        let vtodo = item_to_vtodo(&item);
+        assert!(vtodo.contains("\r\nDUE;VALUE=DATE:20171105\r\n"));
+        assert!(vtodo.contains("\r\nCOMPLETED:20171104T000000Z\r\n"));
+
+        let dues = [
+            // Skipped when the clocks went forward, so an hour later.
+            ("DUE;TZID=Europe/London:20170326T013000", Date::from_local(NaiveDateTime::parse_from_str("20170326T023000", LOCAL_DATE_TIME_FORMAT).unwrap(), London)),
+            // Repeated when the clocks went back, so the first, summer time, one.
+            ("DUE;TZID=Europe/London:20171029T013000", Date::from_millis_in(1509237000000, "Europe/London").unwrap()),
+            ("DUE;TZID=Eastern Standard Time:20171105T120000", Date::from_millis(1509883200000).unwrap()),
+            ("DUE:20171105T120000", Date::from_millis(1509883200000).unwrap()),
+        ];
+        for &(line, due) in dues.iter() {
+            let ics = format!("BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:x\r\n{}\r\nEND:VTODO\r\nEND:VCALENDAR\r\n", line);
+            assert_eq!(parse_ics(&ics).expect("expected items")[0].due_date, Some(due), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_priorities() {
+        let cases = [("0", None), ("1", Some(Priority::High)), ("4", Some(Priority::High)), ("5", Some(Priority::Medium)),
+                     ("6", Some(Priority::Low)), ("9", Some(Priority::Low))];
+        for &(value, priority) in cases.iter() {
+            let ics = format!("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:x\nPRIORITY:{}\nEND:VTODO\nEND:VCALENDAR\n", value);
+            assert_eq!(parse_ics(&ics).unwrap()[0].priority, priority, "PRIORITY:{}", value);
+        }
+        let item = Item::new("uuid".to_string(), "x".to_string(), None, None, vec![]);
+        assert!(!item_to_vtodo(&item).contains("PRIORITY"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:x\nEND:VTODO\n"),
+                   Err(IcalError::UnbalancedComponent("VCALENDAR".to_string())));
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nBEGIN:VTODO\nEND:VCALENDAR\n"),
+                   Err(IcalError::UnbalancedComponent("VCALENDAR".to_string())));
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:x\nEND:VTODO\nEND:VCALENDAR\n"),
+                   Err(IcalError::MissingSummary(2)));
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:x\nDUE:tomorrow\nEND:VTODO\nEND:VCALENDAR\n"),
+                   Err(IcalError::InvalidDate(4, "tomorrow".to_string())));
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:x\nPRIORITY:10\nEND:VTODO\nEND:VCALENDAR\n"),
+                   Err(IcalError::InvalidPriority(4, "10".to_string())));
+        assert_eq!(parse_ics("BEGIN:VCALENDAR\nno separator\nEND:VCALENDAR\n"),
+                   Err(IcalError::MalformedLine(2)));
+    }
+}
@@ -8,28 +8,241 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-use libc::size_t;
-use std::os::raw::{
-    c_char,
-    c_int,
-};
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_char;
 use std::ptr;
+use std::slice;
+use std::str::FromStr;
 
-use time::Timespec;
-
+use ffi_utils::buffers::ByteBuffer;
+use ffi_utils::error::{
+    check_bytes_str,
+    check_optional_str,
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
+use dates::Date;
+#[cfg(feature = "sqlite")]
+use rusqlite;
+#[cfg(feature = "sqlite")]
+use rusqlite::types::{
+    FromSql,
+    FromSqlError,
+    FromSqlResult,
+    ToSql,
+    ToSqlOutput,
+    ValueRef,
+};
 use ffi_utils::strings::{
+    free_c_char,
     string_to_c_char,
-    c_char_to_string,
+    string_to_c_string,
+    StringError,
+};
+use labels::{
+    Label,
+    LabelC,
+    LabelHandle,
+    LabelListHandle,
+    LABELS,
+    LABEL_LISTS,
 };
-use labels::Label;
+use manager_from_handle;
+use ListManagerHandle;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Item {
     pub uuid: String,
     pub name: String,
-    pub due_date: Option<Timespec>,
-    pub completion_date: Option<Timespec>,
+    pub due_date: Option<Date>,
+    pub completion_date: Option<Date>,
     pub labels: Vec<Label>,
+    pub priority: Option<Priority>,
+}
+
+/// How important an item is. Priorities order from `Low` to `High`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    /// The priority numbered `number` as by `Priority::number`, or `None` for 0.
+    pub fn from_number(number: i64) -> Result<Option<Priority>, String> {
+        match number {
+            0 => Ok(None),
+            1 => Ok(Some(Priority::Low)),
+            2 => Ok(Some(Priority::Medium)),
+            3 => Ok(Some(Priority::High)),
+            _ => Err(format!("unknown priority {}", number)),
+        }
+    }
+
+    /// The number the priority is stored and passed across the FFI as, from 1 for
+    /// `Low` to 3 for `High`. No priority is 0.
+    pub fn number(self) -> u8 {
+        match self {
+            Priority::Low => 1,
+            Priority::Medium => 2,
+            Priority::High => 3,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Reads the names `Display` writes, ignoring case, and "med" for `Medium`.
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Priority, String> {
+        match text.to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "medium" | "med" => Ok(Priority::Medium),
+            "high" => Ok(Priority::High),
+            _ => Err(format!("unknown priority {:?}", text)),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.number() as i64))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql for Priority {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        let number = value.as_i64()?;
+        match Priority::from_number(number) {
+            Ok(Some(priority)) => Ok(priority),
+            _ => Err(FromSqlError::OutOfRange(number)),
+        }
+    }
+}
+
+pub type ItemList = Vec<Item>;
+
+/// An item handed across the FFI, freed with `item_destroy`.
+pub type ItemHandle = u64;
+
+/// A list of items handed across the FFI, read with `item_list_count` and
+/// `item_list_entry_at` and freed with `item_list_destroy`.
+pub type ItemListHandle = u64;
+
+pub static ITEMS: HandleRegistry<Item> = HandleRegistry::new("item");
+pub static ITEM_LISTS: HandleRegistry<ItemList> = HandleRegistry::new("item list");
+
+/// A copy of an item's fields that C can read directly. The strings and labels
+/// belong to the snapshot and stay valid until it is passed to `item_snapshot_free`.
+/// Dates are milliseconds since the Unix epoch and are only meaningful when the
+/// matching `has_` flag is set; their zones are NULL when they have none.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ItemC {
+    pub uuid: *const c_char,
+    pub name: *const c_char,
+    pub has_due_date: bool,
+    pub due_date: i64,
+    pub due_date_all_day: bool,
+    pub due_date_zone: *const c_char,
+    pub has_completion_date: bool,
+    pub completion_date: i64,
+    pub completion_date_all_day: bool,
+    pub completion_date_zone: *const c_char,
+    pub labels: *const LabelC,
+    pub labels_len: usize,
+    /// 0 for no priority, otherwise 1 for low to 3 for high.
+    pub priority: u8,
+}
+
+impl ItemC {
+    /// Fails if any of the item's strings has a NUL byte in it.
+    pub fn new(item: &Item) -> Result<ItemC, StringError> {
+        // Check every string before handing any of them over, so that nothing leaks
+        // when one is rejected.
+        let uuid = string_to_c_string(item.uuid.clone())?;
+        let name = string_to_c_string(item.name.clone())?;
+        let due_date_zone = zone_to_c_string(item.due_date)?;
+        let completion_date_zone = zone_to_c_string(item.completion_date)?;
+        let labels = item.labels.iter().map(LabelC::c_strings).collect::<Result<Vec<_>, _>>()?;
+        let labels: Box<[LabelC]> = labels.into_iter()
+                                          .map(|(name, color)| LabelC::from_c_strings(name, color))
+                                          .collect::<Vec<_>>()
+                                          .into_boxed_slice();
+        Ok(ItemC {
+            uuid: uuid.into_raw(),
+            name: name.into_raw(),
+            has_due_date: item.due_date.is_some(),
+            due_date: item.due_date.map_or(0, |date| date.millis()),
+            due_date_all_day: item.due_date.map_or(false, |date| date.is_all_day()),
+            due_date_zone: due_date_zone.map_or(ptr::null(), |zone| zone.into_raw()),
+            has_completion_date: item.completion_date.is_some(),
+            completion_date: item.completion_date.map_or(0, |date| date.millis()),
+            completion_date_all_day: item.completion_date.map_or(false, |date| date.is_all_day()),
+            completion_date_zone: completion_date_zone.map_or(ptr::null(), |zone| zone.into_raw()),
+            labels_len: labels.len(),
+            labels: Box::into_raw(labels) as *const LabelC,
+            priority: item.priority.map_or(0, Priority::number),
+        })
+    }
+
+    /// The snapshot returned when an item can't be read, whose pointers are NULL.
+    pub fn null() -> ItemC {
+        ItemC {
+            uuid: ptr::null(),
+            name: ptr::null(),
+            has_due_date: false,
+            due_date: 0,
+            due_date_all_day: false,
+            due_date_zone: ptr::null(),
+            has_completion_date: false,
+            completion_date: 0,
+            completion_date_all_day: false,
+            completion_date_zone: ptr::null(),
+            labels: ptr::null(),
+            labels_len: 0,
+            priority: 0,
+        }
+    }
+
+    /// Free the strings and labels of a snapshot made by `ItemC::new`.
+    ///
+    /// # Safety
+    ///
+    /// The snapshot must have come from `ItemC::new` or `ItemC::null` and not have
+    /// been freed already, as this takes ownership of its strings and labels. No
+    /// copy of it can be read or freed afterwards.
+    pub unsafe fn free(self) {
+        free_c_char(self.uuid);
+        free_c_char(self.name);
+        free_c_char(self.due_date_zone);
+        free_c_char(self.completion_date_zone);
+        if !self.labels.is_null() {
+            let labels = Box::from_raw(slice::from_raw_parts_mut(self.labels as *mut LabelC, self.labels_len));
+            for label in labels.into_vec() {
+                label.free();
+            }
+        }
+    }
 }
 
 impl Drop for Item {
@@ -39,132 +252,596 @@ impl Drop for Item {
 }
 
 impl Item {
-    pub fn new(uuid: String, name: String, due_date: Option<Timespec>, completion_date: Option<Timespec>, labels: Vec<Label>) -> Self {
+    pub fn new(uuid: String, name: String, due_date: Option<Date>, completion_date: Option<Date>, labels: Vec<Label>) -> Self {
         Item {
             uuid: uuid,
             name: name,
             due_date: due_date,
             completion_date: completion_date,
-            labels: labels
+            labels: labels,
+            priority: None,
         }
     }
 }
 
 #[no_mangle]
-pub extern "C" fn item_new() -> *mut Item {
-    let item = Item{
-        uuid: "".to_string(),
-        name: "".to_string(),
-        due_date: None,
-        completion_date: None,
-        labels: vec![]
-    };
-    let boxed_item = Box::new(item);
-    Box::into_raw(boxed_item)
+pub extern "C" fn item_new() -> ItemHandle {
+    guard(0, || {
+        let item = Item::new("".to_string(), "".to_string(), None, None, vec![]);
+        Ok(ITEMS.insert(item))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_destroy(item: *mut Item) {
-    let _ = Box::from_raw(item);
+pub unsafe extern "C" fn item_destroy(item: ItemHandle) {
+    guard((), || ITEMS.destroy(item))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_get_uuid(item: *const Item) -> *mut c_char {
-    let item = &*item;
-    string_to_c_char(item.uuid.clone())
+pub unsafe extern "C" fn item_get_uuid(item: ItemHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let uuid = ITEMS.get(item, |item| item.uuid.clone())?;
+        Ok(string_to_c_char(uuid)?)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_get_name(item: *const Item) -> *mut c_char {
-    let item = &*item;
-    println!("returning {:?} for item.name", item.name);
-    string_to_c_char(item.name.clone())
+pub unsafe extern "C" fn item_get_name(item: ItemHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let name = ITEMS.get(item, |item| item.name.clone())?;
+        Ok(string_to_c_char(name)?)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_set_name(item: *mut Item, name: *const c_char) {
-    let item = &mut*item;
-    item.name = c_char_to_string(name);
+pub unsafe extern "C" fn item_set_name(item: ItemHandle, name: *const c_char) {
+    guard((), || {
+        let name = check_str(name, "name")?;
+        ITEMS.get_mut(item, |item| item.name = name)
+    })
 }
 
+/// The item's name as UTF-8 without a terminating NUL, for hosts that can take a
+/// length and so can read names with NUL bytes in them. Free with `toodle_buffer_free`.
+///
+/// # Safety
+///
+/// `item` is a handle, so any value is safe; an invalid one returns a NULL buffer
+/// and sets the last error. The returned buffer belongs to the caller, who must free
+/// it once with `toodle_buffer_free`.
 #[no_mangle]
-pub unsafe extern "C" fn item_get_due_date(item: *const Item) -> *mut i64 {
-    let item = &*item;
-    match item.due_date {
-        Some(date) => {
-            println!("item_get_due_date: returning {:?} for {:?}", date.sec, item.name);
-            Box::into_raw(Box::new(date.sec))
-        },
-        None => {
-            println!("item_get_due_date: returning null_mut for {:?}", item.name);
-            ptr::null_mut()
-        }
-    }
+pub unsafe extern "C" fn item_get_name_utf8(item: ItemHandle) -> ByteBuffer {
+    guard(ByteBuffer::null(), || {
+        let name = ITEMS.get(item, |item| item.name.clone())?;
+        Ok(ByteBuffer::from_string(name))
+    })
+}
 
+/// Set the item's name from `len` bytes of UTF-8 at `name`, which needn't end in NUL.
+///
+/// # Safety
+///
+/// A non-NULL `name` must point to at least `len` readable bytes that stay valid for
+/// the call. The caller keeps ownership of them.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_name_utf8(item: ItemHandle, name: *const u8, len: usize) {
+    guard((), || {
+        let name = check_bytes_str(name, len, "name")?;
+        ITEMS.get_mut(item, |item| item.name = name)
+    })
 }
 
+/// The item's due date in milliseconds since the Unix epoch, or NULL if it has none.
+/// Free with `item_date_destroy`.
 #[no_mangle]
-pub unsafe extern "C" fn item_set_due_date(item: *mut Item, due_date: *const size_t) {
-    let item = &mut*item;
-    if !due_date.is_null() {
-        item.due_date = Some(Timespec::new(due_date as i64, 0));
-    } else {
-        item.due_date = None;
-    }
+pub unsafe extern "C" fn item_get_due_date(item: ItemHandle) -> *mut i64 {
+    guard(ptr::null_mut(), || ITEMS.get(item, |item| date_to_ptr(item.due_date)))
 }
 
+/// Free a date returned by `item_get_due_date` or `item_get_completion_date`.
+///
+/// # Safety
+///
+/// `date` must be NULL or a date returned by `item_get_due_date` or
+/// `item_get_completion_date` that hasn't been freed already. It can't be read
+/// afterwards.
 #[no_mangle]
-pub unsafe extern "C" fn item_get_completion_date(item: *const Item) -> *mut i64 {
-    let item = &*item;
-    match item.completion_date {
-        Some(date) => {
-            println!("item_get_due_date: returning {:?} for {:?}", date.sec, item.name);
-            Box::into_raw(Box::new(date.sec))
-        },
-        None => {
-            println!("item_get_due_date: returning null_mut for {:?}", item.name);
-            ptr::null_mut()
+pub unsafe extern "C" fn item_date_destroy(date: *mut i64) {
+    guard((), || {
+        if !date.is_null() {
+            let _ = Box::from_raw(date);
         }
-    }
+        Ok(())
+    })
+}
 
+/// Whether the item's due date is a whole day, given as the midnight UTC that starts it.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_due_date_is_all_day(item: ItemHandle) -> bool {
+    guard(false, || ITEMS.get(item, |item| item.due_date.map_or(false, |date| date.is_all_day())))
 }
 
+/// The IANA time zone of the item's due date, or NULL if it has none. Free with
+/// `toodle_string_free`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`. The returned string belongs to the caller, who must
+/// free it once with `toodle_string_free`.
 #[no_mangle]
-pub unsafe extern "C" fn item_set_completion_date(item: *mut Item, completion_date: *const size_t) {
-    let item = &mut*item;
-    if !completion_date.is_null() {
-        item.completion_date = Some(Timespec::new(completion_date as i64, 0));
-    } else {
-        item.completion_date = None;
-    }
+pub unsafe extern "C" fn item_get_due_date_zone(item: ItemHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let zone = ITEMS.get(item, |item| zone_to_c_string(item.due_date))?;
+        Ok(zone?.map_or(ptr::null_mut(), |zone| zone.into_raw()))
+    })
+}
+
+/// Set the item's due date to a time in milliseconds since the Unix epoch, with no
+/// time zone, or clear it if `due_date` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_due_date(item: ItemHandle, due_date: *const i64) {
+    guard((), || {
+        let due_date = date_from_ptr(due_date)?;
+        ITEMS.get_mut(item, |item| item.due_date = due_date)
+    })
+}
+
+/// Set the item's due date to `millis` since the Unix epoch, shown in the IANA time
+/// zone `zone`, e.g. "America/New_York".
+///
+/// # Safety
+///
+/// `zone` must point to a NUL-terminated string that stays valid for the call; NULL
+/// is rejected with an error. The caller keeps ownership of it.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_due_date_in_zone(item: ItemHandle, millis: i64, zone: *const c_char) {
+    guard((), || {
+        let zone = check_str(zone, "zone")?;
+        let due_date = Date::from_millis_in(millis, &zone)?;
+        ITEMS.get_mut(item, |item| item.due_date = Some(due_date))
+    })
+}
+
+/// Make the item due on the whole UTC day that `millis` falls on.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_all_day_due_date(item: ItemHandle, millis: i64) {
+    guard((), || {
+        let due_date = Date::day_from_millis(millis)?;
+        ITEMS.get_mut(item, |item| item.due_date = Some(due_date))
+    })
+}
+
+/// The item's priority: 0 if it has none, otherwise 1 for low to 3 for high.
+///
+/// # Safety
+///
+/// `item` is a handle looked up in a registry rather than a pointer, so any value is
+/// safe: 0, or a handle that has been destroyed, fails and sets the last error.
+#[no_mangle]
+pub unsafe extern "C" fn item_get_priority(item: ItemHandle) -> u8 {
+    guard(0, || ITEMS.get(item, |item| item.priority.map_or(0, Priority::number)))
+}
+
+/// Set the item's priority to 1 for low to 3 for high, or clear it with 0.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_priority(item: ItemHandle, priority: u8) {
+    guard((), || {
+        let priority = Priority::from_number(priority as i64)?;
+        ITEMS.get_mut(item, |item| item.priority = priority)
+    })
 }
 
+/// The item's completion date in milliseconds since the Unix epoch, or NULL if it has
+/// none. Free with `item_date_destroy`.
 #[no_mangle]
-pub unsafe extern "C" fn item_get_labels(item: *const Item) -> *mut Vec<Label> {
-    let item = &*item;
-    let boxed_labels = Box::new(item.labels.clone());
-    Box::into_raw(boxed_labels)
+pub unsafe extern "C" fn item_get_completion_date(item: ItemHandle) -> *mut i64 {
+    guard(ptr::null_mut(), || ITEMS.get(item, |item| date_to_ptr(item.completion_date)))
 }
 
+/// Whether the item's completion date is a whole day, given as the midnight UTC that
+/// starts it.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
 #[no_mangle]
-pub unsafe extern "C" fn item_labels_count(item: *const Item) -> c_int {
-    let item = &*item;
-    item.labels.len() as c_int
+pub unsafe extern "C" fn item_completion_date_is_all_day(item: ItemHandle) -> bool {
+    guard(false, || ITEMS.get(item, |item| item.completion_date.map_or(false, |date| date.is_all_day())))
 }
 
+/// The IANA time zone of the item's completion date, or NULL if it has none. Free
+/// with `toodle_string_free`.
+///
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
 #[no_mangle]
-pub unsafe extern "C" fn item_label_at(item: *const Item, index: c_int) -> *mut Label {
-    let item = &*item;
-    let index = index as usize;
-    let item = Box::new(item.labels[index].clone());
-    Box::into_raw(item)
+pub unsafe extern "C" fn item_get_completion_date_zone(item: ItemHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let zone = ITEMS.get(item, |item| zone_to_c_string(item.completion_date))?;
+        Ok(zone?.map_or(ptr::null_mut(), |zone| zone.into_raw()))
+    })
+}
+
+/// Set the item's completion date to a time in milliseconds since the Unix epoch,
+/// with no time zone, or clear it if `completion_date` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_completion_date(item: ItemHandle, completion_date: *const i64) {
+    guard((), || {
+        let completion_date = date_from_ptr(completion_date)?;
+        ITEMS.get_mut(item, |item| item.completion_date = completion_date)
+    })
+}
+
+/// Set the item's completion date to `millis` since the Unix epoch, shown in the IANA
+/// time zone `zone`.
+///
+/// # Safety
+///
+/// Same as `item_set_due_date_in_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_completion_date_in_zone(item: ItemHandle, millis: i64, zone: *const c_char) {
+    guard((), || {
+        let zone = check_str(zone, "zone")?;
+        let completion_date = Date::from_millis_in(millis, &zone)?;
+        ITEMS.get_mut(item, |item| item.completion_date = Some(completion_date))
+    })
+}
+
+/// Mark the item as completed on the whole UTC day that `millis` falls on.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn item_set_all_day_completion_date(item: ItemHandle, millis: i64) {
+    guard((), || {
+        let completion_date = Date::day_from_millis(millis)?;
+        ITEMS.get_mut(item, |item| item.completion_date = Some(completion_date))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_get_labels(item: ItemHandle) -> LabelListHandle {
+    guard(0, || {
+        let labels = ITEMS.get(item, |item| item.labels.clone())?;
+        Ok(LABEL_LISTS.insert(labels))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_labels_count(item: ItemHandle) -> usize {
+    guard(0, || ITEMS.get(item, |item| item.labels.len()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_label_at(item: ItemHandle, index: usize) -> LabelHandle {
+    guard(0, || {
+        let label = ITEMS.get(item, |item| item.labels.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(LABELS.insert(label))
+    })
+}
+
+/// Read all of the item's fields, including its labels, in one call. The snapshot
+/// must be freed with `item_snapshot_free`; its pointers are NULL if `item` isn't a
+/// valid handle.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`. The snapshot owns its strings and labels, and must
+/// be passed to `item_snapshot_free` exactly once.
+#[no_mangle]
+pub unsafe extern "C" fn item_snapshot(item: ItemHandle) -> ItemC {
+    guard(ItemC::null(), || {
+        let snapshot = ITEMS.get(item, ItemC::new)?;
+        Ok(snapshot?)
+    })
+}
+
+/// Free a snapshot returned by `item_snapshot`. Each snapshot must be freed once.
+///
+/// # Safety
+///
+/// `snapshot` must have come unchanged from `item_snapshot` and not have been freed
+/// already, as this frees its strings and labels. No copy of it can be read
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn item_snapshot_free(snapshot: ItemC) {
+    guard((), || {
+        snapshot.free();
+        Ok(())
+    })
+}
+
+/// Create an item with the `label_count` labels in `labels`, which may be NULL when
+/// there are none. Dates are milliseconds since the Unix epoch, with no time zone, and
+/// NULL dates leave the item without that date. Returns 0 if the item couldn't be
+/// created, otherwise an item that must be freed with `item_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_create_item(manager: ListManagerHandle, name: *const c_char, due_date: *const i64, completion_date: *const i64, labels: *const LabelHandle, label_count: usize) -> ItemHandle {
+    guard(0, || {
+        let mut manager = manager_from_handle(manager)?;
+        let name = check_str(name, "name")?;
+        let labels = labels_from_handles(labels, label_count)?;
+        let item = Item::new("".to_string(), name, date_from_ptr(due_date)?, date_from_ptr(completion_date)?, labels);
+        let uuid = manager.create_item(&item)?;
+        let item = manager.fetch_item(&uuid).ok_or("item wasn't saved")?;
+        Ok(ITEMS.insert(item))
+    })
+}
+
+/// Save `item` with the given values, which are then also applied to `item` itself;
+/// if saving fails, `item` is left as it was. A NULL `name` keeps the current name;
+/// NULL dates and labels are cleared. A date equal to the one the item already has
+/// keeps its time zone or stays all-day.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_update_item(manager: ListManagerHandle, item: ItemHandle, name: *const c_char, due_date: *const i64, completion_date: *const i64, labels: *const LabelHandle, label_count: usize) {
+    guard((), || {
+        let mut manager = manager_from_handle(manager)?;
+        let labels = labels_from_handles(labels, label_count)?;
+        let name = check_optional_str(name, "name")?;
+        let due_date = date_from_ptr(due_date)?;
+        let completion_date = date_from_ptr(completion_date)?;
+        let mut updated = ITEMS.get(item, |item| item.clone())?;
+        if let Some(name) = name {
+            updated.name = name;
+        }
+        updated.due_date = updated_date(updated.due_date, due_date);
+        updated.completion_date = updated_date(updated.completion_date, completion_date);
+        updated.labels = labels;
+        let existing_labels = manager.fetch_labels_for_item(&updated.uuid);
+        manager.update_item(&updated, existing_labels)?;
+        ITEMS.get_mut(item, |item| *item = updated)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_all_items(manager: ListManagerHandle) -> ItemListHandle {
+    guard(0, || {
+        let mut manager = manager_from_handle(manager)?;
+        Ok(ITEM_LISTS.insert(manager.fetch_items()))
+    })
+}
+
+/// # Safety
+///
+/// `item_list` is a handle, so any value is safe. Once destroyed it is invalid, and
+/// destroying it again, or destroying 0, only fails and sets the last error. Items
+/// copied out of the list are separate handles and stay valid.
+#[no_mangle]
+pub unsafe extern "C" fn item_list_destroy(item_list: ItemListHandle) {
+    guard((), || ITEM_LISTS.destroy(item_list))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_list_count(item_list: ItemListHandle) -> usize {
+    guard(0, || ITEM_LISTS.get(item_list, |items| items.len()))
+}
+
+/// Copy the item at `index`. The returned item must be freed with `item_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn item_list_entry_at(item_list: ItemListHandle, index: usize) -> ItemHandle {
+    guard(0, || {
+        let item = ITEM_LISTS.get(item_list, |items| items.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(ITEMS.insert(item))
+    })
+}
+
+unsafe fn date_from_ptr(millis: *const i64) -> Result<Option<Date>, String> {
+    if millis.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(Date::from_millis(*millis)?))
+    }
+}
+
+fn date_to_ptr(date: Option<Date>) -> *mut i64 {
+    match date {
+        Some(date) => Box::into_raw(Box::new(date.millis())),
+        None => ptr::null_mut(),
+    }
+}
+
+fn zone_to_c_string(date: Option<Date>) -> Result<Option<CString>, StringError> {
+    match date.and_then(|date| date.zone()) {
+        Some(zone) => Ok(Some(string_to_c_string(zone.name().to_string())?)),
+        None => Ok(None),
+    }
+}
+
+/// `date` as passed to an update, or `current` if it's the same instant, so that
+/// passing back an unchanged date doesn't lose its time zone or all-day flag.
+fn updated_date(current: Option<Date>, date: Option<Date>) -> Option<Date> {
+    match (current, date) {
+        (Some(current), Some(date)) if current.millis() == date.millis() => Some(current),
+        (_, date) => date,
+    }
+}
+
+unsafe fn labels_from_handles(labels: *const LabelHandle, count: usize) -> Result<Vec<Label>, String> {
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    if labels.is_null() {
+        return Err("labels is NULL".to_string());
+    }
+    slice::from_raw_parts(labels, count).iter()
+                                        .map(|&label| LABELS.get(label, |label| label.clone()))
+                                        .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
+    use std::ffi::CStr;
+
+    use ffi_utils::error::last_error;
+    use labels::DEFAULT_COLOR;
+    use storage::{
+        ListStorage,
+        MemoryStorage,
+    };
+    use ListManager;
+    use MANAGERS;
+
+    /// Storage in memory that can't save changes to items.
+    #[derive(Debug)]
+    struct ReadOnlyItems(MemoryStorage);
+
+    impl ListStorage for ReadOnlyItems {
+        fn create_label(&self, label: &Label) -> Result<(), String> { self.0.create_label(label) }
+        fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> { self.0.fetch_label(name) }
+        fn fetch_labels(&self) -> Result<Vec<Label>, String> { self.0.fetch_labels() }
+        fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> { self.0.fetch_labels_for_item(uuid) }
+        fn create_item(&self, item: &Item) -> Result<(), String> { self.0.create_item(item) }
+        fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> { self.0.fetch_item(uuid) }
+        fn fetch_items(&self) -> Result<Vec<Item>, String> { self.0.fetch_items() }
+        fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> { self.0.fetch_items_page(offset, limit) }
+        fn count_items(&self) -> Result<usize, String> { self.0.count_items() }
+        fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> { self.0.fetch_items_with_label(name) }
+        fn update_item(&self, _: &Item, _: &[Label]) -> Result<(), String> { Err("attempt to write a readonly database".to_string()) }
+        fn delete_item(&self, uuid: &str) -> Result<bool, String> { self.0.delete_item(uuid) }
+    }
+
+    unsafe fn string(s: *const c_char) -> String {
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
 
     #[test]
     fn test_new_item() {
 
     }
+
+    #[test]
+    fn test_snapshot() {
+        let labels = vec![
+            Label { name: "home".to_string(), color: DEFAULT_COLOR.to_string() },
+            Label { name: "work".to_string(), color: "#ff0000".to_string() },
+        ];
+        let completed = Date::from_millis_in(1509969600123, "Europe/Paris").unwrap();
+        let item = ITEMS.insert(Item::new("a1".to_string(), "buy milk".to_string(), None, Some(completed), labels));
+        unsafe {
+            let snapshot = item_snapshot(item);
+            assert_eq!(string(snapshot.uuid), "a1");
+            assert_eq!(string(snapshot.name), "buy milk");
+            assert!(!snapshot.has_due_date);
+            assert!(snapshot.due_date_zone.is_null());
+            assert!(snapshot.has_completion_date);
+            assert_eq!(snapshot.completion_date, 1509969600123);
+            assert!(!snapshot.completion_date_all_day);
+            assert_eq!(string(snapshot.completion_date_zone), "Europe/Paris");
+            assert_eq!(snapshot.labels_len, 2);
+            let labels = slice::from_raw_parts(snapshot.labels, snapshot.labels_len);
+            assert_eq!(string(labels[1].name), "work");
+            assert_eq!(string(labels[1].color), "#ff0000");
+
+            // The snapshot is a copy, so it outlives the item.
+            item_destroy(item);
+            assert_eq!(string(snapshot.name), "buy milk");
+            item_snapshot_free(snapshot);
+
+            let empty = item_snapshot(item);
+            assert!(empty.uuid.is_null() && empty.labels.is_null());
+            item_snapshot_free(empty);
+        }
+    }
+
+    #[test]
+    fn test_dates() {
+        let item = item_new();
+        unsafe {
+            let millis = 1509883200345;
+            item_set_due_date(item, &millis);
+            let due_date = item_get_due_date(item);
+            assert_eq!(*due_date, millis);
+            item_date_destroy(due_date);
+            assert!(!item_due_date_is_all_day(item));
+            assert!(item_get_due_date_zone(item).is_null());
+
+            item_set_all_day_due_date(item, millis);
+            let due_date = item_get_due_date(item);
+            assert_eq!(*due_date, 1509840000000);
+            item_date_destroy(due_date);
+            assert!(item_due_date_is_all_day(item));
+
+            let zone = CString::new("America/New_York").unwrap();
+            item_set_completion_date_in_zone(item, millis, zone.as_ptr());
+            let completion_zone = item_get_completion_date_zone(item);
+            assert_eq!(string(completion_zone), "America/New_York");
+            free_c_char(completion_zone);
+            assert!(!item_completion_date_is_all_day(item));
+
+            item_set_completion_date(item, ptr::null());
+            assert!(item_get_completion_date(item).is_null());
+            assert!(!item_completion_date_is_all_day(item));
+        }
+        assert_eq!(ITEMS.get(item, |item| item.due_date), Ok(Date::day(2017, 11, 5)));
+        unsafe { item_destroy(item) };
+    }
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(Priority::from_number(0), Ok(None));
+        assert_eq!(Priority::from_number(3), Ok(Some(Priority::High)));
+        assert_eq!(Priority::from_number(4), Err("unknown priority 4".to_string()));
+        assert_eq!("Med".parse(), Ok(Priority::Medium));
+        assert_eq!("urgent".parse::<Priority>(), Err("unknown priority \"urgent\"".to_string()));
+        assert!(Priority::High > Priority::Low);
+
+        let item = item_new();
+        unsafe {
+            assert_eq!(item_get_priority(item), 0);
+            item_set_priority(item, 2);
+            assert_eq!(item_get_priority(item), 2);
+            item_set_priority(item, 7);
+            assert_eq!(item_get_priority(item), 2);
+            let snapshot = item_snapshot(item);
+            assert_eq!(snapshot.priority, 2);
+            item_snapshot_free(snapshot);
+            item_set_priority(item, 0);
+        }
+        assert_eq!(ITEMS.get(item, |item| item.priority), Ok(None));
+        unsafe { item_destroy(item) };
+    }
+
+    #[test]
+    fn test_failed_update_leaves_item() {
+        let mut manager = ListManager::with_storage(ReadOnlyItems(MemoryStorage::new()));
+        let uuid = manager.create_item(&Item::new("".to_string(), "buy milk".to_string(), None, None, vec![])).unwrap();
+        let item = ITEMS.insert(manager.fetch_item(&uuid).unwrap());
+        let manager = MANAGERS.insert(manager);
+        let name = CString::new("buy oat milk").unwrap();
+        let due_date = 1509883200345;
+        unsafe {
+            list_manager_update_item(manager, item, name.as_ptr(), &due_date, ptr::null(), ptr::null(), 0);
+            assert_eq!(last_error(), Some("attempt to write a readonly database".to_string()));
+        }
+        assert_eq!(ITEMS.get(item, |item| (item.name.clone(), item.due_date)).unwrap(), ("buy milk".to_string(), None));
+        unsafe { item_destroy(item) };
+        MANAGERS.destroy(manager).unwrap();
+    }
+
+    #[test]
+    fn test_updated_date() {
+        let day = Date::day(2017, 11, 5);
+        let same_instant = Date::from_millis(day.unwrap().millis()).ok();
+        let later = Date::from_millis(day.unwrap().millis() + 1).ok();
+        assert_eq!(updated_date(day, same_instant), day);
+        assert_eq!(updated_date(day, later), later);
+        assert_eq!(updated_date(day, None), None);
+        assert_eq!(updated_date(None, later), later);
+    }
 }
@@ -0,0 +1,88 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Items, labels and dates as JSON, the shape both the `toodle` command-line tool
+//! and `toodle-server` use.
+//!
+//! Dates have the fields of the C ABI's date snapshots: `millis` since the epoch
+//! (midnight UTC for all-day dates), `all_day`, and the IANA `zone` or null.
+//! Priorities are `"low"`, `"medium"`, `"high"` or null.
+
+use dates::Date;
+use items::Item;
+use labels::Label;
+
+pub fn item_json(item: &Item) -> String {
+    format!("{{\"uuid\":{},\"name\":{},\"due_date\":{},\"completion_date\":{},\"priority\":{},\"labels\":{}}}",
+            json_string(&item.uuid),
+            json_string(&item.name),
+            item.due_date.as_ref().map_or("null".to_string(), date_json),
+            item.completion_date.as_ref().map_or("null".to_string(), date_json),
+            item.priority.map_or("null".to_string(), |priority| json_string(priority.name())),
+            json_array(&item.labels, label_json))
+}
+
+pub fn label_json(label: &Label) -> String {
+    format!("{{\"name\":{},\"color\":{}}}", json_string(&label.name), json_string(&label.color))
+}
+
+pub fn date_json(date: &Date) -> String {
+    format!("{{\"millis\":{},\"all_day\":{},\"zone\":{}}}",
+            date.millis(),
+            date.is_all_day(),
+            date.zone().map_or("null".to_string(), |zone| json_string(zone.name())))
+}
+
+pub fn json_array<T, F>(values: &[T], to_json: F) -> String
+where F: Fn(&T) -> String {
+    format!("[{}]", values.iter().map(to_json).collect::<Vec<String>>().join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use items::Priority;
+
+    #[test]
+    fn test_json() {
+        let labels = vec![
+            Label { name: "home".to_string(), color: "#ff0000".to_string() },
+            Label { name: "errands".to_string(), color: "#00ff00".to_string() },
+        ];
+        let mut item = Item::new("0123456789abcdef".to_string(), "buy \"milk\"\n".to_string(), Date::from_millis_in(1509883200345, "America/New_York").ok(), Date::day(2017, 11, 6), labels);
+        item.priority = Some(Priority::High);
+        assert_eq!(item_json(&item),
+                   "{\"uuid\":\"0123456789abcdef\",\"name\":\"buy \\\"milk\\\"\\n\",\
+                    \"due_date\":{\"millis\":1509883200345,\"all_day\":false,\"zone\":\"America/New_York\"},\
+                    \"completion_date\":{\"millis\":1509926400000,\"all_day\":true,\"zone\":null},\
+                    \"priority\":\"high\",\"labels\":[{\"name\":\"home\",\"color\":\"#ff0000\"},{\"name\":\"errands\",\"color\":\"#00ff00\"}]}");
+        assert_eq!(json_string("tab\there \u{1}"), "\"tab\\there \\u0001\"");
+        assert_eq!(json_array(&[] as &[Label], label_json), "[]");
+    }
+}
@@ -8,14 +8,28 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+use std::ffi::CString;
 use std::os::raw::{
     c_char,
 };
+use std::ptr;
 
+use ffi_utils::error::{
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
 use ffi_utils::strings::{
+    free_c_char,
     string_to_c_char,
-    c_char_to_string,
+    string_to_c_string,
+    StringError,
 };
+use manager_from_handle;
+use ListManagerHandle;
+
+/// Color given to labels that are created implicitly, e.g. when importing items.
+pub const DEFAULT_COLOR: &'static str = "#000000";
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Label {
@@ -23,31 +37,167 @@ pub struct Label {
     pub color: String
 }
 
+pub type LabelList = Vec<Label>;
+
+/// A label handed across the FFI, freed with `label_destroy`.
+pub type LabelHandle = u64;
+
+/// A list of labels handed across the FFI, read with `label_list_count` and
+/// `label_list_entry_at` and freed with `label_list_destroy`.
+pub type LabelListHandle = u64;
+
+pub static LABELS: HandleRegistry<Label> = HandleRegistry::new("label");
+pub static LABEL_LISTS: HandleRegistry<LabelList> = HandleRegistry::new("label list");
+
 impl Drop for Label {
     fn drop(&mut self) {
-        println!("{:?} is being deallocated", self);
+        trace!(name = self.name.as_str(); "deallocating label");
+    }
+}
+
+/// A copy of a label's fields that C can read directly. The strings belong to the
+/// snapshot and stay valid until it is passed to `label_snapshot_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct LabelC {
+    pub name: *const c_char,
+    pub color: *const c_char,
+}
+
+impl LabelC {
+    /// Fails if the name or color has a NUL byte in it.
+    pub fn new(label: &Label) -> Result<LabelC, StringError> {
+        let (name, color) = LabelC::c_strings(label)?;
+        Ok(LabelC::from_c_strings(name, color))
+    }
+
+    /// The label's strings, checked but not yet handed over to C.
+    pub fn c_strings(label: &Label) -> Result<(CString, CString), StringError> {
+        Ok((string_to_c_string(label.name.clone())?, string_to_c_string(label.color.clone())?))
+    }
+
+    pub fn from_c_strings(name: CString, color: CString) -> LabelC {
+        LabelC {
+            name: name.into_raw(),
+            color: color.into_raw(),
+        }
     }
+
+    /// The snapshot returned when a label can't be read, whose strings are NULL.
+    pub fn null() -> LabelC {
+        LabelC {
+            name: ptr::null(),
+            color: ptr::null(),
+        }
+    }
+
+    /// Free the strings of a snapshot made by `LabelC::new`.
+    ///
+    /// # Safety
+    ///
+    /// The snapshot must have come from `LabelC::new` and not have been freed
+    /// already, as this takes ownership of its strings. No copy of it can be read or
+    /// freed afterwards.
+    pub unsafe fn free(self) {
+        free_c_char(self.name);
+        free_c_char(self.color);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn label_destroy(label: LabelHandle) {
+    guard((), || LABELS.destroy(label))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn label_get_name(label: LabelHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let name = LABELS.get(label, |label| label.name.clone())?;
+        Ok(string_to_c_char(name)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn label_get_color(label: LabelHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let color = LABELS.get(label, |label| label.color.clone())?;
+        Ok(string_to_c_char(color)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn label_set_color(label: LabelHandle, color: *const c_char) {
+    guard((), || {
+        let color = check_str(color, "color")?;
+        LABELS.get_mut(label, |label| label.color = color)
+    })
+}
+
+/// Create a label. The returned label must be freed with `label_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_create_label(manager: ListManagerHandle, name: *const c_char, color: *const c_char) -> LabelHandle {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        let name = check_str(name, "name")?;
+        let color = check_str(color, "color")?;
+        let label = manager.create_label(name, color).ok_or("label wasn't saved")?;
+        Ok(LABELS.insert(label))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_all_labels(manager: ListManagerHandle) -> LabelListHandle {
+    guard(0, || {
+        let manager = manager_from_handle(manager)?;
+        Ok(LABEL_LISTS.insert(manager.fetch_labels()))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn label_list_destroy(label_list: LabelListHandle) {
+    guard((), || LABEL_LISTS.destroy(label_list))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn label_destroy(label: *mut Label) {
-    let _ = Box::from_raw(label);
+pub unsafe extern "C" fn label_list_count(label_list: LabelListHandle) -> usize {
+    guard(0, || LABEL_LISTS.get(label_list, |labels| labels.len()))
 }
 
+/// Copy the label at `index`. The returned label must be freed with `label_destroy`.
 #[no_mangle]
-pub unsafe extern "C" fn label_get_name(label: *const Label) -> *mut c_char {
-    let label = &*label;
-    string_to_c_char(label.name.clone())
+pub unsafe extern "C" fn label_list_entry_at(label_list: LabelListHandle, index: usize) -> LabelHandle {
+    guard(0, || {
+        let label = LABEL_LISTS.get(label_list, |labels| labels.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(LABELS.insert(label))
+    })
 }
 
+/// Read all of the label's fields in one call. The snapshot must be freed with
+/// `label_snapshot_free`; its strings are NULL if `label` isn't a valid handle.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`. The snapshot owns its strings, and must be passed to
+/// `label_snapshot_free` exactly once.
 #[no_mangle]
-pub unsafe extern "C" fn label_get_color(label: *const Label) -> *mut c_char {
-    let label = &*label;
-    string_to_c_char(label.color.clone())
+pub unsafe extern "C" fn label_snapshot(label: LabelHandle) -> LabelC {
+    guard(LabelC::null(), || {
+        let snapshot = LABELS.get(label, LabelC::new)?;
+        Ok(snapshot?)
+    })
 }
 
+/// # Safety
+///
+/// `snapshot` must have come unchanged from `label_snapshot` and not have been freed
+/// already, as this frees its strings. No copy of it can be read afterwards.
 #[no_mangle]
-pub unsafe extern "C" fn label_set_color(label: *mut Label, color: *const c_char) {
-    let label = &mut*label;
-    label.color = c_char_to_string(color);
+pub unsafe extern "C" fn label_snapshot_free(snapshot: LabelC) {
+    guard((), || {
+        snapshot.free();
+        Ok(())
+    })
 }
@@ -8,438 +8,398 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-extern crate libc;
+extern crate chrono;
+extern crate chrono_tz;
+#[macro_use]
+extern crate log;
+#[cfg(feature = "sqlite")]
 extern crate rusqlite;
-extern crate time;
 extern crate uuid;
+#[cfg(feature = "sqlite")]
 extern crate store;
+#[cfg(feature = "sqlite")]
+extern crate sha2;
 extern crate ffi_utils;
 
-use std::os::raw::c_char;
-use std::ops::Deref;
+#[cfg(feature = "sqlite")]
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::{
     Arc,
 };
 
-use libc::{
-    c_int,
-    size_t,
-};
-use rusqlite::{
-    Connection
-};
-use time::Timespec;
 use uuid::Uuid;
 
 pub mod labels;
 pub mod items;
+pub mod dates;
+pub mod date_phrases;
+pub mod ical;
+pub mod json;
+pub mod csv;
+pub mod todotxt;
+pub mod markdown;
+pub mod buffers;
+pub mod quick_add;
+pub mod storage;
+pub mod cancellation;
+pub mod cursor;
+pub mod managers;
+#[cfg(feature = "sqlite")]
+pub mod attachments;
+
+#[cfg(feature = "sqlite")]
+use attachments::{
+    AttachmentWriter,
+    Attachments,
+    BlobLocation,
+};
 
+use cancellation::CancellationToken;
+use chrono_tz::Tz;
+use dates::Date;
 use labels::Label;
-use ffi_utils::strings::c_char_to_string;
+pub use managers::{
+    list_manager_destroy,
+    manager_from_handle,
+    MANAGERS,
+};
+use csv::{
+    CsvColumnMapping,
+    CsvError,
+    CsvExportOptions,
+    CsvImport,
+};
+use ical::IcalError;
 use items::Item;
+use markdown::MarkdownImport;
+use quick_add::QuickAdd;
+use storage::ListStorage;
+#[cfg(feature = "sqlite")]
+use storage::SqliteStorage;
+#[cfg(feature = "sqlite")]
 use store::Store;
+use todotxt::{
+    TodoTxtExport,
+    TodoTxtImport,
+};
 
-
+/// Keeps items and labels in a `ListStorage`. `new` uses SQLite, which builds
+/// without the `sqlite` feature, such as for WebAssembly, leave out; they give
+/// `with_storage` another storage instead.
 #[derive(Debug, Clone)]
-#[repr(C)]
 pub struct ListManager {
-    store: Arc<Store>,
+    storage: Arc<dyn ListStorage>,
+    /// Stops imports part way through once cancelled.
+    cancellation: Option<CancellationToken>,
+    /// Files attached to items, kept in the same database as the items. Only
+    /// managers with SQLite storage have them.
+    #[cfg(feature = "sqlite")]
+    attachments: Option<Attachments>,
 }
 
+/// A list manager handed across the FFI, freed with `list_manager_destroy`.
+pub type ListManagerHandle = u64;
+
+/// The value of `result`, or the default after logging why it failed.
+fn or_default<T: Default>(result: Result<T, String>, action: &str) -> T {
+    result.unwrap_or_else(|e| {
+        warn!(error = e.as_str(); "failed to {}", action);
+        T::default()
+    })
+}
+
+#[cfg(feature = "sqlite")]
 impl ListManager {
+    /// A list manager keeping its items in the SQLite database at `uri`, or in an
+    /// in-memory database if there is none.
     pub fn new<T>(uri: T) -> Self
     where T: Into<Option<String>> {
-        let mut manager = ListManager {
-            store: Arc::new(Store::new(uri)),
-        };
-        manager.create_labels_table();
-        manager.create_items_table();
-        manager.create_item_labels_table();
+        let store = Store::new(uri);
+        let mut manager = ListManager::with_storage(SqliteStorage::new(store.clone()));
+        manager.attachments = Some(Attachments::new(store));
         manager
     }
 
-    fn get_store(&self) -> Arc<Store> {
-        Arc::clone(&self.store)
+    /// A list manager keeping its items in the SQLite database at `path`, which is
+    /// created if it doesn't exist, or an error if its directory doesn't exist.
+    pub fn open(path: &str) -> Result<Self, String> {
+        // `Store` can't report a database it fails to open, so catch the likeliest
+        // reason first.
+        if let Some(dir) = Path::new(path).parent() {
+            if !dir.as_os_str().is_empty() && !dir.is_dir() {
+                return Err(format!("{}: no such directory", dir.display()));
+            }
+        }
+        Ok(ListManager::new(Some(path.to_string())))
     }
 
-    fn get_store_mut(&mut self) -> &mut Store {
-        Arc::make_mut(&mut self.store)
+    /// A list manager sharing this one's storage whose attachments are `attachments`,
+    /// which must be in the same database as the items.
+    pub fn with_attachments(&self, attachments: Attachments) -> Self {
+        let mut manager = self.clone();
+        manager.attachments = Some(attachments);
+        manager
     }
 
-    pub fn create_labels_table(&mut self) {
-        let sql = r#"CREATE TABLE IF NOT EXISTS labels (
-                name TEXT NOT NULL PRIMARY KEY,
-                color TEXT NOT NULL
-            )"#;
-        let db = self.get_store_mut().conn.lock().unwrap();
-        let r = db.execute(sql, &[]);
-        if r.is_err() {
-            println!("failed to create labels table {:?}", r.err());
-        }
+    /// Keep the contents of attachments added from now on as files in `directory`
+    /// rather than in the database. The setting is saved in the database, so it
+    /// applies to every list manager on it.
+    pub fn set_attachment_directory(&self, directory: PathBuf) -> Result<(), String> {
+        self.attachments()?.set_location(&BlobLocation::Directory(directory))
     }
 
-    pub fn create_label(&self, name: String, color: String) -> Option<Label> {
-        let db = self.store.conn.lock().unwrap();
-        let sql = r#"INSERT INTO labels (name, color) VALUES (?1, ?2)"#;
-        db.execute(sql, &[&name, &color]).unwrap();
-        self.fetch_label(&name)
+    pub fn attachments(&self) -> Result<&Attachments, String> {
+        self.attachments.as_ref().ok_or_else(|| "attachments aren't available with this storage".to_string())
     }
 
-    pub fn fetch_label(&self, name: &String) -> Option<Label> {
-        let sql = r#"SELECT name, color FROM labels WHERE name=?"#;
-
-        let conn = self.store.conn.lock().unwrap();
-        let mut stmt = conn.prepare(sql).unwrap();
-        let mut label_iter = stmt.query_map(&[name], |row| {
-            Label {
-                name: row.get(0),
-                color: row.get(1)
-            }
-        }).unwrap();
-
-        if let Some(result) = label_iter.next() {
-            result.ok()
-        } else {
-            println!("No label found for name {:?}", name);
-            None
+    /// Start writing a new attachment on the item with `item_uuid`.
+    pub fn attachment_writer(&self, item_uuid: &str, name: &str, mime_type: &str) -> Result<AttachmentWriter, String> {
+        let attachments = self.attachments()?;
+        if self.storage.fetch_item(&item_uuid.to_string())?.is_none() {
+            return Err(format!("no item with uuid {}", item_uuid));
         }
+        attachments.writer(item_uuid, name, mime_type)
     }
 
-    pub fn fetch_labels(&self) -> Vec<Label> {
-        let sql = r#"SELECT name, color
-                     FROM labels"#;
-        let conn = self.store.conn.lock().unwrap();
-        let mut stmt = conn.prepare(sql).unwrap();
-        let mut label_iter = stmt.query_map(&[], |row| {
-            Label {
-                name: row.get(0),
-                color: row.get(1)
+    /// Delete the attachments of the deleted item with `uuid`. A failure only
+    /// leaves them for `Attachments::purge`, so it is logged rather than returned.
+    fn delete_attachments(&self, uuid: &String) {
+        if let Some(ref attachments) = self.attachments {
+            if let Err(e) = attachments.delete_for_item(uuid) {
+                warn!(uuid = uuid.as_str(), error = e.as_str(); "failed to delete attachments of deleted item");
             }
-        }).unwrap();
+        }
+    }
+}
 
-        let mut label_list: Vec<Label> = Vec::new();
-        while let Some(result) = label_iter.next() {
-            if let Some(mut label) = result.ok() {
-                label_list.push(label);
-            }
+impl ListManager {
+    pub fn with_storage<S>(storage: S) -> Self
+    where S: ListStorage + 'static {
+        ListManager {
+            storage: Arc::new(storage),
+            cancellation: None,
+            #[cfg(feature = "sqlite")]
+            attachments: None,
         }
-        label_list
     }
 
-    pub fn fetch_labels_for_item(&self, item_uuid: &String) -> Vec<Label> {
-        let db = self.store.conn.lock().unwrap();
-        self.fetch_labels_for_item_with_conn(db.deref(), item_uuid)
-    }
-
-    pub fn fetch_labels_for_item_with_conn(&self, conn: &Connection, item_uuid: &String) -> Vec<Label> {
-        let sql = r#"SELECT name, color
-                     FROM labels JOIN item_labels on item_labels.label_name=labels.name
-                     WHERE item_labels.item_uuid=?"#;
-        let mut stmt = conn.prepare(sql).unwrap();
-        let mut label_iter = stmt.query_map(&[item_uuid], |row| {
-            Label {
-                name: row.get(0),
-                color: row.get(1)
-            }
-        }).unwrap();
+    /// A list manager sharing this one's storage whose imports stop creating items
+    /// once `token` is cancelled, returning the items created before then.
+    pub fn with_cancellation(&self, token: CancellationToken) -> Self {
+        let mut manager = self.clone();
+        manager.cancellation = Some(token);
+        manager
+    }
 
-        let mut label_list: Vec<Label> = Vec::new();
-        while let Some(result) = label_iter.next() {
-            if let Some(mut label) = result.ok() {
-                label_list.push(label);
-            }
+    /// Where the items and labels are kept. Unlike the methods here that fetch,
+    /// its methods return failures rather than logging them, for callers that need
+    /// to tell a failure from finding nothing.
+    pub fn storage(&self) -> &dyn ListStorage {
+        &*self.storage
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(|token| token.is_cancelled())
+    }
+
+    pub fn create_label(&self, name: String, color: String) -> Option<Label> {
+        if let Err(e) = self.storage.create_label(&Label { name: name.clone(), color: color }) {
+            warn!(name = name.as_str(), error = e.as_str(); "failed to create label");
+            return None;
         }
-        label_list
-    }
-
-    pub fn create_items_table(&mut self) {
-        let sql = r#"CREATE TABLE IF NOT EXISTS items (
-                uuid TEXT NOT NULL PRIMARY KEY,
-                name TEXT NOT NULL,
-                due_date DATETIME,
-                completion_date DATETIME
-            )"#;
-        let db = self.store.conn.lock().unwrap();
-        let r = db.execute(sql, &[]);
-        if r.is_err() {
-            println!("failed to create items table {:?}", r.err());
+        self.fetch_label(&name)
+    }
+
+    pub fn fetch_label(&self, name: &String) -> Option<Label> {
+        let label = or_default(self.storage.fetch_label(name), "fetch label");
+        if label.is_none() {
+            debug!(name = name.as_str(); "no label found");
         }
+        label
     }
 
-    pub fn create_item_labels_table(&mut self) {
-        let sql = r#"CREATE TABLE IF NOT EXISTS item_labels (
-                item_uuid TEXT NOT NULL,
-                label_name TEXT NOT NULL,
-                PRIMARY KEY(item_uuid, label_name)
-            )"#;
-        let db = self.store.conn.lock().unwrap();
-        let r = db.execute(sql, &[]);
-        if r.is_err() {
-            println!("failed to create item_labels table {:?}", r.err());
+    pub fn fetch_or_create_label(&self, name: &String, color: &String) -> Option<Label> {
+        match self.fetch_label(name) {
+            Some(label) => Some(label),
+            None => self.create_label(name.clone(), color.clone()),
         }
     }
 
+    pub fn fetch_labels(&self) -> Vec<Label> {
+        or_default(self.storage.fetch_labels(), "fetch labels")
+    }
+
+    pub fn fetch_labels_for_item(&self, item_uuid: &String) -> Vec<Label> {
+        or_default(self.storage.fetch_labels_for_item(item_uuid), "fetch labels for item")
+    }
+
     pub fn fetch_items(&mut self) -> Vec<Item> {
-        let sql = r#"SELECT uuid, name, due_date, completion_date
-                     FROM items"#;
-        let conn = self.store.conn.lock().unwrap();
-        println!("got connection");
-        let mut stmt = conn.prepare(sql).unwrap();
-        println!("got statement");
-        let mut item_iter = stmt.query_map(&[], |row| {
-            let uuid: String = row.get(0);
-            println!("Found item {}", uuid);
-            Item {
-                uuid: uuid.clone(),
-                name: row.get(1),
-                due_date: row.get(2),
-                completion_date: row.get(3),
-                labels: self.fetch_labels_for_item_with_conn(conn.deref(), &uuid)
-            }
-        }).unwrap();
-        println!("creating item list");
+        let items = or_default(self.storage.fetch_items(), "fetch items");
+        debug!(count = items.len(); "fetched items");
+        items
+    }
 
-        let mut item_list: Vec<Item> = Vec::new();
-        while let Some(result) = item_iter.next() {
-            if let Some(i) = result.ok() {
-                item_list.push(i);
-            }
-        }
-        println!("returning item list {:?}", item_list);
-        item_list
+    /// Up to `limit` of the items `fetch_items` returns, skipping the first `offset`.
+    /// Unlike `fetch_items`, a failure is returned rather than read as no items, so
+    /// paging through a list can't mistake it for the end.
+    pub fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> {
+        self.storage.fetch_items_page(offset, limit)
     }
 
-    pub fn fetch_items_with_label(&mut self, label: &Label) -> Vec<Item> {
-        let sql = r#"SELECT uuid, name, due_date, completion_date
-                     FROM items JOIN item_labels on items.uuid=item_labels.item_uuid
-                     WHERE item_labels.label_name=?"#;
-        let conn = self.store.conn.lock().unwrap();
-        let mut stmt = conn.prepare(sql).unwrap();
-        let mut item_iter = stmt.query_map(&[&label.name], |row| {
-            let uuid: String = row.get(0);
-            Item {
-                uuid: uuid.clone(),
-                name: row.get(1),
-                due_date: row.get(2),
-                completion_date: row.get(3),
-                labels: self.fetch_labels_for_item_with_conn(conn.deref(), &uuid)
-            }
-        }).unwrap();
+    pub fn count_items(&self) -> Result<usize, String> {
+        self.storage.count_items()
+    }
 
-        let mut item_list: Vec<Item> = Vec::new();
-        while let Some(result) = item_iter.next() {
-            if let Some(i) = result.ok() {
-                item_list.push(i);
-            }
-        }
-        item_list
+    pub fn fetch_items_with_label(&mut self, label: &Label) -> Vec<Item> {
+        or_default(self.storage.fetch_items_with_label(&label.name), "fetch items with label")
     }
 
     pub fn fetch_item(&mut self, uuid: &String) -> Option<Item> {
-        let sql = r#"SELECT uuid, name, due_date, completion_date FROM items WHERE uuid=?"#;
-
-        let conn = self.store.conn.lock().unwrap();
-        let mut stmt = conn.prepare(sql).unwrap();
-        let s = &self;
-        let mut item_iter = stmt.query_map(&[uuid], |row| {
-            Item {
-                uuid: row.get(0),
-                name: row.get(1),
-                due_date: row.get(2),
-                completion_date: row.get(3),
-                labels: s.fetch_labels_for_item_with_conn(conn.deref(), uuid)
-            }
-        }).unwrap();
-
-        if let Some(result) = item_iter.next() {
-            result.ok()
-        } else {
-            println!("No item found for uuid {:?}", uuid);
-            None
+        let item = or_default(self.storage.fetch_item(uuid), "fetch item");
+        if item.is_none() {
+            debug!(uuid = uuid.as_str(); "no item found");
         }
+        item
     }
 
-    pub fn create_item(&mut self, name: &String, due_date: &Option<Timespec>, completion_date: &Option<Timespec>, labels: &Vec<Label>) -> String {
-        println!("create item");
-        let item_sql = r#"INSERT INTO items (uuid, name, due_date, completion_date) VALUES (?, ?, ?, ?)"#;
-        let mut store = self.get_store_mut();
-        let mut conn = store.conn.lock().unwrap();
-        let tx = conn.transaction().expect("expected a transaction");
+    /// Save a copy of `item` under a new uuid, which is returned.
+    pub fn create_item(&mut self, item: &Item) -> Result<String, String> {
         let item_uuid = Uuid::new_v4().simple().to_string();
-        println!("item uuid {:?}", item_uuid);
-        println!("item name {:?}", name);
-        println!("item due_date {:?}", due_date);
-        println!("item completion date {:?}", completion_date);
-        let _ = tx.execute(item_sql, &[&item_uuid, name, due_date, completion_date]);
-        println!("creating labels");
-        let item_label_sql = r#"INSERT INTO item_labels (item_uuid, label_name) VALUES (?, ?)"#;
-        println!("item labels {:?}", labels);
-        for label in labels.iter() {
-            println!("creating label {:?}", label);
-            tx.execute(&item_label_sql, &[&item_uuid, &label.name]).unwrap();
-        }
-        let _ = tx.commit();
-        item_uuid
-    }
-
-    pub fn update_item(&mut self, item: &Item, existing_labels: Vec<Label>) {
-        let sql = r#"UPDATE items SET name=?, due_date=?, completion_date=? WHERE uuid=?"#;
-        let mut store = self.get_store_mut();
-        let mut conn = store.conn.lock().unwrap();
-        let tx = conn.transaction().expect("expected a transaction");
-        let _ = tx.execute(sql, &[&item.name, &item.due_date, &item.completion_date, &item.uuid]);
-
-        let item_label_insert_sql = r#"INSERT INTO item_labels (item_uuid, label_name) VALUES (?, ?)"#;
-        for label in item.labels.iter() {
-            if !existing_labels.contains(label) {
-                // add label to item
-                tx.execute(&item_label_insert_sql, &[&item.uuid, &label.name]).unwrap();
-            }
-        }
-        let item_label_delete_sql = r#"DELETE FROM item_labels WHERE item_uuid=? AND label_name=?"#;
-        for label in existing_labels.iter() {
-            if !item.labels.contains(label) {
-                // delete label from item
-                tx.execute(&item_label_delete_sql, &[&item.uuid, &label.name]).unwrap();
-            }
-        }
-        let _ = tx.commit();
+        debug!(uuid = item_uuid.as_str(), labels = item.labels.len(); "creating item");
+        let mut item = item.clone();
+        item.uuid = item_uuid.clone();
+        self.storage.create_item(&item)?;
+        Ok(item_uuid)
     }
-}
 
-fn create_and_fetch_item(manager: &mut ListManager, name: &String, due_date: &Option<Timespec>, completion_date: &Option<Timespec>, labels: &Vec<Label>) -> Option<Item> {
-    println!("Creating item");
-    let l = vec![];
-    let item_uuid = manager.create_item(name, due_date, completion_date, &l);
-    println!("fetching item {:?}", item_uuid);
-    manager.fetch_item(&item_uuid)
-}
+    pub fn update_item(&mut self, item: &Item, existing_labels: Vec<Label>) -> Result<(), String> {
+        debug!(uuid = item.uuid.as_str(); "updating item");
+        self.storage.update_item(item, &existing_labels)
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn list_manager_get_all_labels(manager: *mut ListManager) -> *mut Vec<Label> {
-    let manager = &mut *manager;
-    let label_list = Box::new(manager.fetch_labels());
-    Box::into_raw(label_list)
-}
+    /// Delete the item with `uuid` along with its labels and attachments. Returns
+    /// false if there is no such item. The item goes first, so a failure leaves
+    /// attachments without an item for `Attachments::purge` rather than an item
+    /// without its attachments.
+    pub fn delete_item(&mut self, uuid: &String) -> Result<bool, String> {
+        debug!(uuid = uuid.as_str(); "deleting item");
+        let deleted = self.storage.delete_item(uuid)?;
+        #[cfg(feature = "sqlite")]
+        self.delete_attachments(uuid);
+        Ok(deleted)
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn label_list_count(label_list: *const Vec<Label>) -> c_int {
-    let label_list = &*label_list;
-    label_list.len() as c_int
-}
+    pub fn export_ics(&mut self) -> String {
+        ical::items_to_ics(&self.fetch_items())
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn label_list_entry_at(label_list: *const Vec<Label>, index: c_int) -> *const Label {
-    let label_list = &*label_list;
-    let index = index as usize;
-    let label = Box::new(label_list[index].clone());
-    Box::into_raw(label)
-}
+    /// Create an item for every `VTODO` in `ics`, creating any labels named in
+    /// `CATEGORIES` that don't exist yet. Returns the created items.
+    pub fn import_ics(&mut self, ics: &str) -> Result<Vec<Item>, IcalError> {
+        let parsed = ical::parse_ics(ics)?;
+        Ok(self.create_imported_items(parsed))
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn list_manager_create_item(manager: *mut ListManager, name: *const c_char, due_date: *const size_t, completion_date: *const size_t, label_list: *const Vec<Label>) -> *mut Option<Item> {
-    let manager = &mut *manager;
-    let due: Option<Timespec>;
-    if !due_date.is_null() {
-        let due_date = *due_date as i64;
-        println!("Due date is not null {}", due_date);
-        due = Some(Timespec::new(due_date, 0));
-    } else {
-        println!("Due date is not null");
-        due = None;
-    }
-    println!("Due date is not null {:?}", due);
-    let completion: Option<Timespec>;
-    if !completion_date.is_null() {
-        let completion_date = *completion_date as i64;
-        completion = Some(Timespec::new(completion_date, 0));
-    } else {
-        completion = None;
-    }
-    let labels = &*label_list;
-    let name = c_char_to_string(name);
-    println!("creating temp item object");
-    Box::into_raw(Box::new(create_and_fetch_item(manager, &name, &due, &completion, labels)))
-}
+    pub fn export_csv(&mut self, options: &CsvExportOptions) -> Result<String, CsvError> {
+        csv::export_csv(&self.fetch_items(), options)
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn list_manager_update_item(manager: *mut ListManager, item: *const Item) {
-    let manager = &mut *manager;
-    let item = &*item;
-    let existing_labels = manager.fetch_labels_for_item(&(item.uuid));
-    manager.update_item(item, existing_labels)
-}
+    /// Create an item for every valid row in `csv`, creating any labels that don't
+    /// exist yet. The returned import holds the created items and the rejected rows.
+    pub fn import_csv(&mut self, csv: &str, mapping: &CsvColumnMapping) -> Result<CsvImport, CsvError> {
+        let mut import = csv::parse_csv(csv, mapping)?;
+        let parsed = ::std::mem::replace(&mut import.items, vec![]);
+        import.items = self.create_imported_items(parsed);
+        Ok(import)
+    }
 
+    pub fn export_todo_txt(&mut self) -> TodoTxtExport {
+        todotxt::write_todo_txt(&self.fetch_items())
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn list_manager_get_all_items(manager: *mut ListManager) -> *mut Vec<Item> {
-    let manager = &mut *manager;
-    let item_list = Box::new(manager.fetch_items());
-    Box::into_raw(item_list)
-}
+    /// Create an item for every line of `text`, creating any labels for `+project`
+    /// and `@context` tags that don't exist yet.
+    pub fn import_todo_txt(&mut self, text: &str) -> TodoTxtImport {
+        let mut import = todotxt::parse_todo_txt(text);
+        let parsed = ::std::mem::replace(&mut import.items, vec![]);
+        import.items = self.create_imported_items(parsed);
+        import
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn item_list_count(item_list: *const Vec<Item>) -> c_int {
-    let item_list = &*item_list;
-    item_list.len() as c_int
-}
+    /// Export every item, or only those with `label`, as a Markdown checklist.
+    pub fn export_markdown(&mut self, label: Option<&Label>) -> String {
+        match label {
+            Some(label) => markdown::items_to_markdown(&self.fetch_items_with_label(label), Some(&label.name)),
+            None => markdown::items_to_markdown(&self.fetch_items(), None),
+        }
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn item_list_entry_at(item_list: *const Vec<Item>, index: c_int) -> *const Item {
-    let item_list = &*item_list;
-    let index = index as usize;
-    let item = Box::new(item_list[index].clone());
-    Box::into_raw(item)
-}
+    /// Create an item for every checklist entry in `text`, creating any labels for
+    /// `#tags` that don't exist yet.
+    pub fn import_markdown(&mut self, text: &str) -> MarkdownImport {
+        let mut import = markdown::parse_markdown(text);
+        let parsed = ::std::mem::replace(&mut import.items, vec![]);
+        import.items = self.create_imported_items(parsed);
+        import
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn list_manager_create_label(manager: *mut ListManager, name: *const c_char, color: *const c_char) -> *mut Label {
-    let manager = &mut *manager;
-    let name = c_char_to_string(name);
-    let color = c_char_to_string(color);
-    let label = Box::new(manager.create_label(name, color).unwrap());
-    Box::into_raw(label)
+    /// Create an item from quick-add `text`, such as "Buy milk #groceries !high due
+    /// fri", creating any labels that don't exist yet. Due dates are read as if it
+    /// were `now` in `zone`.
+    pub fn quick_add(&mut self, text: &str, now: &Date, zone: Tz) -> Result<QuickAdd, String> {
+        let mut quick_add = quick_add::parse_quick_add(text, now, zone)?;
+        let item = ::std::mem::replace(&mut quick_add.item, Item::new("".to_string(), "".to_string(), None, None, vec![]));
+        quick_add.item = self.create_imported_items(vec![item]).pop().ok_or("item wasn't created".to_string())?;
+        Ok(quick_add)
+    }
+
+    fn create_imported_items(&mut self, parsed: Vec<Item>) -> Vec<Item> {
+        let mut items = Vec::with_capacity(parsed.len());
+        for mut item in parsed.into_iter() {
+            if self.is_cancelled() {
+                debug!(created = items.len(); "import cancelled");
+                break;
+            }
+            item.labels = item.labels.iter()
+                                     .filter_map(|label| self.fetch_or_create_label(&label.name, &label.color))
+                                     .collect();
+            match create_and_fetch_item(self, &item) {
+                Ok(created) => items.push(created),
+                Err(e) => warn!(name = item.name.as_str(), error = e.as_str(); "failed to import item"),
+            }
+        }
+        items
+    }
 }
 
+fn create_and_fetch_item(manager: &mut ListManager, item: &Item) -> Result<Item, String> {
+    let item_uuid = manager.create_item(item)?;
+    manager.fetch_item(&item_uuid).ok_or_else(|| "item wasn't saved".to_string())
+}
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sqlite"))]
 mod test {
     use super::{
-        Store,
         ListManager,
         Label,
         Item,
+        CsvColumnMapping,
+        CsvExportOptions,
         create_and_fetch_item,
     };
 
-    use std::sync::Arc;
-
-    use time::now_utc;
+    use cancellation::CancellationToken;
+    use dates::Date;
+    use items::Priority;
+    use storage::MemoryStorage;
 
     fn list_manager() -> ListManager {
         ListManager::new(None)
     }
 
-    #[test]
-    fn test_new_list_manager() {
-        let manager = list_manager();
-        let sql = r#"SELECT count(name) FROM sqlite_master WHERE type='table' AND name=?"#;
-        let conn = *manager.get_store().conn.lock().unwrap();
-        // test that items table has been created
-        let mut stmt = conn.prepare(sql).unwrap();
-        let tables = [&"items", &"labels", &"item_labels"];
-        for &table in tables.iter() {
-            let mut r = stmt.query(&[table]).unwrap();
-            match r.next() {
-                Some(Ok(row)) => {
-                    let count: i64 = row.get(0);
-                    assert_eq!(count, 1);
-                },
-                _ => assert!(false),
-            }
-        }
-    }
-
     #[test]
     fn test_create_label() {
         let manager = list_manager();
@@ -492,22 +452,23 @@ mod test {
         };
         let label2 = manager.create_label(l2.name.clone(), l2.color.clone()).unwrap();
 
-        let date = now_utc().to_timespec();
+        let date = Date::now();
         let i = Item {
             uuid: "".to_string(),
             name: "test item".to_string(),
-            due_date: Some(date.clone()),
-            completion_date: Some(date.clone()),
-            labels: vec![label, label2]
+            due_date: Some(date),
+            completion_date: Some(date),
+            labels: vec![label, label2],
+            priority: None,
         };
 
         let item = create_and_fetch_item(&mut manager, &i).expect("expected an item");
         assert!(item.uuid.len() > 0);
         assert_eq!(item.name, i.name);
         let due_date = item.due_date.expect("expecting a due date");
-        assert_eq!(due_date.sec, date.sec);
+        assert_eq!(due_date, date);
         let completion_date = item.completion_date.expect("expecting a completion date");
-        assert_eq!(completion_date.sec, date.sec);
+        assert_eq!(completion_date, date);
         assert_eq!(item.labels, i.labels);
     }
 
@@ -526,13 +487,14 @@ mod test {
         };
         let label2 = manager.create_label(l2.name.clone(), l2.color.clone()).unwrap();
 
-        let date = now_utc().to_timespec();
+        let date = Date::now();
         let i = Item {
             uuid: "".to_string(),
             name: "test item".to_string(),
             due_date: None,
-            completion_date: Some(date.clone()),
-            labels: vec![label, label2]
+            completion_date: Some(date),
+            labels: vec![label, label2],
+            priority: None,
         };
 
         let item = create_and_fetch_item(&mut manager, &i).expect("expected an item");
@@ -540,7 +502,7 @@ mod test {
         assert_eq!(item.name, i.name);
         assert_eq!(item.due_date, i.due_date);
         let completion_date = item.completion_date.expect("expecting a completion date");
-        assert_eq!(completion_date.sec, date.sec);
+        assert_eq!(completion_date, date);
         assert_eq!(item.labels, i.labels);
     }
 
@@ -559,20 +521,21 @@ mod test {
         };
         let label2 = manager.create_label(l2.name.clone(), l2.color.clone()).unwrap();
 
-        let date = now_utc().to_timespec();
+        let date = Date::now();
         let i = Item {
             uuid: "".to_string(),
             name: "test item".to_string(),
-            due_date: Some(date.clone()),
+            due_date: Some(date),
             completion_date: None,
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            priority: None,
         };
 
         let item = create_and_fetch_item(&mut manager, &i).expect("expected an item");
         assert!(item.uuid.len() > 0);
         assert_eq!(item.name, i.name);
         let due_date = item.due_date.expect("expecting a due date");
-        assert_eq!(due_date.sec, date.sec);
+        assert_eq!(due_date, date);
         assert_eq!(item.completion_date, i.completion_date);
         assert_eq!(item.labels, i.labels);
     }
@@ -586,10 +549,11 @@ mod test {
             name: "test item".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label]
+            labels: vec![label],
+            priority: None,
         };
 
-        created_item.uuid = manager.create_item(&created_item);
+        created_item.uuid = manager.create_item(&created_item).unwrap();
         let fetched_item = manager.fetch_item(&created_item.uuid).expect("expected an item");
         assert_eq!(fetched_item, created_item);
 
@@ -609,10 +573,11 @@ mod test {
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
+        item1.uuid = manager.create_item(&item1).unwrap();
 
         let fetched_labels = manager.fetch_labels_for_item(&item1.uuid);
         assert_eq!(fetched_labels, item1.labels);
@@ -629,21 +594,24 @@ mod test {
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label.clone()]
+            labels: vec![label.clone()],
+            priority: None,
         };
         let mut item2 = Item {
             uuid: "".to_string(),
             name: "test item 2".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label.clone()]
+            labels: vec![label.clone()],
+            priority: None,
         };
         let mut item3 = Item {
             uuid: "".to_string(),
             name: "test item 3".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label.clone(), label2.clone()]
+            labels: vec![label.clone(), label2.clone()],
+            priority: None,
         };
 
         let mut item4 = Item {
@@ -651,13 +619,14 @@ mod test {
             name: "test item 4".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label2.clone()]
+            labels: vec![label2.clone()],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
-        item2.uuid = manager.create_item(&item2);
-        item3.uuid = manager.create_item(&item3);
-        item4.uuid = manager.create_item(&item4);
+        item1.uuid = manager.create_item(&item1).unwrap();
+        item2.uuid = manager.create_item(&item2).unwrap();
+        item3.uuid = manager.create_item(&item3).unwrap();
+        item4.uuid = manager.create_item(&item4).unwrap();
 
         let fetched_label1_items = manager.fetch_items_with_label(&label);
         assert_eq!(fetched_label1_items, vec![item1, item2, item3.clone()]);
@@ -677,14 +646,15 @@ mod test {
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
+        item1.uuid = manager.create_item(&item1).unwrap();
         item1.labels.push(label3);
 
         let existing_labels = manager.fetch_labels_for_item(&item1.uuid);
-        manager.update_item(&item1, existing_labels);
+        manager.update_item(&item1, existing_labels).unwrap();
 
         let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item");
         assert_eq!(fetched_item, item1);
@@ -702,14 +672,15 @@ mod test {
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
+        item1.uuid = manager.create_item(&item1).unwrap();
         item1.labels.remove(2);
 
         let existing_labels = manager.fetch_labels_for_item(&item1.uuid);
-        manager.update_item(&item1, existing_labels);
+        manager.update_item(&item1, existing_labels).unwrap();
 
         let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item");
         assert_eq!(fetched_item, item1);
@@ -727,18 +698,19 @@ mod test {
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
-        item1.due_date = Some(now_utc().to_timespec());
+        item1.uuid = manager.create_item(&item1).unwrap();
+        item1.due_date = Some(Date::now());
 
         let existing_labels = manager.fetch_labels_for_item(&item1.uuid);
-        manager.update_item(&item1, existing_labels);
+        manager.update_item(&item1, existing_labels).unwrap();
 
         let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item");
         let due_date = fetched_item.due_date.expect("expected a due date");
-        assert_eq!(due_date.sec, item1.due_date.unwrap().sec);
+        assert_eq!(due_date, item1.due_date.unwrap());
     }
 
     #[test]
@@ -748,25 +720,134 @@ mod test {
         let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).unwrap();
         let label3 = manager.create_label("label3".to_string(), "#000000".to_string()).unwrap();
 
-        let date = now_utc().to_timespec();
+        let date = Date::now();
         let mut item1 = Item {
             uuid: "".to_string(),
             name: "test item 1".to_string(),
             due_date: Some(date),
             completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
+        item1.uuid = manager.create_item(&item1).unwrap();
         item1.name = "new name".to_string();
 
         let existing_labels = manager.fetch_labels_for_item(&item1.uuid);
-        manager.update_item(&item1, existing_labels);
+        manager.update_item(&item1, existing_labels).unwrap();
 
         let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item");
         assert_eq!(fetched_item.name, item1.name);
     }
 
+    #[test]
+    fn test_delete_item() {
+        let mut manager = list_manager();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).unwrap();
+        let item = Item::new("".to_string(), "test item 1".to_string(), None, None, vec![label.clone()]);
+        let uuid = manager.create_item(&item).unwrap();
+
+        assert!(manager.delete_item(&uuid).unwrap());
+        assert_eq!(manager.fetch_item(&uuid), None);
+        assert_eq!(manager.fetch_items_with_label(&label), vec![]);
+        assert!(!manager.delete_item(&uuid).unwrap());
+    }
+
+    #[test]
+    fn test_ics_round_trip() {
+        let mut manager = list_manager();
+        let label = manager.create_label("label1".to_string(), "#ff0000".to_string()).unwrap();
+        let date = Date::from_millis(1509883200000).unwrap();
+        let items = vec![
+            Item::new("".to_string(), "test item 1".to_string(), Some(date), None, vec![label.clone()]),
+            Item::new("".to_string(), "test item 2".to_string(), None, Some(date), vec![]),
+        ];
+        for item in items.iter() {
+            manager.create_item(item).unwrap();
+        }
+
+        let ics = manager.export_ics();
+        let mut other = list_manager();
+        let imported = other.import_ics(&ics).expect("expected items");
+        assert_eq!(imported.len(), items.len());
+        for (imported, original) in imported.iter().zip(items.iter()) {
+            assert!(imported.uuid.len() > 0);
+            assert_eq!(imported.name, original.name);
+            assert_eq!(imported.due_date, original.due_date);
+            assert_eq!(imported.completion_date, original.completion_date);
+            assert_eq!(imported.labels.iter().map(|l| &l.name).collect::<Vec<_>>(),
+                       original.labels.iter().map(|l| &l.name).collect::<Vec<_>>());
+        }
+        assert_eq!(other.fetch_labels().len(), 1);
+    }
+
+    #[test]
+    fn test_import_ics_reuses_existing_labels() {
+        let mut manager = list_manager();
+        let label = manager.create_label("work".to_string(), "#ff0000".to_string()).unwrap();
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:report\r\nCATEGORIES:work,home\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let imported = manager.import_ics(ics).expect("expected items");
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].labels.len(), 2);
+        assert!(imported[0].labels.contains(&label));
+        assert!(imported[0].labels.iter().any(|l| l.name == "home"));
+        assert_eq!(manager.fetch_labels().len(), 2);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut manager = list_manager();
+        let label = manager.create_label("label1".to_string(), "#ff0000".to_string()).unwrap();
+        let date = Date::now();
+        let item = Item::new("".to_string(), "test, item".to_string(), Some(date), None, vec![label.clone()]);
+        manager.create_item(&item).unwrap();
+
+        let csv = manager.export_csv(&CsvExportOptions::default()).expect("expected an export");
+        let mut other = list_manager();
+        other.create_label("label1".to_string(), "#00ff00".to_string()).unwrap();
+        let import = other.import_csv(&csv, &CsvColumnMapping::default()).expect("expected an import");
+        assert_eq!(import.errors, vec![]);
+        assert_eq!(import.items.len(), 1);
+        assert!(import.items[0].uuid.len() > 0);
+        assert_eq!(import.items[0].name, item.name);
+        assert_eq!(import.items[0].due_date, item.due_date);
+        assert_eq!(import.items[0].labels[0].color, "#00ff00");
+    }
+
+    #[test]
+    fn test_todo_txt_round_trip() {
+        let mut manager = list_manager();
+        let import = manager.import_todo_txt("(B) Call @phone Mom +family due:2017-11-05\nx 2017-11-04 Pay rent\n");
+        assert_eq!(import.items.len(), 2);
+        assert_eq!(import.dropped, vec![]);
+        assert_eq!(import.items[0].priority, Some(Priority::Medium));
+        assert!(import.items.iter().all(|item| item.uuid.len() > 0));
+        assert_eq!(manager.fetch_labels().len(), 2);
+
+        let export = manager.export_todo_txt();
+        assert_eq!(export.dropped, vec![]);
+        let mut lines: Vec<&str> = export.text.lines().collect();
+        lines.sort();
+        assert!(lines[0].starts_with("(B) Call Mom "));
+        assert!(lines[0].ends_with(" due:2017-11-05"));
+        assert!(lines[0].contains(" +family") && lines[0].contains(" @phone"));
+        assert_eq!(lines[1], "x 2017-11-04 Pay rent");
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let mut manager = list_manager();
+        let import = manager.import_markdown("- [ ] buy milk #groceries\n- [x] call mum\n  - [ ] ask about #groceries\n");
+        assert_eq!(import.items.len(), 3);
+        assert_eq!(import.flattened.len(), 1);
+        assert_eq!(manager.fetch_labels().len(), 1);
+
+        let groceries = manager.fetch_label(&"groceries".to_string()).unwrap();
+        assert_eq!(manager.export_markdown(Some(&groceries)),
+                   "# groceries\n\n- [ ] buy milk #groceries\n- [ ] ask about #groceries\n");
+        assert_eq!(manager.export_markdown(None).lines().count(), 3);
+    }
+
     #[test]
     fn test_update_item_complete_item() {
         let mut manager = list_manager();
@@ -774,23 +855,65 @@ mod test {
         let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).unwrap();
         let label3 = manager.create_label("label3".to_string(), "#000000".to_string()).unwrap();
 
-        let date = now_utc().to_timespec();
+        let date = Date::now();
         let mut item1 = Item {
             uuid: "".to_string(),
             name: "test item 1".to_string(),
             due_date: None,
             completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            priority: None,
         };
 
-        item1.uuid = manager.create_item(&item1);
+        item1.uuid = manager.create_item(&item1).unwrap();
         item1.completion_date = Some(date);
 
         let existing_labels = manager.fetch_labels_for_item(&item1.uuid);
-        manager.update_item(&item1, existing_labels);
+        manager.update_item(&item1, existing_labels).unwrap();
 
         let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item");
         let completion_date = fetched_item.completion_date.expect("expected a completion_date");
-        assert_eq!(completion_date.sec, date.sec);
+        assert_eq!(completion_date, date);
+    }
+
+    #[test]
+    fn test_dates_keep_their_kind_and_zone() {
+        let mut manager = list_manager();
+        let due = Date::day(2017, 11, 5).unwrap();
+        let completed = Date::from_millis_in(1509883200345, "Europe/London").unwrap();
+        let item = Item::new("".to_string(), "test item".to_string(), Some(due), Some(completed), vec![]);
+
+        let fetched = create_and_fetch_item(&mut manager, &item).expect("expected an item");
+        assert_eq!(fetched.due_date, Some(due));
+        assert_eq!(fetched.completion_date, Some(completed));
+    }
+
+    #[test]
+    fn test_with_memory_storage() {
+        let mut manager = ListManager::with_storage(MemoryStorage::new());
+        let label = manager.create_label("home".to_string(), "#ff0000".to_string()).expect("expected a label");
+        assert_eq!(manager.create_label("home".to_string(), "#00ff00".to_string()), None);
+
+        let item = Item::new("".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![label.clone()]);
+        let fetched = create_and_fetch_item(&mut manager, &item).expect("expected an item");
+        assert_eq!(fetched.uuid.len(), 32);
+        assert_eq!(fetched.labels, vec![label.clone()]);
+        assert_eq!(manager.fetch_items_with_label(&label), vec![fetched.clone()]);
+        assert!(manager.delete_item(&fetched.uuid).unwrap());
+        assert_eq!(manager.fetch_items(), vec![]);
+    }
+
+    #[test]
+    fn test_cancelled_import() {
+        let mut manager = list_manager();
+        let token = CancellationToken::new();
+        let mut cancellable = manager.with_cancellation(token.clone());
+        assert_eq!(cancellable.import_markdown("- [ ] buy milk\n").items.len(), 1);
+
+        token.cancel();
+        assert_eq!(cancellable.import_markdown("- [ ] call mum\n- [ ] walk the dog\n").items, vec![]);
+        // The manager it was made from isn't cancelled.
+        assert_eq!(manager.import_markdown("- [ ] call mum\n").items.len(), 1);
+        assert_eq!(manager.fetch_items().len(), 2);
     }
 }
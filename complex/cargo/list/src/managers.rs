@@ -0,0 +1,39 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The registry of list managers handed across the FFI, and the function that frees
+//! them. It is kept apart from `lib.rs` so the header generator, which reads every
+//! module a source declares, can read it alone.
+
+use ffi_utils::error::guard;
+use ffi_utils::handles::HandleRegistry;
+
+use {
+    ListManager,
+    ListManagerHandle,
+};
+
+pub static MANAGERS: HandleRegistry<ListManager> = HandleRegistry::new("list manager");
+
+/// The list manager `handle` refers to. Clones share the same store, so the copy
+/// can be used without holding on to the registry.
+pub fn manager_from_handle(handle: ListManagerHandle) -> Result<ListManager, String> {
+    MANAGERS.get(handle, |manager| manager.clone())
+}
+
+/// # Safety
+///
+/// `manager` is a handle, so any value is safe. Once destroyed it is invalid, and
+/// destroying it again, or destroying 0, only fails and sets the last error. Other
+/// handles on the same list are unaffected.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_destroy(manager: ListManagerHandle) {
+    guard((), || MANAGERS.destroy(manager))
+}
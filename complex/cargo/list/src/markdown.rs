@@ -0,0 +1,322 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Markdown task lists (`- [ ] buy milk`, `- [x] call mum`).
+//!
+//! Every checklist line becomes an item, with `#tags` in the text becoming labels
+//! and a ticked box marking the item as completed. Lines that aren't checklist
+//! entries are ignored. Items can't have subtasks, so nested entries are imported
+//! as items of their own and listed in the import report along with their parent.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use dates::Date;
+use ffi_utils::error::{
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::strings::string_to_c_char;
+use items::{
+    Item,
+    ItemHandle,
+    ITEMS,
+};
+use labels::{
+    Label,
+    LabelHandle,
+    LABELS,
+    DEFAULT_COLOR,
+};
+use manager_from_handle;
+use ListManagerHandle;
+
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flattened {
+    /// 1-based line of the nested entry.
+    pub line: usize,
+    /// Name of the entry it was nested under.
+    pub parent: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownImport {
+    pub items: Vec<Item>,
+    pub flattened: Vec<Flattened>,
+}
+
+/// The result of a Markdown import handed across the FFI, freed with
+/// `markdown_import_destroy`.
+pub type MarkdownImportHandle = u64;
+
+pub static MARKDOWN_IMPORTS: HandleRegistry<MarkdownImport> = HandleRegistry::new("Markdown import");
+
+/// Parse the checklist entries in `text`. Labels are created with the default
+/// color; it is up to the caller to reconcile them with existing labels.
+pub fn parse_markdown(text: &str) -> MarkdownImport {
+    let mut import = MarkdownImport { items: vec![], flattened: vec![] };
+    // Indentation and name of the entries enclosing the current line.
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    let now = Date::now();
+
+    for (index, line) in text.lines().enumerate() {
+        let (indent, checked, rest) = match parse_entry(line) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        while parents.last().map_or(false, |&(parent_indent, _)| parent_indent >= indent) {
+            parents.pop();
+        }
+        let item = parse_item(rest, if checked { Some(now) } else { None });
+        if let Some(&(_, ref parent)) = parents.last() {
+            import.flattened.push(Flattened { line: index + 1, parent: parent.clone() });
+        }
+        parents.push((indent, item.name.clone()));
+        import.items.push(item);
+    }
+    import
+}
+
+/// Write `items` as a checklist, under a `# title` heading if one is given. Labels
+/// whose names contain whitespace can't be written as tags and are left out.
+pub fn items_to_markdown(items: &[Item], title: Option<&str>) -> String {
+    let mut out = String::new();
+    if let Some(title) = title {
+        out.push_str(&format!("# {}\n\n", title));
+    }
+    for item in items.iter() {
+        out.push_str(if item.completion_date.is_some() { "- [x] " } else { "- [ ] " });
+        out.push_str(&item.name);
+        for label in item.labels.iter() {
+            if !label.name.is_empty() && !label.name.contains(char::is_whitespace) {
+                out.push_str(" #");
+                out.push_str(&label.name);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Split a checklist line into its indentation, whether it's ticked and its text.
+fn parse_entry(line: &str) -> Option<(usize, bool, &str)> {
+    let content = line.trim_start();
+    let indent = line[..line.len() - content.len()].chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum();
+    let mut chars = content.chars();
+    match chars.next() {
+        Some('-') | Some('*') | Some('+') => {},
+        _ => return None,
+    }
+    let rest = chars.as_str();
+    let checked = if rest.starts_with(" [ ]") {
+        false
+    } else if rest.starts_with(" [x]") || rest.starts_with(" [X]") {
+        true
+    } else {
+        return None;
+    };
+    let text = &rest[4..];
+    if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((indent, checked, text.trim()))
+}
+
+fn parse_item(text: &str, completion_date: Option<Date>) -> Item {
+    let mut item = Item::new("".to_string(), "".to_string(), None, completion_date, vec![]);
+    let mut words: Vec<&str> = Vec::new();
+    for word in text.split_whitespace() {
+        match tag_name(word) {
+            Some(name) => {
+                if !item.labels.iter().any(|label| label.name == name) {
+                    item.labels.push(Label { name: name.to_string(), color: DEFAULT_COLOR.to_string() });
+                }
+            },
+            None => words.push(word),
+        }
+    }
+    item.name = words.join(" ");
+    item
+}
+
+fn tag_name(word: &str) -> Option<&str> {
+    if !word.starts_with('#') || word.len() < 2 {
+        return None;
+    }
+    let name = &word[1..];
+    if name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '/') && !name.chars().all(|c| c.is_ascii_digit()) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Export the items with `label`, or every item if `label` is 0, as a Markdown
+/// checklist.
+///
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_export_markdown(manager: ListManagerHandle, label: LabelHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let mut manager = manager_from_handle(manager)?;
+        let label = if label == 0 { None } else { Some(LABELS.get(label, |label| label.clone())?) };
+        Ok(string_to_c_char(manager.export_markdown(label.as_ref()))?)
+    })
+}
+
+/// Create an item for every checklist entry in `markdown`. The result holds the
+/// created items and the nested entries that were flattened into top-level items.
+///
+/// # Safety
+///
+/// `markdown` must point to a NUL-terminated string that stays valid for the call;
+/// NULL is rejected with an error. The caller keeps ownership of it. The import must
+/// be freed once with `markdown_import_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_import_markdown(manager: ListManagerHandle, markdown: *const c_char) -> MarkdownImportHandle {
+    guard(0, || {
+        let mut manager = manager_from_handle(manager)?;
+        let markdown = check_str(markdown, "markdown")?;
+        Ok(MARKDOWN_IMPORTS.insert(manager.import_markdown(&markdown)))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_destroy(import: MarkdownImportHandle) {
+    guard((), || MARKDOWN_IMPORTS.destroy(import))
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_item_count(import: MarkdownImportHandle) -> usize {
+    guard(0, || MARKDOWN_IMPORTS.get(import, |import| import.items.len()))
+}
+
+/// Copy the imported item at `index`. The returned item must be freed with `item_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_item_at(import: MarkdownImportHandle, index: usize) -> ItemHandle {
+    guard(0, || {
+        let item = MARKDOWN_IMPORTS.get(import, |import| import.items.get(index).cloned().ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(ITEMS.insert(item))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_flattened_count(import: MarkdownImportHandle) -> usize {
+    guard(0, || MARKDOWN_IMPORTS.get(import, |import| import.flattened.len()))
+}
+
+/// The 1-based line of the flattened entry at `index`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_flattened_line(import: MarkdownImportHandle, index: usize) -> usize {
+    guard(0, || {
+        MARKDOWN_IMPORTS.get(import, |import| import.flattened.get(index).map(|flattened| flattened.line).ok_or_else(|| format!("index {} out of range", index)))?
+    })
+}
+
+/// The name of the entry the flattened entry at `index` was nested under.
+///
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn markdown_import_flattened_parent(import: MarkdownImportHandle, index: usize) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let parent = MARKDOWN_IMPORTS.get(import, |import| import.flattened.get(index).map(|flattened| flattened.parent.clone()).ok_or_else(|| format!("index {} out of range", index)))??;
+        Ok(string_to_c_char(parent)?)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use items::Item;
+    use labels::Label;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            color: DEFAULT_COLOR.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_checklist() {
+        let markdown = "# Weekend\n\
+                        \n\
+                        Some notes that aren't tasks.\n\
+                        - [ ] buy milk #groceries\n\
+                        - [x] call mum #family #phone\n\
+                        * [X] tidy up #42 #c#\n\
+                        - plain bullet\n\
+                        - [ ]no space\n\
+                        + [ ] plan party #family\n";
+        let import = parse_markdown(markdown);
+        let names: Vec<&str> = import.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["buy milk", "call mum", "tidy up #42 #c#", "plan party"]);
+        assert_eq!(import.items[0].labels, vec![label("groceries")]);
+        assert_eq!(import.items[0].completion_date, None);
+        assert_eq!(import.items[1].labels, vec![label("family"), label("phone")]);
+        assert!(import.items[1].completion_date.is_some());
+        assert!(import.items[2].completion_date.is_some());
+        assert_eq!(import.items[2].labels, vec![]);
+        assert_eq!(import.flattened, vec![]);
+    }
+
+    #[test]
+    fn test_nested_entries_are_flattened() {
+        let markdown = "- [ ] party\n  - [ ] invitations\n    - [x] guest list\n  - [ ] cake\n\t- [ ] music\n- [ ] cleanup\n";
+        let import = parse_markdown(markdown);
+        assert_eq!(import.items.len(), 6);
+        assert_eq!(import.flattened, vec![
+            Flattened { line: 2, parent: "party".to_string() },
+            Flattened { line: 3, parent: "invitations".to_string() },
+            Flattened { line: 4, parent: "party".to_string() },
+            Flattened { line: 5, parent: "cake".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let items = vec![
+            Item::new("".to_string(), "buy milk".to_string(), None, None, vec![label("groceries"), label("errands")]),
+            Item::new("".to_string(), "call mum".to_string(), None, Date::from_millis(1509800000000).ok(), vec![]),
+        ];
+        let markdown = items_to_markdown(&items, Some("Today"));
+        assert_eq!(markdown, "# Today\n\n- [ ] buy milk #groceries #errands\n- [x] call mum\n");
+
+        let import = parse_markdown(&markdown);
+        assert_eq!(import.items[0], items[0]);
+        assert_eq!(import.items[1].name, items[1].name);
+        assert!(import.items[1].completion_date.is_some());
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Whole items typed into a single field, such as "Buy milk #groceries !high due
+//! fri".
+//!
+//! Words starting with `#` are labels, and `!high`, `!medium` or `!low` is the
+//! priority. `due` followed by a phrase that `date_phrases` can read is the due
+//! date; the phrase runs for as many words as still read as a date. The remaining
+//! words, in order, are the name. Only the first priority and due date count, and
+//! any later ones are left in the name.
+
+use std::error::Error;
+use std::fmt;
+use std::os::raw::c_char;
+use std::ptr;
+
+use chrono_tz::Tz;
+use ffi_utils::error::{
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::strings::string_to_c_char;
+
+use dates::parse_zone;
+use dates::Date;
+use date_phrases::{
+    parse_date_phrase,
+    DatePhraseError,
+};
+use items::{
+    ItemHandle,
+    ITEMS,
+};
+use items::{
+    Item,
+    Priority,
+};
+use labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+use {
+    manager_from_handle,
+    ListManagerHandle,
+};
+
+/// An item read from quick-add text, and the parts of the text that weren't its
+/// name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickAdd {
+    pub item: Item,
+    /// In the order they appeared.
+    pub recognised: Vec<Recognised>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recognised {
+    Label(String),
+    Priority(Priority),
+    /// The phrase after `due`, as typed, and the date it was read as.
+    DueDate(String, Date),
+}
+
+impl fmt::Display for Recognised {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Recognised::Label(ref name) => write!(f, "label {:?}", name),
+            Recognised::Priority(priority) => write!(f, "priority {}", priority),
+            Recognised::DueDate(ref phrase, ref date) => write!(f, "due {:?} ({})", phrase, date),
+        }
+    }
+}
+
+impl QuickAdd {
+    /// What was recognised, such as `label "groceries", priority high`, or "" if
+    /// everything was the name.
+    pub fn description(&self) -> String {
+        self.recognised.iter().map(|part| part.to_string()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuickAddError {
+    /// Text with nothing left for the item's name.
+    NoName,
+    /// A due date that reads as a date that doesn't exist, such as "due feb 30".
+    DueDate(DatePhraseError),
+}
+
+impl fmt::Display for QuickAddError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QuickAddError::NoName => write!(f, "no name given"),
+            QuickAddError::DueDate(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for QuickAddError {}
+
+impl From<QuickAddError> for String {
+    fn from(error: QuickAddError) -> String {
+        error.to_string()
+    }
+}
+
+/// Read `text` as an unsaved item, with due dates read as if it were `now` in
+/// `zone`. Labels have the default color; it is up to the caller to reconcile them
+/// with existing labels.
+pub fn parse_quick_add(text: &str, now: &Date, zone: Tz) -> Result<QuickAdd, QuickAddError> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut name: Vec<&str> = vec![];
+    let mut item = Item::new("".to_string(), "".to_string(), None, None, vec![]);
+    let mut recognised = vec![];
+    let mut at = 0;
+    while at < words.len() {
+        let word = words[at];
+        at += 1;
+        if let Some(label) = word.strip_prefix('#').filter(|label| !label.is_empty()) {
+            if !item.labels.iter().any(|existing| existing.name == label) {
+                item.labels.push(Label { name: label.to_string(), color: DEFAULT_COLOR.to_string() });
+                recognised.push(Recognised::Label(label.to_string()));
+            }
+            continue;
+        }
+        if item.priority.is_none() {
+            if let Some(Ok(priority)) = word.strip_prefix('!').map(str::parse) {
+                item.priority = Some(priority);
+                recognised.push(Recognised::Priority(priority));
+                continue;
+            }
+        }
+        if item.due_date.is_none() && word.eq_ignore_ascii_case("due") {
+            if let Some((phrase, date, used)) = due_date(&words[at..], now, zone)? {
+                item.due_date = Some(date);
+                recognised.push(Recognised::DueDate(phrase, date));
+                at += used;
+                continue;
+            }
+        }
+        name.push(word);
+    }
+    if name.is_empty() {
+        return Err(QuickAddError::NoName);
+    }
+    item.name = name.join(" ");
+    Ok(QuickAdd {
+        item: item,
+        recognised: recognised,
+    })
+}
+
+/// The longest date phrase at the start of `words`, the date it reads as and how
+/// many words it takes up. A phrase stops at the next label or priority.
+fn due_date(words: &[&str], now: &Date, zone: Tz) -> Result<Option<(String, Date, usize)>, QuickAddError> {
+    let limit = words.iter().position(|word| word.starts_with('#') || word.starts_with('!')).unwrap_or(words.len());
+    let mut invalid = None;
+    for used in (1..limit + 1).rev() {
+        let phrase = words[..used].join(" ");
+        match parse_date_phrase(&phrase, now, zone) {
+            Ok(date) => return Ok(Some((phrase, date, used))),
+            // A day or time that doesn't exist is a mistake in the date, but words
+            // that aren't a date at all may just be part of the name.
+            Err(DatePhraseError::Empty) | Err(DatePhraseError::Unrecognised(_)) => {},
+            Err(error) => {
+                invalid.get_or_insert(error);
+            },
+        }
+    }
+    match invalid {
+        Some(error) => Err(QuickAddError::DueDate(error)),
+        None => Ok(None),
+    }
+}
+
+/// The result of `list_manager_quick_add`, freed with `quick_add_destroy`.
+pub type QuickAddHandle = u64;
+
+pub static QUICK_ADDS: HandleRegistry<QuickAdd> = HandleRegistry::new("quick add");
+
+/// Create an item from quick-add `text`, such as "Buy milk #groceries !high due
+/// fri", creating any labels that don't exist yet. Due dates are read as if it were
+/// `now` milliseconds since the Unix epoch in the IANA time zone `zone`. Returns 0
+/// if no item was created, otherwise a result to read the item and a description of
+/// what was recognised from.
+///
+/// # Safety
+///
+/// `text` and `zone` must point to NUL-terminated strings that stay valid for the
+/// call; NULL is rejected with an error. The caller keeps ownership of them. The
+/// result must be freed once with `quick_add_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_quick_add(manager: ListManagerHandle, text: *const c_char, now: i64, zone: *const c_char) -> QuickAddHandle {
+    guard(0, || {
+        let mut manager = manager_from_handle(manager)?;
+        let text = check_str(text, "text")?;
+        let zone = parse_zone(&check_str(zone, "zone")?)?;
+        let now = Date::from_millis(now)?;
+        Ok(QUICK_ADDS.insert(manager.quick_add(&text, &now, zone)?))
+    })
+}
+
+/// # Safety
+///
+/// Same as `item_list_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn quick_add_destroy(quick_add: QuickAddHandle) {
+    guard((), || QUICK_ADDS.destroy(quick_add))
+}
+
+/// Copy the created item. The returned item must be freed with `item_destroy`.
+///
+/// # Safety
+///
+/// Same as `item_get_priority`.
+#[no_mangle]
+pub unsafe extern "C" fn quick_add_get_item(quick_add: QuickAddHandle) -> ItemHandle {
+    guard(0, || {
+        let item = QUICK_ADDS.get(quick_add, |quick_add| quick_add.item.clone())?;
+        Ok(ITEMS.insert(item))
+    })
+}
+
+/// What was recognised besides the name, such as `label "groceries", priority
+/// high`, or an empty string if nothing was. Free with `toodle_string_free`.
+///
+/// # Safety
+///
+/// Same as `item_get_due_date_zone`.
+#[no_mangle]
+pub unsafe extern "C" fn quick_add_get_description(quick_add: QuickAddHandle) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let description = QUICK_ADDS.get(quick_add, |quick_add| quick_add.description())?;
+        Ok(string_to_c_char(description)?)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono_tz::America::New_York;
+
+    // Wednesday 2017-11-01, 10:30 in New York.
+    const NOW: i64 = 1509546600000;
+
+    fn parse(text: &str) -> Result<QuickAdd, QuickAddError> {
+        parse_quick_add(text, &Date::from_millis(NOW).unwrap(), New_York)
+    }
+
+    fn label(name: &str) -> Label {
+        Label { name: name.to_string(), color: DEFAULT_COLOR.to_string() }
+    }
+
+    #[test]
+    fn test_parts() {
+        let parsed = parse("Buy milk #groceries !high due fri").unwrap();
+        assert_eq!(parsed.item.name, "Buy milk");
+        assert_eq!(parsed.item.labels, vec![label("groceries")]);
+        assert_eq!(parsed.item.priority, Some(Priority::High));
+        assert_eq!(parsed.item.due_date, Date::day(2017, 11, 3));
+        assert_eq!(parsed.recognised, vec![
+            Recognised::Label("groceries".to_string()),
+            Recognised::Priority(Priority::High),
+            Recognised::DueDate("fri".to_string(), Date::day(2017, 11, 3).unwrap()),
+        ]);
+        assert_eq!(parsed.description(), "label \"groceries\", priority high, due \"fri\" (2017-11-03)");
+    }
+
+    #[test]
+    fn test_table() {
+        // Text, name, labels, priority and the due date as `Date`'s `Display` writes it.
+        let table: [(&str, &str, &[&str], Option<Priority>, Option<&str>); 16] = [
+            ("Buy milk", "Buy milk", &[], None, None),
+            ("  Buy   milk  ", "Buy milk", &[], None, None),
+            ("#home Clean the gutters", "Clean the gutters", &["home"], None, None),
+            ("Pay rent #home #money #home", "Pay rent", &["home", "money"], None, None),
+            ("File taxes !LOW", "File taxes", &[], Some(Priority::Low), None),
+            ("File taxes !med", "File taxes", &[], Some(Priority::Medium), None),
+            ("Shout !high !low", "Shout !low", &[], Some(Priority::High), None),
+            ("Hooray! !urgent", "Hooray! !urgent", &[], None, None),
+            ("Call mum due tomorrow 5pm", "Call mum", &[], None, Some("2017-11-02 21:00:00.000 America/New_York")),
+            ("Call mum Due Tomorrow at 5pm #family", "Call mum", &["family"], None, Some("2017-11-02 21:00:00.000 America/New_York")),
+            ("due next friday Return library books", "Return library books", &[], None, Some("2017-11-10")),
+            ("Report due end of month !high", "Report", &[], Some(Priority::High), Some("2017-11-30")),
+            ("Dentist due in 3 days due friday", "Dentist due friday", &[], None, Some("2017-11-04")),
+            ("Discuss due diligence", "Discuss due diligence", &[], None, None),
+            ("Pay the bill that is due", "Pay the bill that is due", &[], None, None),
+            ("# not a label", "# not a label", &[], None, None),
+        ];
+        for &(text, name, labels, priority, due_date) in table.iter() {
+            let parsed = parse(text).unwrap_or_else(|e| panic!("parsing {:?}: {}", text, e));
+            assert_eq!(parsed.item.name, name, "parsing {:?}", text);
+            assert_eq!(parsed.item.labels, labels.iter().map(|name| label(name)).collect::<Vec<_>>(), "parsing {:?}", text);
+            assert_eq!(parsed.item.priority, priority, "parsing {:?}", text);
+            assert_eq!(parsed.item.due_date.map(|date| date.to_string()), due_date.map(str::to_string), "parsing {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(""), Err(QuickAddError::NoName));
+        assert_eq!(parse("#home !high due tomorrow"), Err(QuickAddError::NoName));
+        assert_eq!(parse("Party due feb 30"), Err(QuickAddError::DueDate(DatePhraseError::NoSuchDay("feb 30".to_string()))));
+        assert_eq!(String::from(parse("Party due 25:00").unwrap_err()), "invalid time \"25:00\"");
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_list_manager_quick_add() {
+        use ListManager;
+
+        let mut manager = ListManager::new(None);
+        let home = manager.create_label("home".to_string(), "#ff0000".to_string()).unwrap();
+        let now = Date::from_millis(NOW).unwrap();
+        let added = manager.quick_add("Fix the fence #home #garden !medium due sat", &now, New_York).unwrap();
+        assert_eq!(added.item.uuid.len(), 32);
+        // Existing labels keep their colors, and missing ones are created.
+        assert_eq!(added.item.labels, vec![home, label("garden")]);
+        assert_eq!(manager.fetch_label(&"garden".to_string()), Some(label("garden")));
+        assert_eq!(manager.fetch_item(&added.item.uuid), Some(added.item.clone()));
+        assert_eq!(added.recognised.len(), 4);
+
+        assert_eq!(manager.quick_add("!low", &now, New_York).unwrap_err(), "no name given");
+        assert_eq!(manager.fetch_items().len(), 1);
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The behavior every `ListStorage` shares. Each implementation's tests call `run`
+//! with a function that makes an empty storage.
+
+use std::slice;
+
+use dates::Date;
+use items::{
+    Item,
+    Priority,
+};
+use labels::Label;
+
+use super::ListStorage;
+
+type Check = fn(&dyn ListStorage);
+
+const CHECKS: [(&'static str, Check); 9] = [
+    ("labels", check_labels),
+    ("items", check_items),
+    ("dates", check_dates),
+    ("priorities", check_priorities),
+    ("pages", check_pages),
+    ("item labels", check_item_labels),
+    ("missing labels", check_missing_labels),
+    ("update", check_update),
+    ("delete", check_delete),
+];
+
+/// Run every check, each against a new storage from `new_storage`.
+pub fn run(new_storage: &dyn Fn() -> Box<dyn ListStorage>) {
+    for &(name, check) in CHECKS.iter() {
+        debug!(check = name; "checking storage");
+        check(&*new_storage());
+    }
+}
+
+fn label(name: &str, color: &str) -> Label {
+    Label { name: name.to_string(), color: color.to_string() }
+}
+
+fn item(uuid: &str, name: &str, labels: Vec<Label>) -> Item {
+    Item::new(uuid.to_string(), name.to_string(), None, None, labels)
+}
+
+fn check_labels(storage: &dyn ListStorage) {
+    assert_eq!(storage.fetch_labels(), Ok(vec![]));
+    assert_eq!(storage.fetch_label("home"), Ok(None));
+
+    storage.create_label(&label("work", "#0000ff")).unwrap();
+    storage.create_label(&label("home", "#ff0000")).unwrap();
+    assert!(storage.create_label(&label("home", "#00ff00")).is_err());
+
+    assert_eq!(storage.fetch_label("home"), Ok(Some(label("home", "#ff0000"))));
+    assert_eq!(storage.fetch_labels(), Ok(vec![label("work", "#0000ff"), label("home", "#ff0000")]));
+}
+
+fn check_items(storage: &dyn ListStorage) {
+    assert_eq!(storage.fetch_items(), Ok(vec![]));
+    assert_eq!(storage.fetch_item("b"), Ok(None));
+
+    let first = item("b", "buy milk", vec![]);
+    let second = item("a", "call mum", vec![]);
+    storage.create_item(&first).unwrap();
+    storage.create_item(&second).unwrap();
+    assert!(storage.create_item(&item("a", "walk the dog", vec![])).is_err());
+
+    assert_eq!(storage.fetch_item("a"), Ok(Some(second.clone())));
+    assert_eq!(storage.fetch_items(), Ok(vec![first, second]));
+}
+
+fn check_dates(storage: &dyn ListStorage) {
+    let mut item = item("a", "buy milk", vec![]);
+    item.due_date = Some(Date::from_millis_in(1509883200345, "America/New_York").unwrap());
+    item.completion_date = Date::day(2017, 11, 5);
+    storage.create_item(&item).unwrap();
+    assert_eq!(storage.fetch_item("a"), Ok(Some(item.clone())));
+
+    item.due_date = Some(Date::from_millis(-86400001).unwrap());
+    item.completion_date = None;
+    storage.update_item(&item, &[]).unwrap();
+    assert_eq!(storage.fetch_item("a"), Ok(Some(item)));
+}
+
+fn check_priorities(storage: &dyn ListStorage) {
+    let home = label("home", "#ff0000");
+    storage.create_label(&home).unwrap();
+    let mut item = item("a", "buy milk", vec![home.clone()]);
+    item.priority = Some(Priority::High);
+    storage.create_item(&item).unwrap();
+    assert_eq!(storage.fetch_item("a"), Ok(Some(item.clone())));
+    assert_eq!(storage.fetch_items_with_label("home"), Ok(vec![item.clone()]));
+
+    item.priority = Some(Priority::Low);
+    storage.update_item(&item, slice::from_ref(&home)).unwrap();
+    assert_eq!(storage.fetch_items_page(0, 1), Ok(vec![item.clone()]));
+    item.priority = None;
+    storage.update_item(&item, slice::from_ref(&home)).unwrap();
+    assert_eq!(storage.fetch_items(), Ok(vec![item]));
+}
+
+fn check_pages(storage: &dyn ListStorage) {
+    assert_eq!(storage.count_items(), Ok(0));
+    assert_eq!(storage.fetch_items_page(0, 10), Ok(vec![]));
+
+    let home = label("home", "#ff0000");
+    storage.create_label(&home).unwrap();
+    for (index, uuid) in ["e", "d", "c", "b", "a"].iter().enumerate() {
+        let labels = if index % 2 == 0 { vec![home.clone()] } else { vec![] };
+        storage.create_item(&item(uuid, &format!("item {}", index), labels)).unwrap();
+    }
+    assert_eq!(storage.count_items(), Ok(5));
+
+    // Pages split up the same items, in the same order, as fetching them all.
+    let all = storage.fetch_items().unwrap();
+    assert_eq!(storage.fetch_items_page(0, 2).unwrap(), &all[0..2]);
+    assert_eq!(storage.fetch_items_page(2, 2).unwrap(), &all[2..4]);
+    assert_eq!(storage.fetch_items_page(4, 2).unwrap(), &all[4..]);
+    assert_eq!(storage.fetch_items_page(5, 2), Ok(vec![]));
+    assert_eq!(storage.fetch_items_page(1, usize::max_value()).unwrap(), &all[1..]);
+    assert_eq!(storage.fetch_items_page(0, 0), Ok(vec![]));
+    assert_eq!(storage.fetch_items_page(1, 1).unwrap()[0].labels, vec![]);
+    assert_eq!(storage.fetch_items_page(2, 1).unwrap()[0].labels, vec![home]);
+
+    storage.delete_item("c").unwrap();
+    assert_eq!(storage.count_items(), Ok(4));
+}
+
+fn check_item_labels(storage: &dyn ListStorage) {
+    let home = label("home", "#ff0000");
+    let work = label("work", "#0000ff");
+    storage.create_label(&home).unwrap();
+    storage.create_label(&work).unwrap();
+    storage.create_item(&item("a", "buy milk", vec![work.clone(), home.clone(), work.clone()])).unwrap();
+    storage.create_item(&item("b", "call mum", vec![home.clone()])).unwrap();
+    storage.create_item(&item("c", "walk the dog", vec![])).unwrap();
+
+    // Labels come back in the order they were added to the item.
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![work.clone(), home.clone()]));
+    assert_eq!(storage.fetch_labels_for_item("c"), Ok(vec![]));
+    assert_eq!(storage.fetch_labels_for_item("missing"), Ok(vec![]));
+
+    let names = |items: Result<Vec<Item>, String>| -> Vec<String> { items.unwrap().into_iter().map(|item| item.name.clone()).collect() };
+    assert_eq!(names(storage.fetch_items_with_label("home")), vec!["buy milk", "call mum"]);
+    assert_eq!(names(storage.fetch_items_with_label("work")), vec!["buy milk"]);
+    assert_eq!(names(storage.fetch_items_with_label("play")), Vec::<String>::new());
+    let with_work = storage.fetch_items_with_label("work").unwrap();
+    assert_eq!(with_work[0].labels, vec![work, home]);
+}
+
+fn check_missing_labels(storage: &dyn ListStorage) {
+    // Items refer to labels by name, and take their colors from the stored labels.
+    storage.create_label(&label("home", "#ff0000")).unwrap();
+    storage.create_item(&item("a", "buy milk", vec![label("home", "#000000"), label("play", "#00ff00")])).unwrap();
+    assert_eq!(storage.fetch_item("a").unwrap().unwrap().labels, vec![label("home", "#ff0000")]);
+
+    // A label created later turns up on the items that already named it.
+    storage.create_label(&label("play", "#00ff00")).unwrap();
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![label("home", "#ff0000"), label("play", "#00ff00")]));
+}
+
+fn check_update(storage: &dyn ListStorage) {
+    let home = label("home", "#ff0000");
+    let work = label("work", "#0000ff");
+    let play = label("play", "#00ff00");
+    for label in [&home, &work, &play].iter() {
+        storage.create_label(label).unwrap();
+    }
+    storage.create_item(&item("a", "buy milk", vec![home.clone(), work.clone()])).unwrap();
+
+    let updated = item("a", "buy oat milk", vec![work.clone(), play.clone()]);
+    storage.update_item(&updated, &[home.clone(), work.clone()]).unwrap();
+    assert_eq!(storage.fetch_item("a"), Ok(Some(updated.clone())));
+
+    // Only the difference from `existing_labels` is applied.
+    storage.update_item(&item("a", "buy milk", vec![home.clone()]), slice::from_ref(&work)).unwrap();
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![play.clone(), home.clone()]));
+
+    // Labels the item already has aren't added twice.
+    storage.update_item(&item("a", "buy milk", vec![home.clone(), play.clone()]), &[]).unwrap();
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![play, home]));
+
+    storage.update_item(&item("missing", "nothing", vec![]), &[]).unwrap();
+    assert_eq!(storage.fetch_items().unwrap().len(), 1);
+}
+
+fn check_delete(storage: &dyn ListStorage) {
+    let home = label("home", "#ff0000");
+    storage.create_label(&home).unwrap();
+    storage.create_item(&item("a", "buy milk", vec![home.clone()])).unwrap();
+    storage.create_item(&item("b", "call mum", vec![home.clone()])).unwrap();
+
+    assert_eq!(storage.delete_item("a"), Ok(true));
+    assert_eq!(storage.delete_item("a"), Ok(false));
+    assert_eq!(storage.fetch_item("a"), Ok(None));
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![]));
+    assert_eq!(storage.fetch_items_with_label("home").unwrap().len(), 1);
+    // Deleting an item leaves its labels.
+    assert_eq!(storage.fetch_labels(), Ok(vec![home.clone()]));
+
+    // A new item with a deleted item's uuid doesn't inherit its labels.
+    storage.create_item(&item("a", "buy bread", vec![])).unwrap();
+    assert_eq!(storage.fetch_labels_for_item("a"), Ok(vec![]));
+}
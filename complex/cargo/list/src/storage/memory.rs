@@ -0,0 +1,177 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Storage that lives only as long as the process, for tests and for builds
+//! without SQLite.
+
+use std::sync::Mutex;
+
+use dates::Date;
+use items::{
+    Item,
+    Priority,
+};
+use labels::Label;
+
+use super::ListStorage;
+
+/// An item as the `items` and `item_labels` tables hold it.
+#[derive(Debug, Clone)]
+struct Row {
+    uuid: String,
+    name: String,
+    due_date: Option<Date>,
+    completion_date: Option<Date>,
+    priority: Option<Priority>,
+    label_names: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    labels: Vec<Label>,
+    items: Vec<Row>,
+}
+
+impl Tables {
+    fn labels_named(&self, names: &[String]) -> Vec<Label> {
+        names.iter().filter_map(|name| self.labels.iter().find(|label| &label.name == name).cloned()).collect()
+    }
+
+    fn item(&self, row: &Row) -> Item {
+        let mut item = Item::new(row.uuid.clone(), row.name.clone(), row.due_date, row.completion_date, self.labels_named(&row.label_names));
+        item.priority = row.priority;
+        item
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl ListStorage for MemoryStorage {
+    fn create_label(&self, label: &Label) -> Result<(), String> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.labels.iter().any(|existing| existing.name == label.name) {
+            return Err(format!("label {:?} already exists", label.name));
+        }
+        tables.labels.push(label.clone());
+        Ok(())
+    }
+
+    fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> {
+        Ok(self.tables.lock().unwrap().labels.iter().find(|label| label.name == name).cloned())
+    }
+
+    fn fetch_labels(&self) -> Result<Vec<Label>, String> {
+        Ok(self.tables.lock().unwrap().labels.clone())
+    }
+
+    fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.items.iter().find(|row| row.uuid == uuid).map_or(vec![], |row| tables.labels_named(&row.label_names)))
+    }
+
+    fn create_item(&self, item: &Item) -> Result<(), String> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.items.iter().any(|row| row.uuid == item.uuid) {
+            return Err(format!("item {} already exists", item.uuid));
+        }
+        let mut label_names: Vec<String> = Vec::with_capacity(item.labels.len());
+        for label in item.labels.iter() {
+            if !label_names.contains(&label.name) {
+                label_names.push(label.name.clone());
+            }
+        }
+        tables.items.push(Row {
+            uuid: item.uuid.clone(),
+            name: item.name.clone(),
+            due_date: item.due_date,
+            completion_date: item.completion_date,
+            priority: item.priority,
+            label_names: label_names,
+        });
+        Ok(())
+    }
+
+    fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.items.iter().find(|row| row.uuid == uuid).map(|row| tables.item(row)))
+    }
+
+    fn fetch_items(&self) -> Result<Vec<Item>, String> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.items.iter().map(|row| tables.item(row)).collect())
+    }
+
+    fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.items.iter().skip(offset).take(limit).map(|row| tables.item(row)).collect())
+    }
+
+    fn count_items(&self) -> Result<usize, String> {
+        Ok(self.tables.lock().unwrap().items.len())
+    }
+
+    fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.items.iter()
+                       .filter(|row| row.label_names.iter().any(|label_name| label_name == name))
+                       .map(|row| tables.item(row))
+                       .collect())
+    }
+
+    fn update_item(&self, item: &Item, existing_labels: &[Label]) -> Result<(), String> {
+        let mut tables = self.tables.lock().unwrap();
+        let row = match tables.items.iter_mut().find(|row| row.uuid == item.uuid) {
+            Some(row) => row,
+            None => return Ok(()),
+        };
+        row.name = item.name.clone();
+        row.due_date = item.due_date;
+        row.completion_date = item.completion_date;
+        row.priority = item.priority;
+        for label in item.labels.iter() {
+            if !existing_labels.contains(label) && !row.label_names.contains(&label.name) {
+                row.label_names.push(label.name.clone());
+            }
+        }
+        for label in existing_labels.iter() {
+            if !item.labels.contains(label) {
+                row.label_names.retain(|name| name != &label.name);
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_item(&self, uuid: &str) -> Result<bool, String> {
+        let mut tables = self.tables.lock().unwrap();
+        let count = tables.items.len();
+        tables.items.retain(|row| row.uuid != uuid);
+        Ok(tables.items.len() < count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryStorage;
+    use storage::conformance;
+
+    #[test]
+    fn test_conformance() {
+        conformance::run(&|| Box::new(MemoryStorage::new()));
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Where a `ListManager` keeps its items and labels.
+//!
+//! `ListStorage` holds the rows and nothing else: uuids, default colors and the
+//! other decisions about what to store are made by `ListManager`. Items refer to
+//! their labels by name, so a fetched item always has its labels' stored colors
+//! and leaves out labels that were never created. Everything is returned in the
+//! order it was created.
+//!
+//! Every implementation passes the same conformance tests, in `conformance`.
+
+use std::fmt;
+
+use items::Item;
+use labels::Label;
+
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(test)]
+mod conformance;
+
+pub use self::memory::MemoryStorage;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStorage;
+
+pub trait ListStorage: fmt::Debug + Send + Sync {
+    /// Store `label`, failing if there is already one with its name.
+    fn create_label(&self, label: &Label) -> Result<(), String>;
+    fn fetch_label(&self, name: &str) -> Result<Option<Label>, String>;
+    fn fetch_labels(&self) -> Result<Vec<Label>, String>;
+    fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String>;
+
+    /// Store `item` under its uuid, failing if there is already one with it.
+    fn create_item(&self, item: &Item) -> Result<(), String>;
+    fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String>;
+    fn fetch_items(&self) -> Result<Vec<Item>, String>;
+    /// Up to `limit` of the items `fetch_items` returns, skipping the first `offset`.
+    fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String>;
+    fn count_items(&self) -> Result<usize, String>;
+    fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String>;
+    /// Save `item`'s name and dates, add the labels it has that aren't in
+    /// `existing_labels`, and remove those in `existing_labels` it doesn't have.
+    fn update_item(&self, item: &Item, existing_labels: &[Label]) -> Result<(), String>;
+    /// Delete the item with `uuid` and its labels. Returns false if there is none.
+    fn delete_item(&self, uuid: &str) -> Result<bool, String>;
+}
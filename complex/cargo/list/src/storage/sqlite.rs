@@ -0,0 +1,404 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Storage in the `labels`, `items` and `item_labels` tables of a SQLite store.
+//!
+//! Items are fetched with their labels in two queries however many there are: one
+//! for the items and one for all of their labels. Statements are prepared through
+//! the connection's statement cache, so repeated calls skip parsing the SQL.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use rusqlite::{
+    self,
+    Connection,
+    Row,
+};
+use rusqlite::types::ToSql;
+use store::Store;
+
+use items::Item;
+use labels::Label;
+
+use super::ListStorage;
+
+const SCHEMA: [(&'static str, &'static str); 4] = [
+    ("labels", r#"CREATE TABLE IF NOT EXISTS labels (
+                name TEXT NOT NULL PRIMARY KEY,
+                color TEXT NOT NULL
+            )"#),
+    ("items", r#"CREATE TABLE IF NOT EXISTS items (
+                uuid TEXT NOT NULL PRIMARY KEY,
+                name TEXT NOT NULL,
+                due_date DATETIME,
+                completion_date DATETIME,
+                priority INTEGER
+            )"#),
+    ("item_labels", r#"CREATE TABLE IF NOT EXISTS item_labels (
+                item_uuid TEXT NOT NULL,
+                label_name TEXT NOT NULL,
+                PRIMARY KEY(item_uuid, label_name)
+            )"#),
+    // The primary key already covers lookups by item.
+    ("item_labels_label_name", r#"CREATE INDEX IF NOT EXISTS item_labels_label_name ON item_labels (label_name)"#),
+];
+
+/// Columns added since their tables were first created, as `(table, column, type)`,
+/// which are added to databases created before them.
+const ADDED_COLUMNS: [(&'static str, &'static str, &'static str); 1] = [
+    ("items", "priority", "INTEGER"),
+];
+
+/// The columns of the items table that `query_items` reads, in order.
+const ITEM_COLUMNS: &'static str = "uuid, name, due_date, completion_date, priority";
+
+/// Enough room in the connection's statement cache for every statement here.
+const STATEMENT_CACHE_CAPACITY: usize = 32;
+
+/// Every item's labels, in the order they were added, as `item_uuid, name, color`.
+const ALL_ITEM_LABELS_SQL: &'static str = r#"SELECT item_labels.item_uuid, labels.name, labels.color
+    FROM item_labels JOIN labels ON labels.name=item_labels.label_name
+    ORDER BY item_labels.rowid"#;
+
+const ITEM_LABELS_SQL: &'static str = r#"SELECT item_labels.item_uuid, labels.name, labels.color
+    FROM item_labels JOIN labels ON labels.name=item_labels.label_name
+    WHERE item_labels.item_uuid=?
+    ORDER BY item_labels.rowid"#;
+
+/// The labels of the items on a page of `LIMIT ? OFFSET ?`.
+const PAGE_ITEM_LABELS_SQL: &'static str = r#"SELECT item_labels.item_uuid, labels.name, labels.color
+    FROM item_labels JOIN labels ON labels.name=item_labels.label_name
+    WHERE item_labels.item_uuid IN (SELECT uuid FROM items ORDER BY rowid LIMIT ? OFFSET ?)
+    ORDER BY item_labels.rowid"#;
+
+/// The labels of every item with the label named by the parameter.
+const LABELLED_ITEM_LABELS_SQL: &'static str = r#"SELECT item_labels.item_uuid, labels.name, labels.color
+    FROM item_labels JOIN labels ON labels.name=item_labels.label_name
+    WHERE item_labels.item_uuid IN (SELECT item_uuid FROM item_labels WHERE label_name=?)
+    ORDER BY item_labels.rowid"#;
+
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    store: Store,
+}
+
+impl SqliteStorage {
+    /// Storage in `store`, creating its tables and indexes if they don't exist yet.
+    pub fn new(store: Store) -> Self {
+        {
+            let conn = store.conn.lock().unwrap();
+            conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+            for &(name, sql) in SCHEMA.iter() {
+                if let Err(e) = conn.execute(sql, &[]) {
+                    error!(name = name, error:% = e; "failed to create table or index");
+                }
+            }
+            for &(table, column, column_type) in ADDED_COLUMNS.iter() {
+                if let Err(e) = add_missing_column(&conn, table, column, column_type) {
+                    error!(table = table, column = column, error:% = e; "failed to add column");
+                }
+            }
+        }
+        SqliteStorage { store: store }
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+}
+
+fn add_missing_column(conn: &Connection, table: &str, column: &str, column_type: &str) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names: rusqlite::Result<Vec<String>> = stmt.query_and_then(&[], |row| row.get_checked(1))?.collect();
+    if !names?.iter().any(|name| name == column) {
+        info!(table = table, column = column; "adding column");
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_type), &[])?;
+    }
+    Ok(())
+}
+
+fn label_from_row(row: &Row) -> rusqlite::Result<Label> {
+    Ok(Label {
+        name: row.get_checked(0)?,
+        color: row.get_checked(1)?,
+    })
+}
+
+/// The labels `sql` selects as `item_uuid, name, color`, by item.
+fn query_item_labels(conn: &Connection, sql: &str, params: &[&dyn ToSql]) -> rusqlite::Result<HashMap<String, Vec<Label>>> {
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt.query_and_then(params, |row| -> rusqlite::Result<(String, Label)> {
+        Ok((row.get_checked(0)?, Label { name: row.get_checked(1)?, color: row.get_checked(2)? }))
+    })?;
+    let mut labels: HashMap<String, Vec<Label>> = HashMap::new();
+    for row in rows {
+        let (uuid, label) = row?;
+        labels.entry(uuid).or_default().push(label);
+    }
+    Ok(labels)
+}
+
+/// The items `items_sql` selects as `ITEM_COLUMNS`, with
+/// the labels `labels_sql` selects for them. Both queries take `params`.
+fn query_items(conn: &Connection, items_sql: &str, labels_sql: &str, params: &[&dyn ToSql]) -> rusqlite::Result<Vec<Item>> {
+    let mut labels = query_item_labels(conn, labels_sql, params)?;
+    let mut stmt = conn.prepare_cached(items_sql)?;
+    let items = stmt.query_and_then(params, |row| -> rusqlite::Result<Item> {
+        let uuid: String = row.get_checked(0)?;
+        trace!(uuid = uuid.as_str(); "found item");
+        let item_labels = labels.remove(&uuid).unwrap_or_default();
+        let mut item = Item::new(uuid, row.get_checked(1)?, row.get_checked(2)?, row.get_checked(3)?, item_labels);
+        item.priority = row.get_checked(4)?;
+        Ok(item)
+    })?.collect();
+    items
+}
+
+fn add_item_labels<'a, I>(conn: &Connection, uuid: &str, labels: I) -> rusqlite::Result<()>
+where I: Iterator<Item=&'a Label> {
+    let mut stmt = conn.prepare_cached(r#"INSERT OR IGNORE INTO item_labels (item_uuid, label_name) VALUES (?, ?)"#)?;
+    for label in labels {
+        trace!(uuid = uuid, label = label.name.as_str(); "adding label to item");
+        stmt.execute(&[&uuid, &label.name])?;
+    }
+    Ok(())
+}
+
+fn remove_item_labels<'a, I>(conn: &Connection, uuid: &str, labels: I) -> rusqlite::Result<()>
+where I: Iterator<Item=&'a Label> {
+    let mut stmt = conn.prepare_cached(r#"DELETE FROM item_labels WHERE item_uuid=? AND label_name=?"#)?;
+    for label in labels {
+        trace!(uuid = uuid, label = label.name.as_str(); "removing label from item");
+        stmt.execute(&[&uuid, &label.name])?;
+    }
+    Ok(())
+}
+
+impl ListStorage for SqliteStorage {
+    fn create_label(&self, label: &Label) -> Result<(), String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = r#"INSERT INTO labels (name, color) VALUES (?1, ?2)"#;
+        conn.execute(sql, &[&label.name, &label.color]).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = r#"SELECT name, color FROM labels WHERE name=?"#;
+        let mut stmt = conn.prepare_cached(sql).map_err(|e| e.to_string())?;
+        let mut labels = stmt.query_and_then(&[&name], label_from_row).map_err(|e| e.to_string())?;
+        labels.next().map_or(Ok(None), |label| label.map(Some).map_err(|e| e.to_string()))
+    }
+
+    fn fetch_labels(&self) -> Result<Vec<Label>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = r#"SELECT name, color FROM labels ORDER BY rowid"#;
+        let mut stmt = conn.prepare_cached(sql).map_err(|e| e.to_string())?;
+        let labels: rusqlite::Result<Vec<Label>> = stmt.query_and_then(&[], label_from_row).map_err(|e| e.to_string())?.collect();
+        labels.map_err(|e| e.to_string())
+    }
+
+    fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let mut labels = query_item_labels(&conn, ITEM_LABELS_SQL, &[&uuid]).map_err(|e| e.to_string())?;
+        Ok(labels.remove(uuid).unwrap_or_default())
+    }
+
+    fn create_item(&self, item: &Item) -> Result<(), String> {
+        let mut conn = self.store.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let item_sql = format!("INSERT INTO items ({}) VALUES (?, ?, ?, ?, ?)", ITEM_COLUMNS);
+        tx.prepare_cached(&item_sql).and_then(|mut stmt| stmt.execute(&[&item.uuid, &item.name, &item.due_date, &item.completion_date, &item.priority]))
+          .map_err(|e| e.to_string())?;
+        add_item_labels(&tx, &item.uuid, item.labels.iter()).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM items WHERE uuid=?", ITEM_COLUMNS);
+        query_items(&conn, &sql, ITEM_LABELS_SQL, &[&uuid]).map(|mut items| items.pop()).map_err(|e| e.to_string())
+    }
+
+    fn fetch_items(&self) -> Result<Vec<Item>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM items ORDER BY rowid", ITEM_COLUMNS);
+        query_items(&conn, &sql, ALL_ITEM_LABELS_SQL, &[]).map_err(|e| e.to_string())
+    }
+
+    fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = format!("SELECT {} FROM items ORDER BY rowid LIMIT ? OFFSET ?", ITEM_COLUMNS);
+        // SQLite reads a negative limit as no limit at all.
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        let offset = i64::try_from(offset).map_err(|e| e.to_string())?;
+        query_items(&conn, &sql, PAGE_ITEM_LABELS_SQL, &[&limit, &offset]).map_err(|e| e.to_string())
+    }
+
+    fn count_items(&self) -> Result<usize, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = r#"SELECT count(*) FROM items"#;
+        let count: i64 = conn.query_row(sql, &[], |row| row.get(0)).map_err(|e| e.to_string())?;
+        Ok(count as usize)
+    }
+
+    fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> {
+        let conn = self.store.conn.lock().unwrap();
+        let sql = r#"SELECT uuid, name, due_date, completion_date, priority
+                     FROM items JOIN item_labels ON items.uuid=item_labels.item_uuid
+                     WHERE item_labels.label_name=?
+                     ORDER BY items.rowid"#;
+        query_items(&conn, sql, LABELLED_ITEM_LABELS_SQL, &[&name]).map_err(|e| e.to_string())
+    }
+
+    fn update_item(&self, item: &Item, existing_labels: &[Label]) -> Result<(), String> {
+        let mut conn = self.store.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let sql = r#"UPDATE items SET name=?, due_date=?, completion_date=?, priority=? WHERE uuid=?"#;
+        tx.prepare_cached(sql).and_then(|mut stmt| stmt.execute(&[&item.name, &item.due_date, &item.completion_date, &item.priority, &item.uuid]))
+          .map_err(|e| e.to_string())?;
+
+        let added = item.labels.iter().filter(|label| !existing_labels.contains(label));
+        add_item_labels(&tx, &item.uuid, added).map_err(|e| e.to_string())?;
+        let removed = existing_labels.iter().filter(|label| !item.labels.contains(label));
+        remove_item_labels(&tx, &item.uuid, removed).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn delete_item(&self, uuid: &str) -> Result<bool, String> {
+        let mut conn = self.store.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(r#"DELETE FROM item_labels WHERE item_uuid=?"#, &[&uuid]).map_err(|e| e.to_string())?;
+        let count = tx.execute(r#"DELETE FROM items WHERE uuid=?"#, &[&uuid]).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(count > 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SqliteStorage;
+
+    use std::cell::Cell;
+
+    use store::Store;
+
+    use dates::Date;
+    use items::{
+        Item,
+        Priority,
+    };
+    use labels::Label;
+    use storage::{
+        conformance,
+        ListStorage,
+    };
+
+    fn storage() -> SqliteStorage {
+        SqliteStorage::new(Store::new(None))
+    }
+
+    #[test]
+    fn test_conformance() {
+        conformance::run(&|| Box::new(storage()));
+    }
+
+    thread_local! {
+        static STATEMENTS: Cell<usize> = Cell::new(0);
+    }
+
+    fn count_statement(_sql: &str) {
+        STATEMENTS.with(|count| count.set(count.get() + 1));
+    }
+
+    /// How many statements `fetch_items` runs to fetch `count` items with a label each.
+    fn statements_fetching(count: usize) -> usize {
+        let storage = storage();
+        let label = Label { name: "home".to_string(), color: "#ff0000".to_string() };
+        storage.create_label(&label).unwrap();
+        for i in 0..count {
+            storage.create_item(&Item::new(format!("item-{}", i), "buy milk".to_string(), None, None, vec![label.clone()])).unwrap();
+        }
+        storage.store().conn.lock().unwrap().trace(Some(count_statement));
+        STATEMENTS.with(|statements| statements.set(0));
+        assert_eq!(storage.fetch_items().unwrap().len(), count);
+        STATEMENTS.with(|statements| statements.get())
+    }
+
+    #[test]
+    fn test_fetch_items_statements_dont_grow_with_items() {
+        let statements = statements_fetching(1);
+        assert!(statements > 0);
+        assert_eq!(statements_fetching(50), statements);
+    }
+
+    #[test]
+    fn test_creates_tables() {
+        let storage = storage();
+        let conn = storage.store().conn.lock().unwrap();
+        let sql = r#"SELECT count(name) FROM sqlite_master WHERE type='table' AND name=?"#;
+        for &table in ["items", "labels", "item_labels"].iter() {
+            let count: i64 = conn.query_row(sql, &[&table], |row| row.get(0)).unwrap();
+            assert_eq!(count, 1, "expected the {} table", table);
+        }
+    }
+
+    #[test]
+    fn test_label_lookups_use_index() {
+        let storage = storage();
+        let conn = storage.store().conn.lock().unwrap();
+        let sql = r#"EXPLAIN QUERY PLAN SELECT item_uuid FROM item_labels WHERE label_name=?"#;
+        let mut stmt = conn.prepare(sql).unwrap();
+        let plan: Vec<String> = stmt.query_map(&[&"home"], |row| row.get(3)).unwrap().map(|detail| detail.unwrap()).collect();
+        assert!(plan.iter().any(|detail| detail.contains("item_labels_label_name")), "{:?}", plan);
+    }
+
+    #[test]
+    fn test_fetch_dates_stored_in_seconds() {
+        let storage = storage();
+        {
+            let conn = storage.store().conn.lock().unwrap();
+            conn.execute("INSERT INTO items (uuid, name, due_date) VALUES ('old', 'old item', '2017-11-05 12:00:00')", &[]).unwrap();
+        }
+        let item = storage.fetch_item("old").unwrap().expect("expected an item");
+        assert_eq!(item.due_date, Some(Date::from_millis(1509883200000).unwrap()));
+        assert_eq!(item.completion_date, None);
+    }
+
+    #[test]
+    fn test_adds_priority_to_old_databases() {
+        let store = Store::new(None);
+        {
+            let conn = store.conn.lock().unwrap();
+            conn.execute("CREATE TABLE items (uuid TEXT NOT NULL PRIMARY KEY, name TEXT NOT NULL, due_date DATETIME, completion_date DATETIME)", &[]).unwrap();
+            conn.execute("INSERT INTO items (uuid, name) VALUES ('old', 'old item')", &[]).unwrap();
+        }
+        let storage = SqliteStorage::new(store);
+        let mut item = storage.fetch_item("old").unwrap().expect("expected an item");
+        assert_eq!(item.priority, None);
+        item.priority = Some(Priority::High);
+        storage.update_item(&item, &[]).unwrap();
+        assert_eq!(storage.fetch_item("old").unwrap().and_then(|item| item.priority), Some(Priority::High));
+        // Opening it again leaves the column alone.
+        let storage = SqliteStorage::new(storage.store().clone());
+        assert_eq!(storage.fetch_items().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_unreadable_dates_are_errors() {
+        let storage = storage();
+        {
+            let conn = storage.store().conn.lock().unwrap();
+            conn.execute("INSERT INTO items (uuid, name, due_date) VALUES ('bad', 'bad item', 'next tuesday')", &[]).unwrap();
+        }
+        assert!(storage.fetch_item("bad").is_err());
+        assert!(storage.fetch_items().is_err());
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Reading and writing the todo.txt format (https://github.com/todotxt/todo.txt).
+//!
+//! A line maps onto an `Item` as follows:
+//!
+//! * a leading `x` and the date after it become the completion date. Items can't be
+//!   completed without a date, so a bare `x` is taken to mean today;
+//! * a leading `(A)`, `(B)` or `(C)` becomes a high, medium or low priority, as does
+//!   a `pri:A`-style tag, which is how completed items keep theirs;
+//! * a `due:YYYY-MM-DD` tag becomes the due date;
+//! * `+project` becomes a label named `project` and `@context` a label named `@context`;
+//! * what remains of the description becomes the name.
+//!
+//! Items have no creation date or priorities below `(C)`, and todo.txt dates are
+//! whole days, so those and the times of timestamps are dropped and listed in the
+//! accompanying report. Dates are read as all-day dates, and timestamps are written
+//! as the day they fall on in their time zone. Other `key:value` tags are kept in
+//! the name so they survive a round trip.
+//!
+//! todo.txt has no way to escape its markers, so a name such as `x marks the spot`
+//! or `email bob@example.com +1` is written as it is and listed in the report, as
+//! reading it back would turn part of it into other fields.
+
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use dates::Date;
+use items::{
+    Item,
+    Priority,
+};
+use labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+
+const DATE_FORMAT: &'static str = "%Y-%m-%d";
+
+/// Something about an item that a line couldn't carry faithfully.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DroppedField {
+    /// A `(D)`-style priority below the lowest an item can have.
+    Priority(char),
+    /// The creation date that follows the priority or completion date.
+    CreationDate(String),
+    /// The time of day of a due or completion date, which todo.txt can't express.
+    TimeOfDay(Date),
+    /// A label whose name contains whitespace and so can't be written as a tag.
+    Label(String),
+    /// A name that would be read back differently, because it starts with a marker
+    /// such as `x`, `(A)` or a date, has words such as `+project`, `@context`,
+    /// `due:` or `pri:` tags, or has runs of whitespace.
+    Name(String),
+    /// A bare `x` without a completion date, which was given the day of the import.
+    CompletionDate,
+}
+
+impl fmt::Display for DroppedField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DroppedField::Priority(priority) => write!(f, "left out priority ({})", priority),
+            DroppedField::CreationDate(ref date) => write!(f, "left out creation date {}", date),
+            DroppedField::TimeOfDay(ref date) => write!(f, "left out time of day of {}", format_date(date)),
+            DroppedField::Label(ref name) => write!(f, "left out label {:?}", name),
+            DroppedField::Name(ref name) => write!(f, "name {:?} won't read back the same", name),
+            DroppedField::CompletionDate => write!(f, "no completion date, so used the day of the import"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dropped {
+    /// 1-based line in the todo.txt text the field was read from or written to.
+    pub line: usize,
+    pub field: DroppedField,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoTxtImport {
+    pub items: Vec<Item>,
+    pub dropped: Vec<Dropped>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoTxtExport {
+    pub text: String,
+    pub dropped: Vec<Dropped>,
+}
+
+/// Parse every non-blank line of `text` into an item. Labels are created with the
+/// default color; it is up to the caller to reconcile them with existing labels.
+pub fn parse_todo_txt(text: &str) -> TodoTxtImport {
+    let mut import = TodoTxtImport { items: vec![], dropped: vec![] };
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut dropped = Vec::new();
+        import.items.push(parse_line(line, &mut dropped));
+        import.dropped.extend(dropped.into_iter().map(|field| Dropped { line: index + 1, field: field }));
+    }
+    import
+}
+
+/// Write one line per item.
+pub fn write_todo_txt(items: &[Item]) -> TodoTxtExport {
+    let mut export = TodoTxtExport { text: String::new(), dropped: vec![] };
+    for (index, item) in items.iter().enumerate() {
+        let mut dropped = Vec::new();
+        export.text.push_str(&write_line(item, &mut dropped));
+        export.text.push('\n');
+        export.dropped.extend(dropped.into_iter().map(|field| Dropped { line: index + 1, field: field }));
+    }
+    export
+}
+
+fn parse_line(line: &str, dropped: &mut Vec<DroppedField>) -> Item {
+    let mut item = Item::new("".to_string(), "".to_string(), None, None, vec![]);
+    let mut tokens = line.split_whitespace().peekable();
+
+    if tokens.peek() == Some(&"x") {
+        tokens.next();
+        item.completion_date = match tokens.peek().and_then(|token| parse_date(token)) {
+            Some(date) => {
+                tokens.next();
+                Some(date)
+            },
+            // Completed without saying when; today is the best we can do, and
+            // unlike now it doesn't make up a time of day.
+            None => {
+                dropped.push(DroppedField::CompletionDate);
+                Some(Date::today())
+            },
+        };
+    } else if let Some(letter) = tokens.peek().and_then(|token| parse_priority(token)) {
+        tokens.next();
+        match priority_from_letter(letter) {
+            Some(priority) => item.priority = Some(priority),
+            None => dropped.push(DroppedField::Priority(letter)),
+        }
+    }
+    if let Some(date) = tokens.peek().map(|token| token.to_string()) {
+        if parse_date(&date).is_some() {
+            tokens.next();
+            dropped.push(DroppedField::CreationDate(date));
+        }
+    }
+
+    let mut words: Vec<&str> = Vec::new();
+    for token in tokens {
+        if token.len() > 1 && token.starts_with('+') {
+            add_label(&mut item, &token[1..]);
+        } else if token.len() > 1 && token.starts_with('@') {
+            add_label(&mut item, token);
+        } else if token.starts_with("due:") && parse_date(&token[4..]).is_some() {
+            item.due_date = parse_date(&token[4..]);
+        } else if let Some(priority) = parse_priority_tag(token).filter(|_| item.priority.is_none()) {
+            item.priority = Some(priority);
+        } else {
+            words.push(token);
+        }
+    }
+    item.name = words.join(" ");
+    item
+}
+
+fn write_line(item: &Item, dropped: &mut Vec<DroppedField>) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(ref date) = item.completion_date {
+        parts.push("x".to_string());
+        parts.push(format_date(date));
+        check_whole_day(date, dropped);
+    } else if let Some(priority) = item.priority {
+        parts.push(format!("({})", priority_letter(priority)));
+    }
+    parts.push(item.name.clone());
+    for label in item.labels.iter() {
+        if label.name.is_empty() || label.name.contains(char::is_whitespace) {
+            dropped.push(DroppedField::Label(label.name.clone()));
+        } else if label.name.starts_with('@') {
+            parts.push(label.name.clone());
+        } else {
+            parts.push(format!("+{}", label.name));
+        }
+    }
+    if let Some(ref date) = item.due_date {
+        parts.push(format!("due:{}", format_date(date)));
+        check_whole_day(date, dropped);
+    }
+    // A completed line can't start with a priority, so it goes in a tag instead.
+    if let (Some(_), Some(priority)) = (item.completion_date, item.priority) {
+        parts.push(format!("pri:{}", priority_letter(priority)));
+    }
+    let line = parts.join(" ");
+    if parse_line(&line, &mut vec![]).name != item.name {
+        dropped.push(DroppedField::Name(item.name.clone()));
+    }
+    line
+}
+
+fn add_label(item: &mut Item, name: &str) {
+    if !item.labels.iter().any(|label| label.name == name) {
+        item.labels.push(Label { name: name.to_string(), color: DEFAULT_COLOR.to_string() });
+    }
+}
+
+fn check_whole_day(date: &Date, dropped: &mut Vec<DroppedField>) {
+    if !date.is_all_day() {
+        dropped.push(DroppedField::TimeOfDay(*date));
+    }
+}
+
+fn parse_priority(token: &str) -> Option<char> {
+    let bytes = token.as_bytes();
+    if bytes.len() == 3 && bytes[0] == b'(' && bytes[2] == b')' && (bytes[1] as char).is_ascii_uppercase() {
+        Some(bytes[1] as char)
+    } else {
+        None
+    }
+}
+
+fn priority_from_letter(letter: char) -> Option<Priority> {
+    match letter {
+        'A' => Some(Priority::High),
+        'B' => Some(Priority::Medium),
+        'C' => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+fn priority_letter(priority: Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+/// The priority of a `pri:A`-style tag. Letters below `C` are left in the name with
+/// the other tags.
+fn parse_priority_tag(token: &str) -> Option<Priority> {
+    let mut letters = token.strip_prefix("pri:")?.chars();
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) => priority_from_letter(letter),
+        _ => None,
+    }
+}
+
+fn parse_date(token: &str) -> Option<Date> {
+    let valid = token.len() == 10 && token.char_indices().all(|(index, c)| {
+        match index {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        }
+    });
+    if !valid {
+        return None;
+    }
+    NaiveDate::parse_from_str(token, DATE_FORMAT).ok().map(Date::AllDay)
+}
+
+fn format_date(date: &Date) -> String {
+    date.calendar_day().format(DATE_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use items::Item;
+    use labels::Label;
+
+    fn label(name: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            color: DEFAULT_COLOR.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let import = parse_todo_txt("(A) 2017-11-01 Call @phone Mom about +GarageSale due:2017-11-05 t:2017-11-03\n\
+                                     \n\
+                                     x 2017-11-04 2017-11-01 Pay rent +home pri:B\n\
+                                     x Water plants\n\
+                                     (D) Someday pri:C\n");
+        assert_eq!(import.items.len(), 4);
+
+        let item = &import.items[0];
+        assert_eq!(item.name, "Call Mom about t:2017-11-03");
+        assert_eq!(item.due_date, Date::day(2017, 11, 5));
+        assert_eq!(item.completion_date, None);
+        assert_eq!(item.labels, vec![label("@phone"), label("GarageSale")]);
+        assert_eq!(item.priority, Some(Priority::High));
+
+        let item = &import.items[1];
+        assert_eq!(item.name, "Pay rent");
+        assert_eq!(item.completion_date, Date::day(2017, 11, 4));
+        assert_eq!(item.labels, vec![label("home")]);
+        assert_eq!(item.priority, Some(Priority::Medium));
+
+        match import.items[2].completion_date {
+            Some(Date::AllDay(_)) => {},
+            ref date => panic!("expected an all-day completion date, got {:?}", date),
+        }
+        assert_eq!(write_todo_txt(&import.items[2..3]).dropped, vec![]);
+        assert_eq!(import.items[2].name, "Water plants");
+        assert_eq!(import.items[2].priority, None);
+
+        // `(D)` is dropped, though the tag still gives the item a priority.
+        assert_eq!(import.items[3].name, "Someday");
+        assert_eq!(import.items[3].priority, Some(Priority::Low));
+
+        assert_eq!(import.dropped, vec![
+            Dropped { line: 1, field: DroppedField::CreationDate("2017-11-01".to_string()) },
+            Dropped { line: 3, field: DroppedField::CreationDate("2017-11-01".to_string()) },
+            Dropped { line: 4, field: DroppedField::CompletionDate },
+            Dropped { line: 5, field: DroppedField::Priority('D') },
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_markers_stay_in_name() {
+        let import = parse_todo_txt("(a) x due:someday + @ 2017-13-45 pri:a pri:AB\n");
+        assert_eq!(import.items[0].name, "(a) x due:someday + @ 2017-13-45 pri:a pri:AB");
+        assert_eq!(import.items[0].priority, None);
+        assert_eq!(import.items[0].labels, vec![]);
+        assert_eq!(import.dropped, vec![]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut items = vec![
+            Item::new("".to_string(), "Buy milk".to_string(), Date::day(2017, 11, 5), None, vec![label("groceries"), label("@shop")]),
+            Item::new("".to_string(), "Pay rent rec:1m".to_string(), None, Date::day(2017, 11, 4), vec![]),
+            Item::new("".to_string(), "Call mum".to_string(), None, None, vec![]),
+            Item::new("".to_string(), "File taxes".to_string(), None, Date::day(2017, 11, 6), vec![]),
+        ];
+        items[2].priority = Some(Priority::Medium);
+        items[3].priority = Some(Priority::Low);
+        let export = write_todo_txt(&items);
+        assert_eq!(export.text, "Buy milk +groceries @shop due:2017-11-05\n\
+                                 x 2017-11-04 Pay rent rec:1m\n\
+                                 (B) Call mum\n\
+                                 x 2017-11-06 File taxes pri:C\n");
+        assert_eq!(export.dropped, vec![]);
+
+        let import = parse_todo_txt(&export.text);
+        assert_eq!(import.items, items);
+        assert_eq!(import.dropped, vec![]);
+        assert_eq!(write_todo_txt(&import.items).text, export.text);
+    }
+
+    #[test]
+    fn test_export_reports_names_that_read_back_differently() {
+        let names = [
+            "x marks the spot",
+            "(A) grade paper",
+            "2017-11-01 retrospective",
+            "email bob +1",
+            "ask @team",
+            "move due:2017-11-05 meeting",
+            "sort pri:A items",
+            "two  spaces",
+        ];
+        let mut items: Vec<Item> = names.iter().map(|name| Item::new("".to_string(), name.to_string(), None, None, vec![])).collect();
+        // Even behind a priority or completion date, a leading date or a tag is misread.
+        items[2].priority = Some(Priority::High);
+        items[3].completion_date = Date::day(2017, 11, 4);
+        let export = write_todo_txt(&items);
+        let expected: Vec<Dropped> = names.iter().enumerate().map(|(index, name)| {
+            Dropped { line: index + 1, field: DroppedField::Name(name.to_string()) }
+        }).collect();
+        assert_eq!(export.dropped, expected);
+        // The names are still written as they are.
+        assert!(export.text.starts_with("x marks the spot\n(A) grade paper\n(A) 2017-11-01 retrospective\n"));
+
+        // Markers that are only special at the start of a line, or that a priority
+        // already overrides, read back fine.
+        let mut items = vec![
+            Item::new("".to_string(), "fix the x (A) 2017-11-01 bug".to_string(), None, None, vec![]),
+            Item::new("".to_string(), "x out pri:A".to_string(), None, None, vec![]),
+            Item::new("".to_string(), "a+b c@d due:soon".to_string(), None, None, vec![]),
+        ];
+        items[1].priority = Some(Priority::Low);
+        let export = write_todo_txt(&items);
+        assert_eq!(export.dropped, vec![]);
+        assert_eq!(parse_todo_txt(&export.text).items, items);
+    }
+
+    #[test]
+    fn test_export_reports_dropped_fields() {
+        let due = Date::from_millis_in(1509933600000, "America/New_York").ok();
+        let items = vec![
+            Item::new("".to_string(), "first".to_string(), None, None, vec![]),
+            Item::new("".to_string(), "second".to_string(), due, None, vec![label("two words"), label("ok")]),
+        ];
+        let export = write_todo_txt(&items);
+        // 02:00 UTC on the 6th is still the 5th in New York.
+        assert_eq!(export.text, "first\nsecond +ok due:2017-11-05\n");
+        assert_eq!(export.dropped, vec![
+            Dropped { line: 2, field: DroppedField::Label("two words".to_string()) },
+            Dropped { line: 2, field: DroppedField::TimeOfDay(due.unwrap()) },
+        ]);
+    }
+}
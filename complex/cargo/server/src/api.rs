@@ -0,0 +1,704 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The REST API, independent of the HTTP server that carries it.
+//!
+//! ```text
+//! GET    /items                    ?label=NAME&status=open|done|all&q=TEXT
+//!                                  &due_before=MILLIS&due_after=MILLIS
+//! POST   /items                    {"name", "due_date", "completion_date", "priority", "labels"}
+//! GET    /items/UUID
+//! PUT    /items/UUID               replaces every field; missing ones are cleared
+//! PATCH  /items/UUID               replaces the fields given
+//! DELETE /items/UUID
+//! GET    /items/UUID/labels
+//! PUT    /items/UUID/labels/NAME   adds the label to the item
+//! DELETE /items/UUID/labels/NAME   removes it
+//! GET    /labels
+//! POST   /labels                   {"name", "color"}
+//! GET    /labels/NAME
+//! GET    /labels/NAME/items
+//! ```
+//!
+//! Items refer to labels by name, and labels that don't exist yet are created
+//! with the default color. Items and lists carry an `ETag`; `If-None-Match` turns a
+//! fetch of an unchanged resource into a 304, and `If-Match` makes a change to an
+//! item fail with a 412 if the item has changed since it was fetched. As the tags
+//! are strong, `If-Match` never matches a weak `W/` tag. Storage failures, whether
+//! reading or saving, are a 500.
+
+use serde_json::Value;
+
+use list::dates::DateError;
+use list::items::Item;
+use list::labels::{
+    Label,
+    DEFAULT_COLOR,
+};
+use list::ListManager;
+
+use json::{
+    date_field,
+    item_to_json,
+    label_to_json,
+    names_field,
+    parse_object,
+    priority_field,
+    string_field,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request<'a> {
+    pub method: &'a str,
+    /// The path, still percent-encoded, followed by any query string.
+    pub url: &'a str,
+    pub if_match: Option<&'a str>,
+    pub if_none_match: Option<&'a str>,
+    pub body: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Option<Value>,
+    pub etag: Option<String>,
+    pub location: Option<String>,
+    /// The methods a path supports, sent with 405s.
+    pub allow: Option<&'static str>,
+}
+
+impl Response {
+    fn new(status: u16, body: Option<Value>) -> Response {
+        Response { status: status, body: body, etag: None, location: None, allow: None }
+    }
+
+    /// A 200 tagged with the ETag of `body`, or a 304 if the client already has it.
+    fn tagged(request: &Request, body: Value) -> Response {
+        let etag = etag(&body);
+        let mut response = if request.if_none_match.is_some_and(|tags| matches_tag(tags, &etag, true)) {
+            Response::new(304, None)
+        } else {
+            Response::new(200, Some(body))
+        };
+        response.etag = Some(etag);
+        response
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// A malformed body or query. (400)
+    BadRequest(String),
+    /// No such item, label or route. (404)
+    NotFound(String),
+    /// A method the path doesn't support, and the ones it does. (405)
+    MethodNotAllowed(&'static str),
+    /// A label that already exists. (409)
+    Conflict(String),
+    /// An `If-Match` that doesn't match the item. (412)
+    PreconditionFailed(String),
+    /// A body larger than the server accepts. (413)
+    PayloadTooLarge,
+    /// Well-formed values the library rejects, like unknown time zones. (422)
+    Unprocessable(String),
+    /// A failure reading from or saving to the storage. (500)
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match *self {
+            ApiError::BadRequest(_) => 400,
+            ApiError::NotFound(_) => 404,
+            ApiError::MethodNotAllowed(_) => 405,
+            ApiError::Conflict(_) => 409,
+            ApiError::PreconditionFailed(_) => 412,
+            ApiError::PayloadTooLarge => 413,
+            ApiError::Unprocessable(_) => 422,
+            ApiError::Internal(_) => 500,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            ApiError::BadRequest(ref message) |
+            ApiError::NotFound(ref message) |
+            ApiError::Conflict(ref message) |
+            ApiError::PreconditionFailed(ref message) |
+            ApiError::Unprocessable(ref message) |
+            ApiError::Internal(ref message) => message.clone(),
+            ApiError::MethodNotAllowed(allow) => format!("method not allowed; use {}", allow),
+            ApiError::PayloadTooLarge => "body is too large".to_string(),
+        }
+    }
+
+    /// The same error about the field `key`.
+    pub fn in_field(self, key: &str) -> ApiError {
+        let prefix = |message: String| format!("{}: {}", key, message);
+        match self {
+            ApiError::BadRequest(message) => ApiError::BadRequest(prefix(message)),
+            ApiError::NotFound(message) => ApiError::NotFound(prefix(message)),
+            ApiError::Conflict(message) => ApiError::Conflict(prefix(message)),
+            ApiError::PreconditionFailed(message) => ApiError::PreconditionFailed(prefix(message)),
+            ApiError::Unprocessable(message) => ApiError::Unprocessable(prefix(message)),
+            ApiError::Internal(message) => ApiError::Internal(prefix(message)),
+            error @ ApiError::MethodNotAllowed(_) |
+            error @ ApiError::PayloadTooLarge => error,
+        }
+    }
+
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new(self.status(), Some(json!({ "error": self.message() })));
+        if let ApiError::MethodNotAllowed(allow) = self {
+            response.allow = Some(allow);
+        }
+        response
+    }
+}
+
+impl From<DateError> for ApiError {
+    fn from(error: DateError) -> ApiError {
+        ApiError::Unprocessable(error.to_string())
+    }
+}
+
+/// Carry out `request` against `manager`.
+pub fn handle(manager: &mut ListManager, request: &Request) -> Response {
+    route(manager, request).unwrap_or_else(ApiError::into_response)
+}
+
+fn route(manager: &mut ListManager, request: &Request) -> Result<Response, ApiError> {
+    let (path, query) = match request.url.find('?') {
+        Some(index) => (&request.url[..index], &request.url[index + 1..]),
+        None => (request.url, ""),
+    };
+    let segments = path.trim_matches('/').split('/').map(percent_decode).collect::<Result<Vec<String>, ApiError>>()?;
+    let segments: Vec<&str> = segments.iter().map(|segment| segment.as_str()).collect();
+
+    match (segments.as_slice(), request.method) {
+        (["items"], "GET") => list_items(manager, request, &parse_query(query)?),
+        (["items"], "POST") => create_item(manager, request),
+        (["items"], _) => Err(ApiError::MethodNotAllowed("GET, POST")),
+        (["items", uuid], "GET") => Ok(Response::tagged(request, item_to_json(&find_item(manager, uuid)?))),
+        (["items", uuid], "PUT") => update_item(manager, request, uuid, false),
+        (["items", uuid], "PATCH") => update_item(manager, request, uuid, true),
+        (["items", uuid], "DELETE") => delete_item(manager, request, uuid),
+        (["items", _], _) => Err(ApiError::MethodNotAllowed("GET, PUT, PATCH, DELETE")),
+        (["items", uuid, "labels"], "GET") => {
+            let labels = find_item(manager, uuid)?.labels.clone();
+            Ok(Response::tagged(request, Value::Array(labels.iter().map(label_to_json).collect())))
+        },
+        (["items", _, "labels"], _) => Err(ApiError::MethodNotAllowed("GET")),
+        (["items", uuid, "labels", name], "PUT") => change_labels(manager, request, uuid, name, true),
+        (["items", uuid, "labels", name], "DELETE") => change_labels(manager, request, uuid, name, false),
+        (["items", _, "labels", _], _) => Err(ApiError::MethodNotAllowed("PUT, DELETE")),
+        (["labels"], "GET") => {
+            let labels = manager.storage().fetch_labels().map_err(ApiError::Internal)?;
+            Ok(Response::tagged(request, Value::Array(labels.iter().map(label_to_json).collect())))
+        },
+        (["labels"], "POST") => create_label(manager, request),
+        (["labels"], _) => Err(ApiError::MethodNotAllowed("GET, POST")),
+        (["labels", name], "GET") => Ok(Response::tagged(request, label_to_json(&find_label(manager, name)?))),
+        (["labels", _], _) => Err(ApiError::MethodNotAllowed("GET")),
+        (["labels", name, "items"], "GET") => {
+            let label = find_label(manager, name)?;
+            let items = manager.storage().fetch_items_with_label(&label.name).map_err(ApiError::Internal)?;
+            Ok(Response::tagged(request, items_to_json(&items)))
+        },
+        (["labels", _, "items"], _) => Err(ApiError::MethodNotAllowed("GET")),
+        _ => Err(ApiError::NotFound(format!("no such resource {}", path))),
+    }
+}
+
+fn list_items(manager: &mut ListManager, request: &Request, query: &[(String, String)]) -> Result<Response, ApiError> {
+    let mut label = None;
+    let mut status = "all".to_string();
+    let mut text = None;
+    let mut due_before = None;
+    let mut due_after = None;
+    for (key, value) in query.iter() {
+        match key.as_str() {
+            "label" => label = Some(find_label(manager, value)?),
+            "status" if ["open", "done", "all"].contains(&value.as_str()) => status = value.clone(),
+            "status" => return Err(ApiError::BadRequest(format!("status must be open, done or all, not {:?}", value))),
+            "q" => text = Some(value.to_lowercase()),
+            "due_before" => due_before = Some(millis_param(key, value)?),
+            "due_after" => due_after = Some(millis_param(key, value)?),
+            _ => return Err(ApiError::BadRequest(format!("unknown query parameter {:?}", key))),
+        }
+    }
+    let items = match label {
+        Some(label) => manager.storage().fetch_items_with_label(&label.name),
+        None => manager.storage().fetch_items(),
+    }.map_err(ApiError::Internal)?;
+    let items: Vec<Item> = items.into_iter().filter(|item| {
+        let due = item.due_date.map(|date| date.millis());
+        (status == "all" || (status == "done") == item.completion_date.is_some()) &&
+            text.as_ref().is_none_or(|text| item.name.to_lowercase().contains(text)) &&
+            due_before.is_none_or(|before| due.is_some_and(|due| due < before)) &&
+            due_after.is_none_or(|after| due.is_some_and(|due| due >= after))
+    }).collect();
+    Ok(Response::tagged(request, items_to_json(&items)))
+}
+
+fn create_item(manager: &mut ListManager, request: &Request) -> Result<Response, ApiError> {
+    let body = parse_object(request.body)?;
+    let name = string_field(&body, "name")?.ok_or_else(|| ApiError::BadRequest("name is missing".to_string()))?;
+    let labels = labels_named(manager, &names_field(&body, "labels")?.unwrap_or_default())?;
+    let mut item = Item::new("".to_string(), name, flatten(date_field(&body, "due_date")?), flatten(date_field(&body, "completion_date")?), labels);
+    item.priority = flatten(priority_field(&body, "priority")?);
+    let uuid = manager.create_item(&item).map_err(ApiError::Internal)?;
+    let mut response = Response::tagged(request, item_to_json(&find_item(manager, &uuid)?));
+    response.status = 201;
+    response.location = Some(format!("/items/{}", uuid));
+    Ok(response)
+}
+
+/// Apply the fields in the body to the item, leaving out missing ones if `partial`
+/// and clearing them otherwise.
+fn update_item(manager: &mut ListManager, request: &Request, uuid: &str, partial: bool) -> Result<Response, ApiError> {
+    let mut item = find_item(manager, uuid)?;
+    check_if_match(request, &item)?;
+    let body = parse_object(request.body)?;
+    let existing_labels = item.labels.clone();
+
+    match string_field(&body, "name")? {
+        Some(name) => item.name = name,
+        None if !partial => return Err(ApiError::BadRequest("name is missing".to_string())),
+        None => {},
+    }
+    match date_field(&body, "due_date")? {
+        Some(due_date) => item.due_date = due_date,
+        None if !partial => item.due_date = None,
+        None => {},
+    }
+    match date_field(&body, "completion_date")? {
+        Some(completion_date) => item.completion_date = completion_date,
+        None if !partial => item.completion_date = None,
+        None => {},
+    }
+    match priority_field(&body, "priority")? {
+        Some(priority) => item.priority = priority,
+        None if !partial => item.priority = None,
+        None => {},
+    }
+    match names_field(&body, "labels")? {
+        Some(names) => item.labels = labels_named(manager, &names)?,
+        None if !partial => item.labels = vec![],
+        None => {},
+    }
+    manager.update_item(&item, existing_labels).map_err(ApiError::Internal)?;
+    Ok(Response::tagged(request, item_to_json(&find_item(manager, uuid)?)))
+}
+
+fn delete_item(manager: &mut ListManager, request: &Request, uuid: &str) -> Result<Response, ApiError> {
+    let item = find_item(manager, uuid)?;
+    check_if_match(request, &item)?;
+    manager.delete_item(&item.uuid).map_err(ApiError::Internal)?;
+    Ok(Response::new(204, None))
+}
+
+/// Add the label called `name` to the item, or remove it.
+fn change_labels(manager: &mut ListManager, request: &Request, uuid: &str, name: &str, add: bool) -> Result<Response, ApiError> {
+    let mut item = find_item(manager, uuid)?;
+    check_if_match(request, &item)?;
+    let existing_labels = item.labels.clone();
+    let has_label = item.labels.iter().any(|label| label.name == name);
+    if add && !has_label {
+        item.labels.extend(labels_named(manager, &[name.to_string()])?);
+    } else if !add && has_label {
+        item.labels.retain(|label| label.name != name);
+    } else if !add {
+        return Err(ApiError::NotFound(format!("item doesn't have label {:?}", name)));
+    }
+    manager.update_item(&item, existing_labels).map_err(ApiError::Internal)?;
+    Ok(Response::tagged(request, item_to_json(&find_item(manager, uuid)?)))
+}
+
+fn create_label(manager: &mut ListManager, request: &Request) -> Result<Response, ApiError> {
+    let body = parse_object(request.body)?;
+    let name = string_field(&body, "name")?.ok_or_else(|| ApiError::BadRequest("name is missing".to_string()))?;
+    if name.is_empty() {
+        return Err(ApiError::Unprocessable("name is empty".to_string()));
+    }
+    let color = string_field(&body, "color")?.unwrap_or_else(|| DEFAULT_COLOR.to_string());
+    let label = Label { name: name.clone(), color: color };
+    if !save_label(manager, &label)? {
+        return Err(ApiError::Conflict(format!("label {:?} already exists", name)));
+    }
+    let mut response = Response::tagged(request, label_to_json(&label));
+    response.status = 201;
+    response.location = Some(format!("/labels/{}", percent_encode(&name)));
+    Ok(response)
+}
+
+fn find_item(manager: &mut ListManager, uuid: &str) -> Result<Item, ApiError> {
+    manager.storage().fetch_item(uuid).map_err(ApiError::Internal)?
+           .ok_or_else(|| ApiError::NotFound(format!("no item {}", uuid)))
+}
+
+fn find_label(manager: &ListManager, name: &str) -> Result<Label, ApiError> {
+    manager.storage().fetch_label(name).map_err(ApiError::Internal)?
+           .ok_or_else(|| ApiError::NotFound(format!("no label {:?}", name)))
+}
+
+/// The labels called `names`, creating any that don't exist with the default color.
+fn labels_named(manager: &ListManager, names: &[String]) -> Result<Vec<Label>, ApiError> {
+    let mut labels: Vec<Label> = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        if name.is_empty() {
+            return Err(ApiError::Unprocessable("labels: name is empty".to_string()));
+        }
+        if labels.iter().any(|label| &label.name == name) {
+            continue;
+        }
+        let label = match manager.storage().fetch_label(name).map_err(ApiError::Internal)? {
+            Some(label) => label,
+            None => {
+                let label = Label { name: name.clone(), color: DEFAULT_COLOR.to_string() };
+                // Another request may have created it in the meantime.
+                if save_label(manager, &label)? { label } else { find_label(manager, name)? }
+            },
+        };
+        labels.push(label);
+    }
+    Ok(labels)
+}
+
+/// Store `label`, returning false if there is already a label with its name.
+fn save_label(manager: &ListManager, label: &Label) -> Result<bool, ApiError> {
+    match manager.storage().create_label(label) {
+        Ok(()) => Ok(true),
+        Err(e) => match manager.storage().fetch_label(&label.name) {
+            Ok(Some(_)) => Ok(false),
+            _ => Err(ApiError::Internal(e)),
+        },
+    }
+}
+
+fn check_if_match(request: &Request, item: &Item) -> Result<(), ApiError> {
+    match request.if_match {
+        Some(tags) if !matches_tag(tags, &etag(&item_to_json(item)), false) => {
+            Err(ApiError::PreconditionFailed(format!("item {} has changed", item.uuid)))
+        },
+        _ => Ok(()),
+    }
+}
+
+fn items_to_json(items: &[Item]) -> Value {
+    Value::Array(items.iter().map(item_to_json).collect())
+}
+
+fn flatten<T>(value: Option<Option<T>>) -> Option<T> {
+    value.and_then(|value| value)
+}
+
+/// A strong ETag for `body`: the 64-bit FNV-1a hash of its JSON, which stays the
+/// same across restarts.
+fn etag(body: &Value) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in body.to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{:016x}\"", hash)
+}
+
+/// Whether the `If-Match` or `If-None-Match` header value `tags` includes `etag`.
+/// `If-None-Match` compares tags weakly, ignoring a `W/` prefix, while `If-Match`
+/// needs a strong match (RFC 7232, section 2.3.2).
+fn matches_tag(tags: &str, etag: &str, weak: bool) -> bool {
+    tags.split(',').map(|tag| tag.trim()).any(|tag| {
+        tag == "*" || tag == etag || (weak && tag.strip_prefix("W/") == Some(etag))
+    })
+}
+
+fn millis_param(key: &str, value: &str) -> Result<i64, ApiError> {
+    value.parse().map_err(|_| ApiError::BadRequest(format!("{} must be milliseconds, not {:?}", key, value)))
+}
+
+fn parse_query(query: &str) -> Result<Vec<(String, String)>, ApiError> {
+    query.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+        let (key, value) = match pair.find('=') {
+            Some(index) => (&pair[..index], &pair[index + 1..]),
+            None => (pair, ""),
+        };
+        Ok((percent_decode(&key.replace('+', " "))?, percent_decode(&value.replace('+', " "))?))
+    }).collect()
+}
+
+fn percent_decode(text: &str) -> Result<String, ApiError> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let byte = text.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| ApiError::BadRequest(format!("bad percent-encoding in {:?}", text)))?;
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| ApiError::BadRequest(format!("{:?} isn't UTF-8 once decoded", text)))
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use list::storage::{
+        ListStorage,
+        MemoryStorage,
+    };
+
+    /// Storage that reads from `MemoryStorage` but fails to save any item.
+    #[derive(Debug)]
+    struct ReadOnlyItems(MemoryStorage);
+
+    impl ListStorage for ReadOnlyItems {
+        fn create_label(&self, label: &Label) -> Result<(), String> { self.0.create_label(label) }
+        fn fetch_label(&self, name: &str) -> Result<Option<Label>, String> { self.0.fetch_label(name) }
+        fn fetch_labels(&self) -> Result<Vec<Label>, String> { self.0.fetch_labels() }
+        fn fetch_labels_for_item(&self, uuid: &str) -> Result<Vec<Label>, String> { self.0.fetch_labels_for_item(uuid) }
+        fn create_item(&self, _: &Item) -> Result<(), String> { Err("disk is full".to_string()) }
+        fn fetch_item(&self, uuid: &str) -> Result<Option<Item>, String> { self.0.fetch_item(uuid) }
+        fn fetch_items(&self) -> Result<Vec<Item>, String> { self.0.fetch_items() }
+        fn fetch_items_page(&self, offset: usize, limit: usize) -> Result<Vec<Item>, String> { self.0.fetch_items_page(offset, limit) }
+        fn count_items(&self) -> Result<usize, String> { self.0.count_items() }
+        fn fetch_items_with_label(&self, name: &str) -> Result<Vec<Item>, String> { self.0.fetch_items_with_label(name) }
+        fn update_item(&self, _: &Item, _: &[Label]) -> Result<(), String> { Err("disk is full".to_string()) }
+        fn delete_item(&self, _: &str) -> Result<bool, String> { Err("disk is full".to_string()) }
+    }
+
+    /// Storage that fails whatever is asked of it.
+    #[derive(Debug)]
+    struct BrokenStorage;
+
+    impl ListStorage for BrokenStorage {
+        fn create_label(&self, _: &Label) -> Result<(), String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_label(&self, _: &str) -> Result<Option<Label>, String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_labels(&self) -> Result<Vec<Label>, String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_labels_for_item(&self, _: &str) -> Result<Vec<Label>, String> { Err("database disk image is malformed".to_string()) }
+        fn create_item(&self, _: &Item) -> Result<(), String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_item(&self, _: &str) -> Result<Option<Item>, String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_items(&self) -> Result<Vec<Item>, String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_items_page(&self, _: usize, _: usize) -> Result<Vec<Item>, String> { Err("database disk image is malformed".to_string()) }
+        fn count_items(&self) -> Result<usize, String> { Err("database disk image is malformed".to_string()) }
+        fn fetch_items_with_label(&self, _: &str) -> Result<Vec<Item>, String> { Err("database disk image is malformed".to_string()) }
+        fn update_item(&self, _: &Item, _: &[Label]) -> Result<(), String> { Err("database disk image is malformed".to_string()) }
+        fn delete_item(&self, _: &str) -> Result<bool, String> { Err("database disk image is malformed".to_string()) }
+    }
+
+    fn request<'a>(method: &'a str, url: &'a str, body: &'a str) -> Request<'a> {
+        Request { method: method, url: url, if_match: None, if_none_match: None, body: body }
+    }
+
+    fn create(manager: &mut ListManager, body: &str) -> Value {
+        let response = handle(manager, &request("POST", "/items", body));
+        assert_eq!(response.status, 201, "{:?}", response.body);
+        response.body.unwrap()
+    }
+
+    #[test]
+    fn test_items() {
+        let mut manager = ListManager::new(None);
+        let item = create(&mut manager, r#"{"name": "buy milk", "due_date": {"millis": 1509840000000, "all_day": true}, "priority": "high", "labels": ["home", "home"]}"#);
+        assert_eq!(item["labels"], json!([{ "name": "home", "color": DEFAULT_COLOR }]));
+        assert_eq!(item["due_date"]["all_day"], json!(true));
+        assert_eq!(item["priority"], json!("high"));
+        let url = format!("/items/{}", item["uuid"].as_str().unwrap());
+
+        let fetched = handle(&mut manager, &request("GET", &url, ""));
+        assert_eq!((fetched.status, fetched.body), (200, Some(item.clone())));
+
+        let patched = handle(&mut manager, &request("PATCH", &url, r#"{"completion_date": {"millis": 1509883200000, "zone": "Europe/Berlin"}}"#));
+        assert_eq!(patched.status, 200);
+        let patched = patched.body.unwrap();
+        assert_eq!(patched["name"], json!("buy milk"));
+        assert_eq!(patched["completion_date"]["zone"], json!("Europe/Berlin"));
+        assert_eq!(patched["priority"], json!("high"));
+
+        let replaced = handle(&mut manager, &request("PUT", &url, r#"{"name": "buy oat milk"}"#)).body.unwrap();
+        assert_eq!(replaced["due_date"], Value::Null);
+        assert_eq!(replaced["priority"], Value::Null);
+        assert_eq!(replaced["labels"], json!([]));
+
+        assert_eq!(handle(&mut manager, &request("DELETE", &url, "")).status, 204);
+        assert_eq!(handle(&mut manager, &request("GET", &url, "")).status, 404);
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut manager = ListManager::new(None);
+        create(&mut manager, r#"{"name": "buy milk", "labels": ["errands"], "due_date": {"millis": 1000}}"#);
+        create(&mut manager, r#"{"name": "Call Mum", "completion_date": {"millis": 1000}, "due_date": {"millis": 2000}}"#);
+        let names = |manager: &mut ListManager, url: &str| -> Vec<String> {
+            let response = handle(manager, &request("GET", url, ""));
+            assert_eq!(response.status, 200, "{:?}", response.body);
+            response.body.unwrap().as_array().unwrap().iter().map(|item| item["name"].as_str().unwrap().to_string()).collect()
+        };
+        assert_eq!(names(&mut manager, "/items").len(), 2);
+        assert_eq!(names(&mut manager, "/items?status=open"), vec!["buy milk"]);
+        assert_eq!(names(&mut manager, "/items?status=done&q=call+mum"), vec!["Call Mum"]);
+        assert_eq!(names(&mut manager, "/items?due_after=1500"), vec!["Call Mum"]);
+        assert_eq!(names(&mut manager, "/items?due_before=1500&label=errands"), vec!["buy milk"]);
+        assert_eq!(names(&mut manager, "/labels/errands/items"), vec!["buy milk"]);
+        assert_eq!(handle(&mut manager, &request("GET", "/items?status=late", "")).status, 400);
+        assert_eq!(handle(&mut manager, &request("GET", "/items?label=play", "")).status, 404);
+        assert_eq!(handle(&mut manager, &request("GET", "/items?sort=name", "")).status, 400);
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut manager = ListManager::new(None);
+        let created = handle(&mut manager, &request("POST", "/labels", r##"{"name": "at home", "color": "#ff0000"}"##));
+        assert_eq!(created.status, 201);
+        assert_eq!(created.location, Some("/labels/at%20home".to_string()));
+        assert_eq!(handle(&mut manager, &request("GET", "/labels/at%20home", "")).body, Some(json!({ "name": "at home", "color": "#ff0000" })));
+        assert_eq!(handle(&mut manager, &request("POST", "/labels", r#"{"name": "at home"}"#)).status, 409);
+        assert_eq!(handle(&mut manager, &request("GET", "/labels", "")).body, Some(json!([{ "name": "at home", "color": "#ff0000" }])));
+
+        let item = create(&mut manager, r#"{"name": "tidy up"}"#);
+        let url = format!("/items/{}/labels/at%20home", item["uuid"].as_str().unwrap());
+        let labelled = handle(&mut manager, &request("PUT", &url, "")).body.unwrap();
+        assert_eq!(labelled["labels"], json!([{ "name": "at home", "color": "#ff0000" }]));
+        assert_eq!(handle(&mut manager, &request("DELETE", &url, "")).status, 200);
+        assert_eq!(handle(&mut manager, &request("DELETE", &url, "")).status, 404);
+    }
+
+    #[test]
+    fn test_etags() {
+        let mut manager = ListManager::new(None);
+        let created = handle(&mut manager, &request("POST", "/items", r#"{"name": "buy milk"}"#));
+        let etag = created.etag.clone().unwrap();
+        let url = created.location.unwrap();
+
+        let mut conditional = request("GET", &url, "");
+        conditional.if_none_match = Some(&etag);
+        let not_modified = handle(&mut manager, &conditional);
+        assert_eq!((not_modified.status, not_modified.body), (304, None));
+
+        let mut stale = request("PATCH", &url, r#"{"name": "buy oat milk"}"#);
+        stale.if_match = Some(&etag);
+        let updated = handle(&mut manager, &stale);
+        assert_eq!(updated.status, 200);
+        assert_ne!(updated.etag, Some(etag.clone()));
+        let rejected = handle(&mut manager, &stale);
+        assert_eq!(rejected.status, 412);
+        assert_eq!(rejected.body, Some(json!({ "error": format!("item {} has changed", &url[7..]) })));
+
+        // `If-None-Match` compares weakly, but `If-Match` only takes the strong tag.
+        let etag = updated.etag.unwrap();
+        let weak = format!("W/{}", etag);
+        let mut conditional = request("GET", &url, "");
+        conditional.if_none_match = Some(&weak);
+        assert_eq!(handle(&mut manager, &conditional).status, 304);
+        let mut weak_match = request("PATCH", &url, r#"{"name": "buy milk"}"#);
+        weak_match.if_match = Some(&weak);
+        assert_eq!(handle(&mut manager, &weak_match).status, 412);
+        let both = format!("{}, {}", weak, etag);
+        weak_match.if_match = Some(&both);
+        assert_eq!(handle(&mut manager, &weak_match).status, 200);
+
+        let mut any = request("DELETE", &url, "");
+        any.if_match = Some("*");
+        assert_eq!(handle(&mut manager, &any).status, 204);
+    }
+
+    #[test]
+    fn test_priority_changes_etag() {
+        let mut manager = ListManager::new(None);
+        let created = handle(&mut manager, &request("POST", "/items", r#"{"name": "buy milk"}"#));
+        let etag = created.etag.clone().unwrap();
+        let url = created.location.unwrap();
+
+        let prioritised = handle(&mut manager, &request("PATCH", &url, r#"{"priority": "low"}"#));
+        assert_eq!(prioritised.status, 200);
+        assert_ne!(prioritised.etag, Some(etag.clone()));
+        let mut stale = request("PATCH", &url, r#"{"name": "buy oat milk"}"#);
+        stale.if_match = Some(&etag);
+        assert_eq!(handle(&mut manager, &stale).status, 412);
+    }
+
+    #[test]
+    fn test_storage_failures() {
+        let storage = ReadOnlyItems(MemoryStorage::new());
+        storage.0.create_item(&Item::new("uuid1".to_string(), "buy milk".to_string(), None, None, vec![])).unwrap();
+        let mut manager = ListManager::with_storage(storage);
+        let cases = [
+            ("POST", "/items", r#"{"name": "call mum"}"#),
+            ("PATCH", "/items/uuid1", r#"{"name": "buy oat milk"}"#),
+            ("PUT", "/items/uuid1/labels/home", ""),
+            ("DELETE", "/items/uuid1", ""),
+        ];
+        for &(method, url, body) in cases.iter() {
+            let response = handle(&mut manager, &request(method, url, body));
+            assert_eq!((response.status, response.body), (500, Some(json!({ "error": "disk is full" }))), "{} {}", method, url);
+        }
+        assert_eq!(manager.fetch_item(&"uuid1".to_string()).map(|item| item.name.clone()), Some("buy milk".to_string()));
+    }
+
+    #[test]
+    fn test_read_failures() {
+        let mut manager = ListManager::with_storage(BrokenStorage);
+        let cases = [
+            ("GET", "/items", ""),
+            ("GET", "/items?label=home", ""),
+            ("GET", "/items/uuid1", ""),
+            ("GET", "/items/uuid1/labels", ""),
+            ("GET", "/labels", ""),
+            ("GET", "/labels/home", ""),
+            ("GET", "/labels/home/items", ""),
+            ("POST", "/labels", r#"{"name": "home"}"#),
+            ("POST", "/items", r#"{"name": "call mum", "labels": ["home"]}"#),
+        ];
+        for &(method, url, body) in cases.iter() {
+            let response = handle(&mut manager, &request(method, url, body));
+            assert_eq!((response.status, response.body), (500, Some(json!({ "error": "database disk image is malformed" }))), "{} {}", method, url);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut manager = ListManager::new(None);
+        let cases = [
+            ("POST", "/items", "{", 400),
+            ("POST", "/items", r#"{"labels": []}"#, 400),
+            ("POST", "/items", r#"{"name": "x", "due_date": {"millis": 0, "zone": "Mars/Olympus"}}"#, 422),
+            ("POST", "/items", r#"{"name": "x", "labels": [""]}"#, 422),
+            ("POST", "/items", r#"{"name": "x", "priority": "urgent"}"#, 422),
+            ("POST", "/labels", r#"{"name": ""}"#, 422),
+            ("GET", "/items/missing", "", 404),
+            ("GET", "/elsewhere", "", 404),
+            ("GET", "/labels/%zz", "", 400),
+            ("DELETE", "/labels", "", 405),
+        ];
+        for &(method, url, body, status) in cases.iter() {
+            let response = handle(&mut manager, &request(method, url, body));
+            assert_eq!(response.status, status, "{} {} {}: {:?}", method, url, body, response.body);
+            assert!(response.body.unwrap()["error"].is_string());
+        }
+        assert_eq!(handle(&mut manager, &request("PATCH", "/items", "")).allow, Some("GET, POST"));
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Items and labels as JSON values, written by `list::json` so they have the same
+//! shape the `toodle` command-line tool prints. Dates in request bodies have the
+//! fields of the C ABI's date snapshots:
+//! `{"millis": 1509883200000, "all_day": false, "zone": "America/New_York"}`.
+
+use serde_json::{
+    Map,
+    Value,
+};
+
+use list::dates::Date;
+use list::items::{
+    Item,
+    Priority,
+};
+use list::json;
+use list::labels::Label;
+
+use api::ApiError;
+
+pub fn item_to_json(item: &Item) -> Value {
+    from_list_json(&json::item_json(item))
+}
+
+pub fn label_to_json(label: &Label) -> Value {
+    from_list_json(&json::label_json(label))
+}
+
+/// `text`, which `list::json` wrote, as a value.
+fn from_list_json(text: &str) -> Value {
+    ::serde_json::from_str(text).expect("expected list::json to write valid JSON")
+}
+
+/// Parse `body` as a JSON object.
+pub fn parse_object(body: &str) -> Result<Map<String, Value>, ApiError> {
+    match ::serde_json::from_str(body) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(ApiError::BadRequest("body isn't a JSON object".to_string())),
+        Err(e) => Err(ApiError::BadRequest(format!("body isn't JSON: {}", e))),
+    }
+}
+
+/// The string `key`, or `None` if it's missing.
+pub fn string_field(object: &Map<String, Value>, key: &str) -> Result<Option<String>, ApiError> {
+    match object.get(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(ApiError::BadRequest(format!("{} isn't a string", key))),
+    }
+}
+
+/// The date `key`, which is `Some(None)` if it's null and `None` if it's missing.
+pub fn date_field(object: &Map<String, Value>, key: &str) -> Result<Option<Option<Date>>, ApiError> {
+    match object.get(key) {
+        None => Ok(None),
+        Some(&Value::Null) => Ok(Some(None)),
+        Some(value) => Ok(Some(Some(date_from_json(value).map_err(|e| e.in_field(key))?))),
+    }
+}
+
+/// The priority `key`, which is `Some(None)` if it's null and `None` if it's missing.
+pub fn priority_field(object: &Map<String, Value>, key: &str) -> Result<Option<Option<Priority>>, ApiError> {
+    match object.get(key) {
+        None => Ok(None),
+        Some(&Value::Null) => Ok(Some(None)),
+        Some(Value::String(name)) => name.parse().map(|priority| Some(Some(priority)))
+                                         .map_err(|e| ApiError::Unprocessable(format!("{}: {}", key, e))),
+        Some(_) => Err(ApiError::BadRequest(format!("{} isn't a string", key))),
+    }
+}
+
+/// The array of label names `key`, or `None` if it's missing.
+pub fn names_field(object: &Map<String, Value>, key: &str) -> Result<Option<Vec<String>>, ApiError> {
+    let values = match object.get(key) {
+        None => return Ok(None),
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(ApiError::BadRequest(format!("{} isn't an array", key))),
+    };
+    values.iter().map(|value| match *value {
+        Value::String(ref name) => Ok(name.clone()),
+        _ => Err(ApiError::BadRequest(format!("{} has an entry that isn't a string", key))),
+    }).collect::<Result<Vec<String>, ApiError>>().map(Some)
+}
+
+fn date_from_json(value: &Value) -> Result<Date, ApiError> {
+    let object = match *value {
+        Value::Object(ref object) => object,
+        _ => return Err(ApiError::BadRequest("isn't a date object".to_string())),
+    };
+    let millis = object.get("millis").and_then(Value::as_i64)
+        .ok_or_else(|| ApiError::BadRequest("needs integer millis".to_string()))?;
+    let all_day = match object.get("all_day") {
+        None | Some(&Value::Null) => false,
+        Some(&Value::Bool(all_day)) => all_day,
+        Some(_) => return Err(ApiError::BadRequest("has an all_day that isn't a boolean".to_string())),
+    };
+    let zone = string_field(object, "zone").map_err(|_| ApiError::BadRequest("has a zone that isn't a string".to_string()))?;
+    match (all_day, zone) {
+        (true, Some(_)) => Err(ApiError::Unprocessable("can't be all-day and have a zone".to_string())),
+        (true, None) => Ok(Date::day_from_millis(millis)?),
+        (false, Some(zone)) => Ok(Date::from_millis_in(millis, &zone)?),
+        (false, None) => Ok(Date::from_millis(millis)?),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn object(json: &str) -> Map<String, Value> {
+        parse_object(json).unwrap()
+    }
+
+    #[test]
+    fn test_item_to_json() {
+        let labels = vec![Label { name: "home".to_string(), color: "#ff0000".to_string() }];
+        let mut item = Item::new("1a2b".to_string(), "buy milk".to_string(), Date::from_millis_in(1509883200345, "America/New_York").ok(), Date::day(2017, 11, 6), labels);
+        item.priority = Some(Priority::High);
+        assert_eq!(item_to_json(&item), json!({
+            "uuid": "1a2b",
+            "name": "buy milk",
+            "due_date": { "millis": 1509883200345i64, "all_day": false, "zone": "America/New_York" },
+            "completion_date": { "millis": 1509926400000i64, "all_day": true, "zone": null },
+            "priority": "high",
+            "labels": [{ "name": "home", "color": "#ff0000" }],
+        }));
+    }
+
+    #[test]
+    fn test_priority_field() {
+        let body = object(r#"{"a": "low", "b": null, "c": "urgent", "d": 3}"#);
+        assert_eq!(priority_field(&body, "a"), Ok(Some(Some(Priority::Low))));
+        assert_eq!(priority_field(&body, "b"), Ok(Some(None)));
+        assert_eq!(priority_field(&body, "c"), Err(ApiError::Unprocessable("c: unknown priority \"urgent\"".to_string())));
+        assert_eq!(priority_field(&body, "d"), Err(ApiError::BadRequest("d isn't a string".to_string())));
+        assert_eq!(priority_field(&body, "e"), Ok(None));
+    }
+
+    #[test]
+    fn test_date_field() {
+        let body = object(r#"{"a": {"millis": 1509883200345}, "b": null, "c": {"millis": 1509883200345, "all_day": true},
+                              "d": {"millis": 1509883200345, "zone": "Europe/Berlin"}}"#);
+        assert_eq!(date_field(&body, "a"), Ok(Some(Date::from_millis(1509883200345).ok())));
+        assert_eq!(date_field(&body, "b"), Ok(Some(None)));
+        assert_eq!(date_field(&body, "c"), Ok(Some(Date::day(2017, 11, 5))));
+        assert_eq!(date_field(&body, "d"), Ok(Some(Date::from_millis_in(1509883200345, "Europe/Berlin").ok())));
+        assert_eq!(date_field(&body, "e"), Ok(None));
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let body = object(r#"{"name": 1, "labels": ["home", 2], "a": "2017-11-05", "b": {"millis": 1.5},
+                              "c": {"millis": 0, "zone": "Mars/Olympus"}, "d": {"millis": 0, "all_day": true, "zone": "UTC"}}"#);
+        assert_eq!(string_field(&body, "name"), Err(ApiError::BadRequest("name isn't a string".to_string())));
+        assert_eq!(names_field(&body, "labels"), Err(ApiError::BadRequest("labels has an entry that isn't a string".to_string())));
+        assert_eq!(date_field(&body, "a"), Err(ApiError::BadRequest("a: isn't a date object".to_string())));
+        assert_eq!(date_field(&body, "b"), Err(ApiError::BadRequest("b: needs integer millis".to_string())));
+        assert_eq!(date_field(&body, "c"), Err(ApiError::Unprocessable("c: unknown time zone \"Mars/Olympus\"".to_string())));
+        assert_eq!(date_field(&body, "d"), Err(ApiError::Unprocessable("d: can't be all-day and have a zone".to_string())));
+        assert_eq!(parse_object("[]"), Err(ApiError::BadRequest("body isn't a JSON object".to_string())));
+        assert!(parse_object("{").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! A local HTTP server for a to-do list database, speaking the JSON API in `api`.
+//!
+//! Requests are handled one at a time on the thread that calls `serve`, so the
+//! list manager never sees concurrent changes; clients that edit the same items
+//! use ETags to find out about each other's changes instead.
+
+extern crate list;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_json;
+extern crate tiny_http;
+
+pub mod api;
+pub mod json;
+
+use std::io::Read;
+use std::net::SocketAddr;
+
+use list::ListManager;
+use tiny_http::{
+    Header,
+    StatusCode,
+};
+
+use api::{
+    ApiError,
+    Request,
+    Response,
+};
+
+/// Bodies larger than this are refused with a 413.
+const MAX_BODY: u64 = 1024 * 1024;
+
+pub struct Server {
+    http: tiny_http::Server,
+    manager: ListManager,
+}
+
+impl Server {
+    /// Listen on `addr`, such as `127.0.0.1:8080`. Port 0 picks a free port, which
+    /// `local_addr` reports.
+    pub fn bind(addr: &str, manager: ListManager) -> Result<Server, String> {
+        let http = tiny_http::Server::http(addr).map_err(|e| format!("can't listen on {}: {}", addr, e))?;
+        Ok(Server { http: http, manager: manager })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.http.server_addr().to_ip().expect("expected a TCP listener")
+    }
+
+    /// Handle requests until the process exits.
+    pub fn serve(mut self) {
+        for mut request in self.http.incoming_requests() {
+            let response = handle(&mut self.manager, &mut request);
+            send(request, response);
+        }
+    }
+}
+
+/// Read `request`'s body and headers and carry it out.
+fn handle(manager: &mut ListManager, request: &mut tiny_http::Request) -> Response {
+    let header = |name: &'static str| -> Option<String> {
+        request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str().to_string())
+    };
+    let if_match = header("If-Match");
+    let if_none_match = header("If-None-Match");
+
+    let mut body = String::new();
+    if request.body_length().is_some_and(|length| length as u64 > MAX_BODY) {
+        return ApiError::PayloadTooLarge.into_response();
+    }
+    if request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body).is_err() {
+        return ApiError::BadRequest("body isn't UTF-8".to_string()).into_response();
+    }
+    if body.len() as u64 > MAX_BODY {
+        return ApiError::PayloadTooLarge.into_response();
+    }
+    api::handle(manager, &Request {
+        method: request.method().as_str(),
+        url: request.url(),
+        if_match: if_match.as_deref(),
+        if_none_match: if_none_match.as_deref(),
+        body: &body,
+    })
+}
+
+fn send(request: tiny_http::Request, response: Response) {
+    let body = response.body.map_or(vec![], |body| format!("{}\n", body).into_bytes());
+    let mut http = tiny_http::Response::from_data(body).with_status_code(StatusCode(response.status));
+    if response.status != 204 && response.status != 304 {
+        http.add_header(header("Content-Type", "application/json"));
+    }
+    if let Some(etag) = response.etag {
+        http.add_header(header("ETag", &etag));
+    }
+    if let Some(location) = response.location {
+        http.add_header(header("Location", &location));
+    }
+    if let Some(allow) = response.allow {
+        http.add_header(header("Allow", allow));
+    }
+    if let Err(e) = request.respond(http) {
+        warn!(error:% = e; "can't send response");
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("expected a valid header")
+}
@@ -0,0 +1,111 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! `toodle-server`, which serves a to-do list database over HTTP on the local
+//! machine.
+
+extern crate list;
+extern crate log;
+extern crate toodle_server;
+
+use std::env;
+use std::fmt::Write;
+use std::process;
+
+use log::{
+    kv,
+    LevelFilter,
+    Log,
+    Metadata,
+    Record,
+};
+
+use list::ListManager;
+use toodle_server::Server;
+
+const USAGE: &'static str = "usage: toodle-server [--db PATH] [--addr HOST:PORT]
+
+Serves the database at PATH, or at $TOODLE_DB, on HOST:PORT, which defaults to
+127.0.0.1:8080.";
+
+const DEFAULT_ADDR: &'static str = "127.0.0.1:8080";
+
+/// Prints warnings and errors from the server and `list` to stderr, with their
+/// structured fields as `key=value` pairs.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        struct Fields(String);
+
+        impl<'kvs> kv::VisitSource<'kvs> for Fields {
+            fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+                write!(self.0, " {}={}", key, value)?;
+                Ok(())
+            }
+        }
+
+        let mut fields = Fields(String::new());
+        // Formatting into a string can't fail.
+        let _ = record.key_values().visit(&mut fields);
+        eprintln!("toodle-server: {}: {}{}", record.level(), record.args(), fields.0);
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() {
+    let mut db = env::var("TOODLE_DB").ok();
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            "--db" | "--addr" => args.next(),
+            _ => usage_error(&format!("unknown argument {:?}", arg)),
+        };
+        match (arg.as_str(), value) {
+            (_, None) => usage_error(&format!("{} needs a value", arg)),
+            ("--db", Some(value)) => db = Some(value),
+            (_, Some(value)) => addr = value,
+        }
+    }
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Warn);
+    }
+    let db = db.unwrap_or_else(|| usage_error("no database given; use --db PATH or set TOODLE_DB"));
+    let manager = ListManager::open(&db).unwrap_or_else(|e| fail(&e));
+    let server = Server::bind(&addr, manager).unwrap_or_else(|e| fail(&e));
+    println!("toodle-server: listening on http://{}", server.local_addr());
+    server.serve();
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("toodle-server: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("toodle-server: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,105 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Talks HTTP to a server on a free local port, backed by an in-memory database.
+
+extern crate list;
+extern crate toodle_server;
+
+use std::io::{
+    Read,
+    Write,
+};
+use std::net::{
+    SocketAddr,
+    TcpStream,
+};
+use std::thread;
+
+use list::ListManager;
+use toodle_server::Server;
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+fn start() -> SocketAddr {
+    let server = Server::bind("127.0.0.1:0", ListManager::new(None)).expect("expected the server to start");
+    let addr = server.local_addr();
+    thread::spawn(move || server.serve());
+    addr
+}
+
+/// Send one request with `headers` and `body`, and read the whole reply.
+fn send(addr: SocketAddr, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> Reply {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n", method, path, addr, body.len());
+    for &(name, value) in headers.iter() {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    let (head, body) = reply.split_at(reply.find("\r\n\r\n").expect("expected a blank line after the headers"));
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+    let headers = lines.map(|line| {
+        let colon = line.find(':').unwrap();
+        (line[..colon].to_string(), line[colon + 1..].trim().to_string())
+    }).collect();
+    Reply { status: status, headers: headers, body: body[4..].to_string() }
+}
+
+#[test]
+fn test_items_over_http() {
+    let addr = start();
+    let created = send(addr, "POST", "/items", &[], r#"{"name": "buy milk", "labels": ["errands"]}"#);
+    assert_eq!(created.status, 201, "{}", created.body);
+    assert_eq!(created.header("Content-Type"), Some("application/json"));
+    assert!(created.body.contains(r#""name":"buy milk""#), "{}", created.body);
+    let location = created.header("Location").unwrap().to_string();
+    let etag = created.header("ETag").unwrap().to_string();
+
+    let fetched = send(addr, "GET", &location, &[], "");
+    assert_eq!((fetched.status, fetched.header("ETag")), (200, Some(etag.as_str())));
+    let unchanged = send(addr, "GET", &location, &[("If-None-Match", &etag)], "");
+    assert_eq!((unchanged.status, unchanged.body.as_str()), (304, ""));
+
+    let patched = send(addr, "PATCH", &location, &[("If-Match", &etag)], r#"{"name": "buy oat milk"}"#);
+    assert_eq!(patched.status, 200, "{}", patched.body);
+    let stale = send(addr, "DELETE", &location, &[("If-Match", &etag)], "");
+    assert_eq!(stale.status, 412);
+    let fresh = patched.header("ETag").unwrap();
+    assert_eq!(send(addr, "DELETE", &location, &[("If-Match", fresh)], "").status, 204);
+    assert_eq!(send(addr, "GET", "/items", &[], "").body, "[]\n");
+}
+
+#[test]
+fn test_errors_over_http() {
+    let addr = start();
+    let wrong_method = send(addr, "DELETE", "/labels", &[], "");
+    assert_eq!((wrong_method.status, wrong_method.header("Allow")), (405, Some("GET, POST")));
+    assert_eq!(wrong_method.body, "{\"error\":\"method not allowed; use GET, POST\"}\n");
+
+    let huge = format!(r#"{{"name": "{}"}}"#, "x".repeat(2 * 1024 * 1024));
+    assert_eq!(send(addr, "POST", "/items", &[], &huge).status, 413);
+    assert_eq!(send(addr, "POST", "/labels", &[], "not json").status, 400);
+}
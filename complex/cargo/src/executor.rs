@@ -0,0 +1,399 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Running list manager operations on background threads, so that hosts don't
+//! block their main thread on the store.
+//!
+//! Each `_async` function checks its arguments straight away, failing like any
+//! other function if they're invalid, and otherwise queues the operation and
+//! returns true. Exactly one call to the completion callback follows, from one of
+//! the executor's threads, with either the operation's result or an error.
+
+use std::os::raw::{
+    c_char,
+    c_void,
+};
+use std::ptr;
+use std::sync::{
+    mpsc,
+    Arc,
+    Mutex,
+};
+use std::thread;
+
+use ffi_utils::error::{
+    check_optional_str,
+    check_str,
+    guard,
+    last_error,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::strings::{
+    free_c_char,
+    string_to_c_char_lossy,
+};
+use list::cancellation::CancellationToken;
+use list::csv::{
+    self,
+    CSV_IMPORTS,
+};
+use list::cursor::ItemCursor;
+use list::dates::Date;
+use list::items::{
+    Item,
+    ITEMS,
+    ITEM_LISTS,
+};
+use list::labels::{
+    LabelHandle,
+    LABELS,
+};
+use list::markdown::MARKDOWN_IMPORTS;
+use list::{
+    manager_from_handle,
+    ListManagerHandle,
+};
+
+/// How many items `list_manager_get_all_items_async` fetches between checks for
+/// cancellation.
+const PAGE_SIZE: usize = 100;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads that run queued jobs in the order they were queued.
+#[derive(Debug)]
+pub struct Executor {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl Executor {
+    pub fn new(threads: usize) -> Result<Executor, String> {
+        if threads == 0 {
+            return Err("thread count is 0".to_string());
+        }
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads).map(|index| {
+            let receiver = receiver.clone();
+            thread::Builder::new().name(format!("toodle-worker-{}", index)).spawn(move || {
+                loop {
+                    // Only wait for a job while holding the lock, not run it.
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                }
+            }).map_err(|e| e.to_string())
+        }).collect::<Result<Vec<_>, String>>()?;
+        Ok(Executor {
+            sender: Some(sender),
+            workers: workers,
+        })
+    }
+
+    pub fn spawn<F>(&self, job: F) -> Result<(), String>
+    where F: FnOnce() + Send + 'static {
+        let sender = self.sender.as_ref().ok_or("executor has stopped")?;
+        sender.send(Box::new(job)).map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for Executor {
+    /// Wait for the queued jobs to finish. When dropped by one of its own jobs, the
+    /// executor can't wait for itself, so its threads finish the queue on their own.
+    fn drop(&mut self) {
+        self.sender = None;
+        let current = thread::current().id();
+        if self.workers.iter().any(|worker| worker.thread().id() == current) {
+            return;
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// An executor handed across the FFI, freed with `toodle_executor_destroy`.
+pub type ExecutorHandle = u64;
+
+/// A cancellation token handed across the FFI, freed with
+/// `toodle_cancellation_token_destroy`.
+pub type CancellationTokenHandle = u64;
+
+pub static EXECUTORS: HandleRegistry<Executor> = HandleRegistry::new("executor");
+pub static CANCELLATION_TOKENS: HandleRegistry<CancellationToken> = HandleRegistry::new("cancellation token");
+
+/// Receives the result of an operation queued with one of the `_async` functions.
+/// On success `error` is NULL and `result` is the handle the matching synchronous
+/// function would have returned, which the callback owns. Otherwise `result` is 0
+/// and `error` describes what went wrong, or is `"cancelled"` if the operation was
+/// cancelled; it is only valid for the duration of the call.
+pub type CompletionCallback = Option<extern "C" fn(context: *mut c_void, result: u64, error: *const c_char)>;
+
+struct Completion {
+    callback: extern "C" fn(*mut c_void, u64, *const c_char),
+    context: *mut c_void,
+}
+
+// The host is responsible for the context being usable from the executor's threads.
+unsafe impl Send for Completion {}
+
+impl Completion {
+    fn complete(self, result: u64, error: Option<String>) {
+        match error {
+            Some(error) => {
+                let error = string_to_c_char_lossy(error);
+                (self.callback)(self.context, 0, error);
+                unsafe { free_c_char(error) };
+            },
+            None => (self.callback)(self.context, result, ptr::null()),
+        }
+    }
+}
+
+/// Queue `operation` on `executor`, to be passed `token`, or a token that's never
+/// cancelled if it's 0, and report its result to `callback`.
+fn queue<F>(executor: ExecutorHandle, token: CancellationTokenHandle, callback: CompletionCallback, context: *mut c_void, operation: F) -> Result<bool, String>
+where F: FnOnce(&CancellationToken) -> Result<u64, String> + Send + 'static {
+    let completion = Completion {
+        callback: callback.ok_or("callback is NULL")?,
+        context: context,
+    };
+    let token = match token {
+        0 => CancellationToken::new(),
+        token => CANCELLATION_TOKENS.get(token, |token| token.clone())?,
+    };
+    EXECUTORS.get(executor, |executor| {
+        executor.spawn(move || {
+            // Errors and panics are reported the same way as for synchronous calls,
+            // then passed on instead of being left for `toodle_last_error`.
+            let result = guard(0, || {
+                token.check()?;
+                operation(&token)
+            });
+            completion.complete(result, last_error());
+        })
+    })??;
+    Ok(true)
+}
+
+/// Start `threads` threads to run operations queued with the `_async` functions.
+/// Returns 0 if `threads` is 0, otherwise an executor that must be freed with
+/// `toodle_executor_destroy`.
+#[no_mangle]
+pub extern "C" fn toodle_executor_new(threads: usize) -> ExecutorHandle {
+    guard(0, || Ok(EXECUTORS.insert(Executor::new(threads)?)))
+}
+
+/// Wait for every queued operation to finish and stop the executor's threads. Cancel
+/// the operations first to avoid waiting for them. Called from a completion callback,
+/// this returns straight away and the threads stop once the queue is empty.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_executor_destroy(executor: ExecutorHandle) {
+    guard((), || EXECUTORS.destroy(executor))
+}
+
+/// Make a token that can cancel any number of queued operations. Cancelled
+/// operations that haven't started yet don't run; those that have stop at their
+/// next check, keeping any changes made before then.
+#[no_mangle]
+pub extern "C" fn toodle_cancellation_token_new() -> CancellationTokenHandle {
+    guard(0, || Ok(CANCELLATION_TOKENS.insert(CancellationToken::new())))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_cancellation_token_cancel(token: CancellationTokenHandle) {
+    guard((), || CANCELLATION_TOKENS.get(token, |token| token.cancel()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_cancellation_token_is_cancelled(token: CancellationTokenHandle) -> bool {
+    guard(false, || CANCELLATION_TOKENS.get(token, |token| token.is_cancelled()))
+}
+
+/// Free a token. Operations it was passed to keep their own reference to it, so
+/// this doesn't cancel them.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_cancellation_token_destroy(token: CancellationTokenHandle) {
+    guard((), || CANCELLATION_TOKENS.destroy(token))
+}
+
+/// Fetch every item as `list_manager_get_all_items` does, checking `token` between
+/// pages. The result is an `ItemListHandle`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_get_all_items_async(executor: ExecutorHandle, manager: ListManagerHandle, token: CancellationTokenHandle, callback: CompletionCallback, context: *mut c_void) -> bool {
+    guard(false, || {
+        let manager = manager_from_handle(manager)?;
+        queue(executor, token, callback, context, move |token| {
+            let mut cursor = ItemCursor::new(manager, PAGE_SIZE)?;
+            let mut items = Vec::with_capacity(cursor.total_count());
+            loop {
+                let page = cursor.next_page()?;
+                if page.is_empty() {
+                    break;
+                }
+                items.extend(page);
+                token.check()?;
+            }
+            Ok(ITEM_LISTS.insert(items))
+        })
+    })
+}
+
+/// Create an item as `list_manager_create_item` does. The labels are read before
+/// this returns, so their handles may be freed straight away. The result is an
+/// `ItemHandle`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_create_item_async(executor: ExecutorHandle, manager: ListManagerHandle, name: *const c_char, due_date: *const i64, completion_date: *const i64, labels: *const LabelHandle, label_count: usize, token: CancellationTokenHandle, callback: CompletionCallback, context: *mut c_void) -> bool {
+    guard(false, || {
+        let mut manager = manager_from_handle(manager)?;
+        let name = check_str(name, "name")?;
+        let labels = if label_count == 0 {
+            vec![]
+        } else if labels.is_null() {
+            return Err("labels is NULL".to_string());
+        } else {
+            ::std::slice::from_raw_parts(labels, label_count).iter()
+                                                             .map(|&label| LABELS.get(label, |label| label.clone()))
+                                                             .collect::<Result<Vec<_>, String>>()?
+        };
+        let due_date = date_from_ptr(due_date)?;
+        let completion_date = date_from_ptr(completion_date)?;
+        queue(executor, token, callback, context, move |_| {
+            let item = Item::new("".to_string(), name, due_date, completion_date, labels);
+            let uuid = manager.create_item(&item)?;
+            let item = manager.fetch_item(&uuid).ok_or("item wasn't saved")?;
+            Ok(ITEMS.insert(item))
+        })
+    })
+}
+
+/// Import items from CSV as `list_manager_import_csv` does, stopping before the next
+/// item once `token` is cancelled. Items imported before then are kept. The result
+/// is a `CsvImportHandle`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_import_csv_async(executor: ExecutorHandle, manager: ListManagerHandle, csv: *const c_char, mapping: *const c_char, date_format: *const c_char, token: CancellationTokenHandle, callback: CompletionCallback, context: *mut c_void) -> bool {
+    guard(false, || {
+        let manager = manager_from_handle(manager)?;
+        let csv = check_str(csv, "csv")?;
+        let mapping = check_optional_str(mapping, "mapping")?;
+        let date_format = check_optional_str(date_format, "date_format")?;
+        queue(executor, token, callback, context, move |token| {
+            let mut manager = manager.with_cancellation(token.clone());
+            let import = csv::import_csv_with_description(&mut manager, &csv, mapping, date_format).map_err(|e| e.to_string())?;
+            token.check()?;
+            Ok(CSV_IMPORTS.insert(import))
+        })
+    })
+}
+
+/// Import a Markdown checklist as `list_manager_import_markdown` does, stopping
+/// before the next item once `token` is cancelled. Items imported before then are
+/// kept. The result is a `MarkdownImportHandle`.
+#[no_mangle]
+pub unsafe extern "C" fn list_manager_import_markdown_async(executor: ExecutorHandle, manager: ListManagerHandle, markdown: *const c_char, token: CancellationTokenHandle, callback: CompletionCallback, context: *mut c_void) -> bool {
+    guard(false, || {
+        let manager = manager_from_handle(manager)?;
+        let markdown = check_str(markdown, "markdown")?;
+        queue(executor, token, callback, context, move |token| {
+            let mut manager = manager.with_cancellation(token.clone());
+            let import = manager.import_markdown(&markdown);
+            token.check()?;
+            Ok(MARKDOWN_IMPORTS.insert(import))
+        })
+    })
+}
+
+unsafe fn date_from_ptr(millis: *const i64) -> Result<Option<Date>, String> {
+    if millis.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(Date::from_millis(*millis)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ffi::CStr;
+    use std::sync::mpsc::{
+        channel,
+        Sender,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_runs_jobs_in_order() {
+        let executor = Executor::new(1).unwrap();
+        let (sender, receiver) = channel();
+        for index in 0..5 {
+            let sender = sender.clone();
+            executor.spawn(move || sender.send(index).unwrap()).unwrap();
+        }
+        drop(executor);
+        assert_eq!(receiver.try_iter().collect::<Vec<i32>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(Executor::new(0).unwrap_err(), "thread count is 0");
+    }
+
+    #[test]
+    fn test_drop_waits_for_jobs() {
+        let executor = Executor::new(2).unwrap();
+        let (sender, receiver) = channel();
+        for _ in 0..4 {
+            let sender = sender.clone();
+            executor.spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                sender.send(()).unwrap();
+            }).unwrap();
+        }
+        drop(executor);
+        assert_eq!(receiver.try_iter().count(), 4);
+    }
+
+    extern "C" fn send_result(context: *mut c_void, result: u64, error: *const c_char) {
+        let sender = unsafe { &*(context as *const Mutex<Sender<Result<u64, String>>>) };
+        let result = if error.is_null() {
+            Ok(result)
+        } else {
+            Err(unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned())
+        };
+        sender.lock().unwrap().send(result).unwrap();
+    }
+
+    #[test]
+    fn test_completion() {
+        let executor = toodle_executor_new(1);
+        let (sender, receiver) = channel::<Result<u64, String>>();
+        let sender = Mutex::new(sender);
+        let context = &sender as *const _ as *mut c_void;
+        let token = toodle_cancellation_token_new();
+        unsafe {
+            assert!(queue(executor, 0, Some(send_result), context, |_| Ok(7)).unwrap());
+            assert!(queue(executor, 0, Some(send_result), context, |_| Err("failed".to_string())).unwrap());
+            assert!(queue(executor, 0, Some(send_result), context, |_| -> Result<u64, String> { panic!("oops") }).unwrap());
+            toodle_cancellation_token_cancel(token);
+            assert!(toodle_cancellation_token_is_cancelled(token));
+            assert!(queue(executor, token, Some(send_result), context, |_| Ok(7)).unwrap());
+            assert!(queue(executor, 0, None, context, |_| Ok(7)).is_err());
+            assert!(queue(0, 0, Some(send_result), context, |_| Ok(7)).is_err());
+            toodle_cancellation_token_destroy(token);
+            toodle_executor_destroy(executor);
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![
+            Ok(7),
+            Err("failed".to_string()),
+            Err("panic: oops".to_string()),
+            Err("cancelled".to_string()),
+        ]);
+    }
+}
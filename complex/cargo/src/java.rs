@@ -0,0 +1,514 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! JNI bindings for the classes in `java/com/mozilla/toodle`, built with the `jni`
+//! feature.
+//!
+//! Each native method calls the C function it mirrors, so Java objects hold the same
+//! handles C callers do and get the same checks. Where a C caller would find an
+//! error with `toodle_last_error()`, Java callers get a `ToodleException` carrying
+//! the message.
+
+extern crate jni;
+
+use std::convert::TryFrom;
+use std::ffi::{
+    CStr,
+    CString,
+};
+use std::os::raw::{
+    c_char,
+    c_void,
+};
+use std::ptr;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use self::jni::{
+    JavaVM,
+    JNIEnv,
+};
+use self::jni::objects::{
+    GlobalRef,
+    JClass,
+    JLongArray,
+    JObject,
+    JString,
+    JValue,
+};
+use self::jni::sys::{
+    jboolean,
+    jint,
+    jlong,
+    jlongArray,
+    jobject,
+    jstring,
+    JNI_FALSE,
+};
+
+use ffi_utils::error::{
+    guard,
+    last_error,
+};
+use ffi_utils::logging::LogLevel;
+use list::items::*;
+use list::labels::*;
+use list::list_manager_destroy;
+
+use {
+    new_toodle,
+    toodle_destroy,
+    toodle_list,
+    toodle_set_logger,
+    toodle_string_free,
+};
+
+const EXCEPTION_CLASS: &'static str = "com/mozilla/toodle/ToodleException";
+const LOG_LEVEL_CLASS: &'static str = "com/mozilla/toodle/LogLevel";
+
+/// Run `body` and, if it fails or the last library call it made recorded an error,
+/// throw a `ToodleException` with the message. Returns `default` if `body` fails.
+fn call<'local, T, F>(env: &mut JNIEnv<'local>, default: T, body: F) -> T
+where F: FnOnce(&mut JNIEnv<'local>) -> Result<T, String> {
+    let value = guard(default, || body(env));
+    if let Some(message) = last_error() {
+        // A failed JNI call may already have left an exception to report.
+        if !env.exception_check().unwrap_or(true) {
+            let _ = env.throw_new(EXCEPTION_CLASS, message);
+        }
+    }
+    value
+}
+
+/// Pass `value` on if the library call that returned it succeeded.
+fn checked<T>(value: T) -> Result<T, String> {
+    match last_error() {
+        Some(message) => Err(message),
+        None => Ok(value),
+    }
+}
+
+fn describe(error: jni::errors::Error) -> String {
+    format!("JNI call failed: {}", error)
+}
+
+/// Copy the string argument `name` into a C string, or `None` if it is null.
+fn c_string_arg(env: &mut JNIEnv, string: &JString, name: &str) -> Result<Option<CString>, String> {
+    if string.is_null() {
+        return Ok(None);
+    }
+    let string: String = env.get_string(string).map_err(describe)?.into();
+    CString::new(string).map(Some)
+                        .map_err(|e| format!("{} has a NUL byte at byte {}", name, e.nul_position()))
+}
+
+fn c_string_ptr(string: &Option<CString>) -> *const c_char {
+    string.as_ref().map_or(ptr::null(), |string| string.as_ptr())
+}
+
+/// Turn a string returned by the library into a Java string, freeing it. NULL
+/// becomes null.
+unsafe fn take_string(env: &mut JNIEnv, string: *mut c_char) -> Result<jstring, String> {
+    if string.is_null() {
+        return Ok(ptr::null_mut());
+    }
+    let java = env.new_string(CStr::from_ptr(string).to_string_lossy());
+    toodle_string_free(string);
+    Ok(java.map_err(describe)?.into_raw())
+}
+
+/// Turn a date returned by the library into a `java.lang.Long`, freeing it. NULL
+/// becomes null.
+unsafe fn take_date(env: &mut JNIEnv, date: *mut i64) -> Result<jobject, String> {
+    if date.is_null() {
+        return Ok(ptr::null_mut());
+    }
+    let millis = *date;
+    item_date_destroy(date);
+    let boxed = env.call_static_method("java/lang/Long", "valueOf", "(J)Ljava/lang/Long;", &[JValue::Long(millis)])
+                   .and_then(|value| value.l())
+                   .map_err(describe)?;
+    Ok(boxed.into_raw())
+}
+
+fn handle_array(env: &mut JNIEnv, handles: &[u64]) -> Result<jlongArray, String> {
+    let array = env.new_long_array(handles.len() as i32).map_err(describe)?;
+    let handles: Vec<jlong> = handles.iter().map(|&handle| handle as jlong).collect();
+    env.set_long_array_region(&array, 0, &handles).map_err(describe)?;
+    Ok(array.into_raw())
+}
+
+fn handles_from_array(env: &mut JNIEnv, array: &JLongArray) -> Result<Vec<u64>, String> {
+    if array.is_null() {
+        return Err("labels is NULL".to_string());
+    }
+    let len = env.get_array_length(array).map_err(describe)?;
+    let mut handles = vec![0; len as usize];
+    env.get_long_array_region(array, 0, &mut handles).map_err(describe)?;
+    Ok(handles.into_iter().map(|handle| handle as u64).collect())
+}
+
+fn optional_date(has_date: jboolean, date: jlong) -> Option<i64> {
+    if has_date == JNI_FALSE { None } else { Some(date) }
+}
+
+fn date_ptr(date: &Option<i64>) -> *const i64 {
+    date.as_ref().map_or(ptr::null(), |date| date as *const i64)
+}
+
+// Toodle
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Toodle_nativeNew(mut env: JNIEnv, _: JClass, uri: JString) -> jlong {
+    call(&mut env, 0, |env| {
+        let uri = c_string_arg(env, &uri, "uri")?;
+        Ok(checked(unsafe { new_toodle(c_string_ptr(&uri)) })? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Toodle_nativeDestroy(mut env: JNIEnv, _: JClass, toodle: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { toodle_destroy(toodle as u64) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Toodle_nativeList(mut env: JNIEnv, _: JClass, toodle: jlong) -> jlong {
+    call(&mut env, 0, |_| Ok(checked(unsafe { toodle_list(toodle as u64) })? as jlong))
+}
+
+/// The Java `Logger` messages are forwarded to, along with what's needed to call it
+/// from threads the JVM didn't start.
+struct JavaLogger {
+    vm: JavaVM,
+    logger: GlobalRef,
+    // Looked up ahead of time because threads attached from native code can't find
+    // application classes on Android.
+    levels: GlobalRef,
+}
+
+static JAVA_LOGGER: Mutex<Option<Arc<JavaLogger>>> = Mutex::new(None);
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Toodle_nativeSetLogger(mut env: JNIEnv, _: JClass, logger: JObject, level: jint) {
+    call(&mut env, (), |env| {
+        let level = match level {
+            1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            4 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        };
+        let mut java_logger = JAVA_LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+        if logger.is_null() {
+            *java_logger = None;
+            unsafe { toodle_set_logger(None, ptr::null_mut(), level as u32) };
+            return Ok(());
+        }
+        let levels = env.find_class(LOG_LEVEL_CLASS).map_err(describe)?;
+        *java_logger = Some(Arc::new(JavaLogger {
+            vm: env.get_java_vm().map_err(describe)?,
+            logger: env.new_global_ref(&logger).map_err(describe)?,
+            levels: env.new_global_ref(&levels).map_err(describe)?,
+        }));
+        let callback: extern "C" fn(*mut c_void, u32, *const c_char, *const c_char, *const c_char) = forward_to_java;
+        unsafe { toodle_set_logger(Some(callback), ptr::null_mut(), level as u32) };
+        Ok(())
+    })
+}
+
+extern "C" fn forward_to_java(_context: *mut c_void, level: u32, target: *const c_char, message: *const c_char, fields: *const c_char) {
+    // Let go of the lock before calling Java, which may call back into the library.
+    let java_logger = match *JAVA_LOGGER.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref java_logger) => java_logger.clone(),
+        None => return,
+    };
+    let mut env = match java_logger.vm.attach_current_thread() {
+        Ok(env) => env,
+        Err(_) => return,
+    };
+    let result = (|| -> jni::errors::Result<()> {
+        let string = |s: *const c_char| unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() };
+        let target = env.new_string(string(target))?;
+        let message = env.new_string(string(message))?;
+        let fields = env.new_string(string(fields))?;
+        let levels: &JClass = java_logger.levels.as_obj().into();
+        let level = env.call_static_method(levels, "fromValue", "(I)Lcom/mozilla/toodle/LogLevel;", &[JValue::Int(level as jint)])?.l()?;
+        env.call_method(java_logger.logger.as_obj(), "log",
+                        "(Lcom/mozilla/toodle/LogLevel;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                        &[JValue::Object(&level), JValue::Object(&target), JValue::Object(&message), JValue::Object(&fields)])?;
+        Ok(())
+    })();
+    // A logger that throws mustn't fail the call that logged.
+    if result.is_err() {
+        let _ = env.exception_clear();
+    }
+}
+
+// ListManager
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeDestroy(mut env: JNIEnv, _: JClass, manager: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { list_manager_destroy(manager as u64) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeGetAllLabels(mut env: JNIEnv, _: JClass, manager: jlong) -> jlongArray {
+    call(&mut env, ptr::null_mut(), |env| {
+        unsafe {
+            let list = checked(list_manager_get_all_labels(manager as u64))?;
+            let labels: Vec<u64> = (0..label_list_count(list)).map(|index| label_list_entry_at(list, index)).collect();
+            label_list_destroy(list);
+            handle_array(env, &labels)
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeCreateLabel(mut env: JNIEnv, _: JClass, manager: jlong, name: JString, color: JString) -> jlong {
+    call(&mut env, 0, |env| {
+        let name = c_string_arg(env, &name, "name")?;
+        let color = c_string_arg(env, &color, "color")?;
+        Ok(checked(unsafe { list_manager_create_label(manager as u64, c_string_ptr(&name), c_string_ptr(&color)) })? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeGetAllItems(mut env: JNIEnv, _: JClass, manager: jlong) -> jlongArray {
+    call(&mut env, ptr::null_mut(), |env| {
+        unsafe {
+            let list = checked(list_manager_get_all_items(manager as u64))?;
+            let items: Vec<u64> = (0..item_list_count(list)).map(|index| item_list_entry_at(list, index)).collect();
+            item_list_destroy(list);
+            handle_array(env, &items)
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeCreateItem(mut env: JNIEnv, _: JClass, manager: jlong, name: JString,
+                                                                           has_due_date: jboolean, due_date: jlong,
+                                                                           has_completion_date: jboolean, completion_date: jlong,
+                                                                           labels: JLongArray) -> jlong {
+    call(&mut env, 0, |env| {
+        let name = c_string_arg(env, &name, "name")?;
+        let due_date = optional_date(has_due_date, due_date);
+        let completion_date = optional_date(has_completion_date, completion_date);
+        let labels = handles_from_array(env, &labels)?;
+        let item = unsafe {
+            list_manager_create_item(manager as u64, c_string_ptr(&name), date_ptr(&due_date), date_ptr(&completion_date), labels.as_ptr(), labels.len())
+        };
+        Ok(checked(item)? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_ListManager_nativeUpdateItem(mut env: JNIEnv, _: JClass, manager: jlong, item: jlong, name: JString,
+                                                                           has_due_date: jboolean, due_date: jlong,
+                                                                           has_completion_date: jboolean, completion_date: jlong,
+                                                                           labels: JLongArray) {
+    call(&mut env, (), |env| {
+        let name = c_string_arg(env, &name, "name")?;
+        let due_date = optional_date(has_due_date, due_date);
+        let completion_date = optional_date(has_completion_date, completion_date);
+        let labels = handles_from_array(env, &labels)?;
+        unsafe {
+            list_manager_update_item(manager as u64, item as u64, c_string_ptr(&name), date_ptr(&due_date), date_ptr(&completion_date), labels.as_ptr(), labels.len());
+        }
+        Ok(())
+    })
+}
+
+// Label
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Label_nativeDestroy(mut env: JNIEnv, _: JClass, label: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { label_destroy(label as u64) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Label_nativeGetName(mut env: JNIEnv, _: JClass, label: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_string(env, checked(label_get_name(label as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Label_nativeGetColor(mut env: JNIEnv, _: JClass, label: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_string(env, checked(label_get_color(label as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Label_nativeSetColor(mut env: JNIEnv, _: JClass, label: jlong, color: JString) {
+    call(&mut env, (), |env| {
+        let color = c_string_arg(env, &color, "color")?;
+        unsafe { label_set_color(label as u64, c_string_ptr(&color)) };
+        Ok(())
+    })
+}
+
+// Item
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeNew(mut env: JNIEnv, _: JClass) -> jlong {
+    call(&mut env, 0, |_| Ok(checked(item_new())? as jlong))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeDestroy(mut env: JNIEnv, _: JClass, item: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { item_destroy(item as u64) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetUuid(mut env: JNIEnv, _: JClass, item: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_string(env, checked(item_get_uuid(item as u64))?) })
+}
+
+/// Names go through the length-delimited functions, since Java strings can hold NUL
+/// characters.
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetName(mut env: JNIEnv, _: JClass, item: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| {
+        let name = checked(unsafe { item_get_name_utf8(item as u64) })?;
+        let name = unsafe { name.into_vec() }.unwrap_or_default();
+        let name = env.new_string(String::from_utf8_lossy(&name)).map_err(describe)?;
+        Ok(name.into_raw())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetName(mut env: JNIEnv, _: JClass, item: jlong, name: JString) {
+    call(&mut env, (), |env| {
+        if name.is_null() {
+            return Err("name is NULL".to_string());
+        }
+        let name: String = env.get_string(&name).map_err(describe)?.into();
+        unsafe { item_set_name_utf8(item as u64, name.as_ptr(), name.len()) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetDueDate(mut env: JNIEnv, _: JClass, item: jlong) -> jobject {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_date(env, checked(item_get_due_date(item as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetDueDate(mut env: JNIEnv, _: JClass, item: jlong, has_due_date: jboolean, due_date: jlong) {
+    call(&mut env, (), |_| {
+        let due_date = optional_date(has_due_date, due_date);
+        unsafe { item_set_due_date(item as u64, date_ptr(&due_date)) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeDueDateIsAllDay(mut env: JNIEnv, _: JClass, item: jlong) -> jboolean {
+    call(&mut env, JNI_FALSE, |_| Ok(checked(unsafe { item_due_date_is_all_day(item as u64) })? as jboolean))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetDueDateZone(mut env: JNIEnv, _: JClass, item: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_string(env, checked(item_get_due_date_zone(item as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetDueDateInZone(mut env: JNIEnv, _: JClass, item: jlong, millis: jlong, zone: JString) {
+    call(&mut env, (), |env| {
+        let zone = c_string_arg(env, &zone, "zone")?;
+        unsafe { item_set_due_date_in_zone(item as u64, millis, c_string_ptr(&zone)) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetAllDayDueDate(mut env: JNIEnv, _: JClass, item: jlong, millis: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { item_set_all_day_due_date(item as u64, millis) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetCompletionDate(mut env: JNIEnv, _: JClass, item: jlong) -> jobject {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_date(env, checked(item_get_completion_date(item as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetCompletionDate(mut env: JNIEnv, _: JClass, item: jlong, has_completion_date: jboolean, completion_date: jlong) {
+    call(&mut env, (), |_| {
+        let completion_date = optional_date(has_completion_date, completion_date);
+        unsafe { item_set_completion_date(item as u64, date_ptr(&completion_date)) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeCompletionDateIsAllDay(mut env: JNIEnv, _: JClass, item: jlong) -> jboolean {
+    call(&mut env, JNI_FALSE, |_| Ok(checked(unsafe { item_completion_date_is_all_day(item as u64) })? as jboolean))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetCompletionDateZone(mut env: JNIEnv, _: JClass, item: jlong) -> jstring {
+    call(&mut env, ptr::null_mut(), |env| unsafe { take_string(env, checked(item_get_completion_date_zone(item as u64))?) })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetCompletionDateInZone(mut env: JNIEnv, _: JClass, item: jlong, millis: jlong, zone: JString) {
+    call(&mut env, (), |env| {
+        let zone = c_string_arg(env, &zone, "zone")?;
+        unsafe { item_set_completion_date_in_zone(item as u64, millis, c_string_ptr(&zone)) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetAllDayCompletionDate(mut env: JNIEnv, _: JClass, item: jlong, millis: jlong) {
+    call(&mut env, (), |_| {
+        unsafe { item_set_all_day_completion_date(item as u64, millis) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetPriority(mut env: JNIEnv, _: JClass, item: jlong) -> jint {
+    call(&mut env, 0, |_| Ok(checked(unsafe { item_get_priority(item as u64) })? as jint))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeSetPriority(mut env: JNIEnv, _: JClass, item: jlong, priority: jint) {
+    call(&mut env, (), |_| {
+        let priority = u8::try_from(priority).map_err(|_| format!("unknown priority {}", priority))?;
+        unsafe { item_set_priority(item as u64, priority) };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_mozilla_toodle_Item_nativeGetLabels(mut env: JNIEnv, _: JClass, item: jlong) -> jlongArray {
+    call(&mut env, ptr::null_mut(), |env| {
+        unsafe {
+            let count = checked(item_labels_count(item as u64))?;
+            let labels: Vec<u64> = (0..count).map(|index| item_label_at(item as u64, index)).collect();
+            handle_array(env, &labels)
+        }
+    })
+}
@@ -9,20 +9,44 @@
 // specific language governing permissions and limitations under the License.
 
 extern crate ffi_utils;
+#[macro_use]
+extern crate log;
 extern crate store;
 extern crate list;
 
+use std::ffi::CString;
 use std::os::raw::{
-    c_char
+    c_char,
+    c_void,
 };
+use std::ptr;
 use std::sync::{
     Arc,
 };
 
-use ffi_utils::strings::c_char_to_string;
-use list::ListManager;
+use ffi_utils::error::{
+    check_str,
+    guard,
+    last_error,
+};
+use ffi_utils::handles::HandleRegistry;
+use ffi_utils::logging::{
+    self,
+    LogCallback,
+    LogLevel,
+};
+use ffi_utils::strings::string_to_c_char_lossy;
+use list::{
+    ListManager,
+    ListManagerHandle,
+    MANAGERS,
+};
 use store::Store;
 
+pub mod executor;
+#[cfg(feature = "jni")]
+pub mod java;
+
 pub struct Toodle {
     list: ListManager
 }
@@ -35,20 +59,65 @@ impl Toodle {
     }
 }
 
+/// A toodle instance handed across the FFI, freed with `toodle_destroy`.
+pub type ToodleHandle = u64;
+
+pub static TOODLES: HandleRegistry<Toodle> = HandleRegistry::new("toodle");
+
+#[no_mangle]
+pub unsafe extern "C" fn new_toodle(uri: *const c_char) -> ToodleHandle {
+    guard(0, || {
+        let uri = check_str(uri, "uri")?;
+        Ok(TOODLES.insert(Toodle::new(uri)))
+    })
+}
+
 #[no_mangle]
-pub extern "C" fn new_toodle(uri: *const c_char) -> *mut Toodle {
-    let uri = c_char_to_string(uri);
-    Box::into_raw(Box::new(Toodle::new(uri)))
+pub unsafe extern "C" fn toodle_destroy(toodle: ToodleHandle) {
+    guard((), || TOODLES.destroy(toodle))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn toodle_destroy(toodle: *mut Toodle) {
-    let _ = Box::from_raw(toodle);
+pub unsafe extern "C" fn toodle_list(toodle: ToodleHandle) -> ListManagerHandle {
+    guard(0, || {
+        trace!("fetching list manager");
+        let list = TOODLES.get(toodle, |toodle| toodle.list.clone())?;
+        Ok(MANAGERS.insert(list))
+    })
 }
 
+/// Free a string returned by any of the library's functions.
 #[no_mangle]
-pub unsafe extern "C" fn toodle_list(toodle: *mut Toodle) -> *mut ListManager {
-    let toodle = &*toodle;
-    println!("fetching list manager");
-    Box::into_raw(Box::new(toodle.list.clone()))
+pub unsafe extern "C" fn toodle_string_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            let _ = CString::from_raw(s);
+        }
+        Ok(())
+    })
+}
+
+/// Pass the library's log messages at `level`, a `LogLevel`, or more severe to
+/// `callback`, along with `context`, replacing any callback set before. A NULL
+/// `callback` turns logging off, which is the default. The callback may be called
+/// from any thread, and may call this to replace itself; keep the old context alive
+/// until calls that had already started have returned.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_set_logger(callback: LogCallback, context: *mut c_void, level: u32) {
+    guard((), || {
+        let level = LogLevel::from_number(level)?;
+        logging::set_logger(callback, context, level)
+    })
+}
+
+/// Describe why the last call into the library on this thread failed, or return NULL
+/// if it succeeded. Functions that fail return NULL, 0 or nothing, so check this when
+/// such a result is unexpected. The returned string must be freed with
+/// `toodle_string_free`.
+#[no_mangle]
+pub extern "C" fn toodle_last_error() -> *mut c_char {
+    match last_error() {
+        Some(message) => string_to_c_char_lossy(message),
+        None => ptr::null_mut(),
+    }
 }
@@ -1,9 +1,173 @@
+#ifndef TOODLE_H
+#define TOODLE_H
+
+/* Generated by build.rs from the Rust sources. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
 #include <stdint.h>
+#include "items.h"
 #include "labels.h"
 
-struct toodle;
+/**
+ * How severe a log message is, from most to least. Levels cross the FFI as their
+ * numbers, since C can pass any value where it expects an enum.
+ */
+typedef enum LogLevel {
+  LogLevel_Error = 1,
+  LogLevel_Warn = 2,
+  LogLevel_Info = 3,
+  LogLevel_Debug = 4,
+  LogLevel_Trace = 5,
+} LogLevel;
+
+/**
+ * A toodle instance handed across the FFI, freed with `toodle_destroy`.
+ */
+typedef uint64_t ToodleHandle;
+
+/**
+ * Receives a log message, or is NULL to turn logging off. `target` names the
+ * module that logged the message, such as `list::items`, and `fields` holds its
+ * structured fields as space-separated `key=value` pairs, or is empty. `level`
+ * is a `LogLevel`. The strings are only valid for the duration of the call, which
+ * may come from any thread.
+ */
+typedef void (*LogCallback)(void *context,
+                            uint32_t level,
+                            const char *target,
+                            const char *message,
+                            const char *fields);
+
+/**
+ * An executor handed across the FFI, freed with `toodle_executor_destroy`.
+ */
+typedef uint64_t ExecutorHandle;
+
+/**
+ * A cancellation token handed across the FFI, freed with
+ * `toodle_cancellation_token_destroy`.
+ */
+typedef uint64_t CancellationTokenHandle;
+
+/**
+ * Receives the result of an operation queued with one of the `_async` functions.
+ * On success `error` is NULL and `result` is the handle the matching synchronous
+ * function would have returned, which the callback owns. Otherwise `result` is 0
+ * and `error` describes what went wrong, or is `"cancelled"` if the operation was
+ * cancelled; it is only valid for the duration of the call.
+ */
+typedef void (*CompletionCallback)(void *context, uint64_t result, const char *error);
+
+ToodleHandle new_toodle(const char *uri);
+
+void toodle_destroy(ToodleHandle toodle);
+
+ListManagerHandle toodle_list(ToodleHandle toodle);
+
+/**
+ * Free a string returned by any of the library's functions.
+ */
+void toodle_string_free(char *s);
+
+/**
+ * Pass the library's log messages at `level`, a `LogLevel`, or more severe to
+ * `callback`, along with `context`, replacing any callback set before. A NULL
+ * `callback` turns logging off, which is the default. The callback may be called
+ * from any thread, and may call this to replace itself; keep the old context alive
+ * until calls that had already started have returned.
+ */
+void toodle_set_logger(LogCallback callback, void *context, uint32_t level);
+
+/**
+ * Describe why the last call into the library on this thread failed, or return NULL
+ * if it succeeded. Functions that fail return NULL, 0 or nothing, so check this when
+ * such a result is unexpected. The returned string must be freed with
+ * `toodle_string_free`.
+ */
+char *toodle_last_error(void);
+
+/**
+ * Start `threads` threads to run operations queued with the `_async` functions.
+ * Returns 0 if `threads` is 0, otherwise an executor that must be freed with
+ * `toodle_executor_destroy`.
+ */
+ExecutorHandle toodle_executor_new(size_t threads);
+
+/**
+ * Wait for every queued operation to finish and stop the executor's threads. Cancel
+ * the operations first to avoid waiting for them. Called from a completion callback,
+ * this returns straight away and the threads stop once the queue is empty.
+ */
+void toodle_executor_destroy(ExecutorHandle executor);
+
+/**
+ * Make a token that can cancel any number of queued operations. Cancelled
+ * operations that haven't started yet don't run; those that have stop at their
+ * next check, keeping any changes made before then.
+ */
+CancellationTokenHandle toodle_cancellation_token_new(void);
+
+void toodle_cancellation_token_cancel(CancellationTokenHandle token);
+
+bool toodle_cancellation_token_is_cancelled(CancellationTokenHandle token);
+
+/**
+ * Free a token. Operations it was passed to keep their own reference to it, so
+ * this doesn't cancel them.
+ */
+void toodle_cancellation_token_destroy(CancellationTokenHandle token);
+
+/**
+ * Fetch every item as `list_manager_get_all_items` does, checking `token` between
+ * pages. The result is an `ItemListHandle`.
+ */
+bool list_manager_get_all_items_async(ExecutorHandle executor,
+                                      ListManagerHandle manager,
+                                      CancellationTokenHandle token,
+                                      CompletionCallback callback,
+                                      void *context);
+
+/**
+ * Create an item as `list_manager_create_item` does. The labels are read before
+ * this returns, so their handles may be freed straight away. The result is an
+ * `ItemHandle`.
+ */
+bool list_manager_create_item_async(ExecutorHandle executor,
+                                    ListManagerHandle manager,
+                                    const char *name,
+                                    const int64_t *due_date,
+                                    const int64_t *completion_date,
+                                    const LabelHandle *labels,
+                                    size_t label_count,
+                                    CancellationTokenHandle token,
+                                    CompletionCallback callback,
+                                    void *context);
+
+/**
+ * Import items from CSV as `list_manager_import_csv` does, stopping before the next
+ * item once `token` is cancelled. Items imported before then are kept. The result
+ * is a `CsvImportHandle`.
+ */
+bool list_manager_import_csv_async(ExecutorHandle executor,
+                                   ListManagerHandle manager,
+                                   const char *csv,
+                                   const char *mapping,
+                                   const char *date_format,
+                                   CancellationTokenHandle token,
+                                   CompletionCallback callback,
+                                   void *context);
 
-struct toodle* new_toodle(const char* uri);
-void toodle_destroy(struct toodle* toodle);
+/**
+ * Import a Markdown checklist as `list_manager_import_markdown` does, stopping
+ * before the next item once `token` is cancelled. Items imported before then are
+ * kept. The result is a `MarkdownImportHandle`.
+ */
+bool list_manager_import_markdown_async(ExecutorHandle executor,
+                                        ListManagerHandle manager,
+                                        const char *markdown,
+                                        CancellationTokenHandle token,
+                                        CompletionCallback callback,
+                                        void *context);
 
-struct list_manager* toodle_list(struct toodle* toodle);
+#endif /* TOODLE_H */
@@ -8,32 +8,36 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+#[macro_use]
+extern crate log;
 extern crate rusqlite;
 extern crate ffi_utils;
 
 use std::os::raw::{
     c_char
 };
-use std::rc::Rc;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use rusqlite::{
     Connection
 };
 
-use ffi_utils::strings::c_char_to_string;
+use ffi_utils::error::{
+    check_str,
+    guard,
+};
+use ffi_utils::handles::HandleRegistry;
 
 #[derive(Debug, Clone)]
-#[repr(C)]
 /// Store containing a SQLite connection
 pub struct Store {
-    pub conn: Rc<Mutex<Connection>>,
+    pub conn: Arc<Mutex<Connection>>,
     uri: Option<String>,
 }
 
 impl Drop for Store {
     fn drop(&mut self) {
-        println!("{:?} is being deallocated", self);
+        trace!(uri:? = self.uri; "closing store");
     }
 }
 
@@ -46,20 +50,26 @@ impl Store {
             &None => Connection::open_in_memory().expect("Expected an in memory connection"),
         };
         Store {
-            conn: Rc::new(Mutex::new(c)),
+            conn: Arc::new(Mutex::new(c)),
             uri: uri_string,
         }
     }
 }
 
+/// A store handed across the FFI, freed with `store_destroy`.
+pub type StoreHandle = u64;
+
+pub static STORES: HandleRegistry<Store> = HandleRegistry::new("store");
+
 #[no_mangle]
-pub extern "C" fn new_store(uri: *const c_char) -> *mut Store {
-    let uri = c_char_to_string(uri);
-    let store = Store::new(Some(uri));
-    Box::into_raw(Box::new(store))
+pub unsafe extern "C" fn new_store(uri: *const c_char) -> StoreHandle {
+    guard(0, || {
+        let uri = check_str(uri, "uri")?;
+        Ok(STORES.insert(Store::new(Some(uri))))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn store_destroy(data: *mut Store) {
-    let _ = Box::from_raw(data);
+pub unsafe extern "C" fn store_destroy(store: StoreHandle) {
+    guard((), || STORES.destroy(store))
 }
@@ -1,8 +1,19 @@
+#ifndef TOODLE_STORE_H
+#define TOODLE_STORE_H
+
+/* Generated by build.rs from the Rust sources. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
 #include <stdint.h>
 
-struct store;
+/**
+ * A store handed across the FFI, freed with `store_destroy`.
+ */
+typedef uint64_t StoreHandle;
 
-struct store* new_store(const char* uri);
-void store_destroy(struct store* store);
+StoreHandle new_store(const char *uri);
 
+void store_destroy(StoreHandle store);
 
+#endif /* TOODLE_STORE_H */
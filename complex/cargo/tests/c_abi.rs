@@ -0,0 +1,99 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Checks that the checked-in headers match the ones `build.rs` generates, then
+//! compiles `tests/c_abi/c_abi.c` against them and the static library with the system
+//! C compiler, runs it, and checks that it calls every function the headers declare.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Command;
+
+const HEADERS: &'static [&'static str] = &["src/toodle.h", "list/items.h", "list/labels.h", "store/store.h"];
+const PROGRAM: &'static str = "tests/c_abi/c_abi.c";
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+                    .expect(&format!("failed to read {:?}", path));
+    contents
+}
+
+/// The names of the functions declared in a generated header.
+fn declared_functions(header: &str) -> Vec<String> {
+    header.lines()
+          .filter(|line| !line.starts_with(|c: char| c.is_whitespace() || c == '#' || c == '/' || c == '*'))
+          .filter(|line| !line.starts_with("typedef"))
+          .filter_map(|line| line.find('(').map(|end| &line[..end]))
+          .filter_map(|declaration| declaration.rsplit(|c: char| c == ' ' || c == '*').next())
+          .map(|name| name.to_string())
+          .collect()
+}
+
+/// The directory this test's executable lives in, which is where `cargo test` builds
+/// the static library. Unlike `cargo build`, it doesn't copy it up a level.
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn test_headers_are_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let generated = Path::new(env!("OUT_DIR"));
+    let stale: Vec<&str> = HEADERS.iter()
+                                  .cloned()
+                                  .filter(|header| read(&root.join(header)) != read(&generated.join(header)))
+                                  .collect();
+    assert!(stale.is_empty(), "{:?} differ from the generated headers; build with TOODLE_UPDATE_HEADERS=1 to update them", stale);
+}
+
+#[test]
+fn test_program_calls_every_declared_function() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let program = read(&root.join(PROGRAM));
+    let mut missing = Vec::new();
+    for header in HEADERS.iter() {
+        let functions = declared_functions(&read(&root.join(header)));
+        assert!(!functions.is_empty(), "no functions found in {}", header);
+        missing.extend(functions.into_iter().filter(|name| !program.contains(&format!("{}(", name))));
+    }
+    assert!(missing.is_empty(), "{} doesn't call {:?}", PROGRAM, missing);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_c_program() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library_dir = library_dir();
+    let executable = library_dir.join("c_abi");
+    let compiler = env::var("CC").unwrap_or("cc".to_string());
+
+    let status = Command::new(&compiler)
+        .args(&["-std=c99", "-Wall", "-Wextra", "-pedantic", "-Werror"])
+        .arg("-I").arg(root.join("src"))
+        .arg("-I").arg(root.join("list"))
+        .arg("-I").arg(root.join("store"))
+        .arg(root.join(PROGRAM))
+        .arg(library_dir.join("libtoodle.a"))
+        .args(&["-lpthread", "-ldl", "-lm"])
+        .arg("-o").arg(&executable)
+        .status()
+        .expect(&format!("failed to run {}", compiler));
+    assert!(status.success(), "failed to compile {}", PROGRAM);
+
+    let output = Command::new(&executable).output().expect("failed to run the C program");
+    assert!(output.status.success(), "C program failed:\n{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,673 @@
+/* Copyright 2016 Mozilla
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+ * this file except in compliance with the License. You may obtain a copy of the
+ * License at http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed
+ * under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+ * CONDITIONS OF ANY KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations under the License. */
+
+/* Calls every function declared in the generated headers through the C ABI, the
+ * way the iOS app does, and checks the results. Built and run by tests/c_abi.rs. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "store.h"
+#include "toodle.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                          \
+    do {                                                                          \
+        if (!(condition)) {                                                       \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,      \
+                    #condition);                                                  \
+            failures++;                                                           \
+        }                                                                         \
+    } while (0)
+
+/* Compare a string returned by the library with `expected` and free it. */
+static int take_string_equals(char *s, const char *expected) {
+    int equal = s != NULL && strcmp(s, expected) == 0;
+    toodle_string_free(s);
+    return equal;
+}
+
+static int label_named(LabelHandle label, const char *name) {
+    return take_string_equals(label_get_name(label), name);
+}
+
+static LabelHandle find_label(ListManagerHandle manager, const char *name) {
+    LabelListHandle labels = list_manager_get_all_labels(manager);
+    LabelHandle found = 0;
+    size_t index;
+    for (index = 0; index < label_list_count(labels); index++) {
+        LabelHandle label = label_list_entry_at(labels, index);
+        if (found == 0 && label_named(label, name)) {
+            found = label;
+        } else {
+            label_destroy(label);
+        }
+    }
+    label_list_destroy(labels);
+    return found;
+}
+
+static ItemHandle find_item(ItemListHandle items, const char *name) {
+    size_t index;
+    for (index = 0; index < item_list_count(items); index++) {
+        ItemHandle item = item_list_entry_at(items, index);
+        if (take_string_equals(item_get_name(item), name)) {
+            return item;
+        }
+        item_destroy(item);
+    }
+    return 0;
+}
+
+static void test_errors(void) {
+    char *error;
+
+    CHECK(toodle_last_error() == NULL);
+    CHECK(label_list_count(0) == 0);
+    error = toodle_last_error();
+    CHECK(take_string_equals(error, "label list handle is 0"));
+}
+
+static int log_messages = 0;
+static int logged_uuid = 0;
+
+static void count_log_message(void *context, uint32_t level, const char *target,
+                              const char *message, const char *fields) {
+    CHECK(context == &log_messages);
+    CHECK(level >= LogLevel_Error && level <= LogLevel_Trace);
+    CHECK(target != NULL && message != NULL && fields != NULL);
+    if (strcmp(message, "creating item") == 0 && strstr(fields, "uuid=") != NULL) {
+        logged_uuid = 1;
+    }
+    log_messages++;
+}
+
+static void test_logging(ListManagerHandle manager) {
+    int logged;
+
+    /* Nothing is logged until a callback is set. */
+    item_destroy(list_manager_create_item(manager, "unlogged", NULL, NULL, NULL, 0));
+    CHECK(log_messages == 0);
+
+    toodle_set_logger(count_log_message, &log_messages, LogLevel_Trace);
+    item_destroy(list_manager_create_item(manager, "logged", NULL, NULL, NULL, 0));
+    CHECK(log_messages > 0);
+    CHECK(logged_uuid);
+
+    toodle_set_logger(count_log_message, &log_messages, LogLevel_Error);
+    logged = log_messages;
+    item_destroy(list_manager_create_item(manager, "quiet", NULL, NULL, NULL, 0));
+    CHECK(log_messages == logged);
+
+    toodle_set_logger(NULL, NULL, LogLevel_Trace);
+    item_destroy(list_manager_create_item(manager, "unlogged", NULL, NULL, NULL, 0));
+    CHECK(log_messages == logged);
+    CHECK(toodle_last_error() == NULL);
+
+    toodle_set_logger(count_log_message, &log_messages, 42);
+    CHECK(take_string_equals(toodle_last_error(), "unknown log level 42"));
+    item_destroy(list_manager_create_item(manager, "still unlogged", NULL, NULL, NULL, 0));
+    CHECK(log_messages == logged);
+}
+
+static void test_store(void) {
+    StoreHandle store = new_store(":memory:");
+    char *error;
+
+    CHECK(store != 0);
+    store_destroy(store);
+    store_destroy(0);
+    CHECK(toodle_last_error() == NULL);
+
+    /* Destroying a handle twice is reported rather than undefined. */
+    store_destroy(store);
+    error = toodle_last_error();
+    CHECK(error != NULL && strstr(error, "has been destroyed") != NULL);
+    toodle_string_free(error);
+}
+
+static void test_labels(ListManagerHandle manager) {
+    LabelHandle home = list_manager_create_label(manager, "home", "#ff0000");
+    LabelHandle work = list_manager_create_label(manager, "work", "#00ff00");
+    LabelListHandle labels;
+    size_t index;
+    int found_home = 0;
+    LabelC snapshot;
+    char *error;
+
+    CHECK(label_named(home, "home"));
+    CHECK(take_string_equals(label_get_color(home), "#ff0000"));
+    label_set_color(home, "#0000ff");
+    CHECK(take_string_equals(label_get_color(home), "#0000ff"));
+
+    labels = list_manager_get_all_labels(manager);
+    CHECK(label_list_count(labels) == 2);
+    for (index = 0; index < label_list_count(labels); index++) {
+        LabelHandle label = label_list_entry_at(labels, index);
+        if (label_named(label, "home")) {
+            found_home = 1;
+            CHECK(take_string_equals(label_get_color(label), "#ff0000"));
+        }
+        label_destroy(label);
+    }
+    CHECK(found_home);
+
+    snapshot = label_snapshot(work);
+    CHECK(strcmp(snapshot.name, "work") == 0);
+    CHECK(strcmp(snapshot.color, "#00ff00") == 0);
+    label_snapshot_free(snapshot);
+
+    label_list_destroy(labels);
+    label_list_destroy(0);
+    label_destroy(home);
+    label_destroy(work);
+    label_destroy(0);
+
+    /* A destroyed handle is rejected instead of being dereferenced. */
+    CHECK(label_get_name(home) == NULL);
+    error = toodle_last_error();
+    CHECK(error != NULL && strstr(error, "has been destroyed") != NULL);
+    toodle_string_free(error);
+}
+
+static void test_items(ListManagerHandle manager) {
+    LabelHandle home = find_label(manager, "home");
+    LabelHandle work = find_label(manager, "work");
+    LabelHandle both[2];
+    int64_t due = 1509883200345;
+    int64_t completed = 1509969600000;
+    int64_t *date;
+    ItemHandle item;
+    ItemHandle fetched;
+    ItemListHandle items;
+    LabelListHandle item_labels;
+    LabelHandle label;
+    ItemC snapshot;
+    ByteBuffer name;
+    char *uuid;
+
+    both[0] = home;
+    both[1] = work;
+
+    item = list_manager_create_item(manager, "buy milk", &due, NULL, both, 2);
+    CHECK(item != 0);
+    uuid = item_get_uuid(item);
+    CHECK(strlen(uuid) > 0);
+    CHECK(take_string_equals(item_get_name(item), "buy milk"));
+    date = item_get_due_date(item);
+    CHECK(date != NULL && *date == due);
+    item_date_destroy(date);
+    CHECK(item_get_completion_date(item) == NULL);
+    CHECK(item_labels_count(item) == 2);
+    label = item_label_at(item, 0);
+    CHECK(label != 0);
+    label_destroy(label);
+    item_labels = item_get_labels(item);
+    CHECK(label_list_count(item_labels) == 2);
+    label_list_destroy(item_labels);
+
+    snapshot = item_snapshot(item);
+    CHECK(strcmp(snapshot.uuid, uuid) == 0);
+    CHECK(strcmp(snapshot.name, "buy milk") == 0);
+    CHECK(snapshot.has_due_date && snapshot.due_date == due);
+    CHECK(!snapshot.has_completion_date);
+    CHECK(snapshot.labels_len == 2);
+    CHECK(strcmp(snapshot.labels[0].color, "#ff0000") == 0 || strcmp(snapshot.labels[1].color, "#ff0000") == 0);
+    item_snapshot_free(snapshot);
+    snapshot = item_snapshot(0);
+    CHECK(snapshot.uuid == NULL && snapshot.labels == NULL);
+    item_snapshot_free(snapshot);
+
+    /* A label array may be NULL when there are no labels. */
+    item_destroy(list_manager_create_item(manager, "call mum", NULL, NULL, NULL, 0));
+
+    /* Update with one label, a completion date and no due date, keeping the name. */
+    list_manager_update_item(manager, item, NULL, NULL, &completed, both, 1);
+    CHECK(take_string_equals(item_get_name(item), "buy milk"));
+    CHECK(item_get_due_date(item) == NULL);
+
+    items = list_manager_get_all_items(manager);
+    CHECK(item_list_count(items) == 2);
+    fetched = find_item(items, "buy milk");
+    CHECK(fetched != 0);
+    if (fetched != 0) {
+        CHECK(take_string_equals(item_get_uuid(fetched), uuid));
+        CHECK(item_get_due_date(fetched) == NULL);
+        date = item_get_completion_date(fetched);
+        CHECK(date != NULL && *date == completed);
+        item_date_destroy(date);
+        CHECK(item_labels_count(fetched) == 1);
+        label = item_label_at(fetched, 0);
+        CHECK(label != 0 && label_named(label, "home"));
+        label_destroy(label);
+        item_destroy(fetched);
+    }
+    item_list_destroy(items);
+
+    list_manager_update_item(manager, item, "buy oat milk", &due, NULL, NULL, 0);
+    items = list_manager_get_all_items(manager);
+    fetched = find_item(items, "buy oat milk");
+    CHECK(fetched != 0);
+    if (fetched != 0) {
+        CHECK(item_labels_count(fetched) == 0);
+        CHECK(item_get_completion_date(fetched) == NULL);
+        item_destroy(fetched);
+    }
+    item_list_destroy(items);
+    item_list_destroy(0);
+
+    /* Setters only change the local copy. */
+    item_set_name(item, "local");
+    item_set_due_date(item, &completed);
+    date = item_get_due_date(item);
+    CHECK(date != NULL && *date == completed);
+    item_date_destroy(date);
+    item_set_due_date(item, NULL);
+    CHECK(item_get_due_date(item) == NULL);
+    item_set_completion_date(item, &due);
+    date = item_get_completion_date(item);
+    CHECK(date != NULL && *date == due);
+    item_date_destroy(date);
+    item_set_completion_date(item, NULL);
+    CHECK(item_get_completion_date(item) == NULL);
+    CHECK(take_string_equals(item_get_name(item), "local"));
+    item_date_destroy(NULL);
+
+    /* All-day dates are the midnight UTC that starts their day. */
+    item_set_all_day_due_date(item, due);
+    date = item_get_due_date(item);
+    CHECK(date != NULL && *date == 1509840000000);
+    item_date_destroy(date);
+    CHECK(item_due_date_is_all_day(item));
+    CHECK(item_get_due_date_zone(item) == NULL);
+    item_set_all_day_completion_date(item, completed);
+    CHECK(item_completion_date_is_all_day(item));
+    CHECK(item_get_completion_date_zone(item) == NULL);
+
+    item_set_due_date_in_zone(item, due, "Australia/Sydney");
+    CHECK(!item_due_date_is_all_day(item));
+    CHECK(take_string_equals(item_get_due_date_zone(item), "Australia/Sydney"));
+    item_set_completion_date_in_zone(item, completed, "Europe/Berlin");
+    CHECK(!item_completion_date_is_all_day(item));
+    CHECK(take_string_equals(item_get_completion_date_zone(item), "Europe/Berlin"));
+    item_set_due_date_in_zone(item, due, "Australia/Sydnye");
+    CHECK(take_string_equals(toodle_last_error(), "unknown time zone \"Australia/Sydnye\""));
+    CHECK(take_string_equals(item_get_due_date_zone(item), "Australia/Sydney"));
+
+    snapshot = item_snapshot(item);
+    CHECK(snapshot.due_date == due && !snapshot.due_date_all_day);
+    CHECK(strcmp(snapshot.due_date_zone, "Australia/Sydney") == 0);
+    CHECK(strcmp(snapshot.completion_date_zone, "Europe/Berlin") == 0);
+    item_snapshot_free(snapshot);
+
+    /* Saving the same instant keeps its zone. */
+    list_manager_update_item(manager, item, NULL, &due, NULL, NULL, 0);
+    CHECK(take_string_equals(item_get_due_date_zone(item), "Australia/Sydney"));
+    CHECK(item_get_completion_date(item) == NULL);
+
+    toodle_string_free(uuid);
+    item_destroy(item);
+
+    item = item_new();
+    CHECK(take_string_equals(item_get_name(item), ""));
+    CHECK(item_labels_count(item) == 0);
+
+    /* Length-delimited names can hold NUL bytes, which C strings can't. */
+    item_set_name_utf8(item, (const uint8_t *)"a\0b", 3);
+    CHECK(item_get_name(item) == NULL);
+    name = item_get_name_utf8(item);
+    CHECK(name.data != NULL && name.len == 3 && memcmp(name.data, "a\0b", 3) == 0);
+    toodle_buffer_free(name);
+    item_destroy(item);
+    item_destroy(0);
+
+    label_destroy(home);
+    label_destroy(work);
+}
+
+static void test_csv(ListManagerHandle manager) {
+    char *csv = list_manager_export_csv(manager, "name,due_date", NULL, NULL, NULL);
+    CsvImportHandle import;
+    ItemHandle item;
+    char *message;
+
+    CHECK(csv != NULL && strncmp(csv, "name,due_date\r\n", 15) == 0);
+    toodle_string_free(csv);
+    CHECK(list_manager_export_csv(manager, "name,notes", NULL, NULL, NULL) == NULL);
+    CHECK(list_manager_export_csv(manager, NULL, "%Q", NULL, NULL) == NULL);
+    CHECK(list_manager_export_csv(manager, NULL, NULL, "%H", NULL) == NULL);
+    CHECK(list_manager_export_csv(manager, NULL, NULL, NULL, "||") == NULL);
+
+    import = list_manager_import_csv(manager, "Task,Tags\nwater plants,home;work\n,no name\n",
+                                     "Task=name,Tags=labels", NULL);
+    CHECK(csv_import_item_count(import) == 1);
+    item = csv_import_item_at(import, 0);
+    CHECK(take_string_equals(item_get_name(item), "water plants"));
+    item_destroy(item);
+    CHECK(csv_import_error_count(import) == 1);
+    CHECK(csv_import_error_row(import, 0) == 3);
+    message = csv_import_error_message(import, 0);
+    CHECK(message != NULL && strlen(message) > 0);
+    toodle_string_free(message);
+    csv_import_destroy(import);
+
+    csv = list_manager_export_csv(manager, "name,labels", NULL, "%d/%m/%Y", "|");
+    CHECK(csv != NULL && strstr(csv, "water plants,home|work\r\n") != NULL);
+    toodle_string_free(csv);
+
+    CHECK(list_manager_import_csv(manager, "", NULL, "%Y") == 0);
+    CHECK(take_string_equals(toodle_last_error(), "no header row"));
+    csv_import_destroy(0);
+}
+
+static void test_markdown(ListManagerHandle manager) {
+    MarkdownImportHandle import = list_manager_import_markdown(manager, "- [ ] plan party #party\n- [x] book venue #party\n  - [ ] pay deposit\n");
+    ItemHandle item;
+    LabelHandle party;
+    char *markdown;
+
+    CHECK(markdown_import_item_count(import) == 3);
+    item = markdown_import_item_at(import, 2);
+    CHECK(take_string_equals(item_get_name(item), "pay deposit"));
+    item_destroy(item);
+    /* The nested entry became a top-level item. */
+    CHECK(markdown_import_flattened_count(import) == 1);
+    CHECK(markdown_import_flattened_line(import, 0) == 3);
+    CHECK(take_string_equals(markdown_import_flattened_parent(import, 0), "book venue"));
+    markdown_import_destroy(import);
+    markdown_import_destroy(0);
+
+    party = find_label(manager, "party");
+    CHECK(party != 0);
+
+    markdown = list_manager_export_markdown(manager, party);
+    CHECK(markdown != NULL && strcmp(markdown, "# party\n\n- [ ] plan party #party\n- [x] book venue #party\n") == 0);
+    toodle_string_free(markdown);
+    label_destroy(party);
+
+    markdown = list_manager_export_markdown(manager, 0);
+    CHECK(markdown != NULL && strstr(markdown, "- [ ] plan party #party\n") != NULL);
+    toodle_string_free(markdown);
+}
+
+static void test_quick_add(ListManagerHandle manager) {
+    /* Wednesday 2017-11-01, 10:30 in New York. */
+    int64_t now = 1509546600000;
+    QuickAddHandle added = list_manager_quick_add(manager, "Buy bread #home #bakery !high due fri", now, "America/New_York");
+    ItemHandle item;
+    LabelHandle bakery;
+    int64_t *due;
+
+    CHECK(added != 0);
+    CHECK(take_string_equals(quick_add_get_description(added),
+                             "label \"home\", label \"bakery\", priority high, due \"fri\" (2017-11-03)"));
+    item = quick_add_get_item(added);
+    CHECK(take_string_equals(item_get_name(item), "Buy bread"));
+    CHECK(item_labels_count(item) == 2);
+    CHECK(item_get_priority(item) == 3);
+    due = item_get_due_date(item);
+    CHECK(due != NULL && *due == 1509667200000);
+    item_date_destroy(due);
+    item_set_priority(item, 1);
+    CHECK(item_get_priority(item) == 1);
+    item_set_priority(item, 0);
+    CHECK(item_get_priority(item) == 0);
+    item_destroy(item);
+    quick_add_destroy(added);
+    quick_add_destroy(0);
+
+    bakery = find_label(manager, "bakery");
+    CHECK(bakery != 0);
+    label_destroy(bakery);
+
+    CHECK(list_manager_quick_add(manager, "#home !low", now, "America/New_York") == 0);
+    CHECK(take_string_equals(toodle_last_error(), "no name given"));
+    CHECK(quick_add_get_item(0) == 0);
+    CHECK(quick_add_get_description(0) == NULL);
+}
+
+static bool count_item(void *context, const ItemC *item) {
+    size_t *count = context;
+    CHECK(item != NULL && item->uuid != NULL && item->name != NULL);
+    (*count)++;
+    return *count < 2;
+}
+
+static void test_cursors(ListManagerHandle manager) {
+    ItemListHandle items = list_manager_get_all_items(manager);
+    size_t total = item_list_count(items);
+    ItemCursorHandle cursor = list_manager_item_cursor(manager, 2);
+    ItemListHandle page;
+    ItemHandle item;
+    size_t seen = 0;
+    size_t visited = 0;
+
+    item_list_destroy(items);
+    CHECK(total > 2);
+    CHECK(item_cursor_total_count(cursor) == total);
+    CHECK(item_cursor_page_size(cursor) == 2);
+
+    page = item_cursor_next_page(cursor);
+    CHECK(item_list_count(page) == 2);
+    seen += item_list_count(page);
+    item_list_destroy(page);
+    while ((item = item_cursor_next(cursor)) != 0) {
+        seen++;
+        item_destroy(item);
+    }
+    CHECK(seen == total);
+    CHECK(toodle_last_error() == NULL);
+    item_cursor_destroy(cursor);
+    item_cursor_destroy(0);
+
+    CHECK(list_manager_item_cursor(manager, 0) == 0);
+    CHECK(take_string_equals(toodle_last_error(), "page size is 0"));
+
+    /* The callback stops iterating by returning false. */
+    CHECK(list_manager_for_each_item(manager, 1, count_item, &visited) == 2);
+    CHECK(visited == 2);
+}
+
+typedef struct {
+    int calls;
+    uint64_t result;
+    char error[64];
+} Completion;
+
+static void complete(void *context, uint64_t result, const char *error) {
+    Completion *completion = context;
+    completion->calls++;
+    completion->result = result;
+    completion->error[0] = '\0';
+    if (error != NULL) {
+        strncat(completion->error, error, sizeof(completion->error) - 1);
+    }
+}
+
+static void test_async(ListManagerHandle manager) {
+    ExecutorHandle executor = toodle_executor_new(1);
+    CancellationTokenHandle token = toodle_cancellation_token_new();
+    LabelHandle home = find_label(manager, "home");
+    Completion items = {0, 0, ""};
+    Completion created = {0, 0, ""};
+    Completion csv = {0, 0, ""};
+    Completion markdown = {0, 0, ""};
+    Completion cancelled = {0, 0, ""};
+
+    CHECK(executor != 0);
+    CHECK(list_manager_get_all_items_async(executor, manager, 0, complete, &items));
+    CHECK(list_manager_create_item_async(executor, manager, "sent later", NULL, NULL, &home, 1, 0, complete, &created));
+    CHECK(list_manager_import_csv_async(executor, manager, "Task\nwash car\n", "Task=name", NULL, 0, complete, &csv));
+    CHECK(!toodle_cancellation_token_is_cancelled(token));
+    toodle_cancellation_token_cancel(token);
+    CHECK(toodle_cancellation_token_is_cancelled(token));
+    CHECK(list_manager_import_markdown_async(executor, manager, "- [ ] never\n", token, complete, &cancelled));
+    toodle_cancellation_token_destroy(token);
+    toodle_cancellation_token_destroy(0);
+
+    /* Invalid arguments fail straight away, without a callback. */
+    CHECK(!list_manager_import_markdown_async(executor, manager, NULL, 0, complete, &markdown));
+    CHECK(take_string_equals(toodle_last_error(), "markdown is NULL"));
+    CHECK(!list_manager_import_markdown_async(executor, manager, "- [ ] later\n", 0, NULL, &markdown));
+    CHECK(take_string_equals(toodle_last_error(), "callback is NULL"));
+    CHECK(list_manager_import_markdown_async(executor, manager, "- [ ] later\n", 0, complete, &markdown));
+
+    /* Destroying the executor waits for everything queued on it. */
+    toodle_executor_destroy(executor);
+    toodle_executor_destroy(0);
+    label_destroy(home);
+
+    CHECK(items.calls == 1 && items.result != 0 && items.error[0] == '\0');
+    CHECK(item_list_count(items.result) > 0);
+    item_list_destroy(items.result);
+    CHECK(created.calls == 1 && item_labels_count(created.result) == 1);
+    CHECK(take_string_equals(item_get_name(created.result), "sent later"));
+    item_destroy(created.result);
+    CHECK(csv.calls == 1 && csv_import_item_count(csv.result) == 1);
+    csv_import_destroy(csv.result);
+    CHECK(markdown.calls == 1 && markdown_import_item_count(markdown.result) == 1);
+    markdown_import_destroy(markdown.result);
+    CHECK(cancelled.calls == 1 && cancelled.result == 0 && strcmp(cancelled.error, "cancelled") == 0);
+    CHECK(toodle_executor_new(0) == 0);
+}
+
+static void test_attachments(ListManagerHandle manager) {
+    ItemHandle item = list_manager_create_item(manager, "renew passport", NULL, NULL, NULL, 0);
+    AttachmentWriterHandle writer = list_manager_attachment_writer(manager, item, "photo.jpg", "image/jpeg");
+    AttachmentHandle attachment;
+    AttachmentHandle copy;
+    AttachmentListHandle attachments;
+    AttachmentReaderHandle reader;
+    char contents[32] = "";
+    char *id;
+    size_t read = 0;
+    size_t chunk;
+
+    CHECK(writer != 0);
+    CHECK(attachment_writer_write(writer, (const uint8_t *)"not really ", 11));
+    CHECK(attachment_writer_write(writer, (const uint8_t *)"a photo", 7));
+    attachment = attachment_writer_finish(writer);
+    CHECK(attachment != 0);
+    CHECK(attachment_get_size(attachment) == 18);
+    CHECK(take_string_equals(attachment_get_name(attachment), "photo.jpg"));
+    CHECK(take_string_equals(attachment_get_mime_type(attachment), "image/jpeg"));
+    CHECK(take_string_equals(attachment_get_hash(attachment),
+                             "8edd7daf0eb75502845657a637ad9e62b78a8dcdb67a1c6861fb7c91752d519a"));
+
+    /* Read it back a few bytes at a time. */
+    reader = list_manager_attachment_reader(manager, attachment);
+    while ((chunk = attachment_reader_read(reader, (uint8_t *)contents + read, 5)) > 0) {
+        read += chunk;
+    }
+    CHECK(read == 18 && strcmp(contents, "not really a photo") == 0);
+    attachment_reader_destroy(reader);
+    attachment_reader_destroy(0);
+
+    /* A writer can be abandoned without saving anything. */
+    writer = list_manager_attachment_writer(manager, item, "draft.txt", NULL);
+    CHECK(attachment_writer_write(writer, (const uint8_t *)"draft", 5));
+    attachment_writer_destroy(writer);
+    attachment_writer_destroy(0);
+
+    attachments = list_manager_get_item_attachments(manager, item);
+    CHECK(attachment_list_count(attachments) == 1);
+    copy = attachment_list_entry_at(attachments, 0);
+    id = attachment_get_id(attachment);
+    CHECK(take_string_equals(attachment_get_id(copy), id));
+    toodle_string_free(id);
+    attachment_destroy(copy);
+    attachment_list_destroy(attachments);
+    attachment_list_destroy(0);
+
+    CHECK(list_manager_delete_attachment(manager, attachment));
+    CHECK(!list_manager_delete_attachment(manager, attachment));
+    CHECK(list_manager_attachment_reader(manager, attachment) == 0);
+    toodle_string_free(toodle_last_error());
+    attachment_destroy(attachment);
+    attachment_destroy(0);
+
+    /* Nothing is left to purge, and attachments can also live in a directory. */
+    CHECK(list_manager_purge_attachments(manager) == 0);
+    list_manager_set_attachment_directory(manager, NULL);
+    CHECK(take_string_equals(toodle_last_error(), "directory is NULL"));
+    item_destroy(item);
+}
+
+static void test_date_phrases(void) {
+    /* Wednesday 2017-11-01, 10:30 in New York. */
+    int64_t now = 1509546600000;
+    DatePhraseC date = {0, false};
+    ItemHandle item = item_new();
+    int64_t *due;
+
+    CHECK(toodle_parse_date_phrase("next friday", now, "America/New_York", &date));
+    CHECK(date.all_day && date.millis == 1510272000000);
+    CHECK(toodle_parse_date_phrase("tomorrow 5pm", now, "America/New_York", &date));
+    CHECK(!date.all_day && date.millis == 1509656400000);
+    CHECK(!toodle_parse_date_phrase("feb 30", now, "America/New_York", &date));
+    CHECK(take_string_equals(toodle_last_error(), "no such day \"feb 30\""));
+
+    CHECK(item_set_due_date_phrase(item, "in 2 hours", now, "Europe/London"));
+    due = item_get_due_date(item);
+    CHECK(due != NULL && *due == now + 2 * 60 * 60 * 1000);
+    item_date_destroy(due);
+    CHECK(take_string_equals(item_get_due_date_zone(item), "Europe/London"));
+    CHECK(!item_set_due_date_phrase(item, NULL, now, "Europe/London"));
+    CHECK(take_string_equals(toodle_last_error(), "phrase is NULL"));
+    item_destroy(item);
+}
+
+static void test_buffers(ListManagerHandle manager) {
+    ByteBuffer items = list_manager_get_all_items_buffer(manager);
+    ByteBuffer labels = list_manager_get_all_labels_buffer(manager);
+    ByteBuffer failed = list_manager_get_all_items_buffer(0);
+
+    /* Both lists are non-empty, so each buffer starts with field 1 as a message. */
+    CHECK(items.data != NULL && items.len > 2 && items.data[0] == 0x0a);
+    CHECK(labels.data != NULL && labels.len > 2 && labels.data[0] == 0x0a);
+    CHECK(failed.data == NULL && failed.len == 0);
+    toodle_buffer_free(items);
+    toodle_buffer_free(labels);
+    toodle_buffer_free(failed);
+}
+
+int main(void) {
+    ToodleHandle toodle = new_toodle(":memory:");
+    ListManagerHandle manager = toodle_list(toodle);
+
+    test_store();
+    test_errors();
+    test_labels(manager);
+    test_items(manager);
+    test_date_phrases();
+    test_csv(manager);
+    test_markdown(manager);
+    test_quick_add(manager);
+    test_cursors(manager);
+    test_async(manager);
+    test_attachments(manager);
+    test_buffers(manager);
+    test_logging(manager);
+
+    list_manager_destroy(manager);
+    list_manager_destroy(0);
+    toodle_destroy(toodle);
+    toodle_destroy(0);
+    toodle_string_free(NULL);
+
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
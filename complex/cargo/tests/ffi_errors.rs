@@ -0,0 +1,662 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Passes NULLs, 0 and destroyed handles and provokes panics through the C ABI,
+//! checking that each call fails with a sentinel value and a message from
+//! `toodle_last_error`.
+
+extern crate list;
+extern crate store;
+extern crate toodle;
+
+use std::ffi::{
+    CStr,
+    CString,
+};
+use std::os::raw::{
+    c_char,
+    c_void,
+};
+use std::ptr;
+
+use list::{
+    list_manager_destroy,
+    ListManagerHandle,
+};
+use list::attachments::*;
+use list::buffers::*;
+use list::csv::*;
+use list::date_phrases::*;
+use list::cursor::*;
+use list::items::*;
+use list::labels::*;
+use list::markdown::*;
+use list::quick_add::*;
+use store::*;
+use toodle::*;
+use toodle::executor::*;
+
+fn c_string(s: &str) -> CString {
+    CString::new(s).unwrap()
+}
+
+unsafe fn take_string(s: *mut c_char) -> String {
+    assert!(!s.is_null());
+    let string = CStr::from_ptr(s).to_string_lossy().into_owned();
+    toodle_string_free(s);
+    string
+}
+
+fn last_error() -> Option<String> {
+    unsafe {
+        let error = toodle_last_error();
+        if error.is_null() {
+            None
+        } else {
+            Some(take_string(error))
+        }
+    }
+}
+
+fn assert_error(expected: &str) {
+    assert_eq!(last_error(), Some(expected.to_string()));
+}
+
+fn assert_panicked(expected: &str) {
+    let error = last_error().expect("expected an error");
+    assert!(error.starts_with(&format!("panic: {}", expected)), "unexpected error {:?}", error);
+}
+
+fn assert_destroyed(kind: &str) {
+    let error = last_error().expect("expected an error");
+    assert!(error.starts_with(&format!("{} handle 0x", kind)) && error.ends_with(" has been destroyed"),
+            "unexpected error {:?}", error);
+}
+
+unsafe fn with_manager<F: FnOnce(ListManagerHandle)>(f: F) {
+    let uri = c_string(":memory:");
+    let toodle = new_toodle(uri.as_ptr());
+    let manager = toodle_list(toodle);
+    f(manager);
+    list_manager_destroy(manager);
+    toodle_destroy(toodle);
+}
+
+#[test]
+fn test_unknown_log_level() {
+    unsafe {
+        toodle_set_logger(None, ptr::null_mut(), 0);
+        assert_error("unknown log level 0");
+        toodle_set_logger(None, ptr::null_mut(), 6);
+        assert_error("unknown log level 6");
+    }
+}
+
+#[test]
+fn test_null_toodle_and_store() {
+    unsafe {
+        assert_eq!(new_toodle(ptr::null()), 0);
+        assert_error("uri is NULL");
+        assert_eq!(toodle_list(0), 0);
+        assert_error("toodle handle is 0");
+        assert_eq!(new_store(ptr::null()), 0);
+        assert_error("uri is NULL");
+
+        // Destroying 0 is allowed and clears the previous error.
+        toodle_destroy(0);
+        assert_eq!(last_error(), None);
+    }
+}
+
+#[test]
+fn test_null_labels() {
+    unsafe {
+        let color = c_string("#000000");
+        assert_eq!(list_manager_create_label(0, color.as_ptr(), color.as_ptr()), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(list_manager_get_all_labels(0), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(label_list_count(0), 0);
+        assert_error("label list handle is 0");
+        assert_eq!(label_list_entry_at(0, 0), 0);
+        assert_error("label list handle is 0");
+        assert!(label_get_name(0).is_null());
+        assert_error("label handle is 0");
+        assert!(label_get_color(0).is_null());
+        assert_error("label handle is 0");
+        label_set_color(0, color.as_ptr());
+        assert_error("label handle is 0");
+        let snapshot = label_snapshot(0);
+        assert!(snapshot.name.is_null() && snapshot.color.is_null());
+        assert_error("label handle is 0");
+        label_snapshot_free(snapshot);
+
+        with_manager(|manager| {
+            assert_eq!(list_manager_create_label(manager, ptr::null(), color.as_ptr()), 0);
+            assert_error("name is NULL");
+            let name = c_string("home");
+            let label = list_manager_create_label(manager, name.as_ptr(), color.as_ptr());
+            assert!(label != 0);
+            assert_eq!(last_error(), None);
+            label_set_color(label, ptr::null());
+            assert_error("color is NULL");
+            assert_eq!(take_string(label_get_color(label)), "#000000");
+            label_destroy(label);
+
+            assert_eq!(list_manager_create_label(manager, name.as_ptr(), color.as_ptr()), 0);
+            assert_error("label wasn't saved");
+        });
+    }
+}
+
+#[test]
+fn test_null_items() {
+    unsafe {
+        let name = c_string("item");
+        assert!(item_get_uuid(0).is_null());
+        assert_error("item handle is 0");
+        assert!(item_get_name(0).is_null());
+        assert_error("item handle is 0");
+        item_set_name(0, name.as_ptr());
+        assert_error("item handle is 0");
+        assert!(item_get_due_date(0).is_null());
+        assert_error("item handle is 0");
+        item_set_due_date(0, ptr::null());
+        assert_error("item handle is 0");
+        assert!(item_get_completion_date(0).is_null());
+        assert_error("item handle is 0");
+        item_set_completion_date(0, ptr::null());
+        assert_error("item handle is 0");
+        assert!(!item_due_date_is_all_day(0));
+        assert_error("item handle is 0");
+        assert!(item_get_due_date_zone(0).is_null());
+        assert_error("item handle is 0");
+        let utc = c_string("UTC");
+        item_set_due_date_in_zone(0, 0, utc.as_ptr());
+        assert_error("item handle is 0");
+        item_set_all_day_due_date(0, 0);
+        assert_error("item handle is 0");
+        assert!(!item_completion_date_is_all_day(0));
+        assert_error("item handle is 0");
+        assert!(item_get_completion_date_zone(0).is_null());
+        assert_error("item handle is 0");
+        item_set_completion_date_in_zone(0, 0, utc.as_ptr());
+        assert_error("item handle is 0");
+        item_set_all_day_completion_date(0, 0);
+        assert_error("item handle is 0");
+        assert_eq!(item_get_labels(0), 0);
+        assert_error("item handle is 0");
+        assert_eq!(item_labels_count(0), 0);
+        assert_error("item handle is 0");
+        assert_eq!(item_label_at(0, 0), 0);
+        assert_error("item handle is 0");
+        let snapshot = item_snapshot(0);
+        assert!(snapshot.uuid.is_null() && snapshot.name.is_null() && snapshot.labels.is_null());
+        assert_error("item handle is 0");
+        item_snapshot_free(snapshot);
+        assert_eq!(last_error(), None);
+        assert_eq!(item_list_count(0), 0);
+        assert_error("item list handle is 0");
+        assert_eq!(item_list_entry_at(0, 0), 0);
+        assert_error("item list handle is 0");
+        assert_eq!(list_manager_get_all_items(0), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(list_manager_create_item(0, name.as_ptr(), ptr::null(), ptr::null(), ptr::null(), 0), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(list_manager_item_cursor(0, 10), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(item_cursor_total_count(0), 0);
+        assert_error("item cursor handle is 0");
+        assert_eq!(item_cursor_page_size(0), 0);
+        assert_error("item cursor handle is 0");
+        assert_eq!(item_cursor_next(0), 0);
+        assert_error("item cursor handle is 0");
+        assert_eq!(item_cursor_next_page(0), 0);
+        assert_error("item cursor handle is 0");
+        assert_eq!(list_manager_for_each_item(0, 10, None, ptr::null_mut()), 0);
+        assert_error("callback is NULL");
+        list_manager_update_item(0, 0, ptr::null(), ptr::null(), ptr::null(), ptr::null(), 0);
+        assert_error("list manager handle is 0");
+
+        assert!(item_get_name_utf8(0).data.is_null());
+        assert_error("item handle is 0");
+        item_set_name_utf8(0, ptr::null(), 0);
+        assert_error("item handle is 0");
+
+        let item = item_new();
+        item_set_name(item, ptr::null());
+        assert_error("name is NULL");
+        item_set_name_utf8(item, ptr::null(), 1);
+        assert_error("name is NULL");
+        item_set_due_date_in_zone(item, 0, ptr::null());
+        assert_error("zone is NULL");
+        item_set_completion_date_in_zone(item, 0, name.as_ptr());
+        assert_error("unknown time zone \"item\"");
+        item_set_all_day_due_date(item, i64::max_value());
+        assert_error(&format!("{} milliseconds is out of range", i64::max_value()));
+        item_destroy(item);
+
+        with_manager(|manager| {
+            assert_eq!(list_manager_create_item(manager, ptr::null(), ptr::null(), ptr::null(), ptr::null(), 0), 0);
+            assert_error("name is NULL");
+            assert_eq!(list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), ptr::null(), 2), 0);
+            assert_error("labels is NULL");
+            assert_eq!(list_manager_item_cursor(manager, 0), 0);
+            assert_error("page size is 0");
+
+            // The end of a cursor isn't an error.
+            let cursor = list_manager_item_cursor(manager, 10);
+            assert_eq!(item_cursor_next(cursor), 0);
+            assert_eq!(last_error(), None);
+            item_cursor_destroy(cursor);
+            item_cursor_destroy(cursor);
+            assert_destroyed("item cursor");
+
+            let color = c_string("#000000");
+            let label = list_manager_create_label(manager, name.as_ptr(), color.as_ptr());
+            let labels = [label, 0];
+            assert_eq!(list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), labels.as_ptr(), 2), 0);
+            assert_error("label handle is 0");
+
+            let item = list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), labels.as_ptr(), 1);
+            assert!(item != 0);
+            list_manager_update_item(manager, 0, ptr::null(), ptr::null(), ptr::null(), ptr::null(), 0);
+            assert_error("item handle is 0");
+            list_manager_update_item(manager, item, ptr::null(), ptr::null(), ptr::null(), labels.as_ptr(), 2);
+            assert_error("label handle is 0");
+            // A failed update leaves the item untouched.
+            assert_eq!(item_labels_count(item), 1);
+            item_destroy(item);
+            label_destroy(label);
+        });
+    }
+}
+
+#[test]
+fn test_null_imports_and_exports() {
+    unsafe {
+        assert!(list_manager_export_csv(0, ptr::null(), ptr::null(), ptr::null(), ptr::null()).is_null());
+        assert_error("list manager handle is 0");
+        assert_eq!(list_manager_import_csv(0, ptr::null(), ptr::null(), ptr::null()), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(csv_import_item_count(0), 0);
+        assert_error("CSV import handle is 0");
+        assert_eq!(csv_import_item_at(0, 0), 0);
+        assert_error("CSV import handle is 0");
+        assert_eq!(csv_import_error_count(0), 0);
+        assert_error("CSV import handle is 0");
+        assert_eq!(csv_import_error_row(0, 0), 0);
+        assert_error("CSV import handle is 0");
+        assert!(csv_import_error_message(0, 0).is_null());
+        assert_error("CSV import handle is 0");
+        assert!(list_manager_export_markdown(0, 0).is_null());
+        assert_error("list manager handle is 0");
+        assert_eq!(list_manager_import_markdown(0, ptr::null()), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(markdown_import_item_count(0), 0);
+        assert_error("Markdown import handle is 0");
+        assert_eq!(markdown_import_item_at(0, 0), 0);
+        assert_error("Markdown import handle is 0");
+        assert_eq!(markdown_import_flattened_count(0), 0);
+        assert_error("Markdown import handle is 0");
+        assert_eq!(markdown_import_flattened_line(0, 0), 0);
+        assert_error("Markdown import handle is 0");
+        assert!(markdown_import_flattened_parent(0, 0).is_null());
+        assert_error("Markdown import handle is 0");
+        assert!(list_manager_get_all_items_buffer(0).data.is_null());
+        assert_error("list manager handle is 0");
+        assert!(list_manager_get_all_labels_buffer(0).data.is_null());
+        assert_error("list manager handle is 0");
+
+        with_manager(|manager| {
+            assert_eq!(list_manager_import_csv(manager, ptr::null(), ptr::null(), ptr::null()), 0);
+            assert_error("csv is NULL");
+            let columns = c_string("name,notes");
+            assert!(list_manager_export_csv(manager, columns.as_ptr(), ptr::null(), ptr::null(), ptr::null()).is_null());
+            assert_error("unknown column \"notes\"");
+            let date_format = c_string("%Q");
+            assert!(list_manager_export_csv(manager, ptr::null(), date_format.as_ptr(), ptr::null(), ptr::null()).is_null());
+            assert_error("invalid date format \"%Q\"");
+            let day_format = c_string("%d/%m/%Y %H:%M");
+            assert!(list_manager_export_csv(manager, ptr::null(), ptr::null(), day_format.as_ptr(), ptr::null()).is_null());
+            assert_error("invalid date format \"%d/%m/%Y %H:%M\"");
+            let label_separator = c_string("; ");
+            assert!(list_manager_export_csv(manager, ptr::null(), ptr::null(), ptr::null(), label_separator.as_ptr()).is_null());
+            assert_error("label_separator \"; \" isn't a single character");
+            let csv = c_string("");
+            assert_eq!(list_manager_import_csv(manager, csv.as_ptr(), ptr::null(), ptr::null()), 0);
+            assert_error("no header row");
+            let mapping = c_string("Task");
+            assert_eq!(list_manager_import_csv(manager, csv.as_ptr(), mapping.as_ptr(), ptr::null()), 0);
+            assert_error("malformed mapping \"Task\", expected header=column");
+            assert_eq!(list_manager_import_markdown(manager, ptr::null()), 0);
+            assert_error("markdown is NULL");
+        });
+    }
+}
+
+extern "C" fn ignore_completion(_context: *mut c_void, _result: u64, _error: *const c_char) {}
+
+#[test]
+fn test_null_async() {
+    unsafe {
+        let markdown = c_string("- [ ] item");
+        assert_eq!(toodle_executor_new(0), 0);
+        assert_error("thread count is 0");
+        toodle_cancellation_token_cancel(0);
+        assert_error("cancellation token handle is 0");
+        assert!(!toodle_cancellation_token_is_cancelled(0));
+        assert_error("cancellation token handle is 0");
+        assert!(!list_manager_get_all_items_async(0, 0, 0, Some(ignore_completion), ptr::null_mut()));
+        assert_error("list manager handle is 0");
+
+        with_manager(|manager| {
+            let executor = toodle_executor_new(1);
+            assert!(!list_manager_get_all_items_async(0, manager, 0, Some(ignore_completion), ptr::null_mut()));
+            assert_error("executor handle is 0");
+            assert!(!list_manager_get_all_items_async(executor, manager, 0, None, ptr::null_mut()));
+            assert_error("callback is NULL");
+            assert!(!list_manager_create_item_async(executor, manager, ptr::null(), ptr::null(), ptr::null(), ptr::null(), 0, 0, Some(ignore_completion), ptr::null_mut()));
+            assert_error("name is NULL");
+            assert!(!list_manager_create_item_async(executor, manager, markdown.as_ptr(), ptr::null(), ptr::null(), ptr::null(), 1, 0, Some(ignore_completion), ptr::null_mut()));
+            assert_error("labels is NULL");
+            assert!(!list_manager_import_csv_async(executor, manager, ptr::null(), ptr::null(), ptr::null(), 0, Some(ignore_completion), ptr::null_mut()));
+            assert_error("csv is NULL");
+            assert!(!list_manager_import_markdown_async(executor, manager, ptr::null(), 0, Some(ignore_completion), ptr::null_mut()));
+            assert_error("markdown is NULL");
+
+            let token = toodle_cancellation_token_new();
+            toodle_cancellation_token_destroy(token);
+            assert!(!list_manager_import_markdown_async(executor, manager, markdown.as_ptr(), token, Some(ignore_completion), ptr::null_mut()));
+            assert_destroyed("cancellation token");
+
+            toodle_executor_destroy(executor);
+            assert_eq!(last_error(), None);
+            assert!(!list_manager_import_markdown_async(executor, manager, markdown.as_ptr(), 0, Some(ignore_completion), ptr::null_mut()));
+            assert_destroyed("executor");
+        });
+    }
+}
+
+#[test]
+fn test_null_attachments() {
+    unsafe {
+        let name = c_string("photo.jpg");
+        assert_eq!(list_manager_attachment_writer(0, 0, name.as_ptr(), ptr::null()), 0);
+        assert_error("list manager handle is 0");
+        assert!(!attachment_writer_write(0, b"data".as_ptr(), 4));
+        assert_error("attachment writer handle is 0");
+        assert_eq!(attachment_writer_finish(0), 0);
+        assert_error("attachment writer handle is 0");
+        assert_eq!(list_manager_get_item_attachments(0, 0), 0);
+        assert_error("list manager handle is 0");
+        assert_eq!(attachment_list_count(0), 0);
+        assert_error("attachment list handle is 0");
+        assert!(attachment_get_name(0).is_null());
+        assert_error("attachment handle is 0");
+        assert_eq!(attachment_get_size(0), 0);
+        assert_error("attachment handle is 0");
+        assert_eq!(attachment_reader_read(0, ptr::null_mut(), 0), 0);
+        assert_error("attachment reader handle is 0");
+        assert_eq!(list_manager_purge_attachments(0), 0);
+        assert_error("list manager handle is 0");
+
+        with_manager(|manager| {
+            assert_eq!(list_manager_attachment_writer(manager, 0, name.as_ptr(), ptr::null()), 0);
+            assert_error("item handle is 0");
+            let item = list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), ptr::null(), 0);
+            assert_eq!(list_manager_attachment_writer(manager, item, ptr::null(), ptr::null()), 0);
+            assert_error("name is NULL");
+            list_manager_set_attachment_directory(manager, ptr::null());
+            assert_error("directory is NULL");
+
+            let writer = list_manager_attachment_writer(manager, item, name.as_ptr(), ptr::null());
+            assert!(!attachment_writer_write(writer, ptr::null(), 4));
+            assert_error("data is NULL");
+            assert!(attachment_writer_write(writer, ptr::null(), 0));
+            let attachment = attachment_writer_finish(writer);
+            assert!(attachment != 0);
+            assert_eq!(attachment_writer_finish(writer), 0);
+            assert_destroyed("attachment writer");
+            assert_eq!(take_string(attachment_get_mime_type(attachment)), "application/octet-stream");
+
+            let reader = list_manager_attachment_reader(manager, attachment);
+            assert_eq!(attachment_reader_read(reader, ptr::null_mut(), 4), 0);
+            assert_error("buffer is NULL");
+            attachment_reader_destroy(reader);
+
+            assert!(list_manager_delete_attachment(manager, attachment));
+            // Deleting it again isn't an error.
+            assert!(!list_manager_delete_attachment(manager, attachment));
+            assert_eq!(last_error(), None);
+            assert_eq!(list_manager_attachment_reader(manager, attachment), 0);
+            assert!(last_error().expect("expected an error").starts_with("no contents for attachment"));
+            attachment_destroy(attachment);
+            item_destroy(item);
+        });
+    }
+}
+
+#[test]
+fn test_date_phrases() {
+    unsafe {
+        let phrase = c_string("tomorrow 5pm");
+        let zone = c_string("America/New_York");
+        let mut date = DatePhraseC::default();
+        assert!(!toodle_parse_date_phrase(ptr::null(), 0, zone.as_ptr(), &mut date));
+        assert_error("phrase is NULL");
+        assert!(!toodle_parse_date_phrase(phrase.as_ptr(), 0, ptr::null(), &mut date));
+        assert_error("zone is NULL");
+        assert!(!toodle_parse_date_phrase(phrase.as_ptr(), 0, zone.as_ptr(), ptr::null_mut()));
+        assert_error("date is NULL");
+        let unknown = c_string("Mars/Olympus_Mons");
+        assert!(!toodle_parse_date_phrase(phrase.as_ptr(), 0, unknown.as_ptr(), &mut date));
+        assert_error("unknown time zone \"Mars/Olympus_Mons\"");
+        assert!(!toodle_parse_date_phrase(phrase.as_ptr(), i64::max_value(), zone.as_ptr(), &mut date));
+        assert_error(&format!("{} milliseconds is out of range", i64::max_value()));
+        let someday = c_string("someday soon");
+        assert!(!toodle_parse_date_phrase(someday.as_ptr(), 0, zone.as_ptr(), &mut date));
+        assert_error("unrecognised \"someday soon\" in date");
+        // A failed parse leaves the date untouched.
+        assert_eq!(date.millis, 0);
+
+        assert!(!item_set_due_date_phrase(0, phrase.as_ptr(), 0, zone.as_ptr()));
+        assert_error("item handle is 0");
+        let item = item_new();
+        let february = c_string("feb 30");
+        assert!(!item_set_due_date_phrase(item, february.as_ptr(), 0, zone.as_ptr()));
+        assert_error("no such day \"feb 30\"");
+        assert!(item_get_due_date(item).is_null());
+        assert!(item_set_due_date_phrase(item, phrase.as_ptr(), 0, zone.as_ptr()));
+        assert_eq!(take_string(item_get_due_date_zone(item)), "America/New_York");
+        item_destroy(item);
+    }
+}
+
+#[test]
+fn test_quick_add() {
+    unsafe {
+        let text = c_string("buy milk #groceries");
+        let zone = c_string("America/New_York");
+        assert_eq!(list_manager_quick_add(0, text.as_ptr(), 0, zone.as_ptr()), 0);
+        assert_error("list manager handle is 0");
+        with_manager(|manager| {
+            assert_eq!(list_manager_quick_add(manager, ptr::null(), 0, zone.as_ptr()), 0);
+            assert_error("text is NULL");
+            assert_eq!(list_manager_quick_add(manager, text.as_ptr(), 0, ptr::null()), 0);
+            assert_error("zone is NULL");
+            let nameless = c_string("#groceries !high");
+            assert_eq!(list_manager_quick_add(manager, nameless.as_ptr(), 0, zone.as_ptr()), 0);
+            assert_error("no name given");
+            let invalid = c_string("party due feb 30");
+            assert_eq!(list_manager_quick_add(manager, invalid.as_ptr(), 0, zone.as_ptr()), 0);
+            assert_error("no such day \"feb 30\"");
+
+            let added = list_manager_quick_add(manager, text.as_ptr(), 0, zone.as_ptr());
+            assert!(added != 0);
+            quick_add_destroy(added);
+            assert_eq!(quick_add_get_item(added), 0);
+            assert_destroyed("quick add");
+            assert!(quick_add_get_description(added).is_null());
+            assert_destroyed("quick add");
+        });
+        assert_eq!(quick_add_get_item(0), 0);
+        assert_error("quick add handle is 0");
+
+        let item = item_new();
+        item_set_priority(item, 4);
+        assert_error("unknown priority 4");
+        assert_eq!(item_get_priority(item), 0);
+        item_set_priority(item, 2);
+        assert_eq!(item_get_priority(item), 2);
+        item_destroy(item);
+        assert_eq!(item_get_priority(0), 0);
+        assert_error("item handle is 0");
+    }
+}
+
+#[test]
+fn test_invalid_strings() {
+    unsafe {
+        let item = item_new();
+        let invalid = b"caf\xe9\0";
+        item_set_name(item, invalid.as_ptr() as *const c_char);
+        assert_error("name isn't valid UTF-8 at byte 3");
+        item_set_name_utf8(item, invalid.as_ptr(), 4);
+        assert_error("name isn't valid UTF-8 at byte 3");
+        assert_eq!(take_string(item_get_name(item)), "");
+
+        // A name with a NUL byte can only be read back with its length.
+        item_set_name_utf8(item, b"a\0b".as_ptr(), 3);
+        assert_eq!(last_error(), None);
+        assert!(item_get_name(item).is_null());
+        assert_error("string has a NUL byte at byte 1");
+        assert!(item_snapshot(item).name.is_null());
+        assert_error("string has a NUL byte at byte 1");
+        let name = item_get_name_utf8(item).into_vec();
+        assert_eq!(name, Some(b"a\0b".to_vec()));
+        item_destroy(item);
+
+        with_manager(|manager| {
+            let columns = b"name,\xff\0";
+            assert!(list_manager_export_csv(manager, columns.as_ptr() as *const c_char, ptr::null(), ptr::null(), ptr::null()).is_null());
+            assert_error("columns isn't valid UTF-8 at byte 5");
+        });
+    }
+}
+
+#[test]
+fn test_stale_handles() {
+    unsafe {
+        with_manager(|manager| {
+            let name = c_string("home");
+            let color = c_string("#000000");
+            let label = list_manager_create_label(manager, name.as_ptr(), color.as_ptr());
+            label_destroy(label);
+            assert_eq!(last_error(), None);
+            label_destroy(label);
+            assert_destroyed("label");
+            assert!(label_get_name(label).is_null());
+            assert_destroyed("label");
+            assert_eq!(list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), &label, 1), 0);
+            assert_destroyed("label");
+
+            // A new object in the freed slot doesn't revive the old handle.
+            let item = item_new();
+            item_destroy(item);
+            let reused = item_new();
+            assert!(item_get_name(item).is_null());
+            assert_destroyed("item");
+            assert_eq!(take_string(item_get_name(reused)), "");
+            item_destroy(reused);
+
+            // Handles of one kind aren't accepted as another.
+            let items = list_manager_get_all_items(manager);
+            assert_eq!(item_list_count(items), 0);
+            assert_eq!(label_list_count(items), 0);
+            let error = last_error().expect("expected an error");
+            assert!(error.ends_with(" is not a label list handle"), "unexpected error {:?}", error);
+            item_list_destroy(items);
+        });
+
+        let uri = c_string(":memory:");
+        let toodle = new_toodle(uri.as_ptr());
+        toodle_destroy(toodle);
+        assert_eq!(toodle_list(toodle), 0);
+        assert_destroyed("toodle");
+        let store = new_store(uri.as_ptr());
+        store_destroy(store);
+        store_destroy(store);
+        assert_destroyed("store");
+    }
+}
+
+#[test]
+fn test_indexes_out_of_range() {
+    unsafe {
+        with_manager(|manager| {
+            let labels = list_manager_get_all_labels(manager);
+            assert_eq!(label_list_entry_at(labels, 5), 0);
+            assert_error("index 5 out of range");
+            label_list_destroy(labels);
+
+            let items = list_manager_get_all_items(manager);
+            assert_eq!(item_list_entry_at(items, 0), 0);
+            assert_error("index 0 out of range");
+            item_list_destroy(items);
+
+            let item = item_new();
+            assert_eq!(item_label_at(item, 1), 0);
+            assert_error("index 1 out of range");
+            item_destroy(item);
+
+            let csv = c_string("Task\nfirst\n");
+            let mapping = c_string("Task=name");
+            let import = list_manager_import_csv(manager, csv.as_ptr(), mapping.as_ptr(), ptr::null());
+            assert_eq!(csv_import_item_count(import), 1);
+            assert_eq!(csv_import_item_at(import, 1), 0);
+            assert_error("index 1 out of range");
+            assert_eq!(csv_import_error_row(import, 0), 0);
+            assert_error("index 0 out of range");
+            assert!(csv_import_error_message(import, 0).is_null());
+            assert_error("index 0 out of range");
+            csv_import_destroy(import);
+
+            let markdown = c_string("- [ ] first\n");
+            let import = list_manager_import_markdown(manager, markdown.as_ptr());
+            assert_eq!(markdown_import_item_count(import), 1);
+            assert_eq!(markdown_import_item_at(import, 1), 0);
+            assert_error("index 1 out of range");
+            assert_eq!(markdown_import_flattened_line(import, 0), 0);
+            assert_error("index 0 out of range");
+            assert!(markdown_import_flattened_parent(import, 0).is_null());
+            assert_error("index 0 out of range");
+            markdown_import_destroy(import);
+
+            let items = list_manager_get_all_items(manager);
+            let item = item_list_entry_at(items, 0);
+            let attachments = list_manager_get_item_attachments(manager, item);
+            assert_eq!(attachment_list_entry_at(attachments, 0), 0);
+            assert_error("index 0 out of range");
+            attachment_list_destroy(attachments);
+            item_destroy(item);
+            item_list_destroy(items);
+        });
+    }
+}
+
+#[test]
+fn test_panics_are_caught() {
+    unsafe {
+        let uri = c_string("/nonexistent/directory/toodle.db");
+        assert_eq!(new_store(uri.as_ptr()), 0);
+        assert_panicked("Expected a connection for URI");
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Drives the C ABI the way a host application would, under an allocator that
+//! counts live bytes, and checks that every allocation handed across the boundary
+//! is released by the matching destroy or free function.
+
+extern crate list;
+extern crate store;
+extern crate toodle;
+
+use std::alloc::{
+    GlobalAlloc,
+    Layout,
+    System,
+};
+use std::ffi::{
+    CStr,
+    CString,
+};
+use std::os::raw::{
+    c_char,
+    c_void,
+};
+use std::ptr;
+use std::sync::atomic::{
+    AtomicIsize,
+    Ordering,
+};
+
+use list::*;
+use list::attachments::*;
+use list::buffers::*;
+use list::csv::*;
+use list::cursor::*;
+use list::items::*;
+use list::labels::*;
+use list::markdown::*;
+use list::quick_add::*;
+use store::*;
+use toodle::*;
+use toodle::executor::*;
+
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            LIVE_BYTES.fetch_add(layout.size() as isize, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE_BYTES.fetch_sub(layout.size() as isize, Ordering::SeqCst);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            LIVE_BYTES.fetch_add(new_size as isize - layout.size() as isize, Ordering::SeqCst);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn c_string(s: &str) -> CString {
+    CString::new(s).unwrap()
+}
+
+/// Take ownership of a string returned by the library and free it.
+unsafe fn take_string(s: *mut c_char) -> String {
+    assert!(!s.is_null());
+    let string = CStr::from_ptr(s).to_string_lossy().into_owned();
+    toodle_string_free(s);
+    string
+}
+
+unsafe fn with_manager<F: FnOnce(ListManagerHandle)>(f: F) {
+    let uri = c_string(":memory:");
+    let toodle = new_toodle(uri.as_ptr());
+    let manager = toodle_list(toodle);
+    f(manager);
+    list_manager_destroy(manager);
+    toodle_destroy(toodle);
+}
+
+unsafe fn create_labels(manager: ListManagerHandle) {
+    for name in ["home", "work"].iter() {
+        let name = c_string(name);
+        let color = c_string("#000000");
+        label_destroy(list_manager_create_label(manager, name.as_ptr(), color.as_ptr()));
+    }
+}
+
+unsafe fn create_item(manager: ListManagerHandle, name: &str) {
+    let name = c_string(name);
+    let due: i64 = 1509883200000;
+    let labels = list_manager_get_all_labels(manager);
+    let label_handles: Vec<LabelHandle> = (0..label_list_count(labels)).map(|index| label_list_entry_at(labels, index)).collect();
+    let item = list_manager_create_item(manager, name.as_ptr(), &due, ptr::null(), label_handles.as_ptr(), label_handles.len());
+    item_destroy(item);
+    for label in label_handles {
+        label_destroy(label);
+    }
+    label_list_destroy(labels);
+}
+
+fn toodle_lifecycle() {
+    unsafe {
+        with_manager(|_| {});
+        toodle_destroy(0);
+        toodle_string_free(ptr::null_mut());
+    }
+}
+
+fn store_lifecycle() {
+    unsafe {
+        let uri = c_string(":memory:");
+        store_destroy(new_store(uri.as_ptr()));
+        store_destroy(0);
+    }
+}
+
+fn labels() {
+    unsafe {
+        with_manager(|manager| {
+            create_labels(manager);
+            let labels = list_manager_get_all_labels(manager);
+            for index in 0..label_list_count(labels) {
+                let label = label_list_entry_at(labels, index);
+                take_string(label_get_name(label));
+                take_string(label_get_color(label));
+                let color = c_string("#ffffff");
+                label_set_color(label, color.as_ptr());
+                label_snapshot_free(label_snapshot(label));
+                label_destroy(label);
+            }
+            label_list_destroy(labels);
+            label_list_destroy(0);
+            label_destroy(0);
+        });
+    }
+}
+
+fn items() {
+    unsafe {
+        with_manager(|manager| {
+            create_labels(manager);
+            create_item(manager, "first");
+            create_item(manager, "second");
+
+            let items = list_manager_get_all_items(manager);
+            for index in 0..item_list_count(items) {
+                let item = item_list_entry_at(items, index);
+                take_string(item_get_uuid(item));
+                take_string(item_get_name(item));
+                item_date_destroy(item_get_due_date(item));
+                item_date_destroy(item_get_completion_date(item));
+
+                item_snapshot_free(item_snapshot(item));
+                let labels = item_get_labels(item);
+                label_list_destroy(labels);
+                for label_index in 0..item_labels_count(item) {
+                    label_destroy(item_label_at(item, label_index));
+                }
+
+                let name = c_string("renamed");
+                let completed: i64 = 1509969600000;
+                let zone = c_string("Europe/London");
+                item_set_name(item, name.as_ptr());
+                item_set_due_date_in_zone(item, completed, zone.as_ptr());
+                take_string(item_get_due_date_zone(item));
+                item_set_completion_date_in_zone(item, completed, zone.as_ptr());
+                take_string(item_get_completion_date_zone(item));
+                item_snapshot_free(item_snapshot(item));
+                list_manager_update_item(manager, item, name.as_ptr(), ptr::null(), &completed, ptr::null(), 0);
+                item_destroy(item);
+            }
+            item_list_destroy(items);
+            item_list_destroy(0);
+            item_date_destroy(ptr::null_mut());
+
+            let item = item_new();
+            let name = c_string("unsaved");
+            item_set_name(item, name.as_ptr());
+            item_set_name_utf8(item, b"a\0b".as_ptr(), 3);
+            toodle_buffer_free(item_get_name_utf8(item));
+            item_snapshot_free(item_snapshot(item));
+            item_destroy(item);
+            item_destroy(0);
+        });
+    }
+}
+
+extern "C" fn read_item(_context: *mut c_void, item: *const ItemC) -> bool {
+    unsafe { !(*item).name.is_null() }
+}
+
+fn cursors() {
+    unsafe {
+        with_manager(|manager| {
+            create_labels(manager);
+            create_item(manager, "first");
+            create_item(manager, "second");
+            create_item(manager, "third");
+
+            let cursor = list_manager_item_cursor(manager, 2);
+            item_destroy(item_cursor_next(cursor));
+            item_list_destroy(item_cursor_next_page(cursor));
+            item_cursor_destroy(cursor);
+
+            // A cursor destroyed part way through a page frees the rest of it.
+            let cursor = list_manager_item_cursor(manager, 3);
+            item_destroy(item_cursor_next(cursor));
+            item_cursor_destroy(cursor);
+            item_cursor_destroy(0);
+
+            list_manager_for_each_item(manager, 2, Some(read_item), ptr::null_mut());
+        });
+    }
+}
+
+fn attachments() {
+    unsafe {
+        with_manager(|manager| {
+            let name = c_string("notes.txt");
+            let mime_type = c_string("text/plain");
+            let item = list_manager_create_item(manager, name.as_ptr(), ptr::null(), ptr::null(), ptr::null(), 0);
+            let writer = list_manager_attachment_writer(manager, item, name.as_ptr(), mime_type.as_ptr());
+            attachment_writer_write(writer, b"hello".as_ptr(), 5);
+            let attachment = attachment_writer_finish(writer);
+            take_string(attachment_get_id(attachment));
+            take_string(attachment_get_name(attachment));
+            take_string(attachment_get_hash(attachment));
+
+            let reader = list_manager_attachment_reader(manager, attachment);
+            let mut buffer = [0u8; 2];
+            while attachment_reader_read(reader, buffer.as_mut_ptr(), buffer.len()) > 0 {}
+            attachment_reader_destroy(reader);
+
+            let list = list_manager_get_item_attachments(manager, item);
+            attachment_destroy(attachment_list_entry_at(list, 0));
+            attachment_list_destroy(list);
+            list_manager_delete_attachment(manager, attachment);
+            attachment_destroy(attachment);
+
+            // A writer that is never finished is discarded along with its contents.
+            let writer = list_manager_attachment_writer(manager, item, name.as_ptr(), ptr::null());
+            attachment_writer_write(writer, b"draft".as_ptr(), 5);
+            attachment_writer_destroy(writer);
+            item_destroy(item);
+        });
+    }
+}
+
+/// Frees each result it receives, as a host would.
+extern "C" fn destroy_item_list(_context: *mut c_void, result: u64, _error: *const c_char) {
+    unsafe { item_list_destroy(result) };
+}
+
+extern "C" fn destroy_markdown_import(_context: *mut c_void, result: u64, _error: *const c_char) {
+    unsafe { markdown_import_destroy(result) };
+}
+
+fn executor() {
+    unsafe {
+        with_manager(|manager| {
+            let executor = toodle_executor_new(2);
+            let token = toodle_cancellation_token_new();
+            let markdown = c_string("- [ ] buy milk #groceries\n");
+            list_manager_import_markdown_async(executor, manager, markdown.as_ptr(), 0, Some(destroy_markdown_import), ptr::null_mut());
+            list_manager_get_all_items_async(executor, manager, 0, Some(destroy_item_list), ptr::null_mut());
+            toodle_cancellation_token_cancel(token);
+            list_manager_get_all_items_async(executor, manager, token, Some(destroy_item_list), ptr::null_mut());
+            toodle_cancellation_token_destroy(token);
+            toodle_executor_destroy(executor);
+        });
+    }
+}
+
+fn csv() {
+    unsafe {
+        with_manager(|manager| {
+            create_labels(manager);
+            create_item(manager, "first");
+            take_string(list_manager_export_csv(manager, ptr::null(), ptr::null(), ptr::null(), ptr::null()));
+
+            let csv = c_string("Task,Tags\nbuy milk,groceries\n,missing name\n");
+            let mapping = c_string("Task=name,Tags=labels");
+            let import = list_manager_import_csv(manager, csv.as_ptr(), mapping.as_ptr(), ptr::null());
+            for index in 0..csv_import_item_count(import) {
+                item_destroy(csv_import_item_at(import, index));
+            }
+            for index in 0..csv_import_error_count(import) {
+                take_string(csv_import_error_message(import, index));
+            }
+            csv_import_destroy(import);
+            csv_import_destroy(0);
+        });
+    }
+}
+
+fn markdown() {
+    unsafe {
+        with_manager(|manager| {
+            let markdown = c_string("- [ ] buy milk #groceries\n  - [x] call mum\n");
+            let import = list_manager_import_markdown(manager, markdown.as_ptr());
+            for index in 0..markdown_import_item_count(import) {
+                item_destroy(markdown_import_item_at(import, index));
+            }
+            for index in 0..markdown_import_flattened_count(import) {
+                take_string(markdown_import_flattened_parent(import, index));
+            }
+            markdown_import_destroy(import);
+            markdown_import_destroy(0);
+            take_string(list_manager_export_markdown(manager, 0));
+        });
+    }
+}
+
+fn quick_add() {
+    unsafe {
+        with_manager(|manager| {
+            let text = c_string("buy milk #groceries !high due tomorrow 5pm");
+            let zone = c_string("America/New_York");
+            let added = list_manager_quick_add(manager, text.as_ptr(), 1509546600000, zone.as_ptr());
+            item_destroy(quick_add_get_item(added));
+            take_string(quick_add_get_description(added));
+            quick_add_destroy(added);
+            let nameless = c_string("#groceries");
+            assert_eq!(list_manager_quick_add(manager, nameless.as_ptr(), 0, zone.as_ptr()), 0);
+        });
+    }
+}
+
+fn buffers() {
+    unsafe {
+        with_manager(|manager| {
+            create_labels(manager);
+            create_item(manager, "first");
+            toodle_buffer_free(list_manager_get_all_items_buffer(manager));
+            toodle_buffer_free(list_manager_get_all_labels_buffer(manager));
+            toodle_buffer_free(list_manager_get_all_items_buffer(0));
+        });
+    }
+}
+
+fn live_bytes() -> isize {
+    LIVE_BYTES.load(Ordering::SeqCst)
+}
+
+fn main() {
+    let checks: Vec<(&str, fn())> = vec![
+        ("toodle_lifecycle", toodle_lifecycle),
+        ("store_lifecycle", store_lifecycle),
+        ("labels", labels),
+        ("items", items),
+        ("cursors", cursors),
+        ("executor", executor),
+        ("attachments", attachments),
+        ("csv", csv),
+        ("markdown", markdown),
+        ("quick_add", quick_add),
+        ("buffers", buffers),
+    ];
+
+    // The first run pays for lazily initialised, process-lifetime state such as the
+    // stdout buffer and the random number generator behind UUIDs.
+    for &(_, check) in checks.iter() {
+        check();
+    }
+
+    let mut leaks = Vec::new();
+    for &(name, check) in checks.iter() {
+        let before = live_bytes();
+        check();
+        let leaked = live_bytes() - before;
+        println!("{}: {} bytes leaked", name, leaked);
+        if leaked != 0 {
+            leaks.push(name);
+        }
+    }
+    assert!(leaks.is_empty(), "leaks found in {:?}", leaks);
+}
@@ -0,0 +1,100 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Compiles the Java bindings in `java/` and `tests/jni/ToodleTest.java` with the
+//! desktop JDK, runs the test against the shared library, and checks that every
+//! native method the bindings declare is implemented. Only built with the `jni`
+//! feature.
+
+use std::env;
+use std::fs::{
+    self,
+    File,
+};
+use std::io::Read;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Command;
+
+const JAVA_SOURCES: &'static str = "java/com/mozilla/toodle";
+const BINDINGS: &'static str = "src/java.rs";
+const PROGRAM: &'static str = "tests/jni/ToodleTest.java";
+
+fn read(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+                    .expect(&format!("failed to read {:?}", path));
+    contents
+}
+
+fn java_sources(root: &Path) -> Vec<PathBuf> {
+    fs::read_dir(root.join(JAVA_SOURCES)).expect("failed to list the Java sources")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "java"))
+        .collect()
+}
+
+/// The JNI symbols for the native methods declared in a Java source file.
+fn native_symbols(path: &Path) -> Vec<String> {
+    let class = path.file_stem().unwrap().to_str().unwrap();
+    read(path).lines()
+              .filter(|line| line.contains(" native "))
+              .filter_map(|line| line.find('(').map(|end| &line[..end]))
+              .filter_map(|declaration| declaration.rsplit(' ').next())
+              .map(|method| format!("Java_com_mozilla_toodle_{}_{}", class, method))
+              .collect()
+}
+
+/// The directory this test's executable lives in, which is where `cargo test` builds
+/// the shared library.
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn test_every_native_method_is_implemented() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let bindings = read(&root.join(BINDINGS));
+    let mut missing = Vec::new();
+    for source in java_sources(root) {
+        missing.extend(native_symbols(&source).into_iter().filter(|symbol| !bindings.contains(&format!("fn {}(", symbol))));
+    }
+    assert!(missing.is_empty(), "{} doesn't implement {:?}", BINDINGS, missing);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_java_program() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library_dir = library_dir();
+    let classes = library_dir.join("jni_classes");
+    let _ = fs::remove_dir_all(&classes);
+
+    let status = Command::new("javac")
+        .arg("-Werror")
+        .arg("-d").arg(&classes)
+        .args(java_sources(root))
+        .arg(root.join(PROGRAM))
+        .status()
+        .expect("failed to run javac");
+    assert!(status.success(), "failed to compile {}", PROGRAM);
+
+    let output = Command::new("java")
+        .arg(format!("-Djava.library.path={}", library_dir.display()))
+        .arg("-Xcheck:jni")
+        .arg("-cp").arg(&classes)
+        .arg("ToodleTest")
+        .output()
+        .expect("failed to run java");
+    assert!(output.status.success(), "Java program failed:\n{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,209 @@
+/* Copyright 2016 Mozilla
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+ * this file except in compliance with the License. You may obtain a copy of the
+ * License at http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed
+ * under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+ * CONDITIONS OF ANY KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations under the License. */
+
+/* Exercises the Java bindings against the shared library, the way the Android app
+ * uses them. Built and run by tests/jni.rs. */
+
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.List;
+
+import com.mozilla.toodle.Item;
+import com.mozilla.toodle.Label;
+import com.mozilla.toodle.ListManager;
+import com.mozilla.toodle.LogLevel;
+import com.mozilla.toodle.Priority;
+import com.mozilla.toodle.Toodle;
+import com.mozilla.toodle.ToodleException;
+
+public class ToodleTest {
+    private static int failures = 0;
+
+    private static void check(boolean condition, String description) {
+        if (!condition) {
+            StackTraceElement caller = new Throwable().getStackTrace()[1];
+            System.err.println("ToodleTest.java:" + caller.getLineNumber() + ": check failed: " + description);
+            failures++;
+        }
+    }
+
+    private interface Call {
+        void run();
+    }
+
+    private static void checkThrows(Call call, String message) {
+        try {
+            call.run();
+            check(false, "expected a ToodleException: " + message);
+        } catch (ToodleException e) {
+            check(message.equals(e.getMessage()), "expected \"" + message + "\", got \"" + e.getMessage() + "\"");
+        }
+    }
+
+    private static Label findLabel(ListManager manager, String name) {
+        Label found = null;
+        for (Label label : manager.getAllLabels()) {
+            if (found == null && label.getName().equals(name)) {
+                found = label;
+            } else {
+                label.close();
+            }
+        }
+        return found;
+    }
+
+    private static Item findItem(ListManager manager, String name) {
+        Item found = null;
+        for (Item item : manager.getAllItems()) {
+            if (found == null && item.getName().equals(name)) {
+                found = item;
+            } else {
+                item.close();
+            }
+        }
+        return found;
+    }
+
+    private static void testLabels(ListManager manager) {
+        Label home = manager.createLabel("home", "#ff0000");
+        check(home.getName().equals("home"), "label name");
+        check(home.getColor().equals("#ff0000"), "label color");
+        home.setColor("#00ff00");
+        check(home.getColor().equals("#00ff00"), "setColor changes the copy");
+        home.close();
+        manager.createLabel("work", "#0000ff").close();
+
+        Label fetched = findLabel(manager, "home");
+        check(fetched != null && fetched.getColor().equals("#ff0000"), "fetched label");
+        if (fetched != null) {
+            fetched.close();
+        }
+        check(manager.getAllLabels().size() == 2, "two labels");
+
+        checkThrows(() -> manager.createLabel(null, "#000000"), "name is NULL");
+        checkThrows(() -> manager.createLabel("a\0b", "#000000"), "name has a NUL byte at byte 1");
+        checkThrows(() -> fetched.getName(), "label handle is 0");
+    }
+
+    private static void testItems(ListManager manager) {
+        long due = 1509883200345L;
+        long completed = 1509969600000L;
+        Label home = findLabel(manager, "home");
+        Label work = findLabel(manager, "work");
+
+        Item item = manager.createItem("buy milk", due, null, Arrays.asList(home, work));
+        String uuid = item.getUuid();
+        check(!uuid.isEmpty(), "created item has a uuid");
+        check(item.getName().equals("buy milk"), "item name");
+        check(item.getDueDate() == due, "due date");
+        check(item.getCompletionDate() == null, "no completion date");
+        check(!item.isDueDateAllDay() && item.getDueDateZone() == null, "due date is UTC");
+        List<Label> labels = item.getLabels();
+        check(labels.size() == 2, "two labels");
+        for (Label label : labels) {
+            label.close();
+        }
+
+        manager.updateItem(item, null, null, completed, Collections.singletonList(home));
+        Item fetched = findItem(manager, "buy milk");
+        check(fetched != null, "updated item is fetched");
+        if (fetched != null) {
+            check(fetched.getUuid().equals(uuid), "same uuid");
+            check(fetched.getDueDate() == null, "due date cleared");
+            check(fetched.getCompletionDate() == completed, "completion date");
+            check(fetched.getLabels().size() == 1, "one label");
+            fetched.close();
+        }
+
+        /* Setters only change the local copy. */
+        item.setName("a\0b");
+        check(item.getName().equals("a\0b"), "names can hold NUL characters");
+        item.setDueDate(completed);
+        check(item.getDueDate() == completed, "setDueDate");
+        item.setDueDate(null);
+        check(item.getDueDate() == null, "setDueDate(null)");
+        item.setAllDayDueDate(due);
+        check(item.getDueDate() == 1509840000000L && item.isDueDateAllDay(), "all-day due date");
+        item.setDueDateInZone(due, "Australia/Sydney");
+        check("Australia/Sydney".equals(item.getDueDateZone()), "due date zone");
+        item.setCompletionDate(due);
+        check(item.getCompletionDate() == due, "setCompletionDate");
+        item.setAllDayCompletionDate(completed);
+        check(item.isCompletionDateAllDay() && item.getCompletionDateZone() == null, "all-day completion date");
+        item.setCompletionDateInZone(completed, "Europe/Berlin");
+        check(!item.isCompletionDateAllDay(), "zoned completion date");
+        check("Europe/Berlin".equals(item.getCompletionDateZone()), "completion date zone");
+        item.setCompletionDate(null);
+        check(item.getCompletionDate() == null, "setCompletionDate(null)");
+        check(item.getPriority() == null, "no priority");
+        item.setPriority(Priority.HIGH);
+        check(item.getPriority() == Priority.HIGH, "setPriority");
+        manager.updateItem(item, "pay rent", null, null, new ArrayList<Label>());
+        Item prioritised = findItem(manager, "pay rent");
+        check(prioritised != null && prioritised.getPriority() == Priority.HIGH, "priority is saved");
+        if (prioritised != null) {
+            prioritised.close();
+        }
+        item.setPriority(null);
+        check(item.getPriority() == null, "setPriority(null)");
+
+        checkThrows(() -> item.setDueDateInZone(due, "Australia/Sydnye"), "unknown time zone \"Australia/Sydnye\"");
+        checkThrows(() -> item.setName(null), "name is NULL");
+        checkThrows(() -> manager.createItem(null, null, null, new ArrayList<Label>()), "name is NULL");
+        home.close();
+        checkThrows(() -> manager.updateItem(item, null, null, null, Arrays.asList(home)), "label handle is 0");
+        item.close();
+        checkThrows(() -> item.getUuid(), "item handle is 0");
+        work.close();
+
+        Item unsaved = new Item();
+        check(unsaved.getName().isEmpty(), "new items have no name");
+        check(unsaved.getLabels().isEmpty(), "new items have no labels");
+        unsaved.close();
+    }
+
+    private static void testLogging(ListManager manager) {
+        List<String> messages = Collections.synchronizedList(new ArrayList<String>());
+        Toodle.setLogger((level, target, message, fields) -> {
+            messages.add(level + " " + target + ": " + message + " " + fields);
+        }, LogLevel.DEBUG);
+        manager.createItem("logged", null, null, new ArrayList<Label>()).close();
+        boolean created = false;
+        for (String message : messages) {
+            created |= message.startsWith("DEBUG list: creating item uuid=");
+        }
+        check(created, "item creation is logged: " + messages);
+
+        Toodle.setLogger(null, LogLevel.TRACE);
+        messages.clear();
+        manager.createItem("unlogged", null, null, new ArrayList<Label>()).close();
+        check(messages.isEmpty(), "nothing is logged once the logger is removed");
+    }
+
+    public static void main(String[] args) {
+        Toodle toodle = new Toodle(":memory:");
+        ListManager manager = toodle.getList();
+
+        testLabels(manager);
+        testItems(manager);
+        testLogging(manager);
+
+        manager.close();
+        toodle.close();
+        checkThrows(() -> toodle.getList(), "toodle handle is 0");
+
+        if (failures > 0) {
+            System.err.println(failures + " checks failed");
+            System.exit(1);
+        }
+        System.out.println("all checks passed");
+    }
+}
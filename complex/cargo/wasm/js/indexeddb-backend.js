@@ -0,0 +1,76 @@
+/* Copyright 2016 Mozilla
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+ * this file except in compliance with the License. You may obtain a copy of the
+ * License at http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed
+ * under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+ * CONDITIONS OF ANY KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations under the License. */
+
+/* A backend for `ListManager.withBackend` that keeps the list in IndexedDB.
+ *
+ * The bindings load and save synchronously, while IndexedDB is asynchronous, so
+ * `open` reads everything into memory first and saves are written in the
+ * background, in order, with failures logged to the console. Await `flush()`
+ * before relying on a save having landed.
+ *
+ *     const backend = await IndexedDbBackend.open("toodle");
+ *     const manager = ListManager.withBackend(backend);
+ */
+
+const STORE = "buffers";
+
+function request(req) {
+    return new Promise((resolve, reject) => {
+        req.onsuccess = () => resolve(req.result);
+        req.onerror = () => reject(req.error);
+    });
+}
+
+export class IndexedDbBackend {
+    constructor(db, cache) {
+        this.db = db;
+        this.cache = cache;
+        this.pending = Promise.resolve();
+    }
+
+    static async open(name) {
+        const open = indexedDB.open(name, 1);
+        open.onupgradeneeded = () => open.result.createObjectStore(STORE);
+        const db = await request(open);
+
+        const store = db.transaction(STORE, "readonly").objectStore(STORE);
+        const [keys, values] = await Promise.all([request(store.getAllKeys()), request(store.getAll())]);
+        const cache = new Map();
+        keys.forEach((key, i) => cache.set(key, values[i]));
+        return new IndexedDbBackend(db, cache);
+    }
+
+    load(key) {
+        return this.cache.get(key);
+    }
+
+    save(key, bytes) {
+        // `bytes` is a view into WebAssembly memory, only valid during the call.
+        const copy = bytes.slice();
+        this.cache.set(key, copy);
+        this.pending = this.pending.then(() => {
+            const tx = this.db.transaction(STORE, "readwrite");
+            tx.objectStore(STORE).put(copy, key);
+            return new Promise((resolve, reject) => {
+                tx.oncomplete = () => resolve();
+                tx.onerror = () => reject(tx.error);
+            });
+        }).catch(error => console.warn(`failed to save ${key}:`, error));
+    }
+
+    /* Resolves once every save so far has been written. */
+    flush() {
+        return this.pending;
+    }
+
+    close() {
+        this.db.close();
+    }
+}
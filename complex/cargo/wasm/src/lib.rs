@@ -0,0 +1,335 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! WebAssembly bindings for the to-do list, for use from JavaScript through
+//! wasm-bindgen.
+//!
+//! SQLite can't be linked into a wasm32 build, so `ListManager` wraps a
+//! `list::ListManager` keeping its items and labels in memory, optionally saving
+//! them to a JavaScript backend such as
+//! `js/indexeddb-backend.js`. Dates cross as milliseconds since the Unix epoch, the
+//! way `Date.prototype.getTime` returns them, and failures are thrown as `Error`s.
+
+extern crate js_sys;
+extern crate list;
+#[macro_use]
+extern crate log;
+extern crate wasm_bindgen;
+
+pub mod store;
+
+use wasm_bindgen::prelude::*;
+
+use list::dates::Date;
+use list::items;
+use list::labels::{
+    self,
+    DEFAULT_COLOR,
+};
+use list::storage::MemoryStorage;
+use store::Backend;
+
+#[wasm_bindgen]
+extern "C" {
+    /// A JavaScript object with `load(key)` and `save(key, bytes)` methods.
+    /// `load` returns the `Uint8Array` last saved under `key`, or `undefined`.
+    /// Either may throw.
+    pub type JsBackend;
+
+    #[wasm_bindgen(method, catch)]
+    fn load(this: &JsBackend, key: &str) -> Result<Option<Vec<u8>>, JsValue>;
+
+    #[wasm_bindgen(method, catch)]
+    fn save(this: &JsBackend, key: &str, bytes: &[u8]) -> Result<(), JsValue>;
+}
+
+impl Backend for JsBackend {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        JsBackend::load(self, key).map_err(describe)
+    }
+
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        JsBackend::save(self, key, bytes).map_err(describe)
+    }
+}
+
+/// The message of a thrown JavaScript value.
+fn describe(error: JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => format!("{:?}", error),
+    }
+}
+
+fn millis(value: f64) -> Result<i64, JsError> {
+    if value.is_finite() {
+        Ok(value as i64)
+    } else {
+        Err(JsError::new(&format!("{} is not a date", value)))
+    }
+}
+
+fn date(value: Option<f64>) -> Result<Option<Date>, JsError> {
+    match value {
+        Some(value) => Ok(Some(Date::from_millis(millis(value)?).map_err(|e| JsError::new(&e.to_string()))?)),
+        None => Ok(None),
+    }
+}
+
+fn all_day(value: f64) -> Result<Date, JsError> {
+    Date::day_from_millis(millis(value)?).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn in_zone(value: f64, zone: &str) -> Result<Date, JsError> {
+    Date::from_millis_in(millis(value)?, zone).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub struct ListManager {
+    manager: list::ListManager,
+    backend: Option<Box<dyn Backend>>,
+}
+
+impl Default for ListManager {
+    fn default() -> Self {
+        ListManager::new()
+    }
+}
+
+impl ListManager {
+    /// Write the list to the backend, if there is one, after a change.
+    fn save(&mut self) {
+        if let Some(ref backend) = self.backend {
+            store::save(&**backend, &mut self.manager);
+        }
+    }
+
+    /// The stored labels with the names of `labels`, creating any that don't exist
+    /// yet with the colors given.
+    fn stored_labels(&self, labels: &[labels::Label]) -> Result<Vec<labels::Label>, JsError> {
+        labels.iter().map(|label| {
+            self.manager.fetch_or_create_label(&label.name, &label.color)
+                        .ok_or_else(|| JsError::new(&format!("can't create label {:?}", label.name)))
+        }).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl ListManager {
+    /// A list that is lost when the page is closed.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ListManager {
+        ListManager { manager: list::ListManager::with_storage(MemoryStorage::new()), backend: None }
+    }
+
+    /// A list holding whatever `backend` has saved, and saving every change to it.
+    #[wasm_bindgen(js_name = withBackend)]
+    pub fn with_backend(backend: JsBackend) -> Result<ListManager, JsError> {
+        let storage = store::load(&backend).map_err(|e| JsError::new(&e))?;
+        Ok(ListManager { manager: list::ListManager::with_storage(storage), backend: Some(Box::new(backend)) })
+    }
+
+    /// Create a label, throwing if there is already one called `name`.
+    #[wasm_bindgen(js_name = createLabel)]
+    pub fn create_label(&mut self, name: String, color: String) -> Result<Label, JsError> {
+        let label = self.manager.create_label(name.clone(), color).ok_or_else(|| JsError::new(&format!("label {:?} already exists", name)))?;
+        self.save();
+        Ok(Label { label: label })
+    }
+
+    pub fn labels(&self) -> Vec<Label> {
+        self.manager.fetch_labels().into_iter().map(|label| Label { label: label }).collect()
+    }
+
+    /// Create an item with the labels called `labels`, creating any that don't exist
+    /// yet, and return it with its new uuid.
+    #[wasm_bindgen(js_name = createItem)]
+    pub fn create_item(&mut self, name: String, due_date: Option<f64>, completion_date: Option<f64>, labels: Vec<String>) -> Result<Item, JsError> {
+        let labels: Vec<labels::Label> = labels.into_iter().map(|name| labels::Label { name: name, color: DEFAULT_COLOR.to_string() }).collect();
+        let item = items::Item::new("".to_string(), name, date(due_date)?, date(completion_date)?, self.stored_labels(&labels)?);
+        let uuid = self.manager.create_item(&item).map_err(|e| JsError::new(&e))?;
+        self.save();
+        self.manager.fetch_item(&uuid).map(|item| Item { item: item }).ok_or_else(|| JsError::new("item wasn't saved"))
+    }
+
+    /// Save the changes made to `item`, which must have come from this list.
+    #[wasm_bindgen(js_name = updateItem)]
+    pub fn update_item(&mut self, item: &Item) -> Result<(), JsError> {
+        let existing = self.manager.fetch_item(&item.item.uuid).ok_or_else(|| JsError::new(&format!("no item with uuid {:?}", item.item.uuid)))?;
+        let mut item = item.item.clone();
+        item.labels = self.stored_labels(&item.labels)?;
+        self.manager.update_item(&item, existing.labels.clone()).map_err(|e| JsError::new(&e))?;
+        self.save();
+        Ok(())
+    }
+
+    pub fn items(&mut self) -> Vec<Item> {
+        self.manager.fetch_items().into_iter().map(|item| Item { item: item }).collect()
+    }
+
+    #[wasm_bindgen(js_name = itemsWithLabel)]
+    pub fn items_with_label(&mut self, label: &Label) -> Vec<Item> {
+        self.manager.fetch_items_with_label(&label.label).into_iter().map(|item| Item { item: item }).collect()
+    }
+
+    /// The item with `uuid`, or `undefined` if there is none.
+    pub fn item(&mut self, uuid: &str) -> Option<Item> {
+        self.manager.fetch_item(&uuid.to_string()).map(|item| Item { item: item })
+    }
+}
+
+/// A copy of an item. Changes are only saved by `ListManager.updateItem`.
+#[wasm_bindgen]
+pub struct Item {
+    item: items::Item,
+}
+
+#[wasm_bindgen]
+impl Item {
+    #[wasm_bindgen(getter)]
+    pub fn uuid(&self) -> String {
+        self.item.uuid.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.item.name.clone()
+    }
+
+    // Named so as not to clash with the C function `item_set_name`, as wasm-bindgen
+    // exports methods as `<class>_<method>`.
+    #[wasm_bindgen(setter = name)]
+    pub fn rename(&mut self, name: String) {
+        self.item.name = name;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn labels(&self) -> Vec<Label> {
+        self.item.labels.iter().map(|label| Label { label: label.clone() }).collect()
+    }
+
+    /// Replace the item's labels with the ones called `names`. Labels that don't
+    /// exist yet are created, with the default color, by `updateItem`.
+    #[wasm_bindgen(js_name = setLabels)]
+    pub fn set_labels(&mut self, names: Vec<String>) {
+        self.item.labels = names.into_iter().map(|name| labels::Label { name: name, color: DEFAULT_COLOR.to_string() }).collect();
+    }
+
+    /// Milliseconds since the epoch; for all-day dates, midnight UTC at the start
+    /// of the day.
+    #[wasm_bindgen(getter, js_name = dueDate)]
+    pub fn due_date(&self) -> Option<f64> {
+        self.item.due_date.map(|date| date.millis() as f64)
+    }
+
+    #[wasm_bindgen(getter, js_name = dueDateAllDay)]
+    pub fn due_date_all_day(&self) -> bool {
+        self.item.due_date.is_some_and(|date| date.is_all_day())
+    }
+
+    #[wasm_bindgen(getter, js_name = dueDateZone)]
+    pub fn due_date_zone(&self) -> Option<String> {
+        self.item.due_date.and_then(|date| date.zone()).map(|zone| zone.name().to_string())
+    }
+
+    #[wasm_bindgen(js_name = setDueDate)]
+    pub fn set_due_date(&mut self, millis: Option<f64>) -> Result<(), JsError> {
+        self.item.due_date = date(millis)?;
+        Ok(())
+    }
+
+    /// Set the due date to the day containing `millis` in UTC.
+    #[wasm_bindgen(js_name = setAllDayDueDate)]
+    pub fn set_all_day_due_date(&mut self, millis: f64) -> Result<(), JsError> {
+        self.item.due_date = Some(all_day(millis)?);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setDueDateInZone)]
+    pub fn set_due_date_in_zone(&mut self, millis: f64, zone: &str) -> Result<(), JsError> {
+        self.item.due_date = Some(in_zone(millis, zone)?);
+        Ok(())
+    }
+
+    /// `"low"`, `"medium"`, `"high"` or `undefined`.
+    #[wasm_bindgen(getter)]
+    pub fn priority(&self) -> Option<String> {
+        self.item.priority.map(|priority| priority.name().to_string())
+    }
+
+    /// Set the priority to `"low"`, `"medium"` or `"high"`, or clear it with
+    /// `undefined`.
+    #[wasm_bindgen(js_name = setPriority)]
+    pub fn set_priority(&mut self, priority: Option<String>) -> Result<(), JsError> {
+        self.item.priority = match priority {
+            Some(name) => Some(name.parse().map_err(|e: String| JsError::new(&e))?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter, js_name = completionDate)]
+    pub fn completion_date(&self) -> Option<f64> {
+        self.item.completion_date.map(|date| date.millis() as f64)
+    }
+
+    #[wasm_bindgen(getter, js_name = completionDateAllDay)]
+    pub fn completion_date_all_day(&self) -> bool {
+        self.item.completion_date.is_some_and(|date| date.is_all_day())
+    }
+
+    #[wasm_bindgen(getter, js_name = completionDateZone)]
+    pub fn completion_date_zone(&self) -> Option<String> {
+        self.item.completion_date.and_then(|date| date.zone()).map(|zone| zone.name().to_string())
+    }
+
+    #[wasm_bindgen(js_name = setCompletionDate)]
+    pub fn set_completion_date(&mut self, millis: Option<f64>) -> Result<(), JsError> {
+        self.item.completion_date = date(millis)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setAllDayCompletionDate)]
+    pub fn set_all_day_completion_date(&mut self, millis: f64) -> Result<(), JsError> {
+        self.item.completion_date = Some(all_day(millis)?);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setCompletionDateInZone)]
+    pub fn set_completion_date_in_zone(&mut self, millis: f64, zone: &str) -> Result<(), JsError> {
+        self.item.completion_date = Some(in_zone(millis, zone)?);
+        Ok(())
+    }
+}
+
+/// A copy of a label. Changing its color doesn't change the stored label.
+#[wasm_bindgen]
+pub struct Label {
+    label: labels::Label,
+}
+
+#[wasm_bindgen]
+impl Label {
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.label.name.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn color(&self) -> String {
+        self.label.color.clone()
+    }
+
+    // Named so as not to clash with the C function `label_set_color`.
+    #[wasm_bindgen(setter = color)]
+    pub fn recolor(&mut self, color: String) {
+        self.label.color = color;
+    }
+}
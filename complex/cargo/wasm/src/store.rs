@@ -0,0 +1,120 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Saving a `list::ListManager` kept in `list::storage::MemoryStorage` to a
+//! `Backend` between sessions, in the protocol buffer encoding from
+//! `list::buffers`.
+
+use list::buffers::{
+    decode_items,
+    decode_labels,
+    encode_items,
+    encode_labels,
+};
+use list::storage::{
+    ListStorage,
+    MemoryStorage,
+};
+use list::ListManager;
+
+const LABELS_KEY: &'static str = "labels";
+const ITEMS_KEY: &'static str = "items";
+
+/// Somewhere to persist the list between sessions, such as IndexedDB.
+pub trait Backend {
+    /// The bytes last saved under `key`, if any.
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+    fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String>;
+}
+
+/// A storage holding whatever `backend` has saved.
+pub fn load(backend: &dyn Backend) -> Result<MemoryStorage, String> {
+    let storage = MemoryStorage::new();
+    if let Some(bytes) = backend.load(LABELS_KEY)? {
+        for label in decode_labels(&bytes).map_err(|e| format!("can't read saved labels: {}", e))?.iter() {
+            storage.create_label(label)?;
+        }
+    }
+    if let Some(bytes) = backend.load(ITEMS_KEY)? {
+        for item in decode_items(&bytes).map_err(|e| format!("can't read saved items: {}", e))?.iter() {
+            storage.create_item(item)?;
+        }
+    }
+    Ok(storage)
+}
+
+/// Write `manager`'s labels and items to `backend`. Failures are logged rather
+/// than returned, as the change has already been made in memory.
+pub fn save(backend: &dyn Backend, manager: &mut ListManager) {
+    let saves = [
+        (LABELS_KEY, encode_labels(&manager.fetch_labels())),
+        (ITEMS_KEY, encode_items(&manager.fetch_items())),
+    ];
+    for &(key, ref bytes) in saves.iter() {
+        if let Err(e) = backend.save(key, bytes) {
+            warn!(key = key, error = e.as_str(); "failed to save");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use list::dates::Date;
+    use list::items::Item;
+    use list::labels::Label;
+
+    #[derive(Clone, Default)]
+    struct MapBackend {
+        saved: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+    }
+
+    impl Backend for MapBackend {
+        fn load(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+            Ok(self.saved.borrow().get(key).cloned())
+        }
+
+        fn save(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+            self.saved.borrow_mut().insert(key.to_string(), bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    fn label(name: &str, color: &str) -> Label {
+        Label { name: name.to_string(), color: color.to_string() }
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let backend = MapBackend::default();
+        let uuid = {
+            let mut manager = ListManager::with_storage(load(&backend).unwrap());
+            let home = manager.create_label("home".to_string(), "#ff0000".to_string()).unwrap();
+            let item = Item::new("".to_string(), "buy milk".to_string(), Date::day(2017, 11, 5), None, vec![home]);
+            let uuid = manager.create_item(&item).unwrap();
+            save(&backend, &mut manager);
+            uuid
+        };
+
+        let mut manager = ListManager::with_storage(load(&backend).unwrap());
+        assert_eq!(manager.fetch_labels(), vec![label("home", "#ff0000")]);
+        let item = manager.fetch_item(&uuid).expect("expected the saved item");
+        assert_eq!(item.due_date, Date::day(2017, 11, 5));
+        assert_eq!(item.labels, vec![label("home", "#ff0000")]);
+
+        backend.save(ITEMS_KEY, &[0xff]).unwrap();
+        assert!(load(&backend).is_err());
+    }
+}
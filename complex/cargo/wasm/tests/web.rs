@@ -0,0 +1,142 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Runs the bindings under Node with
+//! `cargo test -p toodle-wasm --target wasm32-unknown-unknown`.
+
+#![cfg(target_arch = "wasm32")]
+
+extern crate js_sys;
+extern crate toodle_wasm;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_test;
+
+use js_sys::Function;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+use toodle_wasm::{
+    JsBackend,
+    ListManager,
+};
+
+const DUE: f64 = 1509883200345.0;
+const COMPLETED: f64 = 1509969600000.0;
+
+/// A backend keeping what's saved in `saved`, so that list managers can share it.
+fn map_backend(saved: &js_sys::Map) -> JsBackend {
+    let make = Function::new_with_args("saved", "return {
+        load: key => saved.get(key),
+        save: (key, bytes) => { saved.set(key, bytes.slice()); },
+    };");
+    make.call1(&wasm_bindgen::JsValue::NULL, saved).unwrap().unchecked_into()
+}
+
+fn failing_backend() -> JsBackend {
+    let make = Function::new_no_args("return {
+        load: key => undefined,
+        save: (key, bytes) => { throw new Error('quota exceeded'); },
+    };");
+    make.call0(&wasm_bindgen::JsValue::NULL).unwrap().unchecked_into()
+}
+
+#[wasm_bindgen_test]
+fn test_labels() {
+    let mut manager = ListManager::new();
+    let home = manager.create_label("home".to_string(), "#ff0000".to_string()).ok().unwrap();
+    assert_eq!(home.name(), "home");
+    assert_eq!(home.color(), "#ff0000");
+    assert!(manager.create_label("home".to_string(), "#00ff00".to_string()).is_err());
+    assert_eq!(manager.labels().len(), 1);
+}
+
+#[wasm_bindgen_test]
+fn test_items() {
+    let mut manager = ListManager::new();
+    manager.create_label("home".to_string(), "#ff0000".to_string()).ok().unwrap();
+    let mut item = manager.create_item("buy milk".to_string(), Some(DUE), None, vec!["home".to_string(), "work".to_string()]).ok().unwrap();
+    assert_eq!(item.uuid().len(), 32);
+    assert_eq!(item.name(), "buy milk");
+    assert_eq!(item.due_date(), Some(DUE));
+    assert!(!item.due_date_all_day() && item.due_date_zone().is_none());
+    assert_eq!(item.completion_date(), None);
+    let colors: Vec<String> = item.labels().iter().map(|label| label.color()).collect();
+    assert_eq!(colors, vec!["#ff0000", "#000000"]);
+    assert_eq!(manager.labels().len(), 2);
+
+    item.rename("buy oat milk".to_string());
+    item.set_completion_date(Some(COMPLETED)).ok().unwrap();
+    item.set_labels(vec!["errands".to_string()]);
+    manager.update_item(&item).ok().unwrap();
+    let fetched = manager.item(&item.uuid()).unwrap();
+    assert_eq!(fetched.name(), "buy oat milk");
+    assert_eq!(fetched.completion_date(), Some(COMPLETED));
+    let errands = manager.labels().into_iter().find(|label| label.name() == "errands").unwrap();
+    assert_eq!(manager.items_with_label(&errands).len(), 1);
+    assert_eq!(manager.items().len(), 1);
+    assert!(manager.item("missing").is_none());
+}
+
+#[wasm_bindgen_test]
+fn test_dates() {
+    let mut manager = ListManager::new();
+    let mut item = manager.create_item("call mum".to_string(), None, None, vec![]).ok().unwrap();
+    item.set_all_day_due_date(DUE).ok().unwrap();
+    assert_eq!(item.due_date(), Some(1509840000000.0));
+    assert!(item.due_date_all_day());
+    item.set_due_date_in_zone(DUE, "Australia/Sydney").ok().unwrap();
+    assert_eq!(item.due_date_zone(), Some("Australia/Sydney".to_string()));
+    item.set_completion_date_in_zone(COMPLETED, "Europe/Berlin").ok().unwrap();
+    assert_eq!(item.completion_date_zone(), Some("Europe/Berlin".to_string()));
+
+    assert!(item.set_due_date_in_zone(DUE, "Australia/Sydnye").is_err());
+    assert!(item.set_due_date(Some(f64::NAN)).is_err());
+    assert!(manager.create_item("never".to_string(), Some(f64::INFINITY), None, vec![]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_priority() {
+    let mut manager = ListManager::new();
+    let mut item = manager.create_item("pay rent".to_string(), None, None, vec![]).ok().unwrap();
+    assert_eq!(item.priority(), None);
+    item.set_priority(Some("high".to_string())).ok().unwrap();
+    manager.update_item(&item).ok().unwrap();
+    assert_eq!(manager.item(&item.uuid()).unwrap().priority(), Some("high".to_string()));
+    assert!(item.set_priority(Some("urgent".to_string())).is_err());
+    item.set_priority(None).ok().unwrap();
+    assert_eq!(item.priority(), None);
+}
+
+#[wasm_bindgen_test]
+fn test_backend() {
+    let saved = js_sys::Map::new();
+    let uuid = {
+        let mut manager = ListManager::with_backend(map_backend(&saved)).ok().unwrap();
+        manager.create_label("home".to_string(), "#ff0000".to_string()).ok().unwrap();
+        let item = manager.create_item("buy milk".to_string(), Some(DUE), None, vec!["home".to_string()]).ok().unwrap();
+        item.uuid()
+    };
+    assert!(saved.has(&"items".into()) && saved.has(&"labels".into()));
+
+    let mut manager = ListManager::with_backend(map_backend(&saved)).ok().unwrap();
+    let item = manager.item(&uuid).unwrap();
+    assert_eq!(item.due_date(), Some(DUE));
+    assert_eq!(item.labels()[0].color(), "#ff0000");
+
+    saved.set(&"items".into(), &js_sys::Uint8Array::from(&[0xffu8][..]));
+    assert!(ListManager::with_backend(map_backend(&saved)).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_failing_backend_keeps_changes_in_memory() {
+    let mut manager = ListManager::with_backend(failing_backend()).ok().unwrap();
+    manager.create_item("buy milk".to_string(), None, None, vec![]).ok().unwrap();
+    assert_eq!(manager.items().len(), 1);
+}
@@ -5,7 +5,11 @@
 import Foundation
 
 extension Date {
+    init(millisecondsSince1970 millis: Int64) {
+        self.init(timeIntervalSince1970: Double(millis) / 1000)
+    }
+
     func asInt64Timestamp() -> Int64 {
-        return Int64(self.timeIntervalSince1970)
+        return Int64(self.timeIntervalSince1970 * 1000)
     }
 }
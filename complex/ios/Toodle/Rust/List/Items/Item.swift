@@ -5,13 +5,13 @@
 import Foundation
 
 final class Item: RustObject {
-    var raw: OpaquePointer
+    var raw: UInt64
 
-    required init(raw: OpaquePointer) {
+    required init(raw: UInt64) {
         self.raw = raw
     }
 
-    func intoRaw() -> OpaquePointer {
+    func intoRaw() -> UInt64 {
         return self.raw
     }
 
@@ -19,32 +19,54 @@ final class Item: RustObject {
         item_destroy(raw)
     }
 
+    /// The item's fields, read from Rust in one call and kept until a setter changes them.
+    fileprivate struct Fields {
+        let uuid: String
+        let name: String
+        let dueDate: Date?
+        let completionDate: Date?
+    }
+
+    fileprivate var _fields: Fields?
+
+    fileprivate var fields: Fields {
+        if let fields = _fields {
+            return fields
+        }
+        let snapshot = item_snapshot(raw)
+        defer { item_snapshot_free(snapshot) }
+        let fields = Fields(uuid: String(cString: snapshot.uuid),
+                            name: String(cString: snapshot.name),
+                            dueDate: snapshot.has_due_date ? Date(millisecondsSince1970: snapshot.due_date) : nil,
+                            completionDate: snapshot.has_completion_date ? Date(millisecondsSince1970: snapshot.completion_date) : nil)
+        _fields = fields
+        return fields
+    }
+
     var uuid: String {
-        return String(cString: item_get_uuid(raw))
+        return fields.uuid
     }
 
     var name: String {
         get {
-            return String(cString: item_get_name(raw))
+            return fields.name
         }
         set {
             item_set_name(raw, newValue)
+            _fields = nil
         }
     }
 
     var dueDate: Date?
     {
         get {
-            guard let date = item_get_due_date(raw) else {
-                return nil
-            }
-            return Date(timeIntervalSince1970: Double(date.pointee))
+            return fields.dueDate
         }
         set {
             if let d = newValue {
-                let timestamp = d.timeIntervalSince1970
-                var date = Int64(timestamp)
+                var date = d.asInt64Timestamp()
                 item_set_due_date(raw, UnsafeMutablePointer<Int64>(&date))
+                _fields = nil
             }
         }
     }
@@ -52,16 +74,13 @@ final class Item: RustObject {
     var completionDate: Date?
     {
         get {
-            guard let date = item_get_completion_date(raw) else {
-                return nil
-            }
-            return Date(timeIntervalSince1970: Double(date.pointee))
+            return fields.completionDate
         }
         set {
             if let d = newValue {
-                let timestamp = d.timeIntervalSince1970
-                var date = Int64(timestamp)
+                var date = d.asInt64Timestamp()
                 item_set_completion_date(raw, UnsafeMutablePointer<Int64>(&date))
+                _fields = nil
             }
         }
     }
@@ -72,9 +91,10 @@ final class Item: RustObject {
         get {
             if _labels == nil {
                 let ls = item_get_labels(self.raw)
+                defer { label_list_destroy(ls) }
                 _labels = []
-                for index in 0..<item_labels_count(ls) {
-                    let label = Label(raw: item_label_at(ls, index)!)
+                for index in 0..<label_list_count(ls) {
+                    let label = Label(raw: label_list_entry_at(ls, index))
                     _labels?.append(label)
                 }
             }
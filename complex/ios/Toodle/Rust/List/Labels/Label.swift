@@ -6,13 +6,13 @@ import Foundation
 import UIKit
 
 class Label: RustObject {
-    var raw: OpaquePointer
+    var raw: UInt64
 
-    required init(raw: OpaquePointer) {
+    required init(raw: UInt64) {
         self.raw = raw
     }
 
-    func intoRaw() -> OpaquePointer {
+    func intoRaw() -> UInt64 {
         return self.raw
     }
 
@@ -21,12 +21,12 @@ class Label: RustObject {
     }
 
     var name: String {
-        return String(cString: label_get_name(raw))
+        return String(rustString: label_get_name(raw))
     }
 
     var color: UIColor {
         get {
-            return UIColor(hex: String(cString: label_get_color(raw))) ?? UIColor.gray
+            return UIColor(hex: String(rustString: label_get_color(raw))) ?? UIColor.gray
         }
         set {
             if let hex = newValue.toHex() {
@@ -6,30 +6,30 @@ import Foundation
 import UIKit
 
 final class ListManager: RustObject {
-    var raw: OpaquePointer
+    var raw: UInt64
 
-    required init(raw: OpaquePointer) {
+    required init(raw: UInt64) {
         self.raw = raw
     }
 
-    func intoRaw() -> OpaquePointer {
+    func intoRaw() -> UInt64 {
         return self.raw
     }
 
-//    deinit {
-//        list_manager_destroy(raw)
-//    }
+    deinit {
+        list_manager_destroy(raw)
+    }
 
-    fileprivate func toPointerArray(list: [RustObject]) -> OpaquePointer {
-        var pointerArray = list.map({ $0.intoRaw() })
-        return OpaquePointer(UnsafeMutablePointer<[OpaquePointer]>(&pointerArray)) //
+    fileprivate func toHandleArray(list: [RustObject]) -> [UInt64] {
+        return list.map({ $0.intoRaw() })
     }
 
     func allItems() -> [Item] {
         let items = list_manager_get_all_items(self.raw)
+        defer { item_list_destroy(items) }
         var allItems: [Item] = []
         for index in 0..<item_list_count(items) {
-            let item = Item(raw: item_list_entry_at(items, index)!)
+            let item = Item(raw: item_list_entry_at(items, index))
             allItems.append(item)
         }
         return allItems
@@ -37,6 +37,7 @@ final class ListManager: RustObject {
 
     func allLabels() -> [Label] {
         let labels = list_manager_get_all_labels(self.raw)
+        defer { label_list_destroy(labels) }
         var allLabels: [Label] = []
         for index in 0..<label_list_count(labels) {
             let label = Label(raw: label_list_entry_at(labels, index))
@@ -60,14 +61,17 @@ final class ListManager: RustObject {
             var c = completion.asInt64Timestamp()
             cd = UnsafeMutablePointer<Int64>(&c)
         }
-        var pointerArray = self.toPointerArray(list: labels as [RustObject])
-        return Item(raw: list_manager_create_item(self.raw,
-                                  name,
-                                  dd,
-                                  cd,
-                                  UnsafeMutablePointer<OpaquePointer>(&pointerArray)))
-return nil
-        
+        let handleArray = self.toHandleArray(list: labels as [RustObject])
+        let item = list_manager_create_item(self.raw,
+                                            name,
+                                            dd,
+                                            cd,
+                                            handleArray,
+                                            handleArray.count)
+        guard item != 0 else {
+            return nil
+        }
+        return Item(raw: item)
     }
 
     func update(item: Item, name: String, dueDate: Date?, completionDate: Date?, labels: [Label]) {
@@ -81,14 +85,13 @@ return nil
             var c = completion.asInt64Timestamp()
             cd = UnsafeMutablePointer<Int64>(&c)
         }
-        var pointerArray = self.toPointerArray(list: labels as [RustObject])
+        let handleArray = self.toHandleArray(list: labels as [RustObject])
         list_manager_update_item(raw,
                                  item.raw,
                                  name,
                                  dd,
                                  cd,
-                                 UnsafeMutablePointer<OpaquePointer>(&pointerArray))
-
-        
+                                 handleArray,
+                                 handleArray.count)
     }
 }
@@ -5,6 +5,14 @@
 import Foundation
 
 protocol RustObject {
-    init(raw: OpaquePointer)
-    func intoRaw() -> OpaquePointer
+    init(raw: UInt64)
+    func intoRaw() -> UInt64
+}
+
+extension String {
+    /// Copies a string returned by the Rust library and frees the original.
+    init(rustString: UnsafeMutablePointer<CChar>) {
+        self.init(cString: rustString)
+        toodle_string_free(rustString)
+    }
 }
@@ -3,6 +3,7 @@
 // * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 import Foundation
+import os.log
 
 class ToodleLib: RustObject {
 
@@ -13,17 +14,22 @@ class ToodleLib: RustObject {
         return Static.instance
     }
 
-    var raw: OpaquePointer
+    var raw: UInt64
 
-    required init(raw: OpaquePointer) {
+    required init(raw: UInt64) {
         self.raw = raw
     }
 
-    func intoRaw() -> OpaquePointer {
+    func intoRaw() -> UInt64 {
         return self.raw
     }
 
     convenience init() {
+        #if DEBUG
+        ToodleLib.forwardLogs(from: LogLevel_Debug)
+        #else
+        ToodleLib.forwardLogs(from: LogLevel_Warn)
+        #endif
         let paths = FileManager.default.urls(for: .documentDirectory, in: .userDomainMask)
         let documentsURL = paths[0]
         let storeURI = documentsURL.appendingPathComponent("todolist.db", isDirectory: false).absoluteString
@@ -37,6 +43,23 @@ class ToodleLib: RustObject {
     var list: ListManager {
         return ListManager(raw: toodle_list(self.raw));
     }
+
+    /// Send the library's log messages at `level` or more severe to os_log.
+    static func forwardLogs(from level: LogLevel) {
+        toodle_set_logger({ _, level, target, message, fields in
+            guard let target = target, let message = message, let fields = fields else {
+                return
+            }
+            let type: OSLogType
+            switch level {
+            case LogLevel_Error.rawValue: type = .error
+            case LogLevel_Warn.rawValue: type = .default
+            case LogLevel_Info.rawValue: type = .info
+            default: type = .debug
+            }
+            os_log("%{public}s: %{public}s %{public}s", log: .default, type: type, target, message, fields)
+        }, nil, level.rawValue)
+    }
 }
 
 class Singleton {